@@ -1,4 +1,5 @@
 use clap::{Parser, Subcommand};
+use flux_core::objects::signature::DateZone;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -42,6 +43,291 @@ pub enum BranchCommands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum AuthCommands {
+    /// Remove the access token of a server from the credential store
+    Logout {
+        /// Server address (defaults to the origin)
+        url: Option<String>,
+    },
+    /// Show the user and the active access tokens on a server
+    Status {
+        /// Server address (defaults to the origin)
+        url: Option<String>,
+    },
+    /// Replace the stored access token with a new one with the same scopes
+    Refresh {
+        /// Server address (defaults to the origin)
+        url: Option<String>,
+    },
+    /// Revoke access tokens, the stored one unless an id or --all is given
+    Revoke {
+        /// Id of the token to revoke, as listed by flux auth status
+        #[arg(conflicts_with = "all")]
+        id: Option<String>,
+
+        /// Revoke every token of the user
+        #[arg(long)]
+        all: bool,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AccessCommands {
+    /// Show the visibility and the collaborators of the repository
+    List {
+        /// Repository address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Make the repository public, anyone may clone it, or private
+    Visibility {
+        #[arg(value_parser = ["public", "private"])]
+        visibility: String,
+
+        /// Repository address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Give a registered user access to the repository, or change their role
+    Add {
+        #[arg(value_name = "email")]
+        user_email: String,
+
+        /// read to clone, write to also push, admin to also manage access
+        #[arg(long, default_value = "write", value_parser = ["read", "write", "admin"])]
+        role: String,
+
+        /// Repository address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Remove the access of a collaborator
+    Remove {
+        #[arg(value_name = "email")]
+        user_email: String,
+
+        /// Repository address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RepoCommands {
+    /// Create an empty repository
+    Create {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        #[arg(short = 'd', long)]
+        description: Option<String>,
+
+        /// Branch the repository is browsed at (defaults to main)
+        #[arg(long, value_name = "branch")]
+        default_branch: Option<String>,
+
+        /// Only collaborators and organization members may clone it
+        #[arg(long)]
+        private: bool,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// List the repositories of a namespace you can read
+    List {
+        /// User or organization (defaults to you)
+        namespace: Option<String>,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Show the description, default branch and creation time of a repository
+    Describe {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Rename a repository within its namespace
+    Rename {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        #[arg(value_name = "new-name")]
+        new_name: String,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Delete a repository and everything pushed to it
+    Delete {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        /// Do not ask for confirmation
+        #[arg(short = 'y', long)]
+        yes: bool,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// List the branches of a repository
+    Refs {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Show the history of a repository without cloning it
+    Log {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        /// Revision to start from (defaults to the default branch)
+        revision: Option<String>,
+
+        /// Number of commits to show
+        #[arg(short = 'n', long, default_value_t = 20)]
+        max_count: usize,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// List a directory of a repository without cloning it
+    Tree {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        /// Directory to list (defaults to the root)
+        path: Option<String>,
+
+        /// Revision to read (defaults to the default branch)
+        #[arg(short = 'r', long)]
+        revision: Option<String>,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Print a file of a repository without cloning it
+    Show {
+        #[arg(value_name = "repository")]
+        name: String,
+
+        path: String,
+
+        /// Revision to read (defaults to the default branch)
+        #[arg(short = 'r', long)]
+        revision: Option<String>,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OrgCommands {
+    /// Create an organization administered by you
+    Create {
+        name: String,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Add a registered user to an organization, or change their role
+    Add {
+        organization: String,
+
+        #[arg(value_name = "email")]
+        user_email: String,
+
+        /// read to clone, write to also push and create repositories, admin to also manage
+        /// the organization and its repositories
+        #[arg(long, default_value = "write", value_parser = ["read", "write", "admin"])]
+        role: String,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// Remove a member from an organization
+    Remove {
+        organization: String,
+
+        #[arg(value_name = "email")]
+        user_email: String,
+
+        /// Server address (defaults to the origin)
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum StashCommands {
+    /// Save local changes to a new stash entry and reset the work tree to HEAD
+    Push {
+        /// Description of the stashed changes
+        #[arg(short = 'm', long = "message")]
+        message: Option<String>,
+    },
+
+    /// Apply a stash entry and remove it from the stash if there are no conflicts
+    Pop {
+        /// Stash entry, either 'stash@{N}' or 'N' (defaults to the latest entry)
+        stash: Option<String>,
+
+        /// Also restore the staged changes
+        #[arg(long = "index", default_value_t = false)]
+        index: bool,
+    },
+
+    /// Apply a stash entry on top of the current work tree, keeping it in the stash
+    Apply {
+        /// Stash entry, either 'stash@{N}' or 'N' (defaults to the latest entry)
+        stash: Option<String>,
+
+        /// Also restore the staged changes
+        #[arg(long = "index", default_value_t = false)]
+        index: bool,
+    },
+
+    /// List the stash entries
+    List {},
+
+    /// Remove a stash entry
+    Drop {
+        /// Stash entry, either 'stash@{N}' or 'N' (defaults to the latest entry)
+        stash: Option<String>,
+    },
+
+    /// Show the changes recorded in a stash entry
+    Show {
+        /// Stash entry, either 'stash@{N}' or 'N' (defaults to the latest entry)
+        stash: Option<String>,
+
+        /// Show the changes as a patch
+        #[arg(short = 'p', long = "patch", default_value_t = false)]
+        patch: bool,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Initialize a new Flux repository
@@ -63,6 +349,48 @@ pub enum Commands {
         value: String,
     },
 
+    /// Get, set, unset or list configuration values
+    ///
+    /// Values are read from the system (/etc/fluxconfig), global (~/.fluxconfig) and local
+    /// (.flux/config) files, later ones overriding earlier ones. Values are written to the
+    /// local file unless another level is given.
+    Config {
+        /// Use the global file
+        #[arg(long, group = "level")]
+        global: bool,
+
+        /// Use the system file
+        #[arg(long, group = "level")]
+        system: bool,
+
+        /// Use the repository file
+        #[arg(long, group = "level")]
+        local: bool,
+
+        /// Print the value of a key
+        #[arg(long, value_name = "key", group = "action")]
+        get: Option<String>,
+
+        /// Remove a key
+        #[arg(long, value_name = "key", group = "action")]
+        unset: Option<String>,
+
+        /// List every value
+        #[arg(short = 'l', long, group = "action")]
+        list: bool,
+
+        /// Print the file each value comes from
+        #[arg(long)]
+        show_origin: bool,
+
+        /// Key to set
+        #[arg(requires = "value", conflicts_with = "action")]
+        key: Option<String>,
+
+        /// Value to set
+        value: Option<String>,
+    },
+
     /// Display the contents of a repository object
     ///
     /// - Blobs: prints the raw file contents
@@ -92,11 +420,29 @@ pub enum Commands {
     /// Add a file or directory to the staging area
     Add {
         path: String,
+
+        /// Interactively choose the hunks of tracked files to stage
+        #[arg(short, long)]
+        patch: bool,
     },
 
-    /// Remove a file or directory from the staging area
-    Delete {
+    /// Remove tracked files from the index and the work tree
+    Rm {
         path: String,
+
+        /// Only remove the files from the index, keeping them on disk
+        #[arg(long)]
+        cached: bool,
+
+        /// Allow removing directories
+        #[arg(short)]
+        recursive: bool,
+    },
+
+    /// Move or rename a tracked file or directory
+    Mv {
+        source: String,
+        destination: String,
     },
 
     /// Create a commit object from a tree
@@ -115,14 +461,220 @@ pub enum Commands {
     },
 
     /// Create a new commit from the current index
+    ///
+    /// Without -m the message is written in $FLUX_EDITOR, $VISUAL or $EDITOR.
     Commit {
         /// Commit message
         #[arg(short = 'm', long = "message")]
-        message: String,
+        message: Option<String>,
+
+        /// Replace the last commit, keeping its parents and author
+        #[arg(long)]
+        amend: bool,
+
+        /// Keep the message of the amended commit instead of opening the editor
+        #[arg(long, requires = "amend", conflicts_with = "message")]
+        no_edit: bool,
+
+        /// Allow a commit that records no changes
+        #[arg(long)]
+        allow_empty: bool,
+
+        /// Skip the pre-commit and commit-msg hooks
+        #[arg(short = 'n', long)]
+        no_verify: bool,
+
+        /// Sign the commit with the configured user.signing_key
+        #[arg(short = 'S', long)]
+        sign: bool,
+
+        /// Do not sign the commit, even if commit.sign is set
+        #[arg(long, conflicts_with = "sign")]
+        no_sign: bool,
+
+        /// Override the author, e.g. 'Jane Doe <jane@example.com>'
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Override the author date, e.g. '2024-01-31 12:00 +0100', an RFC 3339 date or '@<timestamp>'
+        #[arg(long)]
+        date: Option<String>,
+    },
+
+    /// Check the signatures of commits against the allowed signers
+    VerifyCommit {
+        #[arg(required = true)]
+        revisions: Vec<String>,
+    },
+
+    /// Generate an ed25519 key used to sign commits
+    ///
+    /// The key is written to .flux/signing_key unless a path is given, set as the
+    /// user.signing_key and added to the allowed signers for the configured user.email.
+    SigningKey {
+        path: Option<String>,
     },
 
     /// Show the commit history
-    Log {},
+    Log {
+        /// Revision to start from, e.g. a branch, a hash or 'HEAD@{1}' (defaults to HEAD)
+        revision: Option<String>,
+
+        /// Only show the commits that changed this file, following its renames
+        #[arg(long, value_name = "path")]
+        follow: Option<String>,
+
+        /// Verify and show the signature of every commit
+        #[arg(long)]
+        show_signature: bool,
+
+        /// Print author and committer dates in a timezone: original, local, utc or +hhmm
+        #[arg(long, value_name = "zone")]
+        date: Option<DateZone>,
+    },
+
+    /// Show changes between the work tree, the index and commits
+    ///
+    /// Without revisions the work tree is compared with the index. With one revision
+    /// it is compared with the work tree, with two the revisions are compared together.
+    Diff {
+        /// Up to two revisions to compare
+        #[arg(num_args = 0..=2)]
+        revisions: Vec<String>,
+
+        /// Compare with the index instead of the work tree
+        #[arg(long, alias = "staged")]
+        cached: bool,
+
+        /// Only list the changed paths with their status
+        #[arg(long)]
+        name_status: bool,
+
+        /// Similarity threshold in percent for rename detection (defaults to diff.rename_threshold)
+        #[arg(short = 'M', long = "find-renames", value_name = "n")]
+        find_renames: Option<u8>,
+
+        /// Also detect files copied from other files
+        #[arg(short = 'C', long = "find-copies")]
+        find_copies: bool,
+
+        /// Show renames as a deletion and an addition
+        #[arg(long, conflicts_with_all = ["find_renames", "find_copies"])]
+        no_renames: bool,
+    },
+
+    /// Show the history of a ref's values (defaults to HEAD)
+    ///
+    /// Every branch update, branch switch and deletion is recorded, so entries
+    /// can be used to recover commits with the '<ref>@{N}' syntax.
+    Reflog {
+        #[arg(value_name = "ref")]
+        reference: Option<String>,
+    },
+
+    /// Expire old reflog entries and remove unreachable objects
+    Gc {
+        /// Prune unreachable objects older than this many days
+        #[arg(long = "prune", value_name = "days", default_value_t = 14)]
+        prune: u64,
+
+        /// Expire reflog entries older than this many days
+        #[arg(long = "reflog-expire", value_name = "days", default_value_t = 90)]
+        reflog_expire: u64,
+    },
+
+    /// Remove unreachable objects from the object store
+    ///
+    /// Objects are reachable from branches, HEAD, reflogs, the stash and the index.
+    Prune {
+        /// Only prune objects older than this many days (0 prunes everything unreachable)
+        #[arg(long = "expire", value_name = "days", default_value_t = 14)]
+        expire: u64,
+
+        /// Report what would be removed without deleting anything
+        #[arg(short = 'n', long = "dry-run")]
+        dry_run: bool,
+    },
+
+    /// Verify the integrity of the object store and refs
+    ///
+    /// Every object is re-hashed and parsed, and the links between commits, trees
+    /// and blobs are followed. Dangling objects are listed but are not errors.
+    Fsck {
+        /// Also check tree entry ordering and modes
+        #[arg(long = "strict")]
+        strict: bool,
+    },
+
+    /// Replay the commits of the current branch on top of another base
+    ///
+    /// Commits that are on the current branch but not in <upstream> are applied one
+    /// by one. When a commit conflicts the rebase stops: resolve the conflicts, add
+    /// the files and run 'flux rebase --continue'.
+    Rebase {
+        /// Branch or commit whose history the commits are replayed on
+        #[arg(required_unless_present_any = ["continue_", "abort", "skip"])]
+        upstream: Option<String>,
+
+        /// Replay the commits on this base instead of <upstream>
+        #[arg(long = "onto", value_name = "newbase")]
+        onto: Option<String>,
+
+        /// Read the steps from a todo file with lines like 'pick <commit>',
+        /// 'drop <commit>', 'squash <commit>' or 'reword <commit> <message>'
+        #[arg(long = "todo", value_name = "file")]
+        todo: Option<String>,
+
+        /// Continue after resolving conflicts
+        #[arg(long = "continue", conflicts_with_all = ["upstream", "abort", "skip"])]
+        continue_: bool,
+
+        /// Stop and return the branch to its original state
+        #[arg(long = "abort", conflicts_with_all = ["upstream", "skip"])]
+        abort: bool,
+
+        /// Skip the commit that stopped the rebase
+        #[arg(long = "skip", conflicts_with = "upstream")]
+        skip: bool,
+    },
+
+    /// Apply the changes introduced by existing commits on top of HEAD
+    ///
+    /// Each commit becomes a new commit that records where it was picked from.
+    CherryPick {
+        #[arg(value_name = "commit", required_unless_present_any = ["continue_", "abort", "skip"])]
+        revisions: Vec<String>,
+
+        /// Continue after resolving conflicts
+        #[arg(long = "continue", conflicts_with_all = ["revisions", "abort", "skip"])]
+        continue_: bool,
+
+        /// Stop and return the branch to its original state
+        #[arg(long = "abort", conflicts_with_all = ["revisions", "skip"])]
+        abort: bool,
+
+        /// Skip the commit that stopped the cherry-pick
+        #[arg(long = "skip", conflicts_with = "revisions")]
+        skip: bool,
+    },
+
+    /// Create new commits that undo the changes of existing commits
+    Revert {
+        #[arg(value_name = "commit", required_unless_present_any = ["continue_", "abort", "skip"])]
+        revisions: Vec<String>,
+
+        /// Continue after resolving conflicts
+        #[arg(long = "continue", conflicts_with_all = ["revisions", "abort", "skip"])]
+        continue_: bool,
+
+        /// Stop and return the branch to its original state
+        #[arg(long = "abort", conflicts_with_all = ["revisions", "skip"])]
+        abort: bool,
+
+        /// Skip the commit that stopped the revert
+        #[arg(long = "skip", conflicts_with = "revisions")]
+        skip: bool,
+    },
 
     /// Manage branches
     Branch {
@@ -130,6 +682,14 @@ pub enum Commands {
         subcommand: BranchCommands,
     },
 
+    /// Stash uncommitted changes away and restore them later
+    ///
+    /// Without a subcommand the changes are pushed to a new stash entry.
+    Stash {
+        #[command(subcommand)]
+        subcommand: Option<StashCommands>,
+    },
+
     Push {
         url: Option<String>,
     },
@@ -142,7 +702,50 @@ pub enum Commands {
     Status {
         
     },
+    /// Log in to a server and keep the access token in the credential store
+    ///
+    /// Logs in with a password, asked for on the terminal, or by signing a challenge with an
+    /// SSH key. Tokens are stored per server in ~/.flux/credentials, or by the external helper
+    /// set in credential.helper.
+    #[command(args_conflicts_with_subcommands = true)]
     Auth {
+        #[command(subcommand)]
+        subcommand: Option<AuthCommands>,
+
         url: Option<String>,
-    }
+
+        /// Create an account for the configured user.name and user.email
+        #[arg(long)]
+        register: bool,
+
+        /// Use an ed25519 SSH key instead of a password (defaults to user.signing_key)
+        #[arg(long, value_name = "path")]
+        key: Option<Option<String>>,
+
+        /// Request a scope for the token: read, write or admin (defaults to read and write)
+        #[arg(long = "scope", value_name = "scope")]
+        scopes: Vec<String>,
+    },
+    /// Manage who can access the repository on the server
+    ///
+    /// Without a subcommand, shows the visibility and the collaborators of the repository,
+    /// which needs the write role on it. The repository is the one the origin points to, or
+    /// the path of --url.
+    Access {
+        #[command(subcommand)]
+        subcommand: Option<AccessCommands>,
+    },
+    /// Manage repositories on the server
+    ///
+    /// Repositories are named <namespace>/<name>, the namespace being a user or an
+    /// organization. A bare name is a repository of the logged in user.
+    Repo {
+        #[command(subcommand)]
+        subcommand: RepoCommands,
+    },
+    /// Manage organizations, namespaces whose repositories are shared by their members
+    Org {
+        #[command(subcommand)]
+        subcommand: OrgCommands,
+    },
 }
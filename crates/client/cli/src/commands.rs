@@ -1,4 +1,16 @@
-use flux_core::internals::repository::Repository;
+use crate::cli::Cli;
+use clap::CommandFactory;
+use flux_core::internals::config::{ConfigEntry, ConfigLevel};
+use flux_core::internals::gc::PruneReport;
+use flux_core::internals::merge::Conflict;
+use flux_core::internals::repository::{
+    AuthMethod, CommitOptions, LogOptions, Organization, RemoteRepository, Repository,
+    RepositoryAccess,
+};
+use flux_core::internals::sequencer::{Operation, Progress};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub fn set(repo_path: Option<String>, key: String, value: String) -> anyhow::Result<()> {
     let mut repository = Repository::open(repo_path)?;
@@ -6,6 +18,73 @@ pub fn set(repo_path: Option<String>, key: String, value: String) -> anyhow::Res
     Ok(())
 }
 
+pub enum ConfigAction {
+    Get(String),
+    Set(String, String),
+    Unset(String),
+    List,
+}
+
+/// Runs `action` on the configuration file of `level`. Reads without a level see the merged
+/// configuration, writes without a level go to the repository file.
+pub fn config(
+    repo_path: Option<String>,
+    level: Option<ConfigLevel>,
+    action: ConfigAction,
+    show_origin: bool,
+) -> anyhow::Result<()> {
+    let mut config = Repository::open_config(repo_path)?;
+    let print = |entry: &ConfigEntry, line: String| match show_origin {
+        true => println!("file:{}\t{}", entry.path.display(), line),
+        false => println!("{}", line),
+    };
+
+    match action {
+        ConfigAction::Get(key) => {
+            let entry = match level {
+                Some(level) => config.get_entry_at(level, &key)?,
+                None => config.get_entry(&key)?,
+            };
+            let entry = entry.ok_or_else(|| anyhow::anyhow!("{} is not set", key))?;
+            print(&entry, entry.value.clone());
+        }
+        ConfigAction::Set(key, value) => {
+            config.set_at(level.unwrap_or(ConfigLevel::Local), key, value)?;
+        }
+        ConfigAction::Unset(key) => {
+            let level = level.unwrap_or(ConfigLevel::Local);
+            let removed = config.unset_at(level, &key)?;
+            anyhow::ensure!(removed, "{} is not set in the {} file", key, level);
+        }
+        ConfigAction::List => {
+            for entry in config.list(level) {
+                print(&entry, format!("{}={}", entry.key, entry.value));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces an `alias.<name>` given as the command with the words it is set to, e.g.
+/// `flux co main` with `alias.co = "branch switch"`. Built-in commands cannot be overridden.
+pub fn expand_alias(mut args: Vec<String>) -> Vec<String> {
+    let Some(name) = args.get(1).filter(|arg| !arg.starts_with('-')) else {
+        return args;
+    };
+    if Cli::command().find_subcommand(name).is_some() {
+        return args;
+    }
+
+    let expansion = Repository::open_config(None)
+        .and_then(|config| Ok(config.get(&format!("alias.{name}"))?))
+        .ok()
+        .flatten();
+    if let Some(expansion) = expansion {
+        args.splice(1..2, expansion.split_whitespace().map(String::from));
+    }
+    args
+}
+
 pub fn cat_file(repo_path: Option<String>, hash: String) -> anyhow::Result<()> {
     let repository = Repository::open(repo_path)?;
     repository.cat(&hash)?;
@@ -37,23 +116,259 @@ pub fn add(repo_path: Option<String>, path: String) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn remove(repo_path: Option<String>, path: String) -> anyhow::Result<()> {
+const PATCH_HELP: &str = "y - stage this hunk
+n - do not stage this hunk
+q - quit; do not stage this hunk or any of the remaining ones
+a - stage this hunk and all later hunks in the file
+d - do not stage this hunk or any of the later hunks in the file
+";
+
+pub fn add_patch(repo_path: Option<String>, path: String) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let paths = repository.unstaged_paths(&path)?;
+    if paths.is_empty() {
+        println!("No changes.");
+        return Ok(());
+    }
+
+    let stdin = io::stdin();
+    let mut quit = false;
+    for file in paths {
+        let hunks = repository.file_hunks(&file)?;
+        let mut selected = Vec::new();
+        let mut rest: Option<bool> = None;
+        let total = hunks.len();
+
+        println!("diff a/{file} b/{file}");
+        for (i, hunk) in hunks.iter().enumerate() {
+            if let Some(stage) = rest {
+                if stage {
+                    selected.push(i);
+                }
+                continue;
+            }
+
+            print!("{hunk}");
+            loop {
+                print!("({}/{total}) Stage this hunk [y,n,q,a,d,?]? ", i + 1);
+                io::stdout().flush()?;
+
+                let mut answer = String::new();
+                if stdin.lock().read_line(&mut answer)? == 0 {
+                    quit = true;
+                    break;
+                }
+                match answer.trim() {
+                    "y" => selected.push(i),
+                    "n" => {}
+                    "q" => quit = true,
+                    "a" => {
+                        selected.push(i);
+                        rest = Some(true);
+                    }
+                    "d" => rest = Some(false),
+                    _ => {
+                        print!("{PATCH_HELP}");
+                        continue;
+                    }
+                }
+                break;
+            }
+            if quit {
+                break;
+            }
+        }
+
+        repository.stage_hunks(&file, &selected)?;
+        if quit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub fn remove(
+    repo_path: Option<String>,
+    path: String,
+    cached: bool,
+    recursive: bool,
+) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    for removed in repository.remove(&path, cached, recursive)? {
+        println!("rm '{removed}'");
+    }
+    Ok(())
+}
+
+pub fn move_path(
+    repo_path: Option<String>,
+    source: String,
+    destination: String,
+) -> anyhow::Result<()> {
     let mut repository = Repository::open(repo_path)?;
-    repository.delete(&path)?;
-    println!("Deleted {path} from index");
+    for (from, to) in repository.move_path(&source, &destination)? {
+        println!("renamed '{from}' -> '{to}'");
+    }
     Ok(())
 }
 
-pub fn commit(repo_path: Option<String>, message: String) -> anyhow::Result<String> {
+/// Commits with `message`, or one written in the editor unless `no_edit` keeps the message of
+/// the amended commit.
+pub fn commit(
+    repo_path: Option<String>,
+    message: Option<String>,
+    no_edit: bool,
+    options: CommitOptions,
+) -> anyhow::Result<String> {
     let mut repository = Repository::open(repo_path)?;
-    let hash = repository.commit(message)?;
+    let message = match message {
+        None if !no_edit => Some(repository.edit_commit_message(options.amend)?),
+        message => message,
+    };
+    let hash = repository.commit_with(message, &options)?;
     println!("{hash}");
     Ok(hash)
 }
 
-pub fn log(repo_path: Option<String>) -> anyhow::Result<()> {
+pub fn verify_commit(repo_path: Option<String>, revisions: Vec<String>) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    let mut failed = 0;
+    for revision in &revisions {
+        let (hash, verification) = repository.verify_commit(revision)?;
+        println!("{hash}: {verification}");
+        if !verification.is_good() {
+            failed += 1;
+        }
+    }
+    anyhow::ensure!(failed == 0, "{failed} commit(s) could not be verified");
+    Ok(())
+}
+
+pub fn signing_key(repo_path: Option<String>, path: Option<String>) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let (path, public) = repository.generate_signing_key(path.as_deref().map(Path::new))?;
+    println!("Signing key written to {}", path.display());
+    println!("{public}");
+    Ok(())
+}
+
+pub fn log(
+    repo_path: Option<String>,
+    revision: Option<String>,
+    options: LogOptions,
+) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    repository.log(revision, &options)?;
+    Ok(())
+}
+
+/// `renames` holds the threshold override and whether to look for copies, `None` turns rename
+/// detection off.
+pub fn diff(
+    repo_path: Option<String>,
+    revisions: Vec<String>,
+    cached: bool,
+    name_status: bool,
+    renames: Option<(Option<u8>, bool)>,
+) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    let renames = match renames {
+        Some((threshold, copies)) => {
+            let mut options = repository.rename_options()?;
+            if let Some(threshold) = threshold {
+                anyhow::ensure!(threshold <= 100, "Rename threshold must be at most 100.");
+                options.threshold = threshold;
+            }
+            options.copies = copies;
+            Some(options)
+        }
+        None => None,
+    };
+
+    let changes = repository.diff(&revisions, cached, renames)?;
+    if name_status {
+        for change in &changes {
+            match &change.old_path {
+                Some(old_path) => {
+                    println!("{}\t{old_path}\t{}", change.status.letter(), change.path)
+                }
+                None => println!("{}\t{}", change.status.letter(), change.path),
+            }
+        }
+    } else {
+        print!("{}", repository.diff_patch(&changes)?);
+    }
+    Ok(())
+}
+
+pub fn reflog(repo_path: Option<String>, reference: Option<String>) -> anyhow::Result<()> {
     let repository = Repository::open(repo_path)?;
-    repository.log(None)?;
+    for entry in repository.reflog(reference.as_deref())? {
+        println!("{entry}");
+    }
+    Ok(())
+}
+
+fn days(days: u64) -> Duration {
+    Duration::from_secs(days * 24 * 60 * 60)
+}
+
+pub fn gc(repo_path: Option<String>, prune: u64, reflog_expire: u64) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    let report = repository.gc(days(reflog_expire), days(prune))?;
+    println!(
+        "Expired {} reflog entries, kept {} reachable objects",
+        report.expired_reflog_entries, report.reachable
+    );
+    print_prune_report(&report.prune, false);
+    Ok(())
+}
+
+pub fn prune(repo_path: Option<String>, expire: u64, dry_run: bool) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    let report = repository.prune(days(expire), dry_run)?;
+    print_prune_report(&report, dry_run);
+    Ok(())
+}
+
+fn print_prune_report(report: &PruneReport, dry_run: bool) {
+    if dry_run {
+        for hash in &report.objects {
+            println!("{hash}");
+        }
+        println!(
+            "Would remove {} unreachable objects and {} temporary files, {} bytes",
+            report.objects.len(),
+            report.temp_files,
+            report.bytes
+        );
+    } else {
+        println!(
+            "Removed {} unreachable objects and {} temporary files, reclaimed {} bytes",
+            report.objects.len(),
+            report.temp_files,
+            report.bytes
+        );
+    }
+}
+
+pub fn fsck(repo_path: Option<String>, strict: bool) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    let report = repository.fsck(strict)?;
+    for (hash, object_type) in &report.dangling {
+        println!("dangling {} {hash}", object_type.as_str());
+    }
+    for problem in &report.problems {
+        println!("{problem}");
+    }
+    if !report.is_ok() {
+        anyhow::bail!(
+            "fsck found {} problems in {} objects",
+            report.problems.len(),
+            report.checked
+        );
+    }
+    println!("Checked {} objects", report.checked);
     Ok(())
 }
 
@@ -82,6 +397,143 @@ pub fn switch_branch(repo_path: Option<String>, name: String, force: bool) -> an
     Ok(())
 }
 
+pub fn stash_push(repo_path: Option<String>, message: Option<String>) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let message = repository.stash_push(message)?;
+    println!("Saved working directory and index state {message}");
+    Ok(())
+}
+
+fn print_conflicts(conflicts: &[Conflict]) {
+    for conflict in conflicts {
+        println!(
+            "CONFLICT ({}): Merge conflict in {}",
+            conflict.kind.as_str(),
+            conflict.path
+        );
+    }
+}
+
+pub fn stash_apply(
+    repo_path: Option<String>,
+    stash: Option<String>,
+    index: bool,
+) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let conflicts = repository.stash_apply(stash.as_deref(), index)?;
+    print_conflicts(&conflicts);
+    Ok(())
+}
+
+pub fn stash_pop(
+    repo_path: Option<String>,
+    stash: Option<String>,
+    index: bool,
+) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let conflicts = repository.stash_pop(stash.as_deref(), index)?;
+    if conflicts.is_empty() {
+        println!("Dropped {}", stash.as_deref().unwrap_or("stash@{0}"));
+    } else {
+        print_conflicts(&conflicts);
+        println!("The stash entry is kept in case you need it again.");
+    }
+    Ok(())
+}
+
+pub fn stash_list(repo_path: Option<String>) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    for entry in repository.stash_list()? {
+        println!("{entry}");
+    }
+    Ok(())
+}
+
+pub fn stash_drop(repo_path: Option<String>, stash: Option<String>) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let hash = repository.stash_drop(stash.as_deref())?;
+    println!(
+        "Dropped {} ({hash})",
+        stash.as_deref().unwrap_or("stash@{0}")
+    );
+    Ok(())
+}
+
+pub fn stash_show(
+    repo_path: Option<String>,
+    stash: Option<String>,
+    patch: bool,
+) -> anyhow::Result<()> {
+    let repository = Repository::open(repo_path)?;
+    print!("{}", repository.stash_show(stash.as_deref(), patch)?);
+    Ok(())
+}
+
+fn print_progress(progress: &Progress, operation: &str) {
+    match progress {
+        Progress::UpToDate => println!("Current branch is up to date."),
+        Progress::Done(head) => {
+            println!("Successfully finished {operation}, HEAD is now at {head}")
+        }
+        Progress::Stopped { step, conflicts } => {
+            print_conflicts(conflicts);
+            println!("Could not apply {step}");
+            println!(
+                "Resolve the conflicts, add the files and run 'flux {operation} --continue', \
+                 or use 'flux {operation} --skip' or 'flux {operation} --abort'."
+            );
+        }
+    }
+}
+
+pub fn rebase(
+    repo_path: Option<String>,
+    upstream: String,
+    onto: Option<String>,
+    todo: Option<String>,
+) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let todo = todo.map(std::fs::read_to_string).transpose()?;
+    let progress = repository.rebase(&upstream, onto.as_deref(), todo.as_deref())?;
+    print_progress(&progress, "rebase");
+    Ok(())
+}
+
+pub fn cherry_pick(repo_path: Option<String>, revisions: Vec<String>) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let progress = repository.cherry_pick(&revisions)?;
+    print_progress(&progress, "cherry-pick");
+    Ok(())
+}
+
+pub fn revert(repo_path: Option<String>, revisions: Vec<String>) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let progress = repository.revert(&revisions)?;
+    print_progress(&progress, "revert");
+    Ok(())
+}
+
+pub fn sequence_continue(repo_path: Option<String>, operation: Operation) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let progress = repository.sequence_continue(operation)?;
+    print_progress(&progress, operation.as_str());
+    Ok(())
+}
+
+pub fn sequence_skip(repo_path: Option<String>, operation: Operation) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    let progress = repository.sequence_skip(operation)?;
+    print_progress(&progress, operation.as_str());
+    Ok(())
+}
+
+pub fn sequence_abort(repo_path: Option<String>, operation: Operation) -> anyhow::Result<()> {
+    let mut repository = Repository::open(repo_path)?;
+    repository.sequence_abort(operation)?;
+    println!("Aborted {}", operation.as_str());
+    Ok(())
+}
+
 pub async fn push(repo_path: Option<String>, url: Option<String>) -> anyhow::Result<()> {
     let mut repository = Repository::open(repo_path)?;
     repository.push(url).await?;
@@ -105,8 +557,301 @@ pub fn status(repo_path: Option<String>) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub async fn auth(repo_path: Option<String>, url: Option<String>) -> anyhow::Result<()> {
+pub async fn auth(
+    repo_path: Option<String>,
+    url: Option<String>,
+    register: bool,
+    key: Option<Option<String>>,
+    scopes: Vec<String>,
+) -> anyhow::Result<()> {
     let mut repository = Repository::open(repo_path)?;
-    repository.auth(url).await?;
+    let method = match key {
+        Some(path) => AuthMethod::Key(path.map(PathBuf::from)),
+        None => {
+            let password = rpassword::prompt_password("Password: ")?;
+            if register {
+                let confirmation = rpassword::prompt_password("Confirm password: ")?;
+                anyhow::ensure!(password == confirmation, "The passwords do not match");
+            }
+            AuthMethod::Password(password)
+        }
+    };
+    repository.auth(url, method, register, scopes).await?;
+    Ok(())
+}
+
+pub fn logout(repo_path: Option<String>, url: Option<String>) -> anyhow::Result<()> {
+    match Repository::logout(repo_path, url)? {
+        true => println!("Removed the access token."),
+        false => println!("No access token stored for this server."),
+    }
+    Ok(())
+}
+
+pub async fn auth_status(repo_path: Option<String>, url: Option<String>) -> anyhow::Result<()> {
+    let status = Repository::auth_status(repo_path, url).await?;
+    println!("Logged in as {} <{}>", status.user_name, status.user_email);
+    for token in status.tokens {
+        let marker = if token.id == status.token_id { "*" } else { " " };
+        println!(
+            "{} {} {:<16} issued {}, expires {}",
+            marker,
+            token.id,
+            token.scopes.join(","),
+            token.issued_at.format("%Y-%m-%d %H:%M"),
+            token.expires_at.format("%Y-%m-%d %H:%M"),
+        );
+    }
+    Ok(())
+}
+
+pub async fn refresh_token(repo_path: Option<String>, url: Option<String>) -> anyhow::Result<()> {
+    let expires_at = Repository::refresh_token(repo_path, url).await?;
+    println!(
+        "Stored a new access token, expires {}.",
+        expires_at.format("%Y-%m-%d %H:%M")
+    );
+    Ok(())
+}
+
+pub async fn revoke_tokens(
+    repo_path: Option<String>,
+    url: Option<String>,
+    id: Option<String>,
+    all: bool,
+) -> anyhow::Result<()> {
+    let revoked = Repository::revoke_tokens(repo_path, url, id, all).await?;
+    println!("Revoked {revoked} access token(s).");
+    Ok(())
+}
+
+fn print_access(access: &RepositoryAccess) {
+    println!("Owner: {}", access.owner);
+    println!("Visibility: {}", access.visibility);
+    if access.collaborators.is_empty() {
+        println!("No collaborators.");
+    }
+    for collaborator in &access.collaborators {
+        println!("{:<6} {}", collaborator.role, collaborator.user_email);
+    }
+}
+
+pub async fn show_access(repo_path: Option<String>, url: Option<String>) -> anyhow::Result<()> {
+    print_access(&Repository::access(repo_path, url).await?);
+    Ok(())
+}
+
+pub async fn set_visibility(
+    repo_path: Option<String>,
+    url: Option<String>,
+    visibility: String,
+) -> anyhow::Result<()> {
+    print_access(&Repository::set_visibility(repo_path, url, visibility).await?);
+    Ok(())
+}
+
+pub async fn add_collaborator(
+    repo_path: Option<String>,
+    url: Option<String>,
+    user_email: String,
+    role: String,
+) -> anyhow::Result<()> {
+    print_access(&Repository::add_collaborator(repo_path, url, user_email, role).await?);
+    Ok(())
+}
+
+pub async fn remove_collaborator(
+    repo_path: Option<String>,
+    url: Option<String>,
+    user_email: String,
+) -> anyhow::Result<()> {
+    print_access(&Repository::remove_collaborator(repo_path, url, user_email).await?);
+    Ok(())
+}
+
+fn print_remote(repository: &RemoteRepository) {
+    println!("{}/{}", repository.namespace, repository.name);
+    if !repository.description.is_empty() {
+        println!("Description: {}", repository.description);
+    }
+    println!("Default branch: {}", repository.default_branch);
+    println!("Visibility: {}", repository.visibility);
+    match repository.created_at {
+        Some(created_at) => println!("Created: {}", created_at.format("%Y-%m-%d %H:%M")),
+        None => println!("Created: unknown"),
+    }
+}
+
+pub async fn create_remote(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+    description: String,
+    default_branch: String,
+    private: bool,
+) -> anyhow::Result<()> {
+    let repository =
+        Repository::create_remote(repo_path, url, name, description, default_branch, private)
+            .await?;
+    print_remote(&repository);
+    Ok(())
+}
+
+pub async fn list_remote(
+    repo_path: Option<String>,
+    url: Option<String>,
+    namespace: Option<String>,
+) -> anyhow::Result<()> {
+    let repositories = Repository::list_remote(repo_path, url, namespace).await?;
+    if repositories.is_empty() {
+        println!("No repositories.");
+    }
+    for repository in repositories {
+        println!(
+            "{:<8} {}/{}  {}",
+            repository.visibility, repository.namespace, repository.name, repository.description
+        );
+    }
+    Ok(())
+}
+
+pub async fn describe_remote(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+) -> anyhow::Result<()> {
+    print_remote(&Repository::describe_remote(repo_path, url, name).await?);
+    Ok(())
+}
+
+pub async fn rename_remote(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+    new_name: String,
+) -> anyhow::Result<()> {
+    let repository = Repository::rename_remote(repo_path, url, name, new_name).await?;
+    println!("Renamed to {}/{}.", repository.namespace, repository.name);
+    Ok(())
+}
+
+pub async fn delete_remote(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+    yes: bool,
+) -> anyhow::Result<()> {
+    if !yes {
+        print!("Delete {name} and everything pushed to it? [y/N] ");
+        io::stdout().flush()?;
+        let mut answer = String::new();
+        io::stdin().lock().read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            println!("Aborted.");
+            return Ok(());
+        }
+    }
+    let repository = Repository::delete_remote(repo_path, url, name).await?;
+    println!("Deleted {}/{}.", repository.namespace, repository.name);
+    Ok(())
+}
+
+fn print_organization(organization: &Organization) {
+    println!(
+        "{}, created {}",
+        organization.name,
+        organization.created_at.format("%Y-%m-%d %H:%M")
+    );
+    for member in &organization.members {
+        println!("{:<6} {}", member.role, member.user_email);
+    }
+}
+
+pub async fn create_organization(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+) -> anyhow::Result<()> {
+    print_organization(&Repository::create_organization(repo_path, url, name).await?);
+    Ok(())
+}
+
+pub async fn set_organization_member(
+    repo_path: Option<String>,
+    url: Option<String>,
+    organization: String,
+    user_email: String,
+    role: Option<String>,
+) -> anyhow::Result<()> {
+    let organization =
+        Repository::set_organization_member(repo_path, url, organization, user_email, role)
+            .await?;
+    print_organization(&organization);
+    Ok(())
+}
+
+pub async fn remote_refs(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+) -> anyhow::Result<()> {
+    let refs = Repository::remote_refs(repo_path, url, name).await?;
+    if refs.refs.is_empty() {
+        println!("No branches.");
+    }
+    for (name, commit) in refs.refs {
+        let marker = if name == refs.head { "(*) " } else { "    " };
+        let branch = name.strip_prefix("refs/heads/").unwrap_or(&name);
+        println!("{}{} {}", marker, branch, commit);
+    }
+    Ok(())
+}
+
+pub async fn remote_log(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+    revision: Option<String>,
+    max_count: usize,
+) -> anyhow::Result<()> {
+    let commits = Repository::remote_log(repo_path, url, name, revision, max_count).await?;
+    for commit in commits {
+        println!("commit {}", commit.hash);
+        if let Some(author) = &commit.author {
+            println!("Author: {} <{}>", author.name, author.email);
+            println!("Date:   {}", author.time.format("%a %b %e %H:%M:%S %Y %z"));
+        }
+        println!();
+        for line in commit.message.lines() {
+            println!("    {line}");
+        }
+        println!();
+    }
+    Ok(())
+}
+
+pub async fn remote_tree(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+    revision: Option<String>,
+    path: String,
+) -> anyhow::Result<()> {
+    for entry in Repository::remote_tree(repo_path, url, name, revision, path).await? {
+        let kind = if entry.is_dir() { "tree" } else { "blob" };
+        println!("{} {} {}\t{}", entry.mode, kind, entry.hash, entry.name);
+    }
+    Ok(())
+}
+
+pub async fn remote_show(
+    repo_path: Option<String>,
+    url: Option<String>,
+    name: String,
+    revision: Option<String>,
+    path: String,
+) -> anyhow::Result<()> {
+    let content = Repository::remote_file(repo_path, url, name, revision, path).await?;
+    io::stdout().write_all(&content)?;
     Ok(())
 }
\ No newline at end of file
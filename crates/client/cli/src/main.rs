@@ -1,13 +1,16 @@
-use crate::cli::{BranchCommands, Cli, Commands};
+use crate::cli::{AccessCommands, AuthCommands, OrgCommands, RepoCommands, BranchCommands, Cli, Commands, StashCommands};
 use clap::Parser;
-use flux_core::internals::repository::Repository;
+use commands::ConfigAction;
+use flux_core::internals::config::ConfigLevel;
+use flux_core::internals::repository::{CommitOptions, LogOptions, Repository};
+use flux_core::internals::sequencer::Operation;
 
 pub mod cli;
 pub mod commands;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(commands::expand_alias(std::env::args().collect()));
     let repo_path = cli.repo_path.clone();
 
     match cli.command {
@@ -17,6 +20,34 @@ async fn main() -> anyhow::Result<()> {
         Commands::Set { key, value } => {
             commands::set(repo_path, key, value)?;
         }
+        Commands::Config {
+            global,
+            system,
+            local,
+            get,
+            unset,
+            list,
+            show_origin,
+            key,
+            value,
+        } => {
+            let level = match (global, system, local) {
+                (true, _, _) => Some(ConfigLevel::Global),
+                (_, true, _) => Some(ConfigLevel::System),
+                (_, _, true) => Some(ConfigLevel::Local),
+                _ => None,
+            };
+            let action = match (get, unset, key.zip(value)) {
+                (Some(key), _, _) => ConfigAction::Get(key),
+                (_, Some(key), _) => ConfigAction::Unset(key),
+                (_, _, Some((key, value))) => ConfigAction::Set(key, value),
+                _ if list => ConfigAction::List,
+                _ => anyhow::bail!(
+                    "Nothing to do, use --get, --unset, --list or give a key and a value"
+                ),
+            };
+            commands::config(repo_path, level, action, show_origin)?;
+        }
         Commands::CatFile { object_hash, .. } => {
             commands::cat_file(repo_path, object_hash)?;
         }
@@ -30,17 +61,141 @@ async fn main() -> anyhow::Result<()> {
         } => {
             commands::commit_tree(repo_path, tree_hash, message, parent_hash)?;
         }
-        Commands::Add { path } => {
-            commands::add(repo_path, path)?;
+        Commands::Add { path, patch } => {
+            if patch {
+                commands::add_patch(repo_path, path)?;
+            } else {
+                commands::add(repo_path, path)?;
+            }
+        }
+        Commands::Rm {
+            path,
+            cached,
+            recursive,
+        } => {
+            commands::remove(repo_path, path, cached, recursive)?;
+        }
+        Commands::Mv {
+            source,
+            destination,
+        } => {
+            commands::move_path(repo_path, source, destination)?;
+        }
+        Commands::Commit {
+            message,
+            amend,
+            no_edit,
+            allow_empty,
+            no_verify,
+            sign,
+            no_sign,
+            author,
+            date,
+        } => {
+            let options = CommitOptions {
+                sign: (sign || no_sign).then_some(sign),
+                author,
+                date,
+                amend,
+                allow_empty,
+                no_verify,
+            };
+            commands::commit(repo_path, message, no_edit, options)?;
+        }
+        Commands::VerifyCommit { revisions } => {
+            commands::verify_commit(repo_path, revisions)?;
+        }
+        Commands::SigningKey { path } => {
+            commands::signing_key(repo_path, path)?;
         }
-        Commands::Delete { path } => {
-            commands::remove(repo_path, path)?;
+        Commands::Log {
+            revision,
+            follow,
+            show_signature,
+            date,
+        } => {
+            let options = LogOptions {
+                follow,
+                show_signature,
+                date,
+            };
+            commands::log(repo_path, revision, options)?;
+        }
+        Commands::Diff {
+            revisions,
+            cached,
+            name_status,
+            find_renames,
+            find_copies,
+            no_renames,
+        } => {
+            let renames = (!no_renames).then_some((find_renames, find_copies));
+            commands::diff(repo_path, revisions, cached, name_status, renames)?;
         }
-        Commands::Commit { message } => {
-            commands::commit(repo_path, message)?;
+        Commands::Reflog { reference } => {
+            commands::reflog(repo_path, reference)?;
         }
-        Commands::Log {} => {
-            commands::log(repo_path)?;
+        Commands::Gc {
+            prune,
+            reflog_expire,
+        } => {
+            commands::gc(repo_path, prune, reflog_expire)?;
+        }
+        Commands::Prune { expire, dry_run } => {
+            commands::prune(repo_path, expire, dry_run)?;
+        }
+        Commands::Fsck { strict } => {
+            commands::fsck(repo_path, strict)?;
+        }
+        Commands::Rebase {
+            upstream,
+            onto,
+            todo,
+            continue_,
+            abort,
+            skip,
+        } => {
+            if continue_ {
+                commands::sequence_continue(repo_path, Operation::Rebase)?;
+            } else if abort {
+                commands::sequence_abort(repo_path, Operation::Rebase)?;
+            } else if skip {
+                commands::sequence_skip(repo_path, Operation::Rebase)?;
+            } else if let Some(upstream) = upstream {
+                commands::rebase(repo_path, upstream, onto, todo)?;
+            }
+        }
+        Commands::CherryPick {
+            revisions,
+            continue_,
+            abort,
+            skip,
+        } => {
+            if continue_ {
+                commands::sequence_continue(repo_path, Operation::CherryPick)?;
+            } else if abort {
+                commands::sequence_abort(repo_path, Operation::CherryPick)?;
+            } else if skip {
+                commands::sequence_skip(repo_path, Operation::CherryPick)?;
+            } else {
+                commands::cherry_pick(repo_path, revisions)?;
+            }
+        }
+        Commands::Revert {
+            revisions,
+            continue_,
+            abort,
+            skip,
+        } => {
+            if continue_ {
+                commands::sequence_continue(repo_path, Operation::Revert)?;
+            } else if abort {
+                commands::sequence_abort(repo_path, Operation::Revert)?;
+            } else if skip {
+                commands::sequence_skip(repo_path, Operation::Revert)?;
+            } else {
+                commands::revert(repo_path, revisions)?;
+            }
         }
         Commands::Branch { subcommand } => match subcommand {
             BranchCommands::Show {} => {
@@ -56,6 +211,29 @@ async fn main() -> anyhow::Result<()> {
                 commands::switch_branch(repo_path, name, force)?;
             }
         },
+        Commands::Stash { subcommand } => match subcommand {
+            None => {
+                commands::stash_push(repo_path, None)?;
+            }
+            Some(StashCommands::Push { message }) => {
+                commands::stash_push(repo_path, message)?;
+            }
+            Some(StashCommands::Pop { stash, index }) => {
+                commands::stash_pop(repo_path, stash, index)?;
+            }
+            Some(StashCommands::Apply { stash, index }) => {
+                commands::stash_apply(repo_path, stash, index)?;
+            }
+            Some(StashCommands::List {}) => {
+                commands::stash_list(repo_path)?;
+            }
+            Some(StashCommands::Drop { stash }) => {
+                commands::stash_drop(repo_path, stash)?;
+            }
+            Some(StashCommands::Show { stash, patch }) => {
+                commands::stash_show(repo_path, stash, patch)?;
+            }
+        },
         Commands::Push { url } => {
             commands::push(repo_path, url).await?;
         }
@@ -68,9 +246,141 @@ async fn main() -> anyhow::Result<()> {
         Commands::Status {  } => {
             commands::status(repo_path)?;
         },
-        Commands::Auth { url } => {
-            commands::auth(repo_path, url).await?;
-        }
+        Commands::Auth {
+            subcommand,
+            url,
+            register,
+            key,
+            scopes,
+        } => match subcommand {
+            None => {
+                commands::auth(repo_path, url, register, key, scopes).await?;
+            }
+            Some(AuthCommands::Logout { url }) => {
+                commands::logout(repo_path, url)?;
+            }
+            Some(AuthCommands::Status { url }) => {
+                commands::auth_status(repo_path, url).await?;
+            }
+            Some(AuthCommands::Refresh { url }) => {
+                commands::refresh_token(repo_path, url).await?;
+            }
+            Some(AuthCommands::Revoke { id, all, url }) => {
+                commands::revoke_tokens(repo_path, url, id, all).await?;
+            }
+        },
+        Commands::Access { subcommand } => match subcommand {
+            None => {
+                commands::show_access(repo_path, None).await?;
+            }
+            Some(AccessCommands::List { url }) => {
+                commands::show_access(repo_path, url).await?;
+            }
+            Some(AccessCommands::Visibility { visibility, url }) => {
+                commands::set_visibility(repo_path, url, visibility).await?;
+            }
+            Some(AccessCommands::Add {
+                user_email,
+                role,
+                url,
+            }) => {
+                commands::add_collaborator(repo_path, url, user_email, role).await?;
+            }
+            Some(AccessCommands::Remove { user_email, url }) => {
+                commands::remove_collaborator(repo_path, url, user_email).await?;
+            }
+        },
+        Commands::Repo { subcommand } => match subcommand {
+            RepoCommands::Create {
+                name,
+                description,
+                default_branch,
+                private,
+                url,
+            } => {
+                commands::create_remote(
+                    repo_path,
+                    url,
+                    name,
+                    description.unwrap_or_default(),
+                    default_branch.unwrap_or_default(),
+                    private,
+                )
+                .await?;
+            }
+            RepoCommands::List { namespace, url } => {
+                commands::list_remote(repo_path, url, namespace).await?;
+            }
+            RepoCommands::Describe { name, url } => {
+                commands::describe_remote(repo_path, url, name).await?;
+            }
+            RepoCommands::Rename {
+                name,
+                new_name,
+                url,
+            } => {
+                commands::rename_remote(repo_path, url, name, new_name).await?;
+            }
+            RepoCommands::Delete { name, yes, url } => {
+                commands::delete_remote(repo_path, url, name, yes).await?;
+            }
+            RepoCommands::Refs { name, url } => {
+                commands::remote_refs(repo_path, url, name).await?;
+            }
+            RepoCommands::Log {
+                name,
+                revision,
+                max_count,
+                url,
+            } => {
+                commands::remote_log(repo_path, url, name, revision, max_count).await?;
+            }
+            RepoCommands::Tree {
+                name,
+                path,
+                revision,
+                url,
+            } => {
+                commands::remote_tree(repo_path, url, name, revision, path.unwrap_or_default())
+                    .await?;
+            }
+            RepoCommands::Show {
+                name,
+                path,
+                revision,
+                url,
+            } => {
+                commands::remote_show(repo_path, url, name, revision, path).await?;
+            }
+        },
+        Commands::Org { subcommand } => match subcommand {
+            OrgCommands::Create { name, url } => {
+                commands::create_organization(repo_path, url, name).await?;
+            }
+            OrgCommands::Add {
+                organization,
+                user_email,
+                role,
+                url,
+            } => {
+                commands::set_organization_member(
+                    repo_path,
+                    url,
+                    organization,
+                    user_email,
+                    Some(role),
+                )
+                .await?;
+            }
+            OrgCommands::Remove {
+                organization,
+                user_email,
+                url,
+            } => {
+                commands::set_organization_member(repo_path, url, organization, user_email, None)
+                    .await?;
+            }
+        },
     }
 
     Ok(())
@@ -5,6 +5,7 @@ use std::{
 };
 use thiserror::Error;
 
+use crate::internals::config::ConfigLevel;
 use crate::utils;
 
 #[derive(Error, Debug)]
@@ -162,14 +163,28 @@ pub enum ConfigError {
     #[error(transparent)]
     Io(#[from] IoError),
 
-    #[error("Failed to create toml from string. {0}")]
-    TomlFromString(#[from] toml::de::Error),
+    #[error("Failed to parse the configuration file {path}. {source}")]
+    Parse {
+        path: PathBuf,
+        #[source]
+        source: toml_edit::TomlError,
+    },
 
-    #[error("The variable {0} must be set, try using 'flux set {0} ...'")]
+    #[error("The variable {0} must be set, try using 'flux config {0} ...'")]
     NotSet(String),
 
-    #[error("The field {0} is unsupported by the configuration.")]
-    UnsupportedField(String),
+    #[error("Invalid key '{0}', expected <section>[.<subsection>].<name>.")]
+    InvalidKey(String),
+
+    #[error("There is no {0} configuration file here.")]
+    MissingLevel(ConfigLevel),
+
+    #[error("Invalid value '{value}' for {key}. {expected}")]
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: &'static str,
+    },
 }
 
 #[derive(Debug, Error)]
@@ -188,6 +203,85 @@ pub enum RefsError {
 
     #[error("Cannot delete the current branch '{0}'. Switch to a different branch and try again.")]
     DeleteCurrentBranch(String),
+
+    #[error("Invalid reflog entry for '{ref_name}': {line}")]
+    InvalidReflog { ref_name: String, line: String },
+}
+
+#[derive(Debug, Error)]
+pub enum StashError {
+    #[error(transparent)]
+    Refs(#[from] RefsError),
+
+    #[error("No local changes to save.")]
+    NoLocalChanges,
+
+    #[error("Cannot stash changes before the initial commit.")]
+    NoInitialCommit,
+
+    #[error("No stash entries found.")]
+    Empty,
+
+    #[error("Stash entry stash@{{{0}}} does not exist.")]
+    MissingEntry(usize),
+
+    #[error("Invalid stash reference '{0}', expected 'stash@{{N}}' or 'N'.")]
+    InvalidReference(String),
+
+    #[error("Commit {0} is not a valid stash entry.")]
+    InvalidEntry(String),
+
+    #[error("Conflicts while restoring the stashed index. Try again without --index.")]
+    IndexConflicts,
+}
+
+#[derive(Debug, Error)]
+pub enum HookError {
+    #[error("The {hook} hook failed ({reason}), aborting.")]
+    Failed { hook: String, reason: String },
+}
+
+#[derive(Debug, Error)]
+pub enum CredentialError {
+    #[error(transparent)]
+    Io(#[from] IoError),
+
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+
+    #[error("Cannot find the home directory for the credentials file, set FLUX_CREDENTIALS.")]
+    MissingHome,
+
+    #[error("The credential helper {helper} failed ({reason}).")]
+    Helper { helper: String, reason: String },
+}
+
+#[derive(Debug, Error)]
+pub enum SequencerError {
+    #[error(transparent)]
+    Io(#[from] IoError),
+
+    #[error("A {0} is already in progress. Use --continue, --skip or --abort.")]
+    InProgress(&'static str),
+
+    #[error("No {0} in progress.")]
+    NotInProgress(&'static str),
+
+    #[error("Invalid todo line '{0}', expected '<action> <commit> [message]'.")]
+    InvalidTodo(String),
+
+    #[error("Cannot squash '{0}' without a previous commit.")]
+    NothingToSquash(String),
+
+    #[error("Resolve the conflicts in {} and add them before continuing.", paths.join(", "))]
+    UnresolvedConflicts { paths: Vec<String> },
+
+    #[error("The {operation} was started on branch '{expected}' but '{actual}' is checked out.")]
+    BranchChanged {
+        operation: &'static str,
+        expected: String,
+        actual: String,
+    },
 }
 
 #[derive(Debug, Error)]
@@ -199,6 +293,13 @@ pub enum GrpcClientError {
         source: tonic::transport::Error,
     },
 
+    #[error("Failed to read the CA bundle '{}'. {source}", path.display())]
+    CaBundle {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
     #[error("Failed push to remote repoository. {0}")]
     Push(#[source] tonic::Status),
 
@@ -212,8 +313,38 @@ pub enum GrpcClientError {
     #[error("Failed to clone repository.{0}")]
     Clone(#[source] tonic::Status),
 
-    #[error("Failed authentication for remote server.")]
-    Auth(#[source] tonic::Status)
+    #[error("Failed authentication for remote server. {}", .0.message())]
+    Auth(#[source] tonic::Status),
+
+    #[error("Failed to manage repository access. {}", .0.message())]
+    Access(#[source] tonic::Status),
+
+    #[error("Remote repository request failed. {}", .0.message())]
+    Repository(#[source] tonic::Status),
+
+    #[error("Failed to browse remote repository. {}", .0.message())]
+    Browse(#[source] tonic::Status),
+}
+
+#[derive(Debug, Error)]
+pub enum SigningError {
+    #[error(transparent)]
+    Io(#[from] IoError),
+
+    #[error("Invalid SSH key or signature. {0}")]
+    Key(#[from] ssh_key::Error),
+
+    #[error("Key '{}' already exists.", .0.display())]
+    KeyExists(PathBuf),
+
+    #[error("Signing key '{}' is encrypted, only unencrypted keys are supported.", .0.display())]
+    EncryptedKey(PathBuf),
+
+    #[error("Unsupported {0} signing key, only ed25519 keys are supported.")]
+    UnsupportedKey(String),
+
+    #[error("Invalid allowed signers line: '{0}'.")]
+    InvalidAllowedSigner(String),
 }
 
 #[derive(Debug, Error)]
@@ -243,7 +374,7 @@ pub enum RepositoryError {
     #[error("There was an error trying to operate on path: '{}'.", path.display())]
     PathName { path: PathBuf },
 
-    #[error("Repository has uncommited changes, commit them and try again or use the --force flag")]
+    #[error("Repository has uncommited changes, commit or stash them and try again or use the --force flag")]
     UncommitedChanges,
 
     #[error("User credentials are not set. {0}")]
@@ -265,7 +396,7 @@ pub enum RepositoryError {
     IndexError(#[from] IndexError),
 
     #[error(
-        "Missing origin for remote repository. Specify it in the push command like 'flux push http://originurl' or set it in the config with 'flux set origin http://originurl'"
+        "Missing origin for remote repository. Specify it in the push command like 'flux push http://originurl' or set it in the config with 'flux config remote.origin.url http://originurl'"
     )]
     MissingOrigin(),
 
@@ -275,8 +406,81 @@ pub enum RepositoryError {
     #[error(transparent)]
     Grpc(#[from] GrpcClientError),
 
-    #[error("Missing access token from remote server. Try running flux auth and try again.")]
-    MissingToken
+    #[error("Missing access token for {0}. Try running flux auth and try again.")]
+    MissingToken(String),
+
+    #[error("The remote rejected the push. {0}")]
+    PushRejected(String),
+
+    #[error(transparent)]
+    Stash(#[from] StashError),
+
+    #[error(transparent)]
+    Sequencer(#[from] SequencerError),
+
+    #[error(transparent)]
+    Signing(#[from] SigningError),
+
+    #[error(transparent)]
+    Hook(#[from] HookError),
+
+    #[error(transparent)]
+    Credential(#[from] CredentialError),
+
+    #[error("Unknown revision '{0}'.")]
+    UnknownRevision(String),
+
+    #[error("Short revision '{0}' is ambiguous.")]
+    AmbiguousRevision(String),
+
+    #[error("Your local changes to the following files would be overwritten: {}. Commit or stash them and try again.", paths.join(", "))]
+    LocalChangesOverwritten { paths: Vec<String> },
+
+    #[error("Path '{0}' did not match any tracked or work tree file.")]
+    PathNotFound(String),
+
+    #[error("Path '{path}' does not exist in '{revision}'.")]
+    PathNotInRevision { path: String, revision: String },
+
+    #[error("Path '{0}' is not a directory.")]
+    NotADirectory(String),
+
+    #[error("Path '{0}' is not a file.")]
+    NotAFile(String),
+
+    #[error("Not removing '{0}' recursively without -r.")]
+    RecursiveRemove(String),
+
+    #[error("Destination '{0}' already exists.")]
+    PathExists(String),
+
+    #[error("Cannot move '{src}' to '{dst}', a directory cannot be moved inside itself.")]
+    MoveIntoItself { src: String, dst: String },
+
+    #[error("Invalid identity '{0}', expected 'Name <email>'.")]
+    InvalidIdentity(String),
+
+    #[error("Invalid date '{0}'. Use '<timestamp> <+hhmm>', '@<timestamp>', RFC 3339, RFC 2822 or 'YYYY-MM-DD HH:MM:SS [+hhmm]'.")]
+    InvalidDate(String),
+
+    #[error("You have nothing to amend, there are no commits yet.")]
+    NothingToAmend,
+
+    #[error("Aborting commit due to empty commit message.")]
+    EmptyMessage,
+
+    #[error("Editor '{editor}' failed: {reason}")]
+    Editor { editor: String, reason: String },
+
+    #[error("Cannot split binary file '{0}' into hunks.")]
+    BinaryFile(String),
+
+    #[error("Hunk {index} does not exist, '{path}' has {count} hunks.")]
+    InvalidHunk {
+        path: String,
+        index: usize,
+        count: usize,
+    },
 }
 
 impl RepositoryError {
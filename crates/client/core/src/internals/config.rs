@@ -1,175 +1,853 @@
 use crate::error;
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::Write;
+use std::fmt;
 use std::path::PathBuf;
-use std::str::FromStr;
-use std::{fmt, fs};
+use std::{env, fs};
+use toml_edit::{Decor, DocumentMut, Item, Table, Value};
 
-#[derive(Debug, PartialEq, Eq, Hash)]
-pub enum Field {
-    UserName,
-    UserEmail,
-    Origin,
-    AccessToken
+pub type Result<T> = std::result::Result<T, error::ConfigError>;
+
+/// Type of a setting, values are validated against it when they are set or read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueKind {
+    String,
+    Bool,
+    Int {
+        min: i64,
+        max: i64,
+    },
+    /// A path, `~/` is expanded to the home directory when read.
+    Path,
+    /// Comma separated on the command line, an array in the file.
+    List,
 }
 
-impl FromStr for Field {
-    type Err = ();
+impl ValueKind {
+    fn expected(self) -> &'static str {
+        match self {
+            ValueKind::String | ValueKind::Path => "Expected a string.",
+            ValueKind::Bool => "Expected true or false.",
+            ValueKind::Int { .. } => "Expected an integer in the allowed range.",
+            ValueKind::List => "Expected a comma separated list.",
+        }
+    }
+
+    /// Converts a value given as text into its TOML form.
+    fn parse(self, key: &str, raw: &str) -> Result<Value> {
+        let invalid = || error::ConfigError::InvalidValue {
+            key: key.to_string(),
+            value: raw.to_string(),
+            expected: self.expected(),
+        };
+        let raw = raw.trim();
+
+        Ok(match self {
+            ValueKind::String | ValueKind::Path => Value::from(raw),
+            ValueKind::Bool => match raw {
+                "true" | "yes" | "on" | "1" => Value::from(true),
+                "false" | "no" | "off" | "0" => Value::from(false),
+                _ => return Err(invalid()),
+            },
+            ValueKind::Int { min, max } => {
+                let int: i64 = raw.parse().map_err(|_| invalid())?;
+                if !(min..=max).contains(&int) {
+                    return Err(invalid());
+                }
+                Value::from(int)
+            }
+            ValueKind::List => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .collect::<toml_edit::Array>()
+                .into(),
+        })
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "user_name" => Ok(Field::UserName),
-            "user_email" => Ok(Field::UserEmail),
-            "origin" => Ok(Field::Origin),
-            "access_token" => Ok(Field::AccessToken),
-            _ => Err(()),
+/// A setting flux knows about. `*` in the key stands for any subsection, e.g. the name of
+/// the remote in `remote.*.url`.
+#[derive(Debug)]
+pub struct Setting {
+    pub key: &'static str,
+    pub kind: ValueKind,
+    pub default: Option<&'static str>,
+    pub description: &'static str,
+}
+
+/// Known settings. Keys outside of this list are accepted as strings, so scripts and new
+/// features can store their own values.
+pub const SETTINGS: &[Setting] = &[
+    Setting {
+        key: "user.name",
+        kind: ValueKind::String,
+        default: None,
+        description: "Name recorded as author and committer.",
+    },
+    Setting {
+        key: "user.email",
+        kind: ValueKind::String,
+        default: None,
+        description: "Email recorded as author and committer.",
+    },
+    Setting {
+        key: "user.signing_key",
+        kind: ValueKind::Path,
+        default: None,
+        description: "Private ed25519 key commits are signed with.",
+    },
+    Setting {
+        key: "core.editor",
+        kind: ValueKind::String,
+        default: None,
+        description: "Editor for commit messages, before $VISUAL and $EDITOR.",
+    },
+    Setting {
+        key: "commit.sign",
+        kind: ValueKind::Bool,
+        default: Some("false"),
+        description: "Sign every commit with user.signing_key.",
+    },
+    Setting {
+        key: "diff.rename_threshold",
+        kind: ValueKind::Int { min: 0, max: 100 },
+        default: Some("50"),
+        description: "Similarity percentage for a file to count as renamed.",
+    },
+    Setting {
+        key: "signing.allowed_signers",
+        kind: ValueKind::Path,
+        default: None,
+        description: "Allowed signers file, .flux/allowed_signers when unset.",
+    },
+    Setting {
+        key: "remote.*.url",
+        kind: ValueKind::String,
+        default: None,
+        description: "Address of the remote.",
+    },
+    Setting {
+        key: "branch.*.remote",
+        kind: ValueKind::String,
+        default: None,
+        description: "Remote the branch is pushed to.",
+    },
+    Setting {
+        key: "branch.*.merge",
+        kind: ValueKind::String,
+        default: None,
+        description: "Branch of the remote the branch tracks.",
+    },
+    Setting {
+        key: "credential.helper",
+        kind: ValueKind::String,
+        default: Some("store"),
+        description: "Where access tokens are kept, see `CredentialStore`.",
+    },
+    Setting {
+        key: "http.cainfo",
+        kind: ValueKind::Path,
+        default: None,
+        description: "CA bundle trusted for https remotes, besides the system roots.",
+    },
+    Setting {
+        key: "alias.*",
+        kind: ValueKind::String,
+        default: None,
+        description: "Command the alias expands to.",
+    },
+];
+
+/// Flat keys of the first configuration format and the settings they became.
+const LEGACY_KEYS: &[(&str, &str)] = &[
+    ("user_name", "user.name"),
+    ("user_email", "user.email"),
+    ("origin", "remote.origin.url"),
+    ("access_token", "remote.origin.token"),
+    ("rename_threshold", "diff.rename_threshold"),
+    ("signing_key", "user.signing_key"),
+    ("sign_commits", "commit.sign"),
+    ("allowed_signers", "signing.allowed_signers"),
+];
+
+/// A `<section>[.<subsection>].<name>` key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Key {
+    section: String,
+    subsection: Option<String>,
+    name: String,
+}
+
+impl Key {
+    /// Parses a key, also accepting the legacy flat keys.
+    fn parse(key: &str) -> Result<Self> {
+        let key = LEGACY_KEYS
+            .iter()
+            .find(|(legacy, _)| *legacy == key)
+            .map_or(key, |(_, key)| *key);
+        let invalid = || error::ConfigError::InvalidKey(key.to_string());
+
+        let (section, rest) = key.split_once('.').ok_or_else(invalid)?;
+        let (subsection, name) = match rest.rsplit_once('.') {
+            Some((subsection, name)) => (Some(subsection), name),
+            None => (None, rest),
+        };
+
+        let is_name = |part: &str| {
+            !part.is_empty()
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+        if !is_name(section) || !is_name(name) || subsection.is_some_and(str::is_empty) {
+            return Err(invalid());
         }
+
+        Ok(Self {
+            section: section.to_string(),
+            subsection: subsection.map(String::from),
+            name: name.to_string(),
+        })
+    }
+
+    /// Tables leading to the value, then its name.
+    fn path(&self) -> Vec<&str> {
+        let mut path = vec![self.section.as_str()];
+        path.extend(self.subsection.as_deref());
+        path.push(&self.name);
+        path
+    }
+
+    fn legacy(&self) -> Option<&'static str> {
+        let key = self.to_string();
+        LEGACY_KEYS
+            .iter()
+            .find(|(_, current)| *current == key)
+            .map(|(legacy, _)| *legacy)
+    }
+
+    fn setting(&self) -> Option<&'static Setting> {
+        SETTINGS.iter().find(|setting| {
+            let parts: Vec<&str> = setting.key.split('.').collect();
+            match parts.as_slice() {
+                [section, "*", name] => {
+                    *section == self.section && self.subsection.is_some() && *name == self.name
+                }
+                [section, "*"] => *section == self.section && self.subsection.is_none(),
+                [section, name] => {
+                    *section == self.section && self.subsection.is_none() && *name == self.name
+                }
+                _ => false,
+            }
+        })
+    }
+
+    fn kind(&self) -> ValueKind {
+        self.setting()
+            .map_or(ValueKind::String, |setting| setting.kind)
     }
 }
 
-impl fmt::Display for Field {
+impl fmt::Display for Key {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let s = match self {
-            Field::UserName => "user_name",
-            Field::UserEmail => "user_email",
-            Field::Origin => "origin",
-            Field::AccessToken => "access_token"
-        };
-        write!(f, "{}", s)
+        write!(f, "{}", self.path().join("."))
     }
 }
 
 pub struct Credentials {
     pub user_name: String,
     pub user_email: String,
-    pub access_token: Option<String>
 }
 
-#[derive(Debug)]
-pub struct Config {
-    path: PathBuf,
-    pub map: HashMap<Field, Option<String>>,
+/// Environment variable overriding the path of the system configuration file.
+pub const SYSTEM_CONFIG_ENV: &str = "FLUX_CONFIG_SYSTEM";
+/// Environment variable overriding the path of the global configuration file.
+pub const GLOBAL_CONFIG_ENV: &str = "FLUX_CONFIG_GLOBAL";
+
+/// Configuration files, from the least to the most specific. Values of later levels override
+/// the ones of earlier levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigLevel {
+    /// `/etc/fluxconfig`, shared by every user of the machine.
+    System,
+    /// `~/.fluxconfig`, shared by every repository of the user.
+    Global,
+    /// `.flux/config` of the repository.
+    Local,
 }
 
-impl Config {
-    pub fn empty_map() -> HashMap<Field, Option<String>> {
-        let mut map = HashMap::new();
-        for field in [Field::UserName, Field::UserEmail, Field::Origin] {
-            map.insert(field, None);
+impl ConfigLevel {
+    /// Path of the system or global file, `None` for the local one or without a home directory.
+    pub fn default_path(self) -> Option<PathBuf> {
+        match self {
+            ConfigLevel::System => Some(
+                env::var_os(SYSTEM_CONFIG_ENV)
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|| PathBuf::from("/etc/fluxconfig")),
+            ),
+            ConfigLevel::Global => env::var_os(GLOBAL_CONFIG_ENV)
+                .map(PathBuf::from)
+                .or_else(|| home_dir().map(|home| home.join(".fluxconfig"))),
+            ConfigLevel::Local => None,
         }
-        map
     }
+}
 
-    pub fn default(path: impl Into<PathBuf>) -> Result<Self, error::ConfigError> {
-        let path = path.into();
+impl fmt::Display for ConfigLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ConfigLevel::System => "system",
+            ConfigLevel::Global => "global",
+            ConfigLevel::Local => "local",
+        };
+        write!(f, "{}", s)
+    }
+}
 
-        let mut file = File::create(&path).map_err(|e| error::IoError::Create {
-            path: path.clone(),
-            source: e,
-        })?;
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
 
-        writeln!(
-            file,
-            "\
-# Configuration file for flux
-# Values can be set either by directly modifying the file or by using the set command.
-#
-# user_name  =
-# user_email =
-# origin ="
-        )
-        .map_err(|e| error::IoError::Write {
+/// A value together with the file it was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigEntry {
+    pub level: ConfigLevel,
+    pub path: PathBuf,
+    pub key: String,
+    pub value: String,
+}
+
+/// Removes a top-level legacy key, moving the comments above it to the item that follows so
+/// migrating a value does not lose them. Returns whether the key was set.
+fn remove_legacy(doc: &mut DocumentMut, legacy: &str) -> bool {
+    let root = doc.as_table_mut();
+    let Some(index) = root.iter().position(|(key, _)| key == legacy) else {
+        return false;
+    };
+    let comments = root
+        .key(legacy)
+        .and_then(|key| key.leaf_decor().prefix()?.as_str())
+        .filter(|prefix| prefix.contains('#'))
+        .map(String::from);
+    root.remove(legacy);
+    let Some(comments) = comments else {
+        return true;
+    };
+
+    let prepend = |decor: &mut Decor| {
+        let prefix = decor.prefix().and_then(|p| p.as_str()).unwrap_or_default();
+        decor.set_prefix(format!("{comments}{prefix}"));
+    };
+    if let Some((mut key, _)) = root
+        .iter_mut()
+        .skip(index)
+        .find(|(_, item)| item.is_value())
+    {
+        prepend(key.leaf_decor_mut());
+    } else if let Some(table) = root
+        .iter_mut()
+        .filter_map(|(_, item)| item.as_table_mut())
+        .min_by_key(|table| table.position())
+    {
+        prepend(table.decor_mut());
+    } else {
+        let trailing = doc.trailing().as_str().unwrap_or_default();
+        doc.set_trailing(format!("{comments}{trailing}"));
+    }
+    true
+}
+
+/// Text form of a value, lists are comma separated.
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.value().clone(),
+        Value::Array(array) => array
+            .iter()
+            .map(display_value)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.clone().decorated("", "").to_string(),
+    }
+}
+
+#[derive(Debug)]
+struct ConfigFile {
+    level: ConfigLevel,
+    path: PathBuf,
+    /// Comments of a file without values. A document keeps them after its last item, so they
+    /// are held here to stay above the values set later.
+    header: String,
+    doc: DocumentMut,
+}
+
+impl ConfigFile {
+    /// Reads a configuration file, a missing file has no values.
+    fn read(level: ConfigLevel, path: PathBuf) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self {
+                level,
+                path,
+                header: String::new(),
+                doc: DocumentMut::new(),
+            });
+        }
+
+        let content = fs::read_to_string(&path).map_err(|e| error::IoError::Read {
             path: path.clone(),
             source: e,
         })?;
+        let mut doc =
+            content
+                .parse::<DocumentMut>()
+                .map_err(|source| error::ConfigError::Parse {
+                    path: path.clone(),
+                    source,
+                })?;
+        let mut header = String::new();
+        if doc.as_table().is_empty() {
+            header = doc.trailing().as_str().unwrap_or_default().to_string();
+            doc.set_trailing("");
+        }
 
         Ok(Self {
+            level,
             path,
-            map: Self::empty_map(),
+            header,
+            doc,
         })
     }
 
-    pub fn from(path: impl Into<PathBuf>) -> Result<Self, error::ConfigError> {
-        let path = path.into();
+    /// Writes the document back, keeping its comments and layout.
+    fn write(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(|e| error::IoError::Create {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
 
-        let content = fs::read_to_string(&path).map_err(|e| error::IoError::Read {
-            path: path.clone(),
+        let temp_path = self.path.with_extension("tmp");
+        let content = match (self.header.is_empty(), self.doc.as_table().is_empty()) {
+            (false, false) => format!("{}\n{}", self.header, self.doc),
+            (false, true) => self.header.clone(),
+            (true, _) => self.doc.to_string(),
+        };
+        fs::write(&temp_path, content).map_err(|e| error::IoError::Write {
+            path: temp_path.clone(),
             source: e,
         })?;
 
-        let temp_map: HashMap<String, String> =
-            toml::from_str(&content).map_err(error::ConfigError::TomlFromString)?;
+        fs::rename(&temp_path, &self.path).map_err(|e| error::IoError::Write {
+            path: self.path.clone(),
+            source: e,
+        })?;
 
-        let mut map = Self::empty_map();
+        Ok(())
+    }
 
-        for (key, value) in temp_map {
-            if let Ok(field) = key.parse::<Field>() {
-                map.insert(field, Some(value));
+    fn get(&self, key: &Key) -> Option<String> {
+        let mut item = self.doc.as_item();
+        for part in key.path() {
+            match item.get(part) {
+                Some(next) => item = next,
+                None => {
+                    let legacy = self.doc.get(key.legacy()?)?;
+                    return legacy.as_value().map(display_value);
+                }
             }
         }
+        item.as_value().map(display_value)
+    }
 
-        Ok(Self { path, map })
+    fn set(&mut self, key: &Key, value: Value) -> Result<()> {
+        let path = key.path();
+        let (name, tables) = path.split_last().expect("a key has a name");
+
+        let mut table: &mut Table = self.doc.as_table_mut();
+        for part in tables {
+            let item = table.entry(part).or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            });
+            table = item
+                .as_table_mut()
+                .ok_or_else(|| error::ConfigError::InvalidKey(key.to_string()))?;
+        }
+
+        match table.get_mut(name).and_then(Item::as_value_mut) {
+            // Keep the comments around the value it replaces.
+            Some(current) => {
+                let decor = current.decor().clone();
+                *current = value;
+                *current.decor_mut() = decor;
+            }
+            None => {
+                table.insert(name, Item::Value(value));
+            }
+        }
+        if let Some(legacy) = key.legacy() {
+            remove_legacy(&mut self.doc, legacy);
+        }
+        Ok(())
     }
 
-    pub fn set(&mut self, key: String, value: String) -> Result<(), error::ConfigError> {
-        let field = key
-            .parse::<Field>()
-            .map_err(|_| error::ConfigError::UnsupportedField(key.clone()))?;
+    /// Removes a value, returns whether it was set.
+    fn unset(&mut self, key: &Key) -> bool {
+        let path = key.path();
+        let (name, tables) = path.split_last().expect("a key has a name");
 
-        self.map.insert(field, Some(value));
+        let mut removed = key
+            .legacy()
+            .is_some_and(|legacy| remove_legacy(&mut self.doc, legacy));
+        let mut table = Some(self.doc.as_table_mut());
+        for part in tables {
+            table = table.and_then(|table| table.get_mut(part)?.as_table_mut());
+        }
+        if let Some(table) = table {
+            removed |= table.remove(name).is_some();
+        }
+        removed
+    }
 
-        let mut serializable_map = std::collections::HashMap::new();
-        for (k, v) in &self.map {
-            if let Some(val) = v {
-                serializable_map.insert(k.to_string(), val.clone());
+    /// Every value in the file, in the order of the file.
+    fn entries(&self) -> Vec<(String, String)> {
+        fn walk(table: &Table, prefix: &str, entries: &mut Vec<(String, String)>) {
+            for (name, item) in table.iter() {
+                let key = match prefix {
+                    "" => LEGACY_KEYS
+                        .iter()
+                        .find(|(legacy, _)| *legacy == name)
+                        .map_or(name.to_string(), |(_, key)| key.to_string()),
+                    prefix => format!("{prefix}.{name}"),
+                };
+                match item {
+                    Item::Table(table) => walk(table, &key, entries),
+                    Item::Value(value) => entries.push((key, display_value(value))),
+                    _ => {}
+                }
             }
         }
 
-        let toml_string =
-            toml::to_string(&serializable_map).map_err(|e| error::IoError::Write {
-                path: self.path.clone(),
-                source: std::io::Error::new(std::io::ErrorKind::InvalidData, e),
-            })?;
+        let mut entries = Vec::new();
+        walk(self.doc.as_table(), "", &mut entries);
+        entries
+    }
+}
 
-        let temp_path = self.path.with_extension("tmp");
-        std::fs::write(&temp_path, &toml_string).map_err(|e| error::IoError::Write {
-            path: temp_path.clone(),
-            source: e,
-        })?;
+/// Layered configuration: the system, global and repository files. Each file is a TOML
+/// document with one table per section, e.g. `[user]` or `[remote.origin]`.
+#[derive(Debug)]
+pub struct Config {
+    files: Vec<ConfigFile>,
+}
 
-        std::fs::rename(&temp_path, &self.path).map_err(|e| error::IoError::Write {
-            path: self.path.clone(),
+impl Config {
+    pub fn default(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+
+        fs::write(
+            &path,
+            "\
+# Configuration file for flux
+# Values can be set either by directly modifying the file or by using the config command.
+#
+# [user]
+# name = \"Jane Doe\"
+# email = \"jane@example.com\"
+#
+# [remote.origin]
+# url = \"http://localhost:50051\"
+#
+# [http]
+# cainfo = \"/path/to/ca.pem\"
+",
+        )
+        .map_err(|e| error::IoError::Create {
+            path: path.clone(),
             source: e,
         })?;
 
-        Ok(())
+        Self::load(Some(path))
+    }
+
+    /// Loads the configuration of a repository from its `.flux/config` file.
+    pub fn from(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if !path.exists() {
+            return Err(error::IoError::Missing { path }.into());
+        }
+        Self::load(Some(path))
+    }
+
+    /// Loads the system and global files, and the repository one when there is one.
+    pub fn load(local: Option<PathBuf>) -> Result<Self> {
+        let mut files = Vec::new();
+        for level in [ConfigLevel::System, ConfigLevel::Global] {
+            if let Some(path) = level.default_path() {
+                files.push(ConfigFile::read(level, path)?);
+            }
+        }
+        if let Some(path) = local {
+            files.push(ConfigFile::read(ConfigLevel::Local, path)?);
+        }
+        Ok(Self { files })
+    }
+
+    fn file(&self, level: ConfigLevel) -> Result<&ConfigFile> {
+        self.files
+            .iter()
+            .find(|file| file.level == level)
+            .ok_or(error::ConfigError::MissingLevel(level))
+    }
+
+    fn file_mut(&mut self, level: ConfigLevel) -> Result<&mut ConfigFile> {
+        self.files
+            .iter_mut()
+            .find(|file| file.level == level)
+            .ok_or(error::ConfigError::MissingLevel(level))
+    }
+
+    /// Sets a value in the repository configuration.
+    pub fn set(&mut self, key: String, value: String) -> Result<()> {
+        self.set_at(ConfigLevel::Local, key, value)
+    }
+
+    /// Validates `value` against the type of the setting and writes it to the `level` file.
+    pub fn set_at(&mut self, level: ConfigLevel, key: String, value: String) -> Result<()> {
+        let key = Key::parse(&key)?;
+        let value = key.kind().parse(&key.to_string(), &value)?;
+        let file = self.file_mut(level)?;
+        file.set(&key, value)?;
+        file.write()
+    }
+
+    /// Removes a value from one level, returns whether it was set there.
+    pub fn unset_at(&mut self, level: ConfigLevel, key: &str) -> Result<bool> {
+        let key = Key::parse(key)?;
+        let file = self.file_mut(level)?;
+        if !file.unset(&key) {
+            return Ok(false);
+        }
+        file.write()?;
+        Ok(true)
     }
 
-    pub fn get_required(&self, field: Field) -> Result<String, error::ConfigError> {
-        self.map
-            .get(&field)
-            .and_then(|v| v.clone())
-            .ok_or_else(|| error::ConfigError::NotSet(field.to_string()))
+    pub fn get_required(&self, key: &str) -> Result<String> {
+        self.get(key)?.ok_or_else(|| {
+            error::ConfigError::NotSet(
+                Key::parse(key).map_or(key.to_string(), |key| key.to_string()),
+            )
+        })
     }
 
-    pub fn get_credentials(&self) -> Result<Credentials, error::ConfigError> {
+    pub fn get_credentials(&self) -> Result<Credentials> {
         Ok(Credentials {
-            user_name: self.get_required(Field::UserName)?,
-            user_email: self.get_required(Field::UserEmail)?,
-            access_token: self.get("access_token")?
+            user_name: self.get_required("user.name")?,
+            user_email: self.get_required("user.email")?,
         })
     }
 
-    pub fn get(&self, key: &str) -> Result<Option<String>, error::ConfigError> {
-    let field = key
-        .parse::<Field>()
-        .map_err(|_| error::ConfigError::UnsupportedField(key.to_string()))?;
+    /// Value of `key` from the most specific level that sets it, defaults are not applied.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        Ok(self.get_entry(key)?.map(|entry| entry.value))
+    }
+
+    /// Like `get`, also telling where the value comes from.
+    pub fn get_entry(&self, key: &str) -> Result<Option<ConfigEntry>> {
+        let key = Key::parse(key)?;
+        Ok(self
+            .files
+            .iter()
+            .rev()
+            .find_map(|file| Self::entry(file, &key)))
+    }
 
-    let val = self.map
-        .get(&field)
-        .and_then(|v| v.clone());
+    /// Value of `key` in the `level` file only.
+    pub fn get_entry_at(&self, level: ConfigLevel, key: &str) -> Result<Option<ConfigEntry>> {
+        let key = Key::parse(key)?;
+        Ok(Self::entry(self.file(level)?, &key))
+    }
 
-    Ok(val)
+    fn entry(file: &ConfigFile, key: &Key) -> Option<ConfigEntry> {
+        Some(ConfigEntry {
+            level: file.level,
+            path: file.path.clone(),
+            key: key.to_string(),
+            value: file.get(key)?,
+        })
+    }
+
+    /// Value of `key` validated against its setting, or the default of the setting.
+    fn get_typed(&self, key: &str, kind: ValueKind) -> Result<Option<Value>> {
+        let parsed = Key::parse(key)?;
+        let kind = match parsed.setting() {
+            Some(setting)
+                if std::mem::discriminant(&setting.kind) == std::mem::discriminant(&kind) =>
+            {
+                setting.kind
+            }
+            _ => kind,
+        };
+        let raw = match self.get(key)? {
+            Some(raw) => raw,
+            None => match parsed.setting().and_then(|setting| setting.default) {
+                Some(default) => default.to_string(),
+                None => return Ok(None),
+            },
+        };
+        kind.parse(&parsed.to_string(), &raw).map(Some)
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        Ok(self
+            .get_typed(key, ValueKind::Bool)?
+            .and_then(|value| value.as_bool()))
+    }
+
+    pub fn get_int(&self, key: &str) -> Result<Option<i64>> {
+        let kind = ValueKind::Int {
+            min: i64::MIN,
+            max: i64::MAX,
+        };
+        Ok(self
+            .get_typed(key, kind)?
+            .and_then(|value| value.as_integer()))
+    }
+
+    /// Path value with a leading `~/` expanded to the home directory.
+    pub fn get_path(&self, key: &str) -> Result<Option<PathBuf>> {
+        let Some(value) = self.get_typed(key, ValueKind::Path)? else {
+            return Ok(None);
+        };
+        let path = value.as_str().unwrap_or_default();
+        Ok(Some(match (path.strip_prefix("~/"), home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest),
+            _ => PathBuf::from(path),
+        }))
+    }
+
+    pub fn get_list(&self, key: &str) -> Result<Vec<String>> {
+        let Some(value) = self.get_typed(key, ValueKind::List)? else {
+            return Ok(Vec::new());
+        };
+        Ok(value
+            .as_array()
+            .map(|array| {
+                array
+                    .iter()
+                    .filter_map(|item| item.as_str())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    /// Every value of every level, or of `level` only, from the least specific level.
+    pub fn list(&self, level: Option<ConfigLevel>) -> Vec<ConfigEntry> {
+        self.files
+            .iter()
+            .filter(|file| level.is_none_or(|l| l == file.level))
+            .flat_map(|file| {
+                file.entries().into_iter().map(|(key, value)| ConfigEntry {
+                    level: file.level,
+                    path: file.path.clone(),
+                    key,
+                    value,
+                })
+            })
+            .collect()
+    }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_and_values() {
+        let key = Key::parse("remote.origin.url").unwrap();
+        assert_eq!(key.path(), ["remote", "origin", "url"]);
+        assert_eq!(key.kind(), ValueKind::String);
+        assert_eq!(Key::parse("origin").unwrap(), key);
+        assert_eq!(key.legacy(), Some("origin"));
+        assert_eq!(
+            Key::parse("branch.release.1.merge")
+                .unwrap()
+                .subsection
+                .as_deref(),
+            Some("release.1")
+        );
+        assert!(Key::parse("name").is_err());
+        assert!(Key::parse("user..name").is_err());
+        assert!(Key::parse("alias.co").unwrap().setting().is_some());
+        assert_eq!(Key::parse("http.cainfo").unwrap().kind(), ValueKind::Path);
+
+        let threshold = Key::parse("diff.rename_threshold").unwrap().kind();
+        assert_eq!(threshold.parse("k", "60").unwrap().as_integer(), Some(60));
+        assert!(threshold.parse("k", "101").is_err());
+        assert_eq!(
+            ValueKind::Bool.parse("k", "yes").unwrap().as_bool(),
+            Some(true)
+        );
+        assert!(ValueKind::Bool.parse("k", "maybe").is_err());
+        let list = ValueKind::List.parse("k", "a, b,,c").unwrap();
+        assert_eq!(display_value(&list), "a, b, c");
+    }
+
+    #[test]
+    fn edits_keep_comments() {
+        let content = "\
+# top comment
+user_name = \"legacy\"
+
+[user]
+# the address
+email = \"old@example.com\" # trailing
+";
+        let mut file = ConfigFile {
+            level: ConfigLevel::Local,
+            path: PathBuf::new(),
+            header: String::new(),
+            doc: content.parse().unwrap(),
+        };
+        let name = Key::parse("user.name").unwrap();
+        assert_eq!(file.get(&name).as_deref(), Some("legacy"));
+
+        let email = Key::parse("user.email").unwrap();
+        file.set(&email, Value::from("new@example.com")).unwrap();
+        file.set(&name, Value::from("Jane")).unwrap();
+        file.set(
+            &Key::parse("remote.origin.url").unwrap(),
+            Value::from("http://x"),
+        )
+        .unwrap();
+
+        let written = file.doc.to_string();
+        assert_eq!(
+            written,
+            "\
+# top comment
+
+[user]
+# the address
+email = \"new@example.com\" # trailing
+name = \"Jane\"
+
+[remote.origin]
+url = \"http://x\"
+"
+        );
+        assert_eq!(
+            file.entries(),
+            [
+                ("user.email".to_string(), "new@example.com".to_string()),
+                ("user.name".to_string(), "Jane".to_string()),
+                ("remote.origin.url".to_string(), "http://x".to_string()),
+            ]
+        );
+        assert!(file.unset(&name));
+        assert!(!file.unset(&name));
+    }
 }
@@ -0,0 +1,190 @@
+use crate::error;
+use crate::internals::config::{self, Config};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::{env, fs};
+use url::Url;
+
+pub type Result<T> = std::result::Result<T, error::CredentialError>;
+
+/// Environment variable overriding the path of the credentials file.
+pub const CREDENTIALS_ENV: &str = "FLUX_CREDENTIALS";
+
+/// Name of the built-in helper keeping tokens in `~/.flux/credentials`.
+pub const STORE_HELPER: &str = "store";
+
+/// Where access tokens are kept, chosen with the `credential.helper` setting.
+///
+/// `store` (the default) keeps them in `~/.flux/credentials`, readable by the user only. Any
+/// other value names an external helper: `<name>` runs `flux-credential-<name>`, a path or a
+/// command with arguments runs as is. The helper is called with `get`, `store` or `erase` and
+/// reads `key=value` lines on stdin, `url=<server>` and for `store` also `token=<token>`. For
+/// `get` it prints `token=<token>`, or nothing when it has no token for the server.
+#[derive(Debug)]
+pub enum CredentialStore {
+    File(PathBuf),
+    Helper(String),
+}
+
+impl CredentialStore {
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let helper = config
+            .get("credential.helper")?
+            .unwrap_or_else(|| STORE_HELPER.to_string());
+        if helper != STORE_HELPER {
+            return Ok(Self::Helper(helper));
+        }
+
+        let path = env::var_os(CREDENTIALS_ENV)
+            .map(PathBuf::from)
+            .or_else(|| config::home_dir().map(|home| home.join(".flux").join("credentials")))
+            .ok_or(error::CredentialError::MissingHome)?;
+        Ok(Self::File(path))
+    }
+
+    /// Token stored for the server `url` points to.
+    pub fn get(&self, url: &str) -> Result<Option<String>> {
+        let server = server_key(url);
+        match self {
+            Self::File(path) => Ok(read_file(path)?
+                .into_iter()
+                .find(|(key, _)| *key == server)
+                .map(|(_, token)| token)),
+            Self::Helper(helper) => {
+                let output = run_helper(helper, "get", &format!("url={server}\n"))?;
+                Ok(output
+                    .lines()
+                    .find_map(|line| line.strip_prefix("token="))
+                    .map(str::trim)
+                    .filter(|token| !token.is_empty())
+                    .map(String::from))
+            }
+        }
+    }
+
+    /// Stores `token` for the server `url` points to, replacing the previous one.
+    pub fn store(&self, url: &str, token: &str) -> Result<()> {
+        let server = server_key(url);
+        match self {
+            Self::File(path) => {
+                let mut entries = read_file(path)?;
+                entries.retain(|(key, _)| *key != server);
+                entries.push((server, token.to_string()));
+                write_file(path, &entries)
+            }
+            Self::Helper(helper) => {
+                run_helper(helper, "store", &format!("url={server}\ntoken={token}\n"))?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes the token of the server `url` points to, returns whether there was one. External
+    /// helpers are not asked, so they always report a removal.
+    pub fn erase(&self, url: &str) -> Result<bool> {
+        let server = server_key(url);
+        match self {
+            Self::File(path) => {
+                let mut entries = read_file(path)?;
+                let count = entries.len();
+                entries.retain(|(key, _)| *key != server);
+                if entries.len() == count {
+                    return Ok(false);
+                }
+                write_file(path, &entries)?;
+                Ok(true)
+            }
+            Self::Helper(helper) => {
+                run_helper(helper, "erase", &format!("url={server}\n"))?;
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Tokens are issued per server, so repositories on the same server share one. The key is
+/// the scheme, host and port of the url.
+fn server_key(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(parsed) if parsed.has_host() => parsed.origin().ascii_serialization(),
+        _ => url.trim_end_matches('/').to_string(),
+    }
+}
+
+/// `<server> <token>` lines, a missing file has no tokens.
+fn read_file(path: &PathBuf) -> Result<Vec<(String, String)>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).map_err(|e| error::IoError::read_error(path, e))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| line.trim().split_once(' '))
+        .map(|(server, token)| (server.to_string(), token.trim().to_string()))
+        .collect())
+}
+
+fn write_file(path: &PathBuf, entries: &[(String, String)]) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent).map_err(|e| error::IoError::create_error(parent, e))?;
+    }
+    let content: String = entries
+        .iter()
+        .map(|(server, token)| format!("{server} {token}\n"))
+        .collect();
+
+    let temp_path = path.with_extension("tmp");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&temp_path)
+        .and_then(|mut file| file.write_all(content.as_bytes()))
+        .map_err(|e| error::IoError::write_error(&temp_path, e))?;
+
+    fs::rename(&temp_path, path).map_err(|e| error::IoError::Rename {
+        from: temp_path.clone(),
+        to: path.clone(),
+        source: e,
+    })?;
+    Ok(())
+}
+
+fn run_helper(helper: &str, action: &str, input: &str) -> Result<String> {
+    let command = match helper.contains(['/', ' ']) {
+        true => helper.to_string(),
+        false => format!("flux-credential-{helper}"),
+    };
+    let failed = |reason: String| error::CredentialError::Helper {
+        helper: command.clone(),
+        reason,
+    };
+
+    // Run through the shell so the helper may carry arguments.
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{command} \"$@\""))
+        .arg(&command)
+        .arg(action)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+    if let Some(mut pipe) = child.stdin.take() {
+        // A helper may exit without reading its input, a broken pipe is not a failure.
+        let _ = pipe.write_all(input.as_bytes());
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| failed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(failed(output.status.to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
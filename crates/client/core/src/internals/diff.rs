@@ -0,0 +1,438 @@
+use similar::{ChangeTag, TextDiff};
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+    ops::Range,
+};
+
+/// Minimum similarity, in percent, for two files to be considered a rename or a copy.
+pub const DEFAULT_RENAME_THRESHOLD: u8 = 50;
+
+/// Above this many candidate pairs only exact renames are looked for.
+const RENAME_LIMIT: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeStatus {
+    Added,
+    Modified,
+    Deleted,
+    /// Moved from `old_path`, with the similarity of both versions in percent.
+    Renamed(u8),
+    /// Copied from `old_path`, which is still present, with the similarity in percent.
+    Copied(u8),
+}
+
+impl ChangeStatus {
+    /// Single letter used in name-status style listings.
+    pub fn letter(&self) -> char {
+        match self {
+            Self::Added => 'A',
+            Self::Modified => 'M',
+            Self::Deleted => 'D',
+            Self::Renamed(_) => 'R',
+            Self::Copied(_) => 'C',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    pub status: ChangeStatus,
+    pub path: String,
+    /// Source path of a rename or a copy.
+    pub old_path: Option<String>,
+    pub old_hash: Option<String>,
+    pub new_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenameOptions {
+    /// Minimum similarity in percent, see `DEFAULT_RENAME_THRESHOLD`.
+    pub threshold: u8,
+    /// Also pair added files with the unchanged files they were copied from.
+    pub copies: bool,
+}
+
+impl Default for RenameOptions {
+    fn default() -> Self {
+        Self {
+            threshold: DEFAULT_RENAME_THRESHOLD,
+            copies: false,
+        }
+    }
+}
+
+/// Compares two flattened trees and returns the changed paths, sorted by path.
+pub fn diff_maps(old: &HashMap<String, String>, new: &HashMap<String, String>) -> Vec<FileChange> {
+    let paths: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let old_hash = old.get(path);
+            let new_hash = new.get(path);
+            let status = match (old_hash, new_hash) {
+                (Some(o), Some(n)) if o == n => return None,
+                (Some(_), Some(_)) => ChangeStatus::Modified,
+                (None, Some(_)) => ChangeStatus::Added,
+                (Some(_), None) => ChangeStatus::Deleted,
+                (None, None) => return None,
+            };
+
+            Some(FileChange {
+                status,
+                path: path.clone(),
+                old_path: None,
+                old_hash: old_hash.cloned(),
+                new_hash: new_hash.cloned(),
+            })
+        })
+        .collect()
+}
+
+/// Percentage of lines two file versions have in common. Binary files are only similar
+/// when they are identical.
+pub fn similarity(old: &[u8], new: &[u8]) -> u8 {
+    if old == new {
+        return 100;
+    }
+    let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else {
+        return 0;
+    };
+    (TextDiff::from_lines(old, new).ratio() * 100.0) as u8
+}
+
+/// Pairs the deleted and added files of `changes` whose content is at least
+/// `options.threshold` percent similar into renames, and with `options.copies` the remaining
+/// added files with the files of `old` they were copied from.
+///
+/// `load` returns the content of a blob given its path and hash, so that files which only
+/// exist in the work tree can be compared too.
+pub fn detect_renames<E>(
+    changes: Vec<FileChange>,
+    old: &HashMap<String, String>,
+    options: RenameOptions,
+    mut load: impl FnMut(&str, &str) -> Result<Vec<u8>, E>,
+) -> Result<Vec<FileChange>, E> {
+    let mut deleted = Vec::new();
+    let mut added = Vec::new();
+    let mut result = Vec::new();
+    for change in changes {
+        match change.status {
+            ChangeStatus::Deleted => deleted.push(change),
+            ChangeStatus::Added => added.push(change),
+            _ => result.push(change),
+        }
+    }
+
+    let mut contents: HashMap<String, Vec<u8>> = HashMap::new();
+    let mut content = |path: &str, hash: &str| -> Result<Vec<u8>, E> {
+        if let Some(content) = contents.get(hash) {
+            return Ok(content.clone());
+        }
+        let content = load(path, hash)?;
+        contents.insert(hash.to_string(), content.clone());
+        Ok(content)
+    };
+
+    // Exact renames first, then the most similar pairs.
+    let mut candidates: Vec<(u8, usize, usize)> = Vec::new();
+    for (i, new) in added.iter().enumerate() {
+        for (j, gone) in deleted.iter().enumerate() {
+            if new.new_hash == gone.old_hash {
+                candidates.push((100, i, j));
+            }
+        }
+    }
+    if added.len() * deleted.len() <= RENAME_LIMIT {
+        for (i, new) in added.iter().enumerate() {
+            let new_content = content(&new.path, new.new_hash.as_deref().unwrap_or_default())?;
+            for (j, gone) in deleted.iter().enumerate() {
+                if new.new_hash == gone.old_hash {
+                    continue;
+                }
+                let old_content =
+                    content(&gone.path, gone.old_hash.as_deref().unwrap_or_default())?;
+                let score = similarity(&old_content, &new_content);
+                if score >= options.threshold {
+                    candidates.push((score, i, j));
+                }
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut paired_added = HashSet::new();
+    let mut paired_deleted = HashSet::new();
+    for (score, i, j) in candidates {
+        if paired_added.contains(&i) || paired_deleted.contains(&j) {
+            continue;
+        }
+        paired_added.insert(i);
+        paired_deleted.insert(j);
+        result.push(FileChange {
+            status: ChangeStatus::Renamed(score),
+            path: added[i].path.clone(),
+            old_path: Some(deleted[j].path.clone()),
+            old_hash: deleted[j].old_hash.clone(),
+            new_hash: added[i].new_hash.clone(),
+        });
+    }
+
+    let mut sources: Vec<(&String, &String)> = old.iter().collect();
+    sources.sort();
+    for (i, new) in added.into_iter().enumerate() {
+        if paired_added.contains(&i) {
+            continue;
+        }
+
+        let mut best: Option<(u8, &String, &String)> = None;
+        if options.copies && sources.len() <= RENAME_LIMIT {
+            let new_content = content(&new.path, new.new_hash.as_deref().unwrap_or_default())?;
+            for &(path, hash) in &sources {
+                let score = if new.new_hash.as_ref() == Some(hash) {
+                    100
+                } else {
+                    similarity(&content(path, hash)?, &new_content)
+                };
+                if score >= options.threshold && best.is_none_or(|(top, ..)| score > top) {
+                    best = Some((score, path, hash));
+                }
+            }
+        }
+
+        match best {
+            Some((score, path, hash)) => result.push(FileChange {
+                status: ChangeStatus::Copied(score),
+                old_path: Some(path.clone()),
+                old_hash: Some(hash.clone()),
+                ..new
+            }),
+            None => result.push(new),
+        }
+    }
+
+    result.extend(
+        deleted
+            .into_iter()
+            .enumerate()
+            .filter(|(j, _)| !paired_deleted.contains(j))
+            .map(|(_, change)| change),
+    );
+    result.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(result)
+}
+
+/// Renders a unified diff between two versions of a file.
+pub fn unified_diff(old_path: &str, new_path: &str, old: &[u8], new: &[u8]) -> String {
+    let (Ok(old), Ok(new)) = (std::str::from_utf8(old), std::str::from_utf8(new)) else {
+        return format!("Binary files a/{old_path} and b/{new_path} differ\n");
+    };
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(&format!("a/{old_path}"), &format!("b/{new_path}"))
+        .to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+impl LineKind {
+    pub fn prefix(&self) -> char {
+        match self {
+            Self::Context => ' ',
+            Self::Removed => '-',
+            Self::Added => '+',
+        }
+    }
+}
+
+/// A line of a hunk, `text` keeps its line terminator when the source had one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HunkLine {
+    pub kind: LineKind,
+    pub text: String,
+}
+
+/// A group of nearby changes between two versions of a file, with the line ranges
+/// (zero based, end exclusive) it covers in each of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_range: Range<usize>,
+    pub new_range: Range<usize>,
+    pub lines: Vec<HunkLine>,
+}
+
+impl Hunk {
+    /// The `@@ -a,b +c,d @@` line of the hunk.
+    pub fn header(&self) -> String {
+        let start = |range: &Range<usize>| {
+            if range.is_empty() {
+                range.start
+            } else {
+                range.start + 1
+            }
+        };
+        format!(
+            "@@ -{},{} +{},{} @@",
+            start(&self.old_range),
+            self.old_range.len(),
+            start(&self.new_range),
+            self.new_range.len()
+        )
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.header())?;
+        for line in &self.lines {
+            write!(f, "{}{}", line.kind.prefix(), line.text)?;
+            if !line.text.ends_with('\n') {
+                write!(f, "\n\\ No newline at end of file\n")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits the line diff between `old` and `new` into hunks with three lines of context.
+pub fn hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(old, new);
+
+    diff.grouped_ops(3)
+        .iter()
+        .map(|group| {
+            let (first, last) = (&group[0], &group[group.len() - 1]);
+            let lines = group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| HunkLine {
+                    kind: match change.tag() {
+                        ChangeTag::Equal => LineKind::Context,
+                        ChangeTag::Delete => LineKind::Removed,
+                        ChangeTag::Insert => LineKind::Added,
+                    },
+                    text: change.value().to_string(),
+                })
+                .collect();
+
+            Hunk {
+                old_range: first.old_range().start..last.old_range().end,
+                new_range: first.new_range().start..last.new_range().end,
+                lines,
+            }
+        })
+        .collect()
+}
+
+/// Applies the given hunks of a diff computed against `old`, leaving every other change out.
+pub fn apply_hunks<'a>(old: &str, hunks: impl IntoIterator<Item = &'a Hunk>) -> String {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let mut hunks: Vec<&Hunk> = hunks.into_iter().collect();
+    hunks.sort_by_key(|hunk| hunk.old_range.start);
+
+    let mut result = String::with_capacity(old.len());
+    let mut pos = 0;
+    for hunk in hunks {
+        result.extend(old_lines[pos..hunk.old_range.start].iter().copied());
+        for line in &hunk.lines {
+            if line.kind != LineKind::Removed {
+                result.push_str(&line.text);
+            }
+        }
+        pos = hunk.old_range.end;
+    }
+    result.extend(old_lines[pos..].iter().copied());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries
+            .iter()
+            .map(|(path, hash)| (path.to_string(), hash.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn detects_renames_and_copies() {
+        let contents: HashMap<&str, &str> = HashMap::from([
+            ("h1", "one\ntwo\nthree\nfour\n"),
+            ("h2", "one\ntwo\nthree\nFOUR\n"),
+            ("h3", "something\nelse\n"),
+            ("h4", "a\nb\nc\n"),
+        ]);
+        let load =
+            |_: &str, hash: &str| -> Result<Vec<u8>, ()> { Ok(contents[hash].as_bytes().to_vec()) };
+
+        let old = map(&[("a.txt", "h1"), ("b.txt", "h3"), ("c.txt", "h4")]);
+        let new = map(&[
+            ("moved.txt", "h2"),
+            ("b.txt", "h3"),
+            ("c.txt", "h4"),
+            ("copy.txt", "h4"),
+        ]);
+        let changes =
+            detect_renames(diff_maps(&old, &new), &old, RenameOptions::default(), load).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].status, ChangeStatus::Added);
+        assert_eq!(changes[1].status, ChangeStatus::Renamed(75));
+        assert_eq!(changes[1].old_path.as_deref(), Some("a.txt"));
+
+        let options = RenameOptions {
+            threshold: 80,
+            copies: true,
+        };
+        let changes = detect_renames(diff_maps(&old, &new), &old, options, load).unwrap();
+        let statuses: Vec<(ChangeStatus, &str)> = changes
+            .iter()
+            .map(|change| (change.status, change.path.as_str()))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                (ChangeStatus::Deleted, "a.txt"),
+                (ChangeStatus::Copied(100), "copy.txt"),
+                (ChangeStatus::Added, "moved.txt"),
+            ]
+        );
+        assert_eq!(changes[1].old_path.as_deref(), Some("c.txt"));
+    }
+
+    #[test]
+    fn apply_selected_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nK\nl\nm";
+        let hunks = hunks(old, new);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[0].header(), "@@ -1,5 +1,5 @@");
+        assert_eq!(hunks[1].header(), "@@ -8,5 +8,6 @@");
+
+        assert_eq!(apply_hunks(old, []), old);
+        assert_eq!(apply_hunks(old, &hunks), new);
+        assert_eq!(
+            apply_hunks(old, &hunks[..1]),
+            "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n"
+        );
+        assert_eq!(
+            apply_hunks(old, &hunks[1..]),
+            "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nK\nl\nm"
+        );
+        assert!(
+            hunks[1]
+                .to_string()
+                .ends_with("+m\n\\ No newline at end of file\n")
+        );
+    }
+}
@@ -0,0 +1,344 @@
+use crate::internals::object_store::{ObjectStore, Result};
+use crate::objects::object_type::ObjectType;
+use crate::utils;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt, fs,
+};
+
+/// Tree entry modes accepted by `--strict`.
+const STRICT_MODES: [&str; 4] = ["100644", "100755", "120000", "040000"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FsckProblem {
+    /// The object file cannot be decompressed or parsed.
+    Corrupt { hash: String, reason: String },
+    /// The object content does not hash to its file name.
+    HashMismatch { hash: String, actual: String },
+    /// An object referenced by a tree, commit, ref or the index is not in the store.
+    Missing { hash: String, referenced_by: String },
+    /// A tree entry or commit header points at an object of the wrong type.
+    BrokenLink {
+        from: String,
+        to: String,
+        expected: ObjectType,
+        found: ObjectType,
+    },
+    /// A ref that does not point at a commit.
+    BadRef {
+        name: String,
+        hash: String,
+        reason: String,
+    },
+    /// A malformed tree entry. Ordering and mode problems are only reported in strict mode.
+    TreeEntry {
+        hash: String,
+        name: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for FsckProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Corrupt { hash, reason } => {
+                write!(f, "error: object {hash} is corrupt: {reason}")
+            }
+            Self::HashMismatch { hash, actual } => {
+                write!(f, "error: object {hash} hashes to {actual}")
+            }
+            Self::Missing {
+                hash,
+                referenced_by,
+            } => write!(f, "missing object {hash} referenced by {referenced_by}"),
+            Self::BrokenLink {
+                from,
+                to,
+                expected,
+                found,
+            } => write!(
+                f,
+                "error: {from} links to {to} which is a {}, expected a {}",
+                found.as_str(),
+                expected.as_str()
+            ),
+            Self::BadRef { name, hash, reason } => {
+                write!(f, "error: ref {name} points to {hash}: {reason}")
+            }
+            Self::TreeEntry { hash, name, reason } => {
+                write!(f, "error: tree {hash} entry '{name}': {reason}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FsckReport {
+    /// Number of loose objects that were checked.
+    pub checked: usize,
+    pub problems: Vec<FsckProblem>,
+    /// Valid objects that are not reachable from any ref, reflog or the index.
+    pub dangling: Vec<(String, ObjectType)>,
+}
+
+impl FsckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// An object that passed the hash and header checks.
+struct Checked {
+    object_type: ObjectType,
+    /// Objects this one points to, with the type each of them must have.
+    links: Vec<(String, ObjectType)>,
+}
+
+/// Checks every loose object in the store, then the links between them and the given refs.
+///
+/// `refs` are `(name, hash)` pairs that must point at commits, `roots` are the hashes
+/// reachability starts from when looking for dangling objects.
+pub fn check(
+    object_store: &ObjectStore,
+    refs: &[(String, String)],
+    roots: &[String],
+    strict: bool,
+) -> Result<FsckReport> {
+    let mut report = FsckReport::default();
+    let mut objects: BTreeMap<String, Checked> = BTreeMap::new();
+
+    for hash in object_store.list_objects()? {
+        report.checked += 1;
+        match check_object(object_store, &hash, strict, &mut report.problems) {
+            Ok(checked) => {
+                objects.insert(hash, checked);
+            }
+            Err(problem) => report.problems.push(problem),
+        }
+    }
+
+    let types: HashMap<&String, ObjectType> = objects
+        .iter()
+        .map(|(hash, checked)| (hash, checked.object_type))
+        .collect();
+
+    let mut missing: HashSet<&String> = HashSet::new();
+    for (hash, checked) in &objects {
+        for (to, expected) in &checked.links {
+            match types.get(to) {
+                Some(found) if found != expected => report.problems.push(FsckProblem::BrokenLink {
+                    from: format!("{} {hash}", checked.object_type.as_str()),
+                    to: to.clone(),
+                    expected: *expected,
+                    found: *found,
+                }),
+                Some(_) => {}
+                None if missing.insert(to) => report.problems.push(FsckProblem::Missing {
+                    hash: to.clone(),
+                    referenced_by: format!("{} {hash}", checked.object_type.as_str()),
+                }),
+                None => {}
+            }
+        }
+    }
+
+    for (name, hash) in refs {
+        let reason = match types.get(hash) {
+            Some(ObjectType::Commit) => continue,
+            Some(found) => format!("not a commit but a {}", found.as_str()),
+            None if object_store.exists(hash) => "object is corrupt".to_string(),
+            None => "object is missing".to_string(),
+        };
+        report.problems.push(FsckProblem::BadRef {
+            name: name.clone(),
+            hash: hash.clone(),
+            reason,
+        });
+    }
+
+    let mut reachable: HashSet<&String> = HashSet::new();
+    let mut pending: Vec<&String> = roots.iter().collect();
+    while let Some(hash) = pending.pop() {
+        let Some(checked) = objects.get(hash) else {
+            continue;
+        };
+        if reachable.insert(hash) {
+            pending.extend(checked.links.iter().map(|(to, _)| to));
+        }
+    }
+
+    report.dangling = objects
+        .iter()
+        .filter(|(hash, _)| !reachable.contains(hash))
+        .map(|(hash, checked)| (hash.clone(), checked.object_type))
+        .collect();
+
+    Ok(report)
+}
+
+fn check_object(
+    object_store: &ObjectStore,
+    hash: &str,
+    strict: bool,
+    problems: &mut Vec<FsckProblem>,
+) -> std::result::Result<Checked, FsckProblem> {
+    let corrupt = |reason: &str| FsckProblem::Corrupt {
+        hash: hash.to_string(),
+        reason: reason.to_string(),
+    };
+
+    if !is_hash(hash) {
+        return Err(corrupt("file name is not an object hash"));
+    }
+
+    let path = object_store.object_path(hash);
+    let compressed = fs::read(&path).map_err(|e| corrupt(&e.to_string()))?;
+    let decompressed =
+        utils::try_decompress(&compressed).map_err(|_| corrupt("invalid zlib stream"))?;
+
+    let actual = utils::hash(&decompressed);
+    if actual != hash {
+        return Err(FsckProblem::HashMismatch {
+            hash: hash.to_string(),
+            actual,
+        });
+    }
+
+    let null_pos = decompressed
+        .iter()
+        .position(|&b| b == 0)
+        .ok_or_else(|| corrupt("missing header"))?;
+    let header =
+        std::str::from_utf8(&decompressed[..null_pos]).map_err(|_| corrupt("invalid header"))?;
+    let (kind, size) = header
+        .split_once(' ')
+        .ok_or_else(|| corrupt("invalid header"))?;
+    let content = &decompressed[null_pos + 1..];
+    if size.parse::<usize>().ok() != Some(content.len()) {
+        return Err(corrupt("size in header does not match content"));
+    }
+
+    let (object_type, links) = match kind {
+        "blob" => (ObjectType::Blob, Vec::new()),
+        "tree" => (
+            ObjectType::Tree,
+            tree_links(hash, content, strict, problems)?,
+        ),
+        "commit" => (ObjectType::Commit, commit_links(content).map_err(&corrupt)?),
+        other => return Err(corrupt(&format!("unsupported object type '{other}'"))),
+    };
+
+    Ok(Checked { object_type, links })
+}
+
+fn is_hash(hash: &str) -> bool {
+    hash.len() == 40 && hash.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+/// Name used when comparing tree entries: directories sort as if they ended with '/'.
+fn sort_name(mode: &str, name: &str) -> String {
+    if mode == "040000" {
+        format!("{name}/")
+    } else {
+        name.to_string()
+    }
+}
+
+fn tree_links(
+    hash: &str,
+    content: &[u8],
+    strict: bool,
+    problems: &mut Vec<FsckProblem>,
+) -> std::result::Result<Vec<(String, ObjectType)>, FsckProblem> {
+    let corrupt = || FsckProblem::Corrupt {
+        hash: hash.to_string(),
+        reason: "malformed tree entry".to_string(),
+    };
+    let mut entry_problem = |name: &str, reason: &str| {
+        problems.push(FsckProblem::TreeEntry {
+            hash: hash.to_string(),
+            name: name.to_string(),
+            reason: reason.to_string(),
+        })
+    };
+
+    let mut links = Vec::new();
+    let mut previous: Option<String> = None;
+    let mut pos = 0;
+
+    while pos < content.len() {
+        let space = content[pos..]
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or_else(corrupt)?;
+        let mode = std::str::from_utf8(&content[pos..pos + space]).map_err(|_| corrupt())?;
+        pos += space + 1;
+
+        let null = content[pos..]
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(corrupt)?;
+        let name = String::from_utf8_lossy(&content[pos..pos + null]).to_string();
+        pos += null + 1;
+
+        let hash_bytes = content.get(pos..pos + 20).ok_or_else(corrupt)?;
+        pos += 20;
+
+        if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+            entry_problem(&name, "invalid file name");
+        }
+
+        if strict {
+            if !STRICT_MODES.contains(&mode) {
+                entry_problem(&name, &format!("unsupported mode {mode}"));
+            }
+
+            let current = sort_name(mode, &name);
+            match previous.as_ref().map(|prev| prev.cmp(&current)) {
+                Some(Ordering::Equal) => entry_problem(&name, "duplicate entry"),
+                Some(Ordering::Greater) => entry_problem(&name, "entries are not sorted"),
+                _ => {}
+            }
+            previous = Some(current);
+        }
+
+        let expected = if mode == "040000" {
+            ObjectType::Tree
+        } else {
+            ObjectType::Blob
+        };
+        links.push((hex::encode(hash_bytes), expected));
+    }
+
+    Ok(links)
+}
+
+fn commit_links(content: &[u8]) -> std::result::Result<Vec<(String, ObjectType)>, &'static str> {
+    let content = std::str::from_utf8(content).map_err(|_| "commit is not valid UTF-8")?;
+    let header = content.split("\n\n").next().unwrap_or("");
+
+    let mut links = Vec::new();
+    let (mut author, mut committer) = (false, false);
+
+    for (i, line) in header.lines().enumerate() {
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "tree" if i == 0 && is_hash(value) => links.push((value.to_string(), ObjectType::Tree)),
+            "parent" if is_hash(value) => links.push((value.to_string(), ObjectType::Commit)),
+            "tree" | "parent" => return Err("invalid tree or parent line"),
+            "author" => author = true,
+            "committer" => committer = true,
+            _ => {}
+        }
+    }
+
+    if links.first().map(|(_, t)| *t) != Some(ObjectType::Tree) {
+        return Err("missing tree line");
+    }
+    if !author || !committer {
+        return Err("missing author or committer");
+    }
+
+    Ok(links)
+}
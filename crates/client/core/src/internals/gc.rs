@@ -0,0 +1,127 @@
+use crate::error;
+use crate::internals::object_store::{ObjectStore, Result};
+use crate::objects::object_type::ObjectType;
+use crate::objects::tree::Tree;
+use std::{collections::HashSet, fs, path::Path, time::SystemTime};
+
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    /// Hashes of the unreachable objects that were (or, in a dry run, would be) removed.
+    pub objects: Vec<String>,
+    pub temp_files: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct GcReport {
+    pub reachable: usize,
+    pub expired_reflog_entries: usize,
+    pub prune: PruneReport,
+}
+
+/// Marks every object reachable from `roots`, following commit parents and trees.
+/// Roots that are missing from the store are skipped, fsck reports those.
+pub fn reachable_objects(
+    object_store: &ObjectStore,
+    roots: impl IntoIterator<Item = String>,
+) -> Result<HashSet<String>> {
+    let mut reachable = HashSet::new();
+    let mut pending: Vec<String> = roots.into_iter().collect();
+
+    while let Some(hash) = pending.pop() {
+        if reachable.contains(&hash) || !object_store.exists(&hash) {
+            continue;
+        }
+
+        let object = object_store.retrieve_object(&hash)?;
+        match object.object_type() {
+            ObjectType::Commit => {
+                let commit = object_store.retrieve_commit(&hash)?;
+                pending.push(commit.tree_hash.clone());
+                pending.extend(commit.parents().iter().cloned());
+            }
+            ObjectType::Tree => {
+                let tree = object
+                    .as_any()
+                    .downcast_ref::<Tree>()
+                    .ok_or(error::ObjectStoreError::Downcast { expected: "tree" })?;
+                pending.extend(tree.entries().into_iter().map(|entry| entry.hash));
+            }
+            ObjectType::Blob | ObjectType::Tag => {}
+        }
+
+        reachable.insert(hash);
+    }
+
+    Ok(reachable)
+}
+
+fn is_older(path: &Path, cutoff: SystemTime) -> Result<bool> {
+    let metadata = fs::metadata(path).map_err(|e| error::IoError::metadata_error(path, e))?;
+    let modified = metadata
+        .modified()
+        .map_err(|e| error::IoError::metadata_error(path, e))?;
+    Ok(modified <= cutoff)
+}
+
+fn file_size(path: &Path) -> Result<u64> {
+    let metadata = fs::metadata(path).map_err(|e| error::IoError::metadata_error(path, e))?;
+    Ok(metadata.len())
+}
+
+/// Removes the loose objects missing from `reachable` and the stale `.tmp` files that were
+/// last modified before `cutoff`. With `dry_run` nothing is deleted, only reported.
+pub fn prune(
+    object_store: &ObjectStore,
+    reachable: &HashSet<String>,
+    cutoff: SystemTime,
+    dry_run: bool,
+) -> Result<PruneReport> {
+    let mut report = PruneReport::default();
+
+    for hash in object_store.list_objects()? {
+        let path = object_store.object_path(&hash);
+        if reachable.contains(&hash) || !is_older(&path, cutoff)? {
+            continue;
+        }
+
+        report.bytes += file_size(&path)?;
+        if !dry_run {
+            fs::remove_file(&path).map_err(|e| error::IoError::delete_error(&path, e))?;
+        }
+        report.objects.push(hash);
+    }
+
+    for path in object_store.temp_files()? {
+        if !is_older(&path, cutoff)? {
+            continue;
+        }
+
+        report.bytes += file_size(&path)?;
+        report.temp_files += 1;
+        if !dry_run {
+            fs::remove_file(&path).map_err(|e| error::IoError::delete_error(&path, e))?;
+        }
+    }
+
+    if !dry_run {
+        remove_empty_dirs(&object_store.path)?;
+    }
+
+    Ok(report)
+}
+
+fn remove_empty_dirs(objects_path: &Path) -> Result<()> {
+    let dirs =
+        fs::read_dir(objects_path).map_err(|e| error::IoError::read_error(objects_path, e))?;
+    for dir in dirs {
+        let path = dir
+            .map_err(|e| error::IoError::read_error(objects_path, e))?
+            .path();
+        if path.is_dir() {
+            // Fails on directories that still hold objects, which are kept.
+            let _ = fs::remove_dir(&path);
+        }
+    }
+    Ok(())
+}
@@ -1,12 +1,23 @@
 use crate::error;
+use proto::models::access_service_client::AccessServiceClient;
 use proto::models::auth_serviec_client::AuthServiecClient;
-use proto::models::{Chunk, IssueTokenResponse};
+use proto::models::browse_service_client::BrowseServiceClient;
+use proto::models::repository_service_client::RepositoryServiceClient;
+use proto::models::{
+    AccessResponse, ChallengeRequest, Chunk, CollaboratorRequest, CreateRepositoryRequest,
+    GetBlobRequest, GetBlobResponse, IssueTokenResponse, ListCommitsRequest, ListCommitsResponse,
+    ListRefsRequest, ListRefsResponse, ListRepositoriesRequest, ListTreeRequest, ListTreeResponse,
+    LoginRequest, MemberRequest, OrganizationInfo, OrganizationRequest, RegisterRequest,
+    RenameRepositoryRequest, RepositoryInfo, RepositoryRequest, RevokeRequest, TokenRequest,
+    TokenStatusResponse, VisibilityRequest,
+};
 use proto::models::{CloneRequest, UploadStatus};
 use proto::models::{
     clone_service_client::CloneServiceClient, push_service_client::PushServiceClient,
 };
+use std::path::PathBuf;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::transport::Channel;
+use tonic::transport::{Certificate, Channel, ClientTlsConfig, Endpoint};
 use url::Url;
 // TODO: on clone create the direcotry for the repository, change iside the directory and then do the rest
 // make the clone push to origin so it wont create a new folder on server if i just change the name of the local folder
@@ -17,39 +28,66 @@ const CHUNK_SIZE: usize = 256 * 1024;
 #[derive(Debug)]
 pub struct GrpcClient {
     url: String,
+    /// Sent as the bearer token of every request when set.
+    access_token: Option<String>,
     pub auth_client: AuthServiecClient<Channel>,
     pub push_client: PushServiceClient<Channel>,
     pub clone_client: CloneServiceClient<Channel>,
+    pub access_client: AccessServiceClient<Channel>,
+    pub repository_client: RepositoryServiceClient<Channel>,
+    pub browse_client: BrowseServiceClient<Channel>,
 }
 
 impl GrpcClient {
-    pub async fn connect_remote(url: String) -> Result<Self> {
-        let auth_client = AuthServiecClient::connect(url.clone()).await.map_err(|e| {
-            error::GrpcClientError::ConnectRemote {
-                url: url.clone(),
-                source: e,
-            }
-        })?;
-        let push_client = PushServiceClient::connect(url.clone()).await.map_err(|e| {
-            error::GrpcClientError::ConnectRemote {
-                url: url.clone(),
-                source: e,
+    /// Connects to the server of `url`. The certificate of an `https://` server is verified
+    /// against the system roots, and the PEM certificates of `ca_bundle` when one is given.
+    pub async fn connect_remote(url: String, ca_bundle: Option<PathBuf>) -> Result<Self> {
+        let connect_error = |e| error::GrpcClientError::ConnectRemote {
+            url: url.clone(),
+            source: e,
+        };
+        let mut endpoint = Endpoint::from_shared(url.clone()).map_err(connect_error)?;
+        if endpoint.uri().scheme_str() == Some("https") {
+            let mut tls = ClientTlsConfig::new().with_native_roots();
+            if let Some(path) = ca_bundle {
+                let pem = std::fs::read(&path)
+                    .map_err(|e| error::GrpcClientError::CaBundle { path, source: e })?;
+                tls = tls.ca_certificate(Certificate::from_pem(pem));
             }
-        })?;
-        let clone_client = CloneServiceClient::connect(url.clone())
-            .await
-            .map_err(|e| error::GrpcClientError::ConnectRemote {
-                url: url.clone(),
-                source: e,
-            })?;
+            endpoint = endpoint.tls_config(tls).map_err(connect_error)?;
+        }
+        // Every service shares the one connection.
+        let channel = endpoint.connect().await.map_err(connect_error)?;
         Ok(Self {
-            auth_client,
-            push_client,
-            clone_client,
+            auth_client: AuthServiecClient::new(channel.clone()),
+            push_client: PushServiceClient::new(channel.clone()),
+            clone_client: CloneServiceClient::new(channel.clone()),
+            access_client: AccessServiceClient::new(channel.clone()),
+            repository_client: RepositoryServiceClient::new(channel.clone()),
+            browse_client: BrowseServiceClient::new(channel),
             url,
+            access_token: None,
         })
     }
 
+    /// Authorizes the requests of the client with `access_token`.
+    pub fn with_token(mut self, access_token: Option<String>) -> Self {
+        self.access_token = access_token;
+        self
+    }
+
+    fn request<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        let value = self
+            .access_token
+            .as_ref()
+            .and_then(|token| format!("Bearer {}", token).parse().ok());
+        if let Some(value) = value {
+            request.metadata_mut().insert("authorization", value);
+        }
+        request
+    }
+
     pub fn repo_name(&self) -> Result<String> {
         let url = Url::parse(&self.url).map_err(|e| error::GrpcClientError::Url {
             url: self.url.clone(),
@@ -65,33 +103,112 @@ impl GrpcClient {
         Ok(repo_name.to_string())
     }
 
-    pub async fn auth(
+    /// Creates an account with either a password or an OpenSSH public key, the other one is
+    /// left empty.
+    pub async fn register(
         &mut self,
         user_name: String,
         user_email: String,
+        password: String,
+        public_key: String,
+        scopes: Vec<String>,
     ) -> Result<IssueTokenResponse> {
-        let request = tonic::Request::new(proto::models::IssueTokenRequest {
+        let request = tonic::Request::new(RegisterRequest {
             user_name,
             user_email,
+            password,
+            public_key,
+            scopes,
         });
 
         let response = self
             .auth_client
-            .issue_token(request)
+            .register(request)
             .await
-            .map_err(|e| error::GrpcClientError::Auth(e))?;
+            .map_err(error::GrpcClientError::Auth)?;
 
         Ok(response.into_inner())
     }
 
-    pub async fn push(
+    /// Text to sign with the registered key to log in.
+    pub async fn challenge(&mut self, user_email: String) -> Result<String> {
+        let request = tonic::Request::new(ChallengeRequest { user_email });
+
+        let response = self
+            .auth_client
+            .challenge(request)
+            .await
+            .map_err(error::GrpcClientError::Auth)?;
+
+        Ok(response.into_inner().challenge)
+    }
+
+    /// Logs in with a password, or with a challenge and its signature.
+    pub async fn login(
         &mut self,
-        repo_name: String,
-        content: Vec<u8>,
         user_email: String,
-        user_name: String,
-        access_token: String,
-    ) -> Result<UploadStatus> {
+        password: String,
+        challenge: String,
+        signature: String,
+        scopes: Vec<String>,
+    ) -> Result<IssueTokenResponse> {
+        let request = tonic::Request::new(LoginRequest {
+            user_email,
+            password,
+            challenge,
+            signature,
+            scopes,
+        });
+
+        let response = self
+            .auth_client
+            .login(request)
+            .await
+            .map_err(error::GrpcClientError::Auth)?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Exchanges a valid token for a new one with the same scopes, the old one is revoked.
+    pub async fn refresh(&mut self) -> Result<IssueTokenResponse> {
+        let request = self.request(TokenRequest {});
+
+        let response = self
+            .auth_client
+            .refresh(request)
+            .await
+            .map_err(error::GrpcClientError::Auth)?;
+
+        Ok(response.into_inner())
+    }
+
+    /// Revokes the token `token_id`, every token of the user with `all`, or else the token of
+    /// the client itself. Returns the number of tokens revoked.
+    pub async fn revoke(&mut self, token_id: String, all: bool) -> Result<u32> {
+        let request = self.request(RevokeRequest { token_id, all });
+
+        let response = self
+            .auth_client
+            .revoke(request)
+            .await
+            .map_err(error::GrpcClientError::Auth)?;
+
+        Ok(response.into_inner().revoked)
+    }
+
+    pub async fn token_status(&mut self) -> Result<TokenStatusResponse> {
+        let request = self.request(TokenRequest {});
+
+        let response = self
+            .auth_client
+            .token_status(request)
+            .await
+            .map_err(error::GrpcClientError::Auth)?;
+
+        Ok(response.into_inner())
+    }
+
+    pub async fn push(&mut self, repo_name: String, content: Vec<u8>) -> Result<UploadStatus> {
         let (tx, rx) = tokio::sync::mpsc::channel(32);
 
         tokio::spawn(async move {
@@ -109,18 +226,7 @@ impl GrpcClient {
             println!("Finished sending chunks to server!");
         });
 
-        let stream = ReceiverStream::new(rx);
-        let mut request = tonic::Request::new(stream);
-        request
-            .metadata_mut()
-            .insert("user-email", user_email.parse().unwrap());
-        request
-            .metadata_mut()
-            .insert("user-name", user_name.parse().unwrap());
-        request.metadata_mut().insert(
-            "authorization",
-            format!("Bearer {}", access_token).parse().unwrap(),
-        );
+        let request = self.request(ReceiverStream::new(rx));
 
         let response = self
             .push_client
@@ -142,7 +248,7 @@ impl GrpcClient {
     // Changed temporarily to allow cloning any repo if the correct path on the server is given.
     pub async fn clone_repository(&mut self) -> Result<Vec<u8>> {
         let path = self.extract_path()?;
-        let request = tonic::Request::new(CloneRequest { name: path });
+        let request = self.request(CloneRequest { name: path });
 
         let mut stream = self
             .clone_client
@@ -162,4 +268,267 @@ impl GrpcClient {
 
         Ok(content)
     }
+
+    pub async fn get_access(&mut self) -> Result<AccessResponse> {
+        let repository = self.extract_path()?;
+        let request = self.request(RepositoryRequest { repository });
+
+        let response = self
+            .access_client
+            .get_access(request)
+            .await
+            .map_err(error::GrpcClientError::Access)?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn set_visibility(&mut self, visibility: String) -> Result<AccessResponse> {
+        let repository = self.extract_path()?;
+        let request = self.request(VisibilityRequest {
+            repository,
+            visibility,
+        });
+
+        let response = self
+            .access_client
+            .set_visibility(request)
+            .await
+            .map_err(error::GrpcClientError::Access)?;
+        Ok(response.into_inner())
+    }
+
+    /// Adds `user_email` to the collaborators of the repository, or changes their role.
+    pub async fn add_collaborator(
+        &mut self,
+        user_email: String,
+        role: String,
+    ) -> Result<AccessResponse> {
+        let repository = self.extract_path()?;
+        let request = self.request(CollaboratorRequest {
+            repository,
+            user_email,
+            role,
+        });
+
+        let response = self
+            .access_client
+            .add_collaborator(request)
+            .await
+            .map_err(error::GrpcClientError::Access)?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn remove_collaborator(&mut self, user_email: String) -> Result<AccessResponse> {
+        let repository = self.extract_path()?;
+        let request = self.request(CollaboratorRequest {
+            repository,
+            user_email,
+            role: String::new(),
+        });
+
+        let response = self
+            .access_client
+            .remove_collaborator(request)
+            .await
+            .map_err(error::GrpcClientError::Access)?;
+        Ok(response.into_inner())
+    }
+
+    /// Creates `repository`, `<namespace>/<name>` or a name in the namespace of the user.
+    pub async fn create_repository(
+        &mut self,
+        repository: String,
+        description: String,
+        default_branch: String,
+        visibility: String,
+    ) -> Result<RepositoryInfo> {
+        let request = self.request(CreateRepositoryRequest {
+            repository,
+            description,
+            default_branch,
+            visibility,
+        });
+
+        let response = self
+            .repository_client
+            .create_repository(request)
+            .await
+            .map_err(error::GrpcClientError::Repository)?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn delete_repository(&mut self, repository: String) -> Result<RepositoryInfo> {
+        let request = self.request(RepositoryRequest { repository });
+
+        let response = self
+            .repository_client
+            .delete_repository(request)
+            .await
+            .map_err(error::GrpcClientError::Repository)?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn rename_repository(
+        &mut self,
+        repository: String,
+        new_name: String,
+    ) -> Result<RepositoryInfo> {
+        let request = self.request(RenameRepositoryRequest {
+            repository,
+            new_name,
+        });
+
+        let response = self
+            .repository_client
+            .rename_repository(request)
+            .await
+            .map_err(error::GrpcClientError::Repository)?;
+        Ok(response.into_inner())
+    }
+
+    pub async fn describe_repository(&mut self, repository: String) -> Result<RepositoryInfo> {
+        let request = self.request(RepositoryRequest { repository });
+
+        let response = self
+            .repository_client
+            .describe_repository(request)
+            .await
+            .map_err(error::GrpcClientError::Repository)?;
+        Ok(response.into_inner())
+    }
+
+    /// Repositories of `namespace` the user can read, their own when empty.
+    pub async fn list_repositories(&mut self, namespace: String) -> Result<Vec<RepositoryInfo>> {
+        let request = self.request(ListRepositoriesRequest { namespace });
+
+        let response = self
+            .repository_client
+            .list_repositories(request)
+            .await
+            .map_err(error::GrpcClientError::Repository)?;
+        Ok(response.into_inner().repositories)
+    }
+
+    pub async fn create_organization(&mut self, name: String) -> Result<OrganizationInfo> {
+        let request = self.request(OrganizationRequest { name });
+
+        let response = self
+            .repository_client
+            .create_organization(request)
+            .await
+            .map_err(error::GrpcClientError::Repository)?;
+        Ok(response.into_inner())
+    }
+
+    /// Gives `user_email` the `role` in an organization, or removes them when `role` is empty.
+    pub async fn set_member(
+        &mut self,
+        organization: String,
+        user_email: String,
+        role: String,
+    ) -> Result<OrganizationInfo> {
+        let request = self.request(MemberRequest {
+            organization,
+            user_email,
+            role,
+        });
+
+        let response = self
+            .repository_client
+            .set_member(request)
+            .await
+            .map_err(error::GrpcClientError::Repository)?;
+        Ok(response.into_inner())
+    }
+
+    /// A page of the branches of `repository`, the first one when `page_token` is empty.
+    pub async fn list_refs(
+        &mut self,
+        repository: String,
+        page_token: String,
+    ) -> Result<ListRefsResponse> {
+        let request = self.request(ListRefsRequest {
+            repository,
+            page_size: 0,
+            page_token,
+        });
+
+        let response = self
+            .browse_client
+            .list_refs(request)
+            .await
+            .map_err(error::GrpcClientError::Browse)?;
+        Ok(response.into_inner())
+    }
+
+    /// A page of the first-parent history of `revision`, the default branch when empty.
+    pub async fn list_commits(
+        &mut self,
+        repository: String,
+        revision: String,
+        page_size: u32,
+        page_token: String,
+    ) -> Result<ListCommitsResponse> {
+        let request = self.request(ListCommitsRequest {
+            repository,
+            revision,
+            page_size,
+            page_token,
+        });
+
+        let response = self
+            .browse_client
+            .list_commits(request)
+            .await
+            .map_err(error::GrpcClientError::Browse)?;
+        Ok(response.into_inner())
+    }
+
+    /// A page of the entries of the directory `path` at `revision`.
+    pub async fn list_tree(
+        &mut self,
+        repository: String,
+        revision: String,
+        path: String,
+        page_token: String,
+    ) -> Result<ListTreeResponse> {
+        let request = self.request(ListTreeRequest {
+            repository,
+            revision,
+            path,
+            page_size: 0,
+            page_token,
+        });
+
+        let response = self
+            .browse_client
+            .list_tree(request)
+            .await
+            .map_err(error::GrpcClientError::Browse)?;
+        Ok(response.into_inner())
+    }
+
+    /// The content of the file `path` at `revision`, from `offset` on and as much of it as the
+    /// server sends at once.
+    pub async fn get_blob(
+        &mut self,
+        repository: String,
+        revision: String,
+        path: String,
+        offset: u64,
+    ) -> Result<GetBlobResponse> {
+        let request = self.request(GetBlobRequest {
+            repository,
+            revision,
+            path,
+            offset,
+            limit: 0,
+        });
+
+        let response = self
+            .browse_client
+            .get_blob(request)
+            .await
+            .map_err(error::GrpcClientError::Browse)?;
+        Ok(response.into_inner())
+    }
 }
@@ -0,0 +1,94 @@
+use crate::error;
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+pub type Result<T> = std::result::Result<T, error::HookError>;
+
+/// Runs before a commit is created, a failure aborts the commit.
+pub const PRE_COMMIT: &str = "pre-commit";
+/// Runs with the path of a file holding the commit message, which the hook may edit.
+pub const COMMIT_MSG: &str = "commit-msg";
+/// Runs after HEAD moved with `<previous HEAD> <new HEAD> 1`.
+pub const POST_CHECKOUT: &str = "post-checkout";
+/// Runs before a push with `<remote> <url>` and one
+/// `<local ref> <local hash> <remote ref> <remote hash>` line per pushed ref on stdin.
+pub const PRE_PUSH: &str = "pre-push";
+/// Runs before a rebase with `<upstream> [<branch>]`.
+pub const PRE_REBASE: &str = "pre-rebase";
+/// Runs before each cherry-pick, revert or rebase step commits its merged changes, a
+/// failure stops the operation at that step.
+pub const PRE_MERGE_COMMIT: &str = "pre-merge-commit";
+
+/// Executables in `.flux/hooks/`, named after the hook they implement.
+#[derive(Debug)]
+pub struct Hooks {
+    dir: PathBuf,
+    flux_dir: PathBuf,
+    work_tree: PathBuf,
+}
+
+impl Hooks {
+    pub fn new(flux_dir: &Path, work_tree: &Path) -> Self {
+        Self {
+            dir: flux_dir.join("hooks"),
+            flux_dir: flux_dir.to_path_buf(),
+            work_tree: work_tree.to_path_buf(),
+        }
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Whether an executable `name` hook is installed.
+    pub fn exists(&self, name: &str) -> bool {
+        let path = self.path(name);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            path.metadata()
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        }
+        #[cfg(not(unix))]
+        {
+            path.is_file()
+        }
+    }
+
+    /// Runs the `name` hook from the work tree root with `FLUX_DIR` set. A missing hook
+    /// succeeds, a non-zero exit is an error.
+    pub fn run(&self, name: &str, args: &[&str], stdin: Option<&str>) -> Result<()> {
+        if !self.exists(name) {
+            return Ok(());
+        }
+
+        let failed = |reason: String| error::HookError::Failed {
+            hook: name.to_string(),
+            reason,
+        };
+        let mut child = Command::new(self.path(name))
+            .args(args)
+            .current_dir(&self.work_tree)
+            .env("FLUX_DIR", &self.flux_dir)
+            .stdin(match stdin {
+                Some(_) => Stdio::piped(),
+                None => Stdio::null(),
+            })
+            .spawn()
+            .map_err(|e| failed(e.to_string()))?;
+
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            // A hook may exit without reading its input, a broken pipe is not a failure.
+            let _ = pipe.write_all(input.as_bytes());
+        }
+
+        let status = child.wait().map_err(|e| failed(e.to_string()))?;
+        if !status.success() {
+            return Err(failed(status.to_string()));
+        }
+        Ok(())
+    }
+}
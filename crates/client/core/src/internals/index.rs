@@ -77,6 +77,11 @@ impl Index {
         Ok(res.is_some())
     }
 
+    pub fn replace(&mut self, map: HashMap<String, String>) -> Result<(), error::IndexError> {
+        self.map = map;
+        self.flush()
+    }
+
     pub fn clear(&mut self) -> Result<(), error::IndexError> {
         self.map.clear();
         self.flush()
@@ -0,0 +1,366 @@
+use crate::internals::diff::{self, ChangeStatus, RenameOptions};
+use crate::internals::object_store::{ObjectStore, Result};
+use similar::{Algorithm, DiffOp, capture_diff_slices};
+use std::collections::{BTreeSet, HashMap};
+
+/// Names printed after the conflict markers of each side.
+#[derive(Debug, Clone, Copy)]
+pub struct MergeLabels<'a> {
+    pub ours: &'a str,
+    pub theirs: &'a str,
+}
+
+#[derive(Debug)]
+pub struct TextMerge {
+    pub content: Vec<u8>,
+    pub conflicts: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    /// Both sides changed the same lines.
+    Content,
+    /// Both sides added the same path with different content.
+    AddAdd,
+    /// One side modified the file, the other deleted it.
+    ModifyDelete,
+    /// Both sides changed a file that cannot be merged line by line.
+    Binary,
+}
+
+impl ConflictKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Content => "content",
+            Self::AddAdd => "add/add",
+            Self::ModifyDelete => "modify/delete",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Conflict {
+    pub path: String,
+    pub kind: ConflictKind,
+    /// Content with conflict markers that should be written to the work tree.
+    /// `None` when the version kept in the merged map should be written as is.
+    pub content: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub struct TreeMerge {
+    /// Merged path to blob map. Conflicted paths keep the version that survived on either side.
+    pub map: HashMap<String, String>,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl TreeMerge {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    pub fn conflicted_paths(&self) -> Vec<String> {
+        self.conflicts.iter().map(|c| c.path.clone()).collect()
+    }
+}
+
+fn split_lines(content: &str) -> Vec<&str> {
+    content.split_inclusive('\n').collect()
+}
+
+/// Maps every line of `base` to the line of `other` it is matched with, if any.
+fn matches(base: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let mut result = vec![None; base.len()];
+    for op in capture_diff_slices(Algorithm::Myers, base, other) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            for i in 0..len {
+                result[old_index + i] = Some(new_index + i);
+            }
+        }
+    }
+    result
+}
+
+fn push_block(out: &mut String, lines: &[&str]) {
+    for line in lines {
+        out.push_str(line);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Three-way merges text content line by line (diff3). Returns `None` when any
+/// side is not valid UTF-8.
+pub fn merge_text(
+    base: &[u8],
+    ours: &[u8],
+    theirs: &[u8],
+    labels: MergeLabels,
+) -> Option<TextMerge> {
+    let base = split_lines(std::str::from_utf8(base).ok()?);
+    let ours = split_lines(std::str::from_utf8(ours).ok()?);
+    let theirs = split_lines(std::str::from_utf8(theirs).ok()?);
+
+    let match_ours = matches(&base, &ours);
+    let match_theirs = matches(&base, &theirs);
+
+    let (mut io, mut ia, mut ib) = (0, 0, 0);
+    let mut out = String::new();
+    let mut conflicts = 0;
+
+    loop {
+        let mut stable = 0;
+        while io + stable < base.len()
+            && match_ours[io + stable] == Some(ia + stable)
+            && match_theirs[io + stable] == Some(ib + stable)
+        {
+            out.push_str(base[io + stable]);
+            stable += 1;
+        }
+        if stable > 0 {
+            io += stable;
+            ia += stable;
+            ib += stable;
+            continue;
+        }
+
+        if io == base.len() && ia == ours.len() && ib == theirs.len() {
+            break;
+        }
+
+        let (next_o, next_a, next_b) = (io..base.len())
+            .find_map(|k| match (match_ours[k], match_theirs[k]) {
+                (Some(a), Some(b)) if a >= ia && b >= ib => Some((k, a, b)),
+                _ => None,
+            })
+            .unwrap_or((base.len(), ours.len(), theirs.len()));
+
+        let chunk_base = &base[io..next_o];
+        let chunk_ours = &ours[ia..next_a];
+        let chunk_theirs = &theirs[ib..next_b];
+
+        if chunk_ours == chunk_base || chunk_ours == chunk_theirs {
+            chunk_theirs.iter().for_each(|line| out.push_str(line));
+        } else if chunk_theirs == chunk_base {
+            chunk_ours.iter().for_each(|line| out.push_str(line));
+        } else {
+            conflicts += 1;
+            if !out.is_empty() && !out.ends_with('\n') {
+                out.push('\n');
+            }
+            out.push_str(&format!("<<<<<<< {}\n", labels.ours));
+            push_block(&mut out, chunk_ours);
+            out.push_str("=======\n");
+            push_block(&mut out, chunk_theirs);
+            out.push_str(&format!(">>>>>>> {}\n", labels.theirs));
+        }
+
+        io = next_o;
+        ia = next_a;
+        ib = next_b;
+    }
+
+    Some(TextMerge {
+        content: out.into_bytes(),
+        conflicts,
+    })
+}
+
+/// Renames made from `base` to `side`, as `(old path, new path)` pairs.
+fn renames(
+    base: &HashMap<String, String>,
+    side: &HashMap<String, String>,
+    object_store: &ObjectStore,
+) -> Result<Vec<(String, String)>> {
+    let changes = diff::diff_maps(base, side);
+    let changes = diff::detect_renames(changes, base, RenameOptions::default(), |_, hash| {
+        object_store.blob_content(hash)
+    })?;
+    Ok(changes
+        .into_iter()
+        .filter(|change| matches!(change.status, ChangeStatus::Renamed(_)))
+        .filter_map(|change| change.old_path.map(|old_path| (old_path, change.path)))
+        .collect())
+}
+
+/// Moves `old_path` to `new_path` in each of `maps` that still has it.
+fn move_entry(maps: &mut [&mut HashMap<String, String>], old_path: &str, new_path: &str) {
+    for map in maps {
+        if let Some(hash) = map.remove(old_path) {
+            map.insert(new_path.to_string(), hash);
+        }
+    }
+}
+
+/// Three-way merges two flattened trees (path to blob hash maps) against their common base.
+/// Clean file merges are written to the object store.
+///
+/// Files renamed on one side are moved on the base and the other side first, so changes
+/// made to the old path are merged into the renamed file.
+pub fn merge_trees(
+    base: &HashMap<String, String>,
+    ours: &HashMap<String, String>,
+    theirs: &HashMap<String, String>,
+    object_store: &ObjectStore,
+    labels: MergeLabels,
+) -> Result<TreeMerge> {
+    let mut base = base.clone();
+    let mut ours = ours.clone();
+    let mut theirs = theirs.clone();
+
+    let our_renames = renames(&base, &ours, object_store)?;
+    let their_renames = renames(&base, &theirs, object_store)?;
+    for (old_path, new_path) in &their_renames {
+        if ours.contains_key(old_path) && !ours.contains_key(new_path) {
+            move_entry(&mut [&mut base, &mut ours], old_path, new_path);
+        }
+    }
+    for (old_path, new_path) in &our_renames {
+        if theirs.contains_key(old_path) && !theirs.contains_key(new_path) {
+            move_entry(&mut [&mut base, &mut theirs], old_path, new_path);
+        }
+    }
+
+    let paths: BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+
+    let mut map = HashMap::new();
+    let mut conflicts = Vec::new();
+
+    for path in paths {
+        let base_hash = base.get(path);
+        let ours_hash = ours.get(path);
+        let theirs_hash = theirs.get(path);
+
+        let resolved = if ours_hash == theirs_hash || theirs_hash == base_hash {
+            Some(ours_hash)
+        } else if ours_hash == base_hash {
+            Some(theirs_hash)
+        } else {
+            None
+        };
+
+        if let Some(hash) = resolved {
+            if let Some(hash) = hash {
+                map.insert(path.clone(), hash.clone());
+            }
+            continue;
+        }
+
+        match (ours_hash, theirs_hash) {
+            (Some(ours_hash), Some(theirs_hash)) => {
+                let base_content = match base_hash {
+                    Some(hash) => object_store.blob_content(hash)?,
+                    None => Vec::new(),
+                };
+                let merged = merge_text(
+                    &base_content,
+                    &object_store.blob_content(ours_hash)?,
+                    &object_store.blob_content(theirs_hash)?,
+                    labels,
+                );
+
+                match merged {
+                    Some(merged) if merged.conflicts == 0 => {
+                        let hash = object_store.store_blob(merged.content)?;
+                        map.insert(path.clone(), hash);
+                    }
+                    Some(merged) => {
+                        map.insert(path.clone(), ours_hash.clone());
+                        conflicts.push(Conflict {
+                            path: path.clone(),
+                            kind: if base_hash.is_some() {
+                                ConflictKind::Content
+                            } else {
+                                ConflictKind::AddAdd
+                            },
+                            content: Some(merged.content),
+                        });
+                    }
+                    None => {
+                        map.insert(path.clone(), ours_hash.clone());
+                        conflicts.push(Conflict {
+                            path: path.clone(),
+                            kind: ConflictKind::Binary,
+                            content: None,
+                        });
+                    }
+                }
+            }
+            (Some(hash), None) | (None, Some(hash)) => {
+                map.insert(path.clone(), hash.clone());
+                conflicts.push(Conflict {
+                    path: path.clone(),
+                    kind: ConflictKind::ModifyDelete,
+                    content: None,
+                });
+            }
+            (None, None) => {}
+        }
+    }
+
+    Ok(TreeMerge { map, conflicts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELS: MergeLabels = MergeLabels {
+        ours: "ours",
+        theirs: "theirs",
+    };
+
+    fn merge(base: &str, ours: &str, theirs: &str) -> (String, usize) {
+        let merged = merge_text(base.as_bytes(), ours.as_bytes(), theirs.as_bytes(), LABELS)
+            .expect("text content should merge");
+        (String::from_utf8(merged.content).unwrap(), merged.conflicts)
+    }
+
+    #[test]
+    fn merges_disjoint_changes() {
+        let base = "one\ntwo\nthree\nfour\nfive\n";
+        let ours = "ONE\ntwo\nthree\nfour\nfive\n";
+        let theirs = "one\ntwo\nthree\nfour\nFIVE\nsix\n";
+
+        let (content, conflicts) = merge(base, ours, theirs);
+        assert_eq!(conflicts, 0);
+        assert_eq!(content, "ONE\ntwo\nthree\nfour\nFIVE\nsix\n");
+    }
+
+    #[test]
+    fn reports_overlapping_changes() {
+        let base = "one\ntwo\nthree\n";
+        let ours = "one\nours\nthree\n";
+        let theirs = "one\ntheirs\nthree\n";
+
+        let (content, conflicts) = merge(base, ours, theirs);
+        assert_eq!(conflicts, 1);
+        assert_eq!(
+            content,
+            "one\n<<<<<<< ours\nours\n=======\ntheirs\n>>>>>>> theirs\nthree\n"
+        );
+    }
+
+    #[test]
+    fn identical_changes_are_clean() {
+        let (content, conflicts) = merge("a\n", "b\n", "b\n");
+        assert_eq!(conflicts, 0);
+        assert_eq!(content, "b\n");
+    }
+
+    #[test]
+    fn binary_content_is_not_merged() {
+        assert!(merge_text(b"a", &[0xff, 0xfe], b"b", LABELS).is_none());
+    }
+}
@@ -1,7 +1,18 @@
 pub mod config;
+pub mod credentials;
+pub mod diff;
+pub mod fsck;
+pub mod gc;
 mod grpc_client;
+pub mod hooks;
 mod index;
+pub mod merge;
 mod object_store;
+pub mod reflog;
 mod refs;
 pub mod repository;
+pub mod revision;
+pub mod sequencer;
+pub mod signing;
+pub mod stash;
 mod work_tree;
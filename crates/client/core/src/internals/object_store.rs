@@ -54,6 +54,93 @@ impl ObjectStore {
         }
     }
 
+    pub fn exists(&self, hash: &str) -> bool {
+        if hash.len() < 3 {
+            return false;
+        }
+        let (dir, file) = hash.split_at(2);
+        self.path.join(dir).join(file).is_file()
+    }
+
+    /// Returns the hashes of every stored object starting with `prefix`.
+    pub fn find_by_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        if prefix.len() < 2 {
+            return Ok(Vec::new());
+        }
+
+        let (dir, rest) = prefix.split_at(2);
+        let object_dir = self.path.join(dir);
+        if !object_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let iter =
+            fs::read_dir(&object_dir).map_err(|e| error::IoError::read_error(&object_dir, e))?;
+        let mut hashes = Vec::new();
+        for entry in iter {
+            let entry = entry.map_err(|e| error::IoError::read_error(&object_dir, e))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(rest) && !name.ends_with(".tmp") {
+                hashes.push(format!("{}{}", dir, name));
+            }
+        }
+
+        Ok(hashes)
+    }
+
+    pub fn object_path(&self, hash: &str) -> PathBuf {
+        let (dir, file) = hash.split_at(2);
+        self.path.join(dir).join(file)
+    }
+
+    /// Lists the hashes of every loose object in the store.
+    pub fn list_objects(&self) -> Result<Vec<String>> {
+        let mut hashes = Vec::new();
+        for (dir_name, file_name, _) in self.walk()? {
+            if !file_name.ends_with(".tmp") {
+                hashes.push(format!("{}{}", dir_name, file_name));
+            }
+        }
+        hashes.sort();
+        Ok(hashes)
+    }
+
+    /// Lists the `.tmp` files left behind by interrupted writes.
+    pub fn temp_files(&self) -> Result<Vec<PathBuf>> {
+        Ok(self
+            .walk()?
+            .into_iter()
+            .filter(|(_, file_name, _)| file_name.ends_with(".tmp"))
+            .map(|(_, _, path)| path)
+            .collect())
+    }
+
+    /// Returns `(directory name, file name, path)` for every file inside the object fan-out directories.
+    fn walk(&self) -> Result<Vec<(String, String, PathBuf)>> {
+        let mut files = Vec::new();
+        let dirs =
+            fs::read_dir(&self.path).map_err(|e| error::IoError::read_error(&self.path, e))?;
+
+        for dir in dirs {
+            let dir = dir.map_err(|e| error::IoError::read_error(&self.path, e))?;
+            let dir_path = dir.path();
+            let dir_name = dir.file_name().to_string_lossy().to_string();
+            if !dir_path.is_dir() || dir_name.len() != 2 {
+                continue;
+            }
+
+            let entries =
+                fs::read_dir(&dir_path).map_err(|e| error::IoError::read_error(&dir_path, e))?;
+            for entry in entries {
+                let entry = entry.map_err(|e| error::IoError::read_error(&dir_path, e))?;
+                let file_name = entry.file_name().to_string_lossy().to_string();
+                files.push((dir_name.clone(), file_name, entry.path()));
+            }
+        }
+
+        Ok(files)
+    }
+
     pub fn raw_content(&self, hash: &str) -> Result<Vec<u8>> {
         let (dir, file) = hash.split_at(2);
         let object_path = self.path.join(dir).join(file);
@@ -158,23 +245,53 @@ impl ObjectStore {
         Ok(())
     }
 
-    pub fn commit_to_map(&self, commit_hash: String) -> Result<HashMap<String, String>> {
-        let obj = self.retrieve_object(&commit_hash)?;
-
-        let commit = obj
-            .as_any()
+    pub fn retrieve_commit(&self, hash: &str) -> Result<Commit> {
+        let obj = self.retrieve_object(hash)?;
+        obj.as_any()
             .downcast_ref::<Commit>()
-            .ok_or_else(|| error::ObjectStoreError::Downcast { expected: "commit" })?;
+            .map(|commit| Commit::from_content(commit.content()))
+            .ok_or(error::ObjectStoreError::Downcast { expected: "commit" })
+    }
 
-        let obj = self.retrieve_object(&commit.tree_hash)?;
+    pub fn retrieve_tree(&self, hash: &str) -> Result<Tree> {
+        let obj = self.retrieve_object(hash)?;
+        obj.as_any()
+            .downcast_ref::<Tree>()
+            .map(|tree| Tree::from_content(tree.content()))
+            .ok_or(error::ObjectStoreError::Downcast { expected: "tree" })
+    }
+
+    pub fn commit_to_map(&self, commit_hash: String) -> Result<HashMap<String, String>> {
+        let commit = self.retrieve_commit(&commit_hash)?;
+        self.tree_hash_to_map(&commit.tree_hash)
+    }
+
+    /// Flattens the tree with the given hash into a map of relative file paths to blob hashes.
+    pub fn tree_hash_to_map(&self, tree_hash: &str) -> Result<HashMap<String, String>> {
+        let obj = self.retrieve_object(tree_hash)?;
         let tree = obj
             .as_any()
             .downcast_ref::<Tree>()
-            .ok_or_else(|| error::ObjectStoreError::Downcast { expected: "tree" })?;
+            .ok_or(error::ObjectStoreError::Downcast { expected: "tree" })?;
 
         self.tree_to_map(tree, "")
     }
 
+    /// Stores `content` as a blob and returns its hash.
+    pub fn store_blob(&self, content: Vec<u8>) -> Result<String> {
+        let blob = Blob::from_content(content);
+        self.store(&blob)?;
+        Ok(blob.hash())
+    }
+
+    pub fn blob_content(&self, hash: &str) -> Result<Vec<u8>> {
+        let obj = self.retrieve_object(hash)?;
+        if obj.object_type() != ObjectType::Blob {
+            return Err(error::ObjectStoreError::Downcast { expected: "blob" });
+        }
+        Ok(obj.content())
+    }
+
     fn tree_to_map(&self, tree: &Tree, prefix: &str) -> Result<HashMap<String, String>> {
         let mut map = HashMap::new();
         for entry in tree.entries() {
@@ -0,0 +1,236 @@
+use crate::error;
+use chrono::Local;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+pub type Result<T> = std::result::Result<T, error::RefsError>;
+
+/// Hash recorded as the old or new value when a ref is created or deleted.
+pub const ZERO_HASH: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    pub old_hash: String,
+    pub new_hash: String,
+    pub identity: String,
+    pub timestamp: i64,
+    pub offset: String,
+    pub message: String,
+}
+
+impl ReflogEntry {
+    pub fn new(old_hash: &str, new_hash: &str, identity: &str, message: &str) -> Self {
+        let now = Local::now();
+        Self {
+            old_hash: Self::hash_or_zero(old_hash),
+            new_hash: Self::hash_or_zero(new_hash),
+            identity: identity.to_string(),
+            timestamp: now.timestamp(),
+            offset: now.format("%z").to_string(),
+            message: message.lines().next().unwrap_or("").to_string(),
+        }
+    }
+
+    fn hash_or_zero(hash: &str) -> String {
+        if hash.is_empty() {
+            ZERO_HASH.to_string()
+        } else {
+            hash.to_string()
+        }
+    }
+
+    /// Parses a single reflog line in the format
+    /// `<old> <new> <name> <<email>> <timestamp> <offset>\t<message>`.
+    fn parse(line: &str) -> Option<Self> {
+        let (header, message) = line.split_once('\t').unwrap_or((line, ""));
+        let (old_hash, rest) = header.split_once(' ')?;
+        let (new_hash, rest) = rest.split_once(' ')?;
+        let mut parts = rest.rsplitn(3, ' ');
+        let offset = parts.next()?;
+        let timestamp = parts.next()?.parse().ok()?;
+        let identity = parts.next()?;
+
+        Some(Self {
+            old_hash: old_hash.to_string(),
+            new_hash: new_hash.to_string(),
+            identity: identity.to_string(),
+            timestamp,
+            offset: offset.to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    fn format(&self) -> String {
+        format!(
+            "{} {} {} {} {}\t{}\n",
+            self.old_hash, self.new_hash, self.identity, self.timestamp, self.offset, self.message
+        )
+    }
+}
+
+/// Append-only history of the values a ref has pointed to, stored under `.flux/logs/`.
+#[derive(Debug)]
+pub struct Reflog {
+    logs_path: PathBuf,
+}
+
+impl Reflog {
+    pub fn new(flux_dir: &Path) -> Self {
+        Self {
+            logs_path: flux_dir.join("logs"),
+        }
+    }
+
+    fn log_path(&self, ref_name: &str) -> PathBuf {
+        self.logs_path.join(ref_name)
+    }
+
+    pub fn append(&self, ref_name: &str, entry: &ReflogEntry) -> Result<()> {
+        let path = self.log_path(ref_name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| error::IoError::create_error(parent, e))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| error::IoError::Open {
+                path: path.clone(),
+                source: e,
+            })?;
+
+        file.write_all(entry.format().as_bytes())
+            .map_err(|e| error::IoError::write_error(&path, e))?;
+
+        Ok(())
+    }
+
+    /// Returns the entries of a ref's log, oldest first. A ref without a log has no entries.
+    pub fn read(&self, ref_name: &str) -> Result<Vec<ReflogEntry>> {
+        let path = self.log_path(ref_name);
+        if !path.is_file() {
+            return Ok(Vec::new());
+        }
+
+        let content =
+            fs::read_to_string(&path).map_err(|e| error::IoError::read_error(&path, e))?;
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                ReflogEntry::parse(line).ok_or_else(|| error::RefsError::InvalidReflog {
+                    ref_name: ref_name.to_string(),
+                    line: line.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Replaces the whole log of a ref. Writing no entries removes the log.
+    pub fn write(&self, ref_name: &str, entries: &[ReflogEntry]) -> Result<()> {
+        if entries.is_empty() {
+            return self.delete(ref_name);
+        }
+
+        let path = self.log_path(ref_name);
+        let content: String = entries.iter().map(ReflogEntry::format).collect();
+        fs::write(&path, content).map_err(|e| error::IoError::write_error(&path, e))?;
+
+        Ok(())
+    }
+
+    /// Removes the entries recorded before `cutoff` (a unix timestamp) and returns how many
+    /// were removed. A log left without entries is deleted.
+    pub fn expire(&self, ref_name: &str, cutoff: i64) -> Result<usize> {
+        let entries = self.read(ref_name)?;
+        let kept: Vec<ReflogEntry> = entries
+            .iter()
+            .filter(|entry| entry.timestamp >= cutoff)
+            .cloned()
+            .collect();
+
+        let removed = entries.len() - kept.len();
+        if removed > 0 {
+            self.write(ref_name, &kept)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Lists the names of every ref that has a log, e.g. `HEAD` or `refs/heads/main`.
+    pub fn ref_names(&self) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        if self.logs_path.is_dir() {
+            self.collect_ref_names(&self.logs_path, &mut names)?;
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn collect_ref_names(&self, dir: &Path, names: &mut Vec<String>) -> Result<()> {
+        let iter = fs::read_dir(dir).map_err(|e| error::IoError::read_error(dir, e))?;
+        for entry in iter {
+            let path = entry
+                .map_err(|e| error::IoError::read_error(dir, e))?
+                .path();
+            if path.is_dir() {
+                self.collect_ref_names(&path, names)?;
+            } else if let Ok(rel) = path.strip_prefix(&self.logs_path) {
+                names.push(rel.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, ref_name: &str) -> Result<()> {
+        let path = self.log_path(ref_name);
+        if path.is_file() {
+            fs::remove_file(&path).map_err(|e| error::IoError::delete_error(&path, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn append_and_read() {
+        let dir = tempdir().unwrap();
+        let reflog = Reflog::new(dir.path());
+
+        let first = ReflogEntry::new("", &"a".repeat(40), "Test User <test@example.com>", "first");
+        let second = ReflogEntry::new(
+            &"a".repeat(40),
+            &"b".repeat(40),
+            "Test User <test@example.com>",
+            "second\nignored body",
+        );
+        reflog.append("refs/heads/main", &first).unwrap();
+        reflog.append("refs/heads/main", &second).unwrap();
+
+        let entries = reflog.read("refs/heads/main").unwrap();
+        assert_eq!(entries, vec![first, second]);
+        assert_eq!(entries[0].old_hash, ZERO_HASH);
+        assert_eq!(entries[1].message, "second");
+        assert_eq!(entries[1].identity, "Test User <test@example.com>");
+
+        assert_eq!(
+            reflog.ref_names().unwrap(),
+            vec!["refs/heads/main".to_string()]
+        );
+        assert_eq!(reflog.expire("refs/heads/main", 0).unwrap(), 0);
+        assert_eq!(reflog.expire("refs/heads/main", i64::MAX).unwrap(), 2);
+        assert!(reflog.ref_names().unwrap().is_empty());
+
+        reflog.write("refs/heads/main", &[]).unwrap();
+        assert!(reflog.read("refs/heads/main").unwrap().is_empty());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error;
+use crate::internals::reflog::{Reflog, ReflogEntry};
 use std::{
     collections::HashMap,
     fs::{self, File},
@@ -10,8 +11,12 @@ pub struct Refs {
     pub refs_path: PathBuf,
     pub branches: HashMap<String, String>,
     pub head_path: PathBuf,
+    pub reflog: Reflog,
+    identity: String,
 }
 
+const UNKNOWN_IDENTITY: &str = "unknown <unknown>";
+
 pub type Result<T> = std::result::Result<T, error::RefsError>;
 
 impl Refs {
@@ -55,6 +60,8 @@ impl Refs {
             refs_path,
             branches,
             head_path,
+            reflog: Reflog::new(flux_dir),
+            identity: UNKNOWN_IDENTITY.to_string(),
         })
     }
 
@@ -87,9 +94,47 @@ impl Refs {
             refs_path,
             branches: map,
             head_path: flux_dir.join("HEAD"),
+            reflog: Reflog::new(flux_dir),
+            identity: UNKNOWN_IDENTITY.to_string(),
         })
     }
 
+    /// Sets the `Name <email>` identity recorded in the reflog for subsequent ref updates.
+    pub fn set_identity(&mut self, identity: String) {
+        self.identity = identity;
+    }
+
+    fn log_update(
+        &self,
+        ref_name: &str,
+        old_hash: &str,
+        new_hash: &str,
+        reason: &str,
+    ) -> Result<()> {
+        let entry = ReflogEntry::new(old_hash, new_hash, &self.identity, reason);
+        self.reflog.append(ref_name, &entry)
+    }
+
+    /// Expands a short ref name (`HEAD`, `main`, `stash`) to its full name, e.g. `refs/heads/main`.
+    /// Branches that were deleted but still have a reflog are also resolved.
+    pub fn full_ref_name(&self, name: &str) -> Option<String> {
+        if name == "HEAD" || name.starts_with("refs/") {
+            return Some(name.to_string());
+        }
+        if name == "stash" {
+            return Some("refs/stash".to_string());
+        }
+
+        let branch_ref = format!("refs/heads/{}", name);
+        let has_log = self
+            .reflog
+            .read(&branch_ref)
+            .map(|entries| !entries.is_empty())
+            .unwrap_or(false);
+
+        (self.branches.contains_key(name) || has_log).then_some(branch_ref)
+    }
+
     pub fn head_ref(&self) -> Result<String> {
         let raw = fs::read_to_string(&self.head_path)
             .map_err(|e| error::IoError::read_error(&self.head_path, e))?;
@@ -126,9 +171,22 @@ impl Refs {
         Ok(last_commit.trim().to_string())
     }
 
+    /// Points HEAD at another branch, recording the move in the HEAD reflog.
     pub fn set_head(&self, branch: &str) -> Result<()> {
+        let from_branch = self.current_branch().ok();
+        let old_commit = self.head_commit().unwrap_or_default();
+
         fs::write(&self.head_path, format!("ref: refs/heads/{}\n", branch))
             .map_err(|e| error::IoError::write_error(&self.head_path, e))?;
+
+        let new_commit = self.head_commit().unwrap_or_default();
+        let reason = format!(
+            "checkout: moving from {} to {}",
+            from_branch.as_deref().unwrap_or("HEAD"),
+            branch
+        );
+        self.log_update("HEAD", &old_commit, &new_commit, &reason)?;
+
         Ok(())
     }
 
@@ -143,6 +201,9 @@ impl Refs {
         fs::write(&path, start_commit.as_bytes())
             .map_err(|e| error::IoError::write_error(&path, e))?;
 
+        let reason = format!("branch: Created from {}", self.current_branch()?);
+        self.log_update(&format!("refs/heads/{}", name), "", &start_commit, &reason)?;
+
         self.branches.insert(name.to_string(), start_commit);
         self.set_head(name)?;
 
@@ -160,7 +221,18 @@ impl Refs {
             return Err(error::RefsError::MissingBranch(name.to_string()))?;
         }
 
+        let last_commit =
+            fs::read_to_string(&path).map_err(|e| error::IoError::read_error(&path, e))?;
         fs::remove_file(&path).map_err(|e| error::IoError::delete_error(&path, e))?;
+
+        // The log of a deleted branch is kept so its commits can still be recovered.
+        self.log_update(
+            &format!("refs/heads/{}", name),
+            last_commit.trim(),
+            "",
+            &format!("branch: deleted {}", name),
+        )?;
+
         self.branches.remove(name);
         Ok(())
     }
@@ -174,11 +246,18 @@ impl Refs {
         Ok(())
     }
 
-    pub fn update_head(&mut self, commit_hash: &str) -> Result<()> {
+    /// Moves the current branch to `commit_hash`. `reason` is recorded in the reflog of
+    /// both the branch and HEAD.
+    pub fn update_head(&mut self, commit_hash: &str, reason: &str) -> Result<()> {
+        let old_commit = self.head_commit().unwrap_or_default();
         let path = self.head_ref_path()?;
         fs::write(&path, commit_hash.as_bytes())
             .map_err(|e| error::IoError::write_error(&path, e))?;
 
+        let head_ref = self.head_ref()?;
+        self.log_update(&head_ref, &old_commit, commit_hash, reason)?;
+        self.log_update("HEAD", &old_commit, commit_hash, reason)?;
+
         let branch = self.current_branch()?;
         self.branches.insert(branch, commit_hash.to_string());
 
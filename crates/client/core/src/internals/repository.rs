@@ -3,23 +3,242 @@ use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use tar::Archive;
 use crate::error;
-use crate::internals::config::{Config, Field};
+use crate::internals::config::{Config, ConfigLevel};
+use crate::internals::credentials::CredentialStore;
+use crate::internals::diff::{self, ChangeStatus};
+use crate::internals::fsck;
+use crate::internals::gc;
 use crate::internals::grpc_client::GrpcClient;
+use crate::internals::hooks::{self, Hooks};
 use crate::internals::index::Index;
+use crate::internals::merge::{self, Conflict, MergeLabels};
 use crate::internals::object_store::ObjectStore;
+use crate::internals::reflog::ZERO_HASH;
 use crate::internals::refs::Refs;
+use crate::internals::revision::{self, Revision};
+use crate::internals::sequencer::{Action, Operation, Progress, Sequencer, Step};
+use crate::internals::signing;
+use crate::internals::stash::{STASH_REF, Stash};
 use crate::internals::work_tree::WorkTree;
 use crate::objects::blob::Blob;
-use crate::objects::commit::Commit;
+use crate::objects::commit::{self, Commit};
 use crate::objects::object_type::{FluxObject, ObjectType};
-use crate::objects::tree::Tree;
-use std::collections::HashMap;
+use crate::objects::signature::{self, DateZone, Signature};
+use crate::objects::tree::{Tree, TreeEntry};
+use chrono::{DateTime, FixedOffset, Local};
+use proto::models::{
+    AccessResponse, CommitInfo, OrganizationInfo, Person, RepositoryInfo, TreeEntryInfo,
+    UploadStatusCode,
+};
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs;
 use std::io::Cursor;
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub type Result<T> = std::result::Result<T, error::RepositoryError>;
 
+/// How `Repository::auth` proves the identity of the user to the server.
+#[derive(Debug, Clone)]
+pub enum AuthMethod {
+    Password(String),
+    /// Signs a login challenge with an SSH key, the configured `user.signing_key` when `None`.
+    Key(Option<PathBuf>),
+}
+
+/// An access token the server issued to the user.
+#[derive(Debug, Clone)]
+pub struct TokenInfo {
+    pub id: String,
+    pub scopes: Vec<String>,
+    pub issued_at: DateTime<Local>,
+    pub expires_at: DateTime<Local>,
+}
+
+/// Result of `Repository::auth_status`.
+#[derive(Debug, Clone)]
+pub struct AuthStatus {
+    pub user_name: String,
+    pub user_email: String,
+    /// Id of the stored token.
+    pub token_id: String,
+    /// Tokens of the user that are neither revoked nor expired, the stored one included.
+    pub tokens: Vec<TokenInfo>,
+}
+
+/// A user with access to a repository on the server, besides its owner.
+#[derive(Debug, Clone)]
+pub struct Collaborator {
+    pub user_email: String,
+    /// `read`, `write` or `admin`.
+    pub role: String,
+}
+
+/// Who can access a repository on the server.
+#[derive(Debug, Clone)]
+pub struct RepositoryAccess {
+    pub owner: String,
+    /// `public` repositories can be cloned by anyone, `private` ones by collaborators only.
+    pub visibility: String,
+    pub collaborators: Vec<Collaborator>,
+}
+
+impl From<AccessResponse> for RepositoryAccess {
+    fn from(response: AccessResponse) -> Self {
+        Self {
+            owner: response.owner,
+            visibility: response.visibility,
+            collaborators: response
+                .collaborators
+                .into_iter()
+                .map(|c| Collaborator {
+                    user_email: c.user_email,
+                    role: c.role,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A repository on the server.
+#[derive(Debug, Clone)]
+pub struct RemoteRepository {
+    /// Name of the user or organization the repository belongs to.
+    pub namespace: String,
+    pub name: String,
+    pub description: String,
+    pub default_branch: String,
+    /// `None` for repositories pushed before the server kept their metadata.
+    pub created_at: Option<DateTime<Local>>,
+    pub visibility: String,
+}
+
+impl From<RepositoryInfo> for RemoteRepository {
+    fn from(info: RepositoryInfo) -> Self {
+        Self {
+            namespace: info.namespace,
+            name: info.name,
+            description: info.description,
+            default_branch: info.default_branch,
+            created_at: (info.created_at > 0).then(|| local_time(info.created_at)),
+            visibility: info.visibility,
+        }
+    }
+}
+
+/// A namespace on the server shared by its members.
+#[derive(Debug, Clone)]
+pub struct Organization {
+    pub name: String,
+    pub created_at: DateTime<Local>,
+    /// Role of each member on every repository of the organization.
+    pub members: Vec<Collaborator>,
+}
+
+impl From<OrganizationInfo> for Organization {
+    fn from(info: OrganizationInfo) -> Self {
+        Self {
+            name: info.name,
+            created_at: local_time(info.created_at),
+            members: info
+                .members
+                .into_iter()
+                .map(|m| Collaborator {
+                    user_email: m.user_email,
+                    role: m.role,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Branches of a repository on the server.
+#[derive(Debug, Clone)]
+pub struct RemoteRefs {
+    /// Ref HEAD points at in the pushed repository.
+    pub head: String,
+    /// Full ref names and the commits they point at.
+    pub refs: Vec<(String, String)>,
+}
+
+/// A commit of a repository on the server.
+#[derive(Debug, Clone)]
+pub struct RemoteCommit {
+    pub hash: String,
+    pub parents: Vec<String>,
+    pub author: Option<Signature>,
+    pub committer: Option<Signature>,
+    pub message: String,
+}
+
+impl From<CommitInfo> for RemoteCommit {
+    fn from(info: CommitInfo) -> Self {
+        Self {
+            hash: info.hash,
+            parents: info.parents,
+            author: info.author.and_then(person_signature),
+            committer: info.committer.and_then(person_signature),
+            message: info.message,
+        }
+    }
+}
+
+fn person_signature(person: Person) -> Option<Signature> {
+    let offset = FixedOffset::east_opt(person.utc_offset)?;
+    let time = DateTime::from_timestamp(person.time, 0)?.with_timezone(&offset);
+    Some(Signature::new(&person.name, &person.email, time))
+}
+
+impl From<TreeEntryInfo> for TreeEntry {
+    fn from(info: TreeEntryInfo) -> Self {
+        Self {
+            mode: info.mode,
+            hash: info.hash,
+            name: info.name,
+        }
+    }
+}
+
+/// Seconds since the epoch, as sent by the server, in local time.
+fn local_time(secs: u64) -> DateTime<Local> {
+    DateTime::from_timestamp(secs as i64, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+}
+
+/// Options of `Repository::commit_with`.
+#[derive(Debug, Default, Clone)]
+pub struct CommitOptions {
+    /// Sign the commit with the configured `user.signing_key`. `None` follows `commit.sign`.
+    pub sign: Option<bool>,
+    /// Author identity, `Name <email>`. Defaults to `FLUX_AUTHOR_NAME`/`FLUX_AUTHOR_EMAIL`,
+    /// then to the configured user.
+    pub author: Option<String>,
+    /// Author date in any format `signature::parse_date` accepts. Defaults to
+    /// `FLUX_AUTHOR_DATE`, then to now.
+    pub date: Option<String>,
+    /// Replace HEAD with a commit on the same parents, keeping its author unless overridden.
+    pub amend: bool,
+    /// Record a commit even when nothing is staged.
+    pub allow_empty: bool,
+    /// Skip the `pre-commit` and `commit-msg` hooks.
+    pub no_verify: bool,
+}
+
+/// Options of `Repository::log`.
+#[derive(Debug, Default, Clone)]
+pub struct LogOptions {
+    /// Only show the commits that changed this file, following its renames.
+    pub follow: Option<String>,
+    /// Print the result of verifying the signature of every commit.
+    pub show_signature: bool,
+    /// Print a summary of every commit with its dates in this timezone, instead of the raw
+    /// commit content.
+    pub date: Option<DateZone>,
+}
+
 #[derive(Debug)]
 pub struct Repository {
     pub name: String,
@@ -29,6 +248,8 @@ pub struct Repository {
     pub config: Config,
     pub index: Index,
     pub object_store: ObjectStore,
+    pub stash: Stash,
+    pub hooks: Hooks,
 }
 
 impl Repository {
@@ -62,10 +283,16 @@ impl Repository {
         fs::create_dir_all(&flux_dir).map_err(|e| error::IoError::create_error(&flux_dir, e))?;
 
         let object_store = ObjectStore::new(&flux_dir)?;
-        let refs = Refs::new(&flux_dir)?;
         let config = Config::default(flux_dir.join("config"))?;
+        // The global configuration may already have an identity for the initial commit.
+        let mut refs = Refs::new(&flux_dir)?;
+        if let Some(identity) = Self::identity(&config) {
+            refs.set_identity(identity);
+        }
         let index = Index::new(&flux_dir)?;
+        let hooks = Hooks::new(&flux_dir, &work_tree_path);
         let work_tree = WorkTree::new(work_tree_path);
+        let stash = Stash::new(&flux_dir);
 
         let repo = Self {
             work_tree,
@@ -74,6 +301,8 @@ impl Repository {
             flux_dir,
             config,
             refs,
+            stash,
+            hooks,
             name: repo_name,
         };
 
@@ -110,7 +339,12 @@ impl Repository {
         let config = Config::from(&config_path)?;
         let index = Index::load(&store_dir)?;
         let object_store = ObjectStore::load(&store_dir)?;
-        let refs = Refs::load(&store_dir)?;
+        let mut refs = Refs::load(&store_dir)?;
+        if let Some(identity) = Self::identity(&config) {
+            refs.set_identity(identity);
+        }
+        let stash = Stash::new(&store_dir);
+        let hooks = Hooks::new(&store_dir, &work_tree_path);
 
         Ok(Self {
             refs,
@@ -119,31 +353,448 @@ impl Repository {
             flux_dir: store_dir,
             config,
             index,
+            stash,
+            hooks,
             name: repo_name,
         })
     }
 
-    pub async fn auth(&mut self, url: Option<String>) -> Result<()> {
+    /// Logs in to the server `url` (the origin by default), or creates an account there when
+    /// `register` is set, and keeps the issued token in the credential store.
+    pub async fn auth(
+        &mut self,
+        url: Option<String>,
+        method: AuthMethod,
+        register: bool,
+        scopes: Vec<String>,
+    ) -> Result<()> {
         let url = match url {
             Some(u) => u,
             None => self
                 .config
-                .get_required(Field::Origin)
+                .get_required("remote.origin.url")
                 .map_err(|_| error::RepositoryError::MissingOrigin())?,
         };
-        self.config.set("origin".to_string(), url.clone())?;
-        let mut client = GrpcClient::connect_remote(url).await?;
+        self.config
+            .set("remote.origin.url".to_string(), url.clone())?;
+        let mut client = Self::connect(&self.config, url.clone()).await?;
         let credentials = self.config.get_credentials()?;
-        let token = client
-            .auth(credentials.user_name, credentials.user_email)
-            .await?;
+        let (name, email) = (credentials.user_name, credentials.user_email);
+        let token = match method {
+            AuthMethod::Password(password) if register => {
+                client
+                    .register(name, email, password, String::new(), scopes)
+                    .await?
+            }
+            AuthMethod::Password(password) => {
+                client
+                    .login(email, password, String::new(), String::new(), scopes)
+                    .await?
+            }
+            AuthMethod::Key(path) => {
+                let path = match path {
+                    Some(path) => path,
+                    None => {
+                        let path = self.config.get_path("user.signing_key")?.ok_or_else(|| {
+                            error::ConfigError::NotSet("user.signing_key".to_string())
+                        })?;
+                        self.work_tree.path().join(path)
+                    }
+                };
+                let key = signing::load_key(&path)?;
+                if register {
+                    let public_key = key
+                        .public_key()
+                        .to_openssh()
+                        .map_err(error::SigningError::from)?;
+                    client
+                        .register(name, email, String::new(), public_key, scopes)
+                        .await?
+                } else {
+                    let challenge = client.challenge(email.clone()).await?;
+                    let signature = signing::sign(&key, challenge.as_bytes())?;
+                    client
+                        .login(email, String::new(), challenge, signature, scopes)
+                        .await?
+                }
+            }
+        };
+        CredentialStore::from_config(&self.config)?.store(&url, &token.access_token)?;
+        // Tokens used to be kept in the repository config, which is copied to every work tree.
         self.config
-            .set("access_token".to_string(), token.access_token)?;
+            .unset_at(ConfigLevel::Local, "remote.origin.token")?;
         Ok(())
     }
 
+    /// Removes the token of the server `url` (the origin by default) from the credential
+    /// store, returns whether there was one.
+    pub fn logout(path: Option<String>, url: Option<String>) -> Result<bool> {
+        let config = Self::open_config(path)?;
+        let url = match url {
+            Some(url) => url,
+            None => config
+                .get_required("remote.origin.url")
+                .map_err(|_| error::RepositoryError::MissingOrigin())?,
+        };
+        Ok(CredentialStore::from_config(&config)?.erase(&url)?)
+    }
+
+    /// Asks the server `url` (the origin by default) about the stored token and the other
+    /// active tokens of the user.
+    pub async fn auth_status(path: Option<String>, url: Option<String>) -> Result<AuthStatus> {
+        let config = Self::open_config(path)?;
+        let status = Self::remote_client(&config, url).await?.token_status().await?;
+
+        Ok(AuthStatus {
+            user_name: status.user_name,
+            user_email: status.user_email,
+            token_id: status.token_id,
+            tokens: status
+                .tokens
+                .into_iter()
+                .map(|token| TokenInfo {
+                    id: token.id,
+                    scopes: token.scopes,
+                    issued_at: local_time(token.issued_at),
+                    expires_at: local_time(token.expires_at),
+                })
+                .collect(),
+        })
+    }
+
+    /// Replaces the stored token of the server `url` (the origin by default) with a new one
+    /// with the same scopes, returns when the new token expires.
+    pub async fn refresh_token(
+        path: Option<String>,
+        url: Option<String>,
+    ) -> Result<DateTime<Local>> {
+        let config = Self::open_config(path)?;
+        let (url, access_token) = Self::remote_token(&config, url)?;
+        let mut client = Self::connect(&config, url.clone())
+            .await?
+            .with_token(Some(access_token));
+        let token = client.refresh().await?;
+
+        CredentialStore::from_config(&config)?.store(&url, &token.access_token)?;
+        Ok(local_time(token.expires_at))
+    }
+
+    /// Revokes the token `id` on the server `url` (the origin by default), every token of the
+    /// user with `all`, or else the stored token. The stored token is erased once it is no
+    /// longer valid. Returns the number of tokens revoked.
+    pub async fn revoke_tokens(
+        path: Option<String>,
+        url: Option<String>,
+        id: Option<String>,
+        all: bool,
+    ) -> Result<u32> {
+        let config = Self::open_config(path)?;
+        let (url, access_token) = Self::remote_token(&config, url)?;
+        let mut client = Self::connect(&config, url.clone())
+            .await?
+            .with_token(Some(access_token));
+
+        // Only the token id tells whether a single revoked token is the stored one.
+        let current = match (&id, all) {
+            (Some(id), false) => client.token_status().await?.token_id == *id,
+            _ => true,
+        };
+        let revoked = client.revoke(id.unwrap_or_default(), all).await?;
+        if current {
+            CredentialStore::from_config(&config)?.erase(&url)?;
+        }
+        Ok(revoked)
+    }
+
+    /// Visibility and collaborators of the repository at `url` (the origin by default).
+    pub async fn access(path: Option<String>, url: Option<String>) -> Result<RepositoryAccess> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.get_access().await?.into())
+    }
+
+    /// Makes the repository at `url` (the origin by default) `public` or `private`.
+    pub async fn set_visibility(
+        path: Option<String>,
+        url: Option<String>,
+        visibility: String,
+    ) -> Result<RepositoryAccess> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.set_visibility(visibility).await?.into())
+    }
+
+    /// Gives `user_email` the `role` (write when empty) on the repository at `url` (the origin
+    /// by default).
+    pub async fn add_collaborator(
+        path: Option<String>,
+        url: Option<String>,
+        user_email: String,
+        role: String,
+    ) -> Result<RepositoryAccess> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.add_collaborator(user_email, role).await?.into())
+    }
+
+    pub async fn remove_collaborator(
+        path: Option<String>,
+        url: Option<String>,
+        user_email: String,
+    ) -> Result<RepositoryAccess> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.remove_collaborator(user_email).await?.into())
+    }
+
+    /// Creates `repository` on the server of `url` (the origin by default). It is
+    /// `<namespace>/<name>`, or a name in the namespace of the user.
+    pub async fn create_remote(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+        description: String,
+        default_branch: String,
+        private: bool,
+    ) -> Result<RemoteRepository> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        let visibility = match private {
+            true => "private",
+            false => "public",
+        };
+        let info = client
+            .create_repository(
+                repository,
+                description,
+                default_branch,
+                visibility.to_string(),
+            )
+            .await?;
+        Ok(info.into())
+    }
+
+    pub async fn delete_remote(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+    ) -> Result<RemoteRepository> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.delete_repository(repository).await?.into())
+    }
+
+    /// Renames `repository` within its namespace.
+    pub async fn rename_remote(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+        new_name: String,
+    ) -> Result<RemoteRepository> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.rename_repository(repository, new_name).await?.into())
+    }
+
+    pub async fn describe_remote(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+    ) -> Result<RemoteRepository> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.describe_repository(repository).await?.into())
+    }
+
+    /// Repositories of `namespace` (the user by default) the user can read.
+    pub async fn list_remote(
+        path: Option<String>,
+        url: Option<String>,
+        namespace: Option<String>,
+    ) -> Result<Vec<RemoteRepository>> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        let repositories = client
+            .list_repositories(namespace.unwrap_or_default())
+            .await?;
+        Ok(repositories.into_iter().map(RemoteRepository::from).collect())
+    }
+
+    /// Creates an organization on the server, administered by the user.
+    pub async fn create_organization(
+        path: Option<String>,
+        url: Option<String>,
+        name: String,
+    ) -> Result<Organization> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        Ok(client.create_organization(name).await?.into())
+    }
+
+    /// Gives `user_email` the `role` on every repository of an organization, or removes them
+    /// from it when `role` is `None`.
+    pub async fn set_organization_member(
+        path: Option<String>,
+        url: Option<String>,
+        organization: String,
+        user_email: String,
+        role: Option<String>,
+    ) -> Result<Organization> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        let info = client
+            .set_member(organization, user_email, role.unwrap_or_default())
+            .await?;
+        Ok(info.into())
+    }
+
+    /// Branches of `repository` on the server of `url` (the origin by default).
+    pub async fn remote_refs(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+    ) -> Result<RemoteRefs> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        let mut refs = RemoteRefs {
+            head: String::new(),
+            refs: Vec::new(),
+        };
+
+        let mut page_token = String::new();
+        loop {
+            let page = client.list_refs(repository.clone(), page_token).await?;
+            refs.head = page.head;
+            refs.refs
+                .extend(page.refs.into_iter().map(|r| (r.name, r.commit)));
+            if page.next_page_token.is_empty() {
+                return Ok(refs);
+            }
+            page_token = page.next_page_token;
+        }
+    }
+
+    /// At most `max_count` commits of the first-parent history of `revision` in `repository`,
+    /// its default branch when `None`.
+    pub async fn remote_log(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+        revision: Option<String>,
+        max_count: usize,
+    ) -> Result<Vec<RemoteCommit>> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        let revision = revision.unwrap_or_default();
+
+        let mut commits = Vec::new();
+        let mut page_token = String::new();
+        while commits.len() < max_count {
+            let page_size = u32::try_from(max_count - commits.len()).unwrap_or(u32::MAX);
+            let page = client
+                .list_commits(repository.clone(), revision.clone(), page_size, page_token)
+                .await?;
+            commits.extend(page.commits.into_iter().map(RemoteCommit::from));
+            if page.next_page_token.is_empty() {
+                break;
+            }
+            page_token = page.next_page_token;
+        }
+
+        commits.truncate(max_count);
+        Ok(commits)
+    }
+
+    /// Entries of the directory `dir` of `repository` at `revision`, its default branch when
+    /// `None`.
+    pub async fn remote_tree(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+        revision: Option<String>,
+        dir: String,
+    ) -> Result<Vec<TreeEntry>> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        let mut revision = revision.unwrap_or_default();
+
+        let mut entries = Vec::new();
+        let mut page_token = String::new();
+        loop {
+            let page = client
+                .list_tree(repository.clone(), revision, dir.clone(), page_token)
+                .await?;
+            entries.extend(page.entries.into_iter().map(TreeEntry::from));
+            if page.next_page_token.is_empty() {
+                return Ok(entries);
+            }
+            // The next pages are read from the same commit, even if the branch moves meanwhile.
+            revision = page.commit;
+            page_token = page.next_page_token;
+        }
+    }
+
+    /// Content of the file `file` of `repository` at `revision`, its default branch when
+    /// `None`.
+    pub async fn remote_file(
+        path: Option<String>,
+        url: Option<String>,
+        repository: String,
+        revision: Option<String>,
+        file: String,
+    ) -> Result<Vec<u8>> {
+        let config = Self::open_config(path)?;
+        let mut client = Self::remote_client(&config, url).await?;
+        let mut revision = revision.unwrap_or_default();
+
+        let mut content = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = client
+                .get_blob(repository.clone(), revision, file.clone(), offset)
+                .await?;
+            content.extend(page.content);
+            if page.next_offset == 0 {
+                return Ok(content);
+            }
+            revision = page.commit;
+            offset = page.next_offset;
+        }
+    }
+
+    /// Client for the server of `url` (the origin by default), authorized with its stored token.
+    async fn remote_client(config: &Config, url: Option<String>) -> Result<GrpcClient> {
+        let (url, access_token) = Self::remote_token(config, url)?;
+        Ok(Self::connect(config, url)
+            .await?
+            .with_token(Some(access_token)))
+    }
+
+    /// Connects to the server of `url`, also trusting the CA bundle of `http.cainfo` if set.
+    async fn connect(config: &Config, url: String) -> Result<GrpcClient> {
+        let ca_bundle = config.get_path("http.cainfo")?;
+        Ok(GrpcClient::connect_remote(url, ca_bundle).await?)
+    }
+
+    /// The url of `url` or else the origin, with the stored token of its server.
+    fn remote_token(config: &Config, url: Option<String>) -> Result<(String, String)> {
+        let url = match url {
+            Some(u) => u,
+            None => config
+                .get_required("remote.origin.url")
+                .map_err(|_| error::RepositoryError::MissingOrigin())?,
+        };
+        let access_token = CredentialStore::from_config(config)?
+            .get(&url)?
+            .ok_or_else(|| error::RepositoryError::MissingToken(url.clone()))?;
+        Ok((url, access_token))
+    }
+
     pub async fn clone(url: String, path: Option<String>) -> Result<Self> {
-        let mut clinet = GrpcClient::connect_remote(url).await?;
+        // Public repositories clone anonymously, private ones need a token for the server.
+        let config = Config::load(None)?;
+        let access_token = CredentialStore::from_config(&config)?.get(&url)?;
+        let mut clinet = Self::connect(&config, url).await?.with_token(access_token);
         let repo_name = clinet.repo_name()?;
         let archive = clinet.clone_repository().await?;
         let path = path.clone().unwrap_or(".".to_string());
@@ -155,7 +806,8 @@ impl Repository {
         Ok(repository)
     }
 
-    fn dearchive(archive_bytes: Vec<u8>, output_dir: &Path) -> Result<()> {
+    /// Unpacks a `.flux` directory archived for push or clone into `output_dir`.
+    pub fn dearchive(archive_bytes: Vec<u8>, output_dir: &Path) -> Result<()> {
         fs::create_dir_all(output_dir)?;
         let cursor = Cursor::new(archive_bytes);
         let decoder = GzDecoder::new(cursor);
@@ -173,10 +825,36 @@ impl Repository {
 
     pub fn set(&mut self, key: String, value: String) -> Result<()> {
         self.config.set(key, value)?;
+        if let Some(identity) = Self::identity(&self.config) {
+            self.refs.set_identity(identity);
+        }
         Ok(())
     }
 
+    /// `Name <email>` recorded in the reflog, when the configuration has a user.
+    fn identity(config: &Config) -> Option<String> {
+        let credentials = config.get_credentials().ok()?;
+        Some(format!(
+            "{} <{}>",
+            credentials.user_name, credentials.user_email
+        ))
+    }
+
+    /// Configuration seen from `path` (the current directory by default): every level inside
+    /// a repository, only the system and global levels outside of one.
+    pub fn open_config(path: Option<String>) -> Result<Config> {
+        let local = PathBuf::from(path.unwrap_or_else(|| ".".to_string()))
+            .join(".flux")
+            .join("config");
+        Ok(Config::load(local.exists().then_some(local))?)
+    }
+
     pub fn add(&mut self, path: &str) -> Result<()> {
+        // A non-empty index is the full snapshot of the next commit, staging into an empty
+        // one starts from HEAD so the files that are not added stay tracked.
+        if self.index.is_empty() {
+            self.index.replace(self.head_map()?)?;
+        }
         let full_path = self.work_tree.path().join(path);
         self.add_path(&full_path)?;
         self.remove_deleted_files_from_index(path)?;
@@ -257,198 +935,1590 @@ impl Repository {
         Ok(())
     }
 
-    pub fn delete(&mut self, rel: &str) -> Result<()> {
-        let abs = self.work_tree.path().join(rel);
-
-        let key = abs
-            .to_str()
-            .ok_or_else(|| error::RepositoryError::PathName { path: abs.clone() })?;
+    /// Removes the tracked files matching `path` from the index and, unless `cached`, from
+    /// the work tree. Directories are only removed with `recursive`. Returns the removed paths.
+    pub fn remove(&mut self, path: &str, cached: bool, recursive: bool) -> Result<Vec<String>> {
+        let mut snapshot = self.staged_map(&self.head_map()?);
+        let path = normalize_path(path);
 
-        let removed = self.index.remove(key)?;
+        let mut removed = tracked_under(&snapshot, &path);
+        if removed.is_empty() {
+            return Err(error::RepositoryError::PathNotFound(path));
+        }
+        if !recursive && removed != [path.clone()] {
+            return Err(error::RepositoryError::RecursiveRemove(path));
+        }
+        removed.sort();
+
+        if !cached {
+            // Refuse to throw away changes that only exist in the work tree.
+            let work_map = self.work_tree_map(&removed, false)?;
+            let modified: Vec<String> = removed
+                .iter()
+                .filter(|p| work_map.contains_key(*p) && work_map.get(*p) != snapshot.get(*p))
+                .cloned()
+                .collect();
+            if !modified.is_empty() {
+                return Err(error::RepositoryError::LocalChangesOverwritten { paths: modified });
+            }
+        }
 
-        if !removed {
-            eprint!("warning: {key} is not tracked");
+        for file in &removed {
+            snapshot.remove(file);
+            if !cached {
+                self.work_tree.remove_file(file)?;
+            }
         }
 
-        Ok(())
+        self.index.replace(snapshot)?;
+        Ok(removed)
     }
 
-    pub fn status(&self) -> Result<()> {
-        let index = &self.index.map;
-
-        let prev_commit_map = match self.refs.head_commit() {
-            Ok(hash) => self.object_store.commit_to_map(hash)?,
-            Err(_) => HashMap::new(),
-        };
-
-        let mut new_files = Vec::new();
-        let mut modified_files = Vec::new();
-        let mut deleted_files = Vec::new();
+    /// Renames the tracked file or directory `src` to `dst` in the work tree and the index.
+    /// When `dst` is an existing directory, `src` is moved inside it. Returns the renamed
+    /// `(from, to)` pairs.
+    pub fn move_path(&mut self, src: &str, dst: &str) -> Result<Vec<(String, String)>> {
+        let mut snapshot = self.staged_map(&self.head_map()?);
+        let src = normalize_path(src);
+        let mut dst = normalize_path(dst);
+
+        let mut sources = tracked_under(&snapshot, &src);
+        if sources.is_empty() {
+            return Err(error::RepositoryError::PathNotFound(src));
+        }
+        sources.sort();
 
-        for (path, hash) in index {
-            if let Some(prev_hash) = prev_commit_map.get(path) {
-                if prev_hash != hash {
-                    modified_files.push(path.clone());
-                }
+        if self.work_tree.path().join(&dst).is_dir() {
+            let name = src.rsplit('/').next().unwrap_or(&src);
+            dst = if dst.is_empty() {
+                name.to_string()
             } else {
-                new_files.push(path.clone());
+                format!("{dst}/{name}")
+            };
+        }
+        if dst == src || dst.starts_with(&format!("{src}/")) {
+            return Err(error::RepositoryError::MoveIntoItself { src, dst });
+        }
+
+        let moves: Vec<(String, String)> = sources
+            .into_iter()
+            .map(|from| {
+                let to = format!("{dst}{}", &from[src.len()..]);
+                (from, to)
+            })
+            .collect();
+
+        for (from, to) in &moves {
+            if snapshot.contains_key(to) || self.work_tree.path().join(to).exists() {
+                return Err(error::RepositoryError::PathExists(to.clone()));
+            }
+            if !self.work_tree.path().join(from).is_file() {
+                return Err(error::RepositoryError::PathNotFound(from.clone()));
             }
         }
 
-        for (path, _) in &prev_commit_map {
-            if !index.contains_key(path) {
-                deleted_files.push(path.clone());
+        for (from, to) in &moves {
+            self.work_tree.rename_file(from, to)?;
+            if let Some(hash) = snapshot.remove(from) {
+                snapshot.insert(to.clone(), hash);
             }
         }
 
-        if new_files.is_empty() && modified_files.is_empty() && deleted_files.is_empty() {
+        self.index.replace(snapshot)?;
+        Ok(moves)
+    }
+
+    /// Tracked files under `path` whose work tree content differs from the staged one.
+    pub fn unstaged_paths(&self, path: &str) -> Result<Vec<String>> {
+        let staged = self.staged_map(&self.head_map()?);
+        let mut paths = tracked_under(&staged, &normalize_path(path));
+        paths.sort();
+
+        let work_map = self.work_tree_map(&paths, false)?;
+        paths.retain(|p| work_map.get(p) != staged.get(p));
+        Ok(paths)
+    }
+
+    /// Hunks between the staged version of `path` and its work tree content. These are
+    /// the hunks `stage_hunks` selects from.
+    pub fn file_hunks(&self, path: &str) -> Result<Vec<diff::Hunk>> {
+        let (_, staged, work) = self.hunk_sources(path)?;
+        Ok(diff::hunks(&staged, &work.unwrap_or_default()))
+    }
+
+    /// Stages the hunks of `path` at the given positions of `file_hunks`, writing a blob that
+    /// holds only those changes on top of the staged version. The work tree is left as is.
+    pub fn stage_hunks(&mut self, path: &str, selected: &[usize]) -> Result<()> {
+        let (mut snapshot, staged, work) = self.hunk_sources(path)?;
+        let hunks = diff::hunks(&staged, work.as_deref().unwrap_or_default());
+
+        let mut picked = Vec::with_capacity(selected.len());
+        for &index in selected {
+            let hunk = hunks
+                .get(index)
+                .ok_or_else(|| error::RepositoryError::InvalidHunk {
+                    path: path.to_string(),
+                    index,
+                    count: hunks.len(),
+                })?;
+            picked.push(hunk);
+        }
+        if picked.is_empty() {
+            return Ok(());
+        }
+
+        let content = diff::apply_hunks(&staged, picked);
+        if work.is_none() && content.is_empty() {
+            snapshot.remove(path);
+        } else {
+            let hash = self.object_store.store_blob(content.into_bytes())?;
+            snapshot.insert(path.to_string(), hash);
+        }
+
+        // The index is the full snapshot of the next commit, not only the changed paths.
+        self.index.replace(snapshot)?;
+        Ok(())
+    }
+
+    /// Staged snapshot, staged text of `path` and its work tree text (`None` when deleted).
+    fn hunk_sources(
+        &self,
+        path: &str,
+    ) -> Result<(HashMap<String, String>, String, Option<String>)> {
+        let snapshot = self.staged_map(&self.head_map()?);
+        let staged = match snapshot.get(path) {
+            Some(hash) => Some(self.object_store.blob_content(hash)?),
+            None => None,
+        };
+        let work = self.work_tree.read_file(path)?;
+        if staged.is_none() && work.is_none() {
+            return Err(error::RepositoryError::PathNotFound(path.to_string()));
+        }
+
+        let text = |content: Option<Vec<u8>>| {
+            content
+                .map(String::from_utf8)
+                .transpose()
+                .map_err(|_| error::RepositoryError::BinaryFile(path.to_string()))
+        };
+        let staged = text(staged)?.unwrap_or_default();
+        let work = text(work)?;
+        Ok((snapshot, staged, work))
+    }
+
+    pub fn status(&self) -> Result<()> {
+        let changes = self.staged_changes(&self.head_map()?)?;
+
+        if changes.is_empty() {
             println!("nothing to commit, working tree clean");
             return Ok(());
         }
 
         println!("These changes will be included in the next commit:\n");
-        if !new_files.is_empty() {
-            println!("Added: ");
-            for file in new_files {
-                println!(" + {}", file);
+        let sections = [
+            ("Added", ChangeStatus::Added.letter(), '+'),
+            ("Modified", ChangeStatus::Modified.letter(), '~'),
+            ("Renamed", ChangeStatus::Renamed(0).letter(), '>'),
+            ("Removed", ChangeStatus::Deleted.letter(), '-'),
+        ];
+        let mut first = true;
+        for (title, letter, marker) in sections {
+            let files: Vec<&diff::FileChange> = changes
+                .iter()
+                .filter(|change| change.status.letter() == letter)
+                .collect();
+            if files.is_empty() {
+                continue;
             }
-        }
 
-        if !modified_files.is_empty() {
-            println!("\nModified: ");
-            for file in modified_files {
-                println!(" ~ {}", file);
+            if !first {
+                println!();
             }
-        }
-
-        if !deleted_files.is_empty() {
-            println!("\nRemoved: ");
-            for file in deleted_files {
-                println!(" - {}", file);
+            first = false;
+            println!("{title}: ");
+            for change in files {
+                match (&change.old_path, change.status) {
+                    (Some(old_path), ChangeStatus::Renamed(score)) => {
+                        println!(" {marker} {old_path} -> {} ({score}%)", change.path)
+                    }
+                    _ => println!(" {marker} {}", change.path),
+                }
             }
         }
 
         Ok(())
     }
 
-    pub fn commit(&mut self, message: String) -> Result<String> {
-        if self.index.is_empty() {
-            return Err(error::RepositoryError::IndexEmpty);
+    /// Rename detection settings, the threshold comes from `diff.rename_threshold`.
+    pub fn rename_options(&self) -> Result<diff::RenameOptions> {
+        let mut options = diff::RenameOptions::default();
+        if let Some(threshold) = self.config.get_int("diff.rename_threshold")? {
+            // The setting is validated to lie between 0 and 100.
+            options.threshold = threshold as u8;
         }
+        Ok(options)
+    }
 
-        let tree_hash = self
-            .work_tree
-            .build_tree_from_index(&self.index.map, &self.object_store)?;
-
-        let credentials = self
-            .config
-            .get_credentials()
-            .map_err(error::RepositoryError::Credentials)?;
-        let user_name = credentials.user_name;
-        let user_email = credentials.user_email;
+    /// Lists the changes between two snapshots.
+    ///
+    /// Without revisions the work tree is compared with the index, or the index with HEAD when
+    /// `cached` is set. A single revision is compared with the work tree (the index when
+    /// `cached`), two revisions with each other. Untracked files are not listed.
+    pub fn diff(
+        &self,
+        revisions: &[String],
+        cached: bool,
+        renames: Option<diff::RenameOptions>,
+    ) -> Result<Vec<diff::FileChange>> {
+        let head_map = self.head_map()?;
+        let staged_map = self.staged_map(&head_map);
+        let revision_map = |revision: &String| -> Result<HashMap<String, String>> {
+            let commit = self.resolve_revision(revision)?;
+            Ok(self.object_store.commit_to_map(commit)?)
+        };
 
-        let last = self.refs.head_commit()?;
-        let parent = (!last.is_empty()).then_some(last);
-        let commit = Commit::new(tree_hash, user_name, user_email, parent, message);
-        self.object_store.store(&commit)?;
-        let hash = commit.hash();
-        self.refs.update_head(&hash)?;
-        self.index.clear()?;
+        let (old, new) = match revisions {
+            [] if cached => (head_map, staged_map),
+            [] => {
+                let work_map = self.work_tree_map(staged_map.keys(), false)?;
+                (staged_map, work_map)
+            }
+            [revision] if cached => (revision_map(revision)?, staged_map),
+            [revision] => {
+                let old = revision_map(revision)?;
+                let work_map = self.work_tree_map(staged_map.keys().chain(old.keys()), false)?;
+                (old, work_map)
+            }
+            [from, to] => (revision_map(from)?, revision_map(to)?),
+            _ => {
+                return Err(error::RepositoryError::UnknownRevision(revisions.join(" ")));
+            }
+        };
 
-        Ok(hash)
+        let changes = diff::diff_maps(&old, &new);
+        match renames {
+            Some(options) => self.find_renames(changes, &old, options),
+            None => Ok(changes),
+        }
     }
 
-    pub fn log(&self, _reference: Option<String>) -> Result<()> {
-        let mut current_hash = self.refs.head_commit().ok().filter(|s| !s.is_empty());
+    /// Renders the changes returned by `diff` as a patch.
+    pub fn diff_patch(&self, changes: &[diff::FileChange]) -> Result<String> {
+        let mut out = String::new();
+        for change in changes {
+            let old_path = change.old_path.as_deref().unwrap_or(&change.path);
+            out.push_str(&format!("diff a/{old_path} b/{}\n", change.path));
+            match change.status {
+                ChangeStatus::Renamed(score) | ChangeStatus::Copied(score) => {
+                    let kind = match change.status {
+                        ChangeStatus::Renamed(_) => "rename",
+                        _ => "copy",
+                    };
+                    out.push_str(&format!("similarity index {score}%\n"));
+                    out.push_str(&format!("{kind} from {old_path}\n"));
+                    out.push_str(&format!("{kind} to {}\n", change.path));
+                }
+                ChangeStatus::Added => out.push_str("new file\n"),
+                ChangeStatus::Deleted => out.push_str("deleted file\n"),
+                ChangeStatus::Modified => {}
+            }
 
-        while let Some(hash) = current_hash {
-            self.cat(&hash)?;
-            let current = self.object_store.retrieve_object(&hash)?;
-            if let Some(commit) = current.as_any().downcast_ref::<Commit>() {
-                current_hash = commit.parent_hash().map(String::from);
-            } else {
-                break;
+            if change.old_hash == change.new_hash {
+                continue;
             }
+            let old = match &change.old_hash {
+                Some(hash) => self.diff_content(old_path, hash)?,
+                None => Vec::new(),
+            };
+            let new = match &change.new_hash {
+                Some(hash) => self.diff_content(&change.path, hash)?,
+                None => Vec::new(),
+            };
+            out.push_str(&diff::unified_diff(old_path, &change.path, &old, &new));
         }
-
-        Ok(())
+        Ok(out)
     }
 
-    pub fn show_branches(&self) -> Result<String> {
-        let branches = self.refs.format_branches()?;
-        Ok(branches)
+    /// Content of a blob, read from the work tree when it was never written to the store.
+    fn diff_content(&self, path: &str, hash: &str) -> Result<Vec<u8>> {
+        if self.object_store.exists(hash) {
+            return Ok(self.object_store.blob_content(hash)?);
+        }
+        Ok(self.work_tree.read_file(path)?.unwrap_or_default())
     }
 
-    pub fn list_branches(&self) -> Result<Vec<String>> {
-        let branches = self.refs.list_branches()?;
-        Ok(branches)
+    /// Changes the next commit records relative to `base`, with renames detected.
+    fn staged_changes(&self, base: &HashMap<String, String>) -> Result<Vec<diff::FileChange>> {
+        let staged_map = self.staged_map(&self.head_map()?);
+        let changes = diff::diff_maps(base, &staged_map);
+        self.find_renames(changes, base, self.rename_options()?)
     }
 
-    pub fn new_branch(&mut self, name: &str) -> Result<()> {
-        self.refs.new_branch(name)?;
-        Ok(())
+    fn find_renames(
+        &self,
+        changes: Vec<diff::FileChange>,
+        old: &HashMap<String, String>,
+        options: diff::RenameOptions,
+    ) -> Result<Vec<diff::FileChange>> {
+        diff::detect_renames(changes, old, options, |path, hash| {
+            self.diff_content(path, hash)
+        })
     }
 
-    pub fn delete_branch(&mut self, name: &str) -> Result<()> {
-        self.refs.delete_branch(name)?;
-        Ok(())
+    pub fn commit(&mut self, message: String) -> Result<String> {
+        self.commit_with(Some(message), &CommitOptions::default())
     }
 
-    pub fn switch_branch(&mut self, name: &str, force: bool) -> Result<()> {
-        if self.has_uncommitted_changes() && !force {
-            return Err(error::RepositoryError::UncommitedChanges);
+    /// Records the staged snapshot, see `CommitOptions`. Without a message the message of
+    /// the amended commit is kept.
+    pub fn commit_with(
+        &mut self,
+        message: Option<String>,
+        options: &CommitOptions,
+    ) -> Result<String> {
+        let head = self.refs.head_commit()?;
+        let amended = match options.amend {
+            true if head.is_empty() => return Err(error::RepositoryError::NothingToAmend),
+            true => Some(self.object_store.retrieve_commit(&head)?),
+            false => None,
+        };
+        if self.index.is_empty() && amended.is_none() && !options.allow_empty {
+            return Err(error::RepositoryError::IndexEmpty);
+        }
+        if !options.no_verify {
+            self.hooks.run(hooks::PRE_COMMIT, &[], None)?;
         }
 
-        self.refs.switch_branch(name)?;
-        self.index.clear()?;
-        self.work_tree.clear()?;
-        let commit = self.refs.head_commit()?;
-
-        if !commit.is_empty() {
-            self.work_tree
-                .restore_from_commit(&commit, &self.object_store)?
+        let mut message = match (message, &amended) {
+            (Some(message), _) => message,
+            (None, Some(amended)) => amended.message(),
+            (None, None) => String::new(),
+        };
+        if !options.no_verify && self.hooks.exists(hooks::COMMIT_MSG) {
+            message = self.run_commit_msg_hook(&message)?;
+        }
+        if message.trim().is_empty() {
+            return Err(error::RepositoryError::EmptyMessage);
         }
 
-        Ok(())
-    }
+        // With nothing staged the snapshot of HEAD is recorded again.
+        let snapshot = self.staged_map(&self.head_map()?);
+        let tree_hash = self
+            .work_tree
+            .build_tree_from_index(&snapshot, &self.object_store)?;
+
+        let committer = self.committer_signature()?;
+        let author = self.author_signature(options, amended.as_ref())?;
+
+        let summary = message.lines().next().unwrap_or("");
+        let (reason, parents) = match &amended {
+            Some(amended) => (
+                format!("commit (amend): {summary}"),
+                amended.parents().to_vec(),
+            ),
+            None if head.is_empty() => (format!("commit (initial): {summary}"), Vec::new()),
+            None => (format!("commit: {summary}"), vec![head]),
+        };
+        let commit = Commit::with_signatures(
+            tree_hash,
+            author.to_string(),
+            committer.to_string(),
+            parents,
+            message,
+        );
+        let commit = self.sign_if_enabled(commit, options.sign)?;
+        self.object_store.store(&commit)?;
+        let hash = commit.hash();
+        self.refs.update_head(&hash, &reason)?;
+        self.index.clear()?;
 
-    pub async fn push(&mut self, url: Option<String>) -> Result<()> {
-        let content = self.archive()?;
-        let credentials = self.config.get_credentials()?;
+        Ok(hash)
+    }
 
-        let access_token = credentials
-            .access_token
-            .ok_or_else(|| error::RepositoryError::MissingToken)?;
+    /// Lets the `commit-msg` hook check or rewrite `message` through `.flux/COMMIT_EDITMSG`.
+    fn run_commit_msg_hook(&self, message: &str) -> Result<String> {
+        let path = self.flux_dir.join("COMMIT_EDITMSG");
+        fs::write(&path, message).map_err(|e| error::IoError::write_error(&path, e))?;
+        self.hooks
+            .run(hooks::COMMIT_MSG, &[&path.to_string_lossy()], None)?;
+        Ok(fs::read_to_string(&path).map_err(|e| error::IoError::read_error(&path, e))?)
+    }
 
-        let url = match url {
-            Some(u) => u,
-            None => self
-                .config
-                .get_required(Field::Origin)
-                .map_err(|_| error::RepositoryError::MissingOrigin())?,
+    /// Message template for a commit: the message being amended, followed by the changes the
+    /// commit records as comment lines.
+    pub fn commit_template(&self, amend: bool) -> Result<String> {
+        let head = self.refs.head_commit()?;
+        let (message, base) = match amend && !head.is_empty() {
+            true => {
+                let commit = self.object_store.retrieve_commit(&head)?;
+                let base = match commit.parent_hash() {
+                    Some(parent) => self.object_store.commit_to_map(parent.to_string())?,
+                    None => HashMap::new(),
+                };
+                (commit.message(), base)
+            }
+            false => (String::new(), self.head_map()?),
         };
 
-        let mut client = GrpcClient::connect_remote(url.clone())
-            .await
-            .map_err(|e| error::RepositoryError::from("Connection to remote failed.", e))?;
+        let mut template = message.trim_end().to_string();
+        template.push_str("\n\n");
+        template.push_str("# Please enter the commit message for your changes. Lines starting\n");
+        template.push_str("# with '#' will be ignored, and an empty message aborts the commit.\n");
+        template.push_str("#\n");
+
+        let changes = self.staged_changes(&base)?;
+        if changes.is_empty() {
+            template.push_str("# No changes\n");
+            return Ok(template);
+        }
+        template.push_str("# Changes to be committed:\n");
+        for change in changes {
+            let line = match (change.status, &change.old_path) {
+                (ChangeStatus::Added, _) => format!("new file:   {}", change.path),
+                (ChangeStatus::Modified, _) => format!("modified:   {}", change.path),
+                (ChangeStatus::Deleted, _) => format!("deleted:    {}", change.path),
+                (ChangeStatus::Renamed(_), Some(old)) => {
+                    format!("renamed:    {old} -> {}", change.path)
+                }
+                (ChangeStatus::Copied(_), Some(old)) => {
+                    format!("copied:     {old} -> {}", change.path)
+                }
+                (_, None) => format!("changed:    {}", change.path),
+            };
+            template.push_str(&format!("#\t{line}\n"));
+        }
+        Ok(template)
+    }
+
+    /// Opens the commit template in `.flux/COMMIT_EDITMSG` with the editor from `FLUX_EDITOR`,
+    /// `core.editor`, `VISUAL` or `EDITOR` (`vi` by default) and returns the message without
+    /// comment lines.
+    pub fn edit_commit_message(&self, amend: bool) -> Result<String> {
+        let path = self.flux_dir.join("COMMIT_EDITMSG");
+        fs::write(&path, self.commit_template(amend)?)
+            .map_err(|e| error::IoError::write_error(&path, e))?;
+
+        let from_env = |var: &str| env::var(var).ok();
+        let editor = from_env("FLUX_EDITOR")
+            .into_iter()
+            .chain(self.config.get("core.editor")?)
+            .chain(from_env("VISUAL"))
+            .chain(from_env("EDITOR"))
+            .find(|editor| !editor.trim().is_empty())
+            .unwrap_or_else(|| "vi".to_string());
+        let failed = |reason: String| error::RepositoryError::Editor {
+            editor: editor.clone(),
+            reason,
+        };
+        // Run through the shell so the editor may carry arguments, e.g. `code --wait`.
+        let status = Command::new("sh")
+            .arg("-c")
+            .arg(format!("{editor} \"$@\""))
+            .arg(&editor)
+            .arg(&path)
+            .status()
+            .map_err(|e| failed(e.to_string()))?;
+        if !status.success() {
+            return Err(failed(status.to_string()));
+        }
+
+        let message =
+            fs::read_to_string(&path).map_err(|e| error::IoError::read_error(&path, e))?;
+        Ok(commit::clean_message(&message))
+    }
+
+    /// Prints the first-parent history starting at `reference` (HEAD by default).
+    pub fn log(&self, reference: Option<String>, options: &LogOptions) -> Result<()> {
+        let mut current_hash = match reference {
+            Some(revision) => Some(self.resolve_revision(&revision)?),
+            None => self.refs.head_commit().ok().filter(|s| !s.is_empty()),
+        };
+        let mut follow = options.follow.as_deref().map(normalize_path);
+        let renames = self.rename_options()?;
+        let allowed = match options.show_signature {
+            true => Some(self.allowed_signers()?),
+            false => None,
+        };
+        let show = |hash: &str| -> Result<()> {
+            if let Some(allowed) = &allowed {
+                println!("{}", self.commit_verification(hash, allowed)?);
+            }
+            match options.date {
+                Some(zone) => self.print_commit(hash, zone),
+                None => self.cat(hash),
+            }
+        };
+
+        while let Some(hash) = current_hash {
+            let current = self.object_store.retrieve_object(&hash)?;
+            let Some(commit) = current.as_any().downcast_ref::<Commit>() else {
+                break;
+            };
+            let parent = commit.parent_hash().map(String::from);
+
+            let Some(path) = follow.take() else {
+                show(&hash)?;
+                current_hash = parent;
+                continue;
+            };
+
+            let map = self.object_store.commit_to_map(hash.clone())?;
+            let parent_map = match &parent {
+                Some(parent) => self.object_store.commit_to_map(parent.clone())?,
+                None => HashMap::new(),
+            };
+            let Some(file_hash) = map.get(&path) else {
+                break;
+            };
+
+            if parent_map.get(&path) != Some(file_hash) {
+                show(&hash)?;
+            }
+            if parent_map.contains_key(&path) {
+                follow = Some(path);
+            } else {
+                // The file was added here, keep going only if it was renamed from another one.
+                let changes = diff::diff_maps(&parent_map, &map);
+                follow = self
+                    .find_renames(changes, &parent_map, renames)?
+                    .into_iter()
+                    .find(|change| {
+                        change.path == path && matches!(change.status, ChangeStatus::Renamed(_))
+                    })
+                    .and_then(|change| change.old_path);
+                if follow.is_none() {
+                    break;
+                }
+            }
+            current_hash = parent;
+        }
+
+        Ok(())
+    }
+
+    /// Prints a commit with its author and committer dates shown in `zone`.
+    fn print_commit(&self, hash: &str, zone: DateZone) -> Result<()> {
+        let commit = self.object_store.retrieve_commit(hash)?;
+        let date = |signature: &Signature| {
+            signature
+                .time_in(zone)
+                .format("%a %b %e %H:%M:%S %Y %z")
+                .to_string()
+        };
+
+        println!("commit {hash}");
+        if let Some(author) = commit.author_signature() {
+            println!("Author:     {} <{}>", author.name, author.email);
+            println!("AuthorDate: {}", date(&author));
+        }
+        if let Some(committer) = commit.committer_signature() {
+            println!("Commit:     {} <{}>", committer.name, committer.email);
+            println!("CommitDate: {}", date(&committer));
+        }
+        println!();
+        for line in commit.message().lines() {
+            println!("    {line}");
+        }
+        println!();
+        Ok(())
+    }
+
+    /// Generates an ed25519 key pair (`.flux/signing_key` by default), configures it as the
+    /// `user.signing_key` and adds it to the allowed signers for the configured email.
+    /// Returns the private key path and the public key.
+    pub fn generate_signing_key(&mut self, path: Option<&Path>) -> Result<(PathBuf, String)> {
+        let credentials = self
+            .config
+            .get_credentials()
+            .map_err(error::RepositoryError::Credentials)?;
+        let path = match path {
+            Some(path) => self.work_tree.path().join(path),
+            None => self.flux_dir.join("signing_key"),
+        };
+
+        let public = signing::generate_key(&path, &credentials.user_email)?;
+        let public = public.to_openssh().map_err(error::SigningError::from)?;
+
+        let allowed_path = self.allowed_signers_path()?;
+        let mut allowed = match allowed_path.is_file() {
+            true => fs::read_to_string(&allowed_path)
+                .map_err(|e| error::IoError::read_error(&allowed_path, e))?,
+            false => String::new(),
+        };
+        if !allowed.is_empty() && !allowed.ends_with('\n') {
+            allowed.push('\n');
+        }
+        allowed.push_str(&format!("{} {public}\n", credentials.user_email));
+        fs::write(&allowed_path, allowed)
+            .map_err(|e| error::IoError::write_error(&allowed_path, e))?;
+
+        let key_path = path
+            .to_str()
+            .ok_or_else(|| error::RepositoryError::PathName { path: path.clone() })?;
+        self.config
+            .set("user.signing_key".to_string(), key_path.to_string())?;
+        Ok((path, public))
+    }
+
+    /// Checks the signature of the commit `revision` resolves to against the allowed signers.
+    pub fn verify_commit(&self, revision: &str) -> Result<(String, signing::Verification)> {
+        let hash = self.resolve_revision(revision)?;
+        let verification = self.commit_verification(&hash, &self.allowed_signers()?)?;
+        Ok((hash, verification))
+    }
+
+    /// Checks the signatures of the commits reachable from the branches that are not in
+    /// `known`, e.g. the commits a push adds to a repository.
+    pub fn verify_new_commits(
+        &self,
+        known: &HashSet<String>,
+        allowed: &signing::AllowedSigners,
+    ) -> Result<Vec<(String, signing::Verification)>> {
+        let mut pending: Vec<String> = self.refs.branches.values().cloned().collect();
+        let mut seen = HashSet::new();
+        let mut result = Vec::new();
+
+        while let Some(hash) = pending.pop() {
+            if hash.is_empty() || known.contains(&hash) || !seen.insert(hash.clone()) {
+                continue;
+            }
+            let commit = self.object_store.retrieve_commit(&hash)?;
+            pending.extend(commit.parents().iter().cloned());
+            result.push((hash.clone(), self.commit_verification(&hash, allowed)?));
+        }
+
+        Ok(result)
+    }
+
+    /// Checks the signature of a commit, made on behalf of its committer's email.
+    pub fn commit_verification(
+        &self,
+        hash: &str,
+        allowed: &signing::AllowedSigners,
+    ) -> Result<signing::Verification> {
+        let commit = self.object_store.retrieve_commit(hash)?;
+        let email = commit
+            .committer_signature()
+            .map(|committer| committer.email)
+            .unwrap_or_default();
+
+        Ok(signing::verify(
+            &commit.signed_payload(),
+            commit.signature().as_deref(),
+            &email,
+            allowed,
+        ))
+    }
+
+    /// Allowed signers file, `signing.allowed_signers` or `.flux/allowed_signers`.
+    fn allowed_signers_path(&self) -> Result<PathBuf> {
+        Ok(match self.config.get_path("signing.allowed_signers")? {
+            Some(path) => self.work_tree.path().join(path),
+            None => self.flux_dir.join("allowed_signers"),
+        })
+    }
+
+    fn allowed_signers(&self) -> Result<signing::AllowedSigners> {
+        let path = self.allowed_signers_path()?;
+        Ok(signing::AllowedSigners::load(&path)?)
+    }
+
+    /// Signs `commit` with the `user.signing_key` when `sign` is set, or when it is `None` and
+    /// `commit.sign` is `true`.
+    fn sign_if_enabled(&self, commit: Commit, sign: Option<bool>) -> Result<Commit> {
+        let sign = match sign {
+            Some(sign) => sign,
+            None => self.config.get_bool("commit.sign")?.unwrap_or_default(),
+        };
+        if !sign {
+            return Ok(commit);
+        }
+
+        let key_path = self
+            .config
+            .get_path("user.signing_key")?
+            .ok_or_else(|| error::ConfigError::NotSet("user.signing_key".to_string()))?;
+        let key = signing::load_key(&self.work_tree.path().join(key_path))?;
+        let signature = signing::sign(&key, &commit.content)?;
+        Ok(commit.with_signature(&signature))
+    }
+
+    /// Lists the reflog of a ref (HEAD by default), newest entry first.
+    pub fn reflog(&self, reference: Option<&str>) -> Result<Vec<String>> {
+        let name = reference.unwrap_or("HEAD");
+        let ref_name = self
+            .refs
+            .full_ref_name(name)
+            .ok_or_else(|| error::RepositoryError::UnknownRevision(name.to_string()))?;
+
+        let entries = if ref_name == STASH_REF {
+            self.stash.list()?
+        } else {
+            let mut entries = self.refs.reflog.read(&ref_name)?;
+            entries.reverse();
+            entries
+        };
+
+        Ok(entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                format!(
+                    "{} {}@{{{}}}: {}",
+                    &entry.new_hash[..7.min(entry.new_hash.len())],
+                    name,
+                    i,
+                    entry.message
+                )
+            })
+            .collect())
+    }
+
+    /// Removes reflog entries recorded before `cutoff` (a unix timestamp) from every ref
+    /// except the stash, whose entries are only removed explicitly. Returns the number of
+    /// entries removed.
+    pub fn expire_reflogs(&self, cutoff: i64) -> Result<usize> {
+        let mut removed = 0;
+        for ref_name in self.refs.reflog.ref_names()? {
+            if ref_name != STASH_REF {
+                removed += self.refs.reflog.expire(&ref_name, cutoff)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Hashes every reachability walk starts from: branch tips, HEAD, every hash recorded
+    /// in a reflog (including the stash) and the blobs staged in the index.
+    fn gc_roots(&self) -> Result<Vec<String>> {
+        let mut roots: Vec<String> = self.refs.branches.values().cloned().collect();
+        roots.push(self.refs.head_commit()?);
+
+        for ref_name in self.refs.reflog.ref_names()? {
+            for entry in self.refs.reflog.read(&ref_name)? {
+                roots.push(entry.old_hash);
+                roots.push(entry.new_hash);
+            }
+        }
+
+        roots.extend(self.index.map.values().cloned());
+
+        for operation in [Operation::Rebase, Operation::CherryPick] {
+            let state = self.sequencer_state(operation);
+            if state.is_active() {
+                roots.extend(state.read("orig-head")?);
+                roots.extend(state.read("onto")?);
+                roots.extend(state.todo()?.into_iter().map(|step| step.commit));
+                roots.extend(state.stopped()?.map(|(step, _)| step.commit));
+            }
+        }
+        roots.retain(|hash| !hash.is_empty() && hash != ZERO_HASH);
+        Ok(roots)
+    }
+
+    /// Removes unreachable loose objects and stale temporary files last modified more than
+    /// `grace` ago. With `dry_run` the objects are only reported.
+    pub fn prune(&self, grace: Duration, dry_run: bool) -> Result<gc::PruneReport> {
+        let reachable = gc::reachable_objects(&self.object_store, self.gc_roots()?)?;
+        let cutoff = SystemTime::now() - grace;
+        let report = gc::prune(&self.object_store, &reachable, cutoff, dry_run)?;
+        Ok(report)
+    }
+
+    /// Expires reflog entries older than `reflog_expire`, then prunes the objects that are
+    /// no longer reachable and older than `grace`.
+    pub fn gc(&self, reflog_expire: Duration, grace: Duration) -> Result<gc::GcReport> {
+        let cutoff = SystemTime::now() - reflog_expire;
+        let cutoff = cutoff
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let expired_reflog_entries = self.expire_reflogs(cutoff)?;
+
+        let reachable = gc::reachable_objects(&self.object_store, self.gc_roots()?)?;
+        let prune = gc::prune(
+            &self.object_store,
+            &reachable,
+            SystemTime::now() - grace,
+            false,
+        )?;
+
+        Ok(gc::GcReport {
+            reachable: reachable.len(),
+            expired_reflog_entries,
+            prune,
+        })
+    }
+
+    /// Verifies every object in the store and the refs pointing into it.
+    ///
+    /// `strict` additionally checks tree entry ordering and modes.
+    pub fn fsck(&self, strict: bool) -> Result<fsck::FsckReport> {
+        let mut refs: Vec<(String, String)> = self
+            .refs
+            .branches
+            .iter()
+            .filter(|(_, hash)| !hash.is_empty())
+            .map(|(name, hash)| (format!("refs/heads/{name}"), hash.clone()))
+            .collect();
+        refs.sort();
+
+        let head = self.refs.head_commit()?;
+        if !head.is_empty() {
+            refs.insert(0, ("HEAD".to_string(), head));
+        }
+        if let Some(entry) = self.stash.list()?.first() {
+            refs.push((STASH_REF.to_string(), entry.new_hash.clone()));
+        }
+
+        let mut report = fsck::check(&self.object_store, &refs, &self.gc_roots()?, strict)?;
+        for hash in self.index.map.values() {
+            if !self.object_store.exists(hash) {
+                report.problems.push(fsck::FsckProblem::Missing {
+                    hash: hash.clone(),
+                    referenced_by: "index".to_string(),
+                });
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Resolves a revision to a commit hash.
+    ///
+    /// Supports `HEAD`, branch names, `stash`, full or abbreviated hashes, the reflog
+    /// syntax `<ref>@{N}` and the ancestry suffixes `~N` and `^N`.
+    pub fn resolve_revision(&self, revision: &str) -> Result<String> {
+        let unknown = || error::RepositoryError::UnknownRevision(revision.to_string());
+        let parsed = Revision::parse(revision).ok_or_else(unknown)?;
+
+        let mut hash = match parsed.reflog_index {
+            Some(index) => {
+                let ref_name = self.refs.full_ref_name(&parsed.name).ok_or_else(unknown)?;
+                let mut entries = self.refs.reflog.read(&ref_name)?;
+                entries.reverse();
+                entries
+                    .into_iter()
+                    .nth(index)
+                    .map(|entry| entry.new_hash)
+                    .ok_or_else(unknown)?
+            }
+            None => self.resolve_name(&parsed.name)?,
+        };
+
+        for step in parsed.steps {
+            let (parent, generations) = match step {
+                revision::Step::Parent(n) => (n - 1, 1),
+                revision::Step::Ancestor(n) => (0, n),
+            };
+            for _ in 0..generations {
+                let commit = self.object_store.retrieve_commit(&hash)?;
+                hash = commit.parents().get(parent).cloned().ok_or_else(unknown)?;
+            }
+        }
+
+        Ok(hash)
+    }
+
+    fn resolve_name(&self, name: &str) -> Result<String> {
+        let unknown = || error::RepositoryError::UnknownRevision(name.to_string());
+
+        let hash = if name == "HEAD" {
+            self.refs.head_commit()?
+        } else if let Some(hash) = self
+            .refs
+            .branches
+            .get(name.strip_prefix("refs/heads/").unwrap_or(name))
+        {
+            hash.clone()
+        } else if name == "stash" || name == STASH_REF {
+            self.stash.get(0)?.new_hash
+        } else if name.len() >= 4 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+            let mut matches = self.object_store.find_by_prefix(&name.to_lowercase())?;
+            if matches.len() > 1 {
+                return Err(error::RepositoryError::AmbiguousRevision(name.to_string()));
+            }
+            matches.pop().ok_or_else(unknown)?
+        } else {
+            return Err(unknown());
+        };
+
+        if hash.is_empty() || hash == ZERO_HASH {
+            return Err(unknown());
+        }
+        Ok(hash)
+    }
+
+    pub fn show_branches(&self) -> Result<String> {
+        let branches = self.refs.format_branches()?;
+        Ok(branches)
+    }
+
+    pub fn list_branches(&self) -> Result<Vec<String>> {
+        let branches = self.refs.list_branches()?;
+        Ok(branches)
+    }
+
+    pub fn new_branch(&mut self, name: &str) -> Result<()> {
+        self.refs.new_branch(name)?;
+        Ok(())
+    }
+
+    pub fn delete_branch(&mut self, name: &str) -> Result<()> {
+        self.refs.delete_branch(name)?;
+        Ok(())
+    }
+
+    pub fn switch_branch(&mut self, name: &str, force: bool) -> Result<()> {
+        if self.has_uncommitted_changes() && !force {
+            return Err(error::RepositoryError::UncommitedChanges);
+        }
+
+        let previous = self.refs.head_commit()?;
+        self.refs.switch_branch(name)?;
+        self.index.clear()?;
+        self.work_tree.clear()?;
+        let commit = self.refs.head_commit()?;
+
+        if !commit.is_empty() {
+            self.work_tree
+                .restore_from_commit(&commit, &self.object_store)?
+        }
+
+        // The branch is already checked out, a failing hook cannot undo the switch.
+        let hash = |hash: String| match hash.is_empty() {
+            true => ZERO_HASH.to_string(),
+            false => hash,
+        };
+        if let Err(err) = self.hooks.run(
+            hooks::POST_CHECKOUT,
+            &[&hash(previous), &hash(commit), "1"],
+            None,
+        ) {
+            eprintln!("warning: {err}");
+        }
+        Ok(())
+    }
+
+    /// Saves the staged and work tree changes as a stash entry and resets both to HEAD.
+    ///
+    /// The entry is a commit whose tree is the work tree state and whose parents are
+    /// HEAD and a commit recording the index state.
+    pub fn stash_push(&mut self, message: Option<String>) -> Result<String> {
+        let head = self.refs.head_commit()?;
+        if head.is_empty() {
+            return Err(error::StashError::NoInitialCommit.into());
+        }
+
+        let head_commit = self.object_store.retrieve_commit(&head)?;
+        let head_map = self.object_store.tree_hash_to_map(&head_commit.tree_hash)?;
+        let staged_map = self.staged_map(&head_map);
+        let work_map = self.work_tree_map(staged_map.keys().chain(head_map.keys()), true)?;
+
+        if staged_map == head_map && work_map == head_map {
+            return Err(error::StashError::NoLocalChanges.into());
+        }
+
+        let credentials = self
+            .config
+            .get_credentials()
+            .map_err(error::RepositoryError::Credentials)?;
+        let identity = format!("{} <{}>", credentials.user_name, credentials.user_email);
+        let branch = self.refs.current_branch()?;
+        let description = format!("{}: {} {}", branch, &head[..7], head_commit.summary());
+
+        let index_tree = self
+            .work_tree
+            .build_tree_from_index(&staged_map, &self.object_store)?;
+        let index_commit = Commit::new(
+            index_tree,
+            credentials.user_name.clone(),
+            credentials.user_email.clone(),
+            vec![head.clone()],
+            format!("index on {description}"),
+        );
+        self.object_store.store(&index_commit)?;
+
+        let message = match message {
+            Some(message) => format!("On {branch}: {message}"),
+            None => format!("WIP on {description}"),
+        };
+        let work_tree = self
+            .work_tree
+            .build_tree_from_index(&work_map, &self.object_store)?;
+        let stash_commit = Commit::new(
+            work_tree,
+            credentials.user_name,
+            credentials.user_email,
+            vec![head, index_commit.hash()],
+            message.clone(),
+        );
+        self.object_store.store(&stash_commit)?;
+
+        self.stash.push(&stash_commit.hash(), &identity, &message)?;
+        self.work_tree
+            .checkout_map(&work_map, &head_map, &self.object_store)?;
+        self.index.clear()?;
+
+        Ok(message)
+    }
+
+    /// Three-way merges a stash entry onto the current HEAD and returns the conflicts left
+    /// in the work tree. With `restore_index` the stashed index is merged back as well.
+    pub fn stash_apply(
+        &mut self,
+        reference: Option<&str>,
+        restore_index: bool,
+    ) -> Result<Vec<Conflict>> {
+        if self.has_uncommitted_changes() {
+            return Err(error::RepositoryError::UncommitedChanges);
+        }
+
+        let entry = self.stash.get(Stash::parse_reference(reference)?)?;
+        let stash_commit = self.object_store.retrieve_commit(&entry.new_hash)?;
+        let [base, index_commit] = stash_commit.parents() else {
+            return Err(error::StashError::InvalidEntry(entry.new_hash).into());
+        };
+
+        let base_map = self.object_store.commit_to_map(base.clone())?;
+        let head_map = self.head_map()?;
+        let stash_map = self
+            .object_store
+            .tree_hash_to_map(&stash_commit.tree_hash)?;
+
+        let labels = MergeLabels {
+            ours: "Updated upstream",
+            theirs: "Stashed changes",
+        };
+        let merged =
+            merge::merge_trees(&base_map, &head_map, &stash_map, &self.object_store, labels)?;
+
+        let index_map = if restore_index {
+            let index_map = self.object_store.commit_to_map(index_commit.clone())?;
+            let merged_index =
+                merge::merge_trees(&base_map, &head_map, &index_map, &self.object_store, labels)?;
+            if !merged_index.is_clean() {
+                return Err(error::StashError::IndexConflicts.into());
+            }
+            Some(merged_index.map).filter(|map| map != &head_map)
+        } else {
+            None
+        };
+
+        self.write_merge_result(&head_map, &merged)?;
+
+        if let Some(map) = index_map {
+            self.index.replace(map)?;
+        }
+
+        Ok(merged.conflicts)
+    }
+
+    /// Applies a stash entry and drops it when it applied without conflicts.
+    pub fn stash_pop(
+        &mut self,
+        reference: Option<&str>,
+        restore_index: bool,
+    ) -> Result<Vec<Conflict>> {
+        let conflicts = self.stash_apply(reference, restore_index)?;
+        if conflicts.is_empty() {
+            self.stash_drop(reference)?;
+        }
+        Ok(conflicts)
+    }
+
+    /// Removes a stash entry and returns the hash of the dropped stash commit.
+    pub fn stash_drop(&mut self, reference: Option<&str>) -> Result<String> {
+        let dropped = self.stash.drop(Stash::parse_reference(reference)?)?;
+        Ok(dropped.new_hash)
+    }
+
+    pub fn stash_list(&self) -> Result<Vec<String>> {
+        let entries = self.stash.list()?;
+        Ok(entries
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| format!("stash@{{{}}}: {}", i, entry.message))
+            .collect())
+    }
+
+    /// Describes the changes recorded in a stash entry, as a file list or as a patch.
+    pub fn stash_show(&self, reference: Option<&str>, patch: bool) -> Result<String> {
+        let entry = self.stash.get(Stash::parse_reference(reference)?)?;
+        let stash_commit = self.object_store.retrieve_commit(&entry.new_hash)?;
+        let base = stash_commit
+            .parent_hash()
+            .ok_or_else(|| error::StashError::InvalidEntry(entry.new_hash.clone()))?;
+
+        let base_map = self.object_store.commit_to_map(base.to_string())?;
+        let stash_map = self
+            .object_store
+            .tree_hash_to_map(&stash_commit.tree_hash)?;
+
+        let mut out = String::new();
+        for change in diff::diff_maps(&base_map, &stash_map) {
+            if patch {
+                let old = match &change.old_hash {
+                    Some(hash) => self.object_store.blob_content(hash)?,
+                    None => Vec::new(),
+                };
+                let new = match &change.new_hash {
+                    Some(hash) => self.object_store.blob_content(hash)?,
+                    None => Vec::new(),
+                };
+                out.push_str(&diff::unified_diff(&change.path, &change.path, &old, &new));
+            } else {
+                out.push_str(&format!("{}  {}\n", change.status.letter(), change.path));
+            }
+        }
+
+        Ok(out)
+    }
+
+    fn sequencer_state(&self, operation: Operation) -> Sequencer {
+        Sequencer::new(&self.flux_dir, operation.dir_name())
+    }
+
+    /// Fails when a rebase, cherry-pick or revert is in progress or when changes are staged.
+    fn check_no_sequence(&self) -> Result<()> {
+        for running in [Operation::Rebase, Operation::CherryPick] {
+            let state = self.sequencer_state(running);
+            if state.is_active() {
+                let running = state.operation()?.unwrap_or(running);
+                return Err(error::SequencerError::InProgress(running.as_str()).into());
+            }
+        }
+        if self.has_uncommitted_changes() {
+            return Err(error::RepositoryError::UncommitedChanges);
+        }
+        Ok(())
+    }
+
+    /// Records the state shared by every operation.
+    fn start_sequence(&self, operation: Operation, steps: &[Step]) -> Result<Sequencer> {
+        let state = self.sequencer_state(operation);
+        state.start()?;
+        state.write("operation", Some(operation.as_str()))?;
+        state.write("head-name", Some(&self.refs.current_branch()?))?;
+        state.write("orig-head", Some(&self.refs.head_commit()?))?;
+        state.write_todo(steps)?;
+        Ok(state)
+    }
+
+    /// Replays the commits of the current branch that are not in `upstream` on top of
+    /// `onto` (`upstream` by default), moving the branch as each commit is applied.
+    ///
+    /// `todo` replaces the generated list of `pick` steps with a todo list whose steps
+    /// can also drop, squash or reword commits.
+    pub fn rebase(
+        &mut self,
+        upstream: &str,
+        onto: Option<&str>,
+        todo: Option<&str>,
+    ) -> Result<Progress> {
+        self.check_no_sequence()?;
+        let head = self.resolve_revision("HEAD")?;
+        let upstream_hash = self.resolve_revision(upstream)?;
+        self.hooks.run(hooks::PRE_REBASE, &[upstream], None)?;
+        let (onto_name, onto_hash) = match onto {
+            Some(onto) => (onto, self.resolve_revision(onto)?),
+            None => (upstream, upstream_hash.clone()),
+        };
+
+        let commits = self.commits_since(&upstream_hash, &head)?;
+        let steps = match todo {
+            Some(todo) => {
+                let mut steps = Step::parse_todo(todo)?;
+                for step in &mut steps {
+                    step.commit = self.resolve_revision(&step.commit)?;
+                }
+                steps
+            }
+            None => {
+                let base = match commits.first() {
+                    Some(first) => self
+                        .object_store
+                        .retrieve_commit(first)?
+                        .parent_hash()
+                        .map(String::from),
+                    None => Some(head.clone()),
+                };
+                if base.as_deref() == Some(onto_hash.as_str()) {
+                    return Ok(Progress::UpToDate);
+                }
+
+                let mut steps = Vec::new();
+                for hash in &commits {
+                    let commit = self.object_store.retrieve_commit(hash)?;
+                    steps.push(Step::new(Action::Pick, hash, &commit.summary()));
+                }
+                steps
+            }
+        };
+
+        let state = self.start_sequence(Operation::Rebase, &steps)?;
+        let head_map = self.head_map()?;
+        let onto_map = self.object_store.commit_to_map(onto_hash.clone())?;
+        let checkout = merge::TreeMerge {
+            map: onto_map,
+            conflicts: Vec::new(),
+        };
+        if let Err(err) = self.write_merge_result(&head_map, &checkout) {
+            state.clear()?;
+            return Err(err);
+        }
+
+        state.write("onto", Some(&onto_hash))?;
+        self.refs
+            .update_head(&onto_hash, &format!("rebase (start): checkout {onto_name}"))?;
+
+        self.run_sequence(&state, Operation::Rebase)
+    }
+
+    /// Applies the changes introduced by each of `revisions` on top of HEAD as new commits
+    /// that record the commit they were picked from.
+    pub fn cherry_pick(&mut self, revisions: &[String]) -> Result<Progress> {
+        self.start_picks(Operation::CherryPick, Action::Pick, revisions)
+    }
+
+    /// Creates, for each of `revisions`, a new commit on HEAD that undoes its changes.
+    pub fn revert(&mut self, revisions: &[String]) -> Result<Progress> {
+        self.start_picks(Operation::Revert, Action::Revert, revisions)
+    }
+
+    fn start_picks(
+        &mut self,
+        operation: Operation,
+        action: Action,
+        revisions: &[String],
+    ) -> Result<Progress> {
+        self.check_no_sequence()?;
+        self.resolve_revision("HEAD")?;
+
+        let mut steps = Vec::new();
+        for revision in revisions {
+            let hash = self.resolve_revision(revision)?;
+            let commit = self.object_store.retrieve_commit(&hash)?;
+            steps.push(Step::new(action, &hash, &commit.summary()));
+        }
+
+        let state = self.start_sequence(operation, &steps)?;
+        self.run_sequence(&state, operation)
+    }
+
+    /// Commits the resolved conflicts of the stopped step and applies the remaining ones.
+    pub fn sequence_continue(&mut self, operation: Operation) -> Result<Progress> {
+        let state = self.check_sequence(operation)?;
+
+        if let Some((step, paths)) = state.stopped()? {
+            let head_map = self.head_map()?;
+            let staged = self.staged_map(&head_map);
+            let work_map = self.work_tree_map(&paths, false)?;
+            let unresolved: Vec<String> = paths
+                .into_iter()
+                .filter(|path| work_map.get(path) != staged.get(path))
+                .collect();
+            if !unresolved.is_empty() {
+                return Err(
+                    error::SequencerError::UnresolvedConflicts { paths: unresolved }.into(),
+                );
+            }
+
+            let original = self.object_store.retrieve_commit(&step.commit)?;
+            self.commit_step(operation, &step, &original, &staged)?;
+            state.set_stopped(None)?;
+            state.mark_done(&step)?;
+        }
+
+        self.run_sequence(&state, operation)
+    }
+
+    /// Drops the stopped step, or the next one when nothing stopped, and applies the rest.
+    pub fn sequence_skip(&mut self, operation: Operation) -> Result<Progress> {
+        let state = self.check_sequence(operation)?;
+
+        match state.stopped()? {
+            Some((step, paths)) => {
+                let head_map = self.head_map()?;
+                self.reset_hard(&head_map, &paths)?;
+                state.set_stopped(None)?;
+                state.mark_done(&step)?;
+            }
+            None => {
+                let mut todo = state.todo()?;
+                if !todo.is_empty() {
+                    let step = todo.remove(0);
+                    state.write_todo(&todo)?;
+                    state.mark_done(&step)?;
+                }
+            }
+        }
+
+        self.run_sequence(&state, operation)
+    }
+
+    /// Stops the operation and moves the branch, index and work tree back to where they
+    /// were before it started.
+    pub fn sequence_abort(&mut self, operation: Operation) -> Result<()> {
+        let state = self.check_sequence(operation)?;
+
+        let orig_head = state
+            .read("orig-head")?
+            .ok_or(error::SequencerError::NotInProgress(operation.as_str()))?;
+        let paths = state.stopped()?.map(|(_, paths)| paths).unwrap_or_default();
+        let orig_map = self.object_store.commit_to_map(orig_head.clone())?;
+        self.reset_hard(&orig_map, &paths)?;
+
+        if self.refs.head_commit()? != orig_head {
+            let branch = self.refs.current_branch()?;
+            self.refs.update_head(
+                &orig_head,
+                &format!(
+                    "{} (abort): returning to refs/heads/{branch}",
+                    operation.as_str()
+                ),
+            )?;
+        }
+        state.clear()?;
+
+        Ok(())
+    }
+
+    fn check_sequence(&self, operation: Operation) -> Result<Sequencer> {
+        let state = self.sequencer_state(operation);
+        if !state.is_active() || state.operation()? != Some(operation) {
+            return Err(error::SequencerError::NotInProgress(operation.as_str()).into());
+        }
+
+        let expected = state.read("head-name")?.unwrap_or_default();
+        let actual = self.refs.current_branch()?;
+        if expected != actual {
+            return Err(error::SequencerError::BranchChanged {
+                operation: operation.as_str(),
+                expected,
+                actual,
+            }
+            .into());
+        }
+        Ok(state)
+    }
+
+    fn run_sequence(&mut self, state: &Sequencer, operation: Operation) -> Result<Progress> {
+        loop {
+            let mut todo = state.todo()?;
+            if todo.is_empty() {
+                break;
+            }
+            let step = todo.remove(0);
+
+            if step.action == Action::Drop {
+                state.write_todo(&todo)?;
+                state.mark_done(&step)?;
+                continue;
+            }
+
+            let head = self.refs.head_commit()?;
+            if step.action == Action::Squash && state.read("onto")?.as_deref() == Some(&head) {
+                return Err(error::SequencerError::NothingToSquash(step.commit).into());
+            }
+
+            let commit = self.object_store.retrieve_commit(&step.commit)?;
+            let parent_map = match commit.parent_hash() {
+                Some(parent) => self.object_store.commit_to_map(parent.to_string())?,
+                None => HashMap::new(),
+            };
+            let commit_map = self.object_store.tree_hash_to_map(&commit.tree_hash)?;
+            let head_map = self.head_map()?;
+
+            let label = format!("{} ({})", &step.commit[..7], commit.summary());
+            let (base_map, theirs_map, theirs) = if step.action == Action::Revert {
+                (commit_map, parent_map, format!("parent of {label}"))
+            } else {
+                (parent_map, commit_map, label)
+            };
+            let labels = MergeLabels {
+                ours: "HEAD",
+                theirs: &theirs,
+            };
+            let merged = merge::merge_trees(
+                &base_map,
+                &head_map,
+                &theirs_map,
+                &self.object_store,
+                labels,
+            )?;
+
+            self.write_merge_result(&head_map, &merged)?;
+            state.write_todo(&todo)?;
+
+            if !merged.is_clean() {
+                self.index.replace(merged.map)?;
+                state.set_stopped(Some((&step, &merged.conflicts)))?;
+                return Ok(Progress::Stopped {
+                    step,
+                    conflicts: merged.conflicts,
+                });
+            }
+
+            if let Err(err) = self.commit_step(operation, &step, &commit, &merged.map) {
+                // Stopped like a conflict, so the step can be fixed and continued or aborted.
+                self.index.replace(merged.map)?;
+                state.set_stopped(Some((&step, &[])))?;
+                return Err(err);
+            }
+            state.mark_done(&step)?;
+        }
+
+        let head = self.refs.head_commit()?;
+        state.clear()?;
+        Ok(Progress::Done(head))
+    }
+
+    /// Records the result of applying `step` to `original` as a new commit on HEAD. Steps
+    /// that no longer change anything are left out.
+    fn commit_step(
+        &mut self,
+        operation: Operation,
+        step: &Step,
+        original: &Commit,
+        map: &HashMap<String, String>,
+    ) -> Result<()> {
+        self.hooks.run(hooks::PRE_MERGE_COMMIT, &[], None)?;
+        let head = self.refs.head_commit()?;
+        let head_commit = self.object_store.retrieve_commit(&head)?;
+        let tree_hash = self
+            .work_tree
+            .build_tree_from_index(map, &self.object_store)?;
+        let committer = self.committer_signature()?.to_string();
+        let author = original.author().unwrap_or_else(|| committer.clone());
+
+        let (author, parents, message) = match step.action {
+            Action::Squash => (
+                head_commit.author().unwrap_or_else(|| committer.clone()),
+                head_commit.parents().to_vec(),
+                format!(
+                    "{}\n\n{}",
+                    head_commit.message().trim_end(),
+                    original.message()
+                ),
+            ),
+            _ if tree_hash == head_commit.tree_hash => {
+                self.index.clear()?;
+                return Ok(());
+            }
+            Action::Reword if !step.message.is_empty() => {
+                (author, vec![head], step.message.clone())
+            }
+            Action::Revert => (
+                committer.clone(),
+                vec![head],
+                format!(
+                    "Revert \"{}\"\n\nThis reverts commit {}.\n",
+                    original.summary(),
+                    step.commit
+                ),
+            ),
+            _ if operation == Operation::CherryPick => (
+                author,
+                vec![head],
+                format!(
+                    "{}\n\n(cherry picked from commit {})\n",
+                    original.message().trim_end(),
+                    step.commit
+                ),
+            ),
+            _ => (author, vec![head], original.message()),
+        };
 
+        let commit = Commit::with_signatures(tree_hash, author, committer, parents, message);
+        let commit = self.sign_if_enabled(commit, None)?;
+        self.object_store.store(&commit)?;
+        let reason = match operation {
+            Operation::Rebase => format!("rebase ({}): {}", step.action.as_str(), commit.summary()),
+            _ => format!("{}: {}", operation.as_str(), commit.summary()),
+        };
+        self.refs.update_head(&commit.hash(), &reason)?;
+        self.index.clear()?;
+
+        Ok(())
+    }
+
+    /// First-parent history of `head` that is not reachable from `upstream`, oldest first.
+    fn commits_since(&self, upstream: &str, head: &str) -> Result<Vec<String>> {
+        let mut upstream_history = HashSet::new();
+        let mut pending = vec![upstream.to_string()];
+        while let Some(hash) = pending.pop() {
+            if upstream_history.insert(hash.clone()) {
+                let commit = self.object_store.retrieve_commit(&hash)?;
+                pending.extend(commit.parents().iter().cloned());
+            }
+        }
+
+        let mut commits = Vec::new();
+        let mut current = Some(head.to_string());
+        while let Some(hash) = current.filter(|hash| !upstream_history.contains(hash)) {
+            let commit = self.object_store.retrieve_commit(&hash)?;
+            current = commit.parent_hash().map(String::from);
+            commits.push(hash);
+        }
+        commits.reverse();
+
+        Ok(commits)
+    }
+
+    pub async fn push(&mut self, url: Option<String>) -> Result<()> {
+        let content = self.archive()?;
+
+        let (url, access_token) = Self::remote_token(&self.config, url)?;
+        self.run_pre_push_hook(&url)?;
+
+        let mut client = Self::connect(&self.config, url.clone())
+            .await
+            .map_err(|e| error::RepositoryError::from("Connection to remote failed.", e))?
+            .with_token(Some(access_token));
+
+        // The path of the url names the repository on the server, `<owner>/<repo>` for one
+        // shared with the user.
+        let repository = client
+            .extract_path()?
+            .trim_end_matches('/')
+            .to_string();
+        let repository = match repository.is_empty() {
+            true => self.name.clone(),
+            false => repository,
+        };
         let response = client
-            .push(
-                self.name.clone(),
-                content,
-                credentials.user_email,
-                credentials.user_name,
-                access_token,
-            )
+            .push(repository, content)
             .await
             .map_err(|e| error::RepositoryError::from("Failed to push to remote", e))?;
+        if response.code() == UploadStatusCode::Failed {
+            return Err(error::RepositoryError::PushRejected(
+                response.response_message,
+            ));
+        }
 
-        self.config.set("origin".to_string(), url)?;
+        self.config.set("remote.origin.url".to_string(), url)?;
         println!("Server response: {}", response.response_message);
 
         Ok(())
     }
 
+    /// Runs the `pre-push` hook with every branch, the whole repository being pushed.
+    fn run_pre_push_hook(&self, url: &str) -> Result<()> {
+        if !self.hooks.exists(hooks::PRE_PUSH) {
+            return Ok(());
+        }
+
+        let mut refs = String::new();
+        for branch in self.refs.branch_names() {
+            let hash = self.resolve_revision(&branch)?;
+            let name = format!("refs/heads/{branch}");
+            refs.push_str(&format!("{name} {hash} {name} {ZERO_HASH}\n"));
+        }
+        self.hooks
+            .run(hooks::PRE_PUSH, &["origin", url], Some(&refs))?;
+        Ok(())
+    }
+
     fn archive(&self) -> Result<Vec<u8>> {
         let flux_dir = self
             .flux_dir
@@ -464,6 +2534,10 @@ impl Repository {
             let path = entry.path();
             let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
 
+            // Hooks are local executables, they are never shared through the remote.
+            if file_name == "hooks" {
+                continue;
+            }
             if file_name == "config" {
                 let mut header = tar::Header::new_gnu();
                 header
@@ -517,6 +2591,77 @@ impl Repository {
         Ok(())
     }
 
+    /// Commits of the first-parent history starting at the commit `revision` resolves to,
+    /// newest first and at most `limit` of them.
+    pub fn history(&self, revision: &str, limit: usize) -> Result<Vec<(String, Commit)>> {
+        let mut commits = Vec::new();
+        let mut current = Some(self.resolve_revision(revision)?);
+
+        while let Some(hash) = current {
+            if commits.len() == limit {
+                break;
+            }
+            let commit = self.object_store.retrieve_commit(&hash)?;
+            current = commit.parent_hash().map(String::from);
+            commits.push((hash, commit));
+        }
+
+        Ok(commits)
+    }
+
+    /// Hash and entries of the directory `path`, the root when empty, in the commit
+    /// `revision` resolves to.
+    pub fn tree_at(&self, revision: &str, path: &str) -> Result<(String, Vec<TreeEntry>)> {
+        let entry = self.entry_at(revision, path)?;
+        if !entry.is_dir() {
+            return Err(error::RepositoryError::NotADirectory(path.to_string()));
+        }
+        let tree = self.object_store.retrieve_tree(&entry.hash)?;
+        Ok((entry.hash, tree.entries()))
+    }
+
+    /// Hash and content of the file `path` in the commit `revision` resolves to.
+    pub fn blob_at(&self, revision: &str, path: &str) -> Result<(String, Vec<u8>)> {
+        let entry = self.entry_at(revision, path)?;
+        if entry.is_dir() {
+            return Err(error::RepositoryError::NotAFile(path.to_string()));
+        }
+        let content = self.object_store.blob_content(&entry.hash)?;
+        Ok((entry.hash, content))
+    }
+
+    /// Tree entry of `path` in the commit `revision` resolves to. The root tree has no name.
+    fn entry_at(&self, revision: &str, path: &str) -> Result<TreeEntry> {
+        let commit = self
+            .object_store
+            .retrieve_commit(&self.resolve_revision(revision)?)?;
+        let path = normalize_path(path);
+        let missing = || error::RepositoryError::PathNotInRevision {
+            path: path.clone(),
+            revision: revision.to_string(),
+        };
+
+        let mut entry = TreeEntry {
+            mode: "040000".to_string(),
+            hash: commit.tree_hash.clone(),
+            name: String::new(),
+        };
+        for name in path.split('/').filter(|name| !name.is_empty()) {
+            if !entry.is_dir() {
+                return Err(missing());
+            }
+            entry = self
+                .object_store
+                .retrieve_tree(&entry.hash)?
+                .entries()
+                .into_iter()
+                .find(|entry| entry.name == name)
+                .ok_or_else(missing)?;
+        }
+
+        Ok(entry)
+    }
+
     pub fn commit_tree(
         &self,
         tree_hash: String,
@@ -537,12 +2682,203 @@ impl Repository {
             return Err(error::RepositoryError::CommitRoot { hash: tree.hash() });
         }
 
-        let commit = Commit::new(tree.hash(), user_name, user_email, parent_hash, message);
+        let parents = parent_hash.into_iter().collect();
+        let commit = Commit::new(tree.hash(), user_name, user_email, parents, message);
         self.object_store.store(&commit)?;
         Ok(commit.hash())
     }
 
+    /// Signature of the configured user at the current time, as recorded in commits.
+    /// Committer of new commits: `FLUX_COMMITTER_NAME`, `FLUX_COMMITTER_EMAIL` and
+    /// `FLUX_COMMITTER_DATE` when set, otherwise the configured user at the current time.
+    fn committer_signature(&self) -> Result<Signature> {
+        self.identity_from_env("COMMITTER", None, None)
+    }
+
+    /// Author of a new commit, see `CommitOptions`. An amended commit keeps its author.
+    fn author_signature(
+        &self,
+        options: &CommitOptions,
+        amended: Option<&Commit>,
+    ) -> Result<Signature> {
+        let identity = match &options.author {
+            Some(author) => Some(
+                Signature::parse_identity(author)
+                    .ok_or_else(|| error::RepositoryError::InvalidIdentity(author.clone()))?,
+            ),
+            None => None,
+        };
+
+        match amended.and_then(Commit::author_signature) {
+            Some(original) => {
+                let (name, email) = identity.unwrap_or((original.name, original.email));
+                let time = match &options.date {
+                    Some(date) => parse_date(date)?,
+                    None => original.time,
+                };
+                Ok(Signature::new(&name, &email, time))
+            }
+            None => self.identity_from_env("AUTHOR", identity, options.date.as_deref()),
+        }
+    }
+
+    /// Builds a signature from the explicit values, then the `FLUX_<ROLE>_*` variables and
+    /// finally the configured user and the current time.
+    fn identity_from_env(
+        &self,
+        role: &str,
+        identity: Option<(String, String)>,
+        date: Option<&str>,
+    ) -> Result<Signature> {
+        let var = |field: &str| env::var(format!("FLUX_{role}_{field}")).ok();
+
+        let (name, email) = match identity {
+            Some(identity) => identity,
+            None => match (var("NAME"), var("EMAIL")) {
+                (Some(name), Some(email)) => (name, email),
+                (name, email) => {
+                    let credentials = self
+                        .config
+                        .get_credentials()
+                        .map_err(error::RepositoryError::Credentials)?;
+                    (
+                        name.unwrap_or(credentials.user_name),
+                        email.unwrap_or(credentials.user_email),
+                    )
+                }
+            },
+        };
+
+        let time = match date.map(String::from).or_else(|| var("DATE")) {
+            Some(date) => parse_date(&date)?,
+            None => Local::now().fixed_offset(),
+        };
+
+        Ok(Signature::new(&name, &email, time))
+    }
+
+    /// Forces the index and the work tree to `target`, dropping the changes made to
+    /// tracked files and to `paths`. Untracked files are left alone.
+    fn reset_hard(&mut self, target: &HashMap<String, String>, paths: &[String]) -> Result<()> {
+        let head_map = self.head_map()?;
+        let work_map = self.work_tree_map(
+            self.index
+                .map
+                .keys()
+                .chain(head_map.keys())
+                .chain(target.keys())
+                .chain(paths),
+            false,
+        )?;
+        self.work_tree
+            .checkout_map(&work_map, target, &self.object_store)?;
+        self.index.clear()?;
+        Ok(())
+    }
+
     fn has_uncommitted_changes(&self) -> bool {
         !self.index.is_empty()
     }
+
+    /// Flattened tree of the commit HEAD points to, empty before the first commit.
+    fn head_map(&self) -> Result<HashMap<String, String>> {
+        let head = self.refs.head_commit()?;
+        if head.is_empty() {
+            return Ok(HashMap::new());
+        }
+        Ok(self.object_store.commit_to_map(head)?)
+    }
+
+    /// Snapshot the next commit would record. An empty index means nothing is staged.
+    fn staged_map(&self, head_map: &HashMap<String, String>) -> HashMap<String, String> {
+        if self.index.is_empty() {
+            head_map.clone()
+        } else {
+            self.index.map.clone()
+        }
+    }
+
+    /// Hashes the work tree content of `paths`, leaving out files that no longer exist.
+    /// With `write` the blobs are also stored in the object store.
+    fn work_tree_map<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a String>,
+        write: bool,
+    ) -> Result<HashMap<String, String>> {
+        let mut map = HashMap::new();
+        for path in paths {
+            if map.contains_key(path) {
+                continue;
+            }
+            if let Some(content) = self.work_tree.read_file(path)? {
+                let blob = Blob::from_content(content);
+                if write {
+                    self.object_store.store(&blob)?;
+                }
+                map.insert(path.clone(), blob.hash());
+            }
+        }
+        Ok(map)
+    }
+
+    /// Writes a merge result over the `current` snapshot of the work tree, refusing to
+    /// touch files whose work tree content differs from `current`.
+    fn write_merge_result(
+        &self,
+        current: &HashMap<String, String>,
+        merged: &merge::TreeMerge,
+    ) -> Result<()> {
+        let changes = diff::diff_maps(current, &merged.map);
+        let touched: Vec<&String> = changes
+            .iter()
+            .map(|change| &change.path)
+            .chain(merged.conflicts.iter().map(|conflict| &conflict.path))
+            .collect();
+
+        let work_map = self.work_tree_map(touched.iter().copied(), false)?;
+        let mut dirty: Vec<String> = touched
+            .into_iter()
+            .filter(|path| work_map.get(*path) != current.get(*path))
+            .cloned()
+            .collect();
+        if !dirty.is_empty() {
+            dirty.sort();
+            dirty.dedup();
+            return Err(error::RepositoryError::LocalChangesOverwritten { paths: dirty });
+        }
+
+        self.work_tree
+            .checkout_map(current, &merged.map, &self.object_store)?;
+        for conflict in &merged.conflicts {
+            if let Some(content) = &conflict.content {
+                self.work_tree.write_file(&conflict.path, content)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Turns a user given path into the relative form used by the index, `""` being the root.
+fn normalize_path(path: &str) -> String {
+    let path = path.trim_start_matches("./").trim_end_matches('/');
+    if path == "." {
+        String::new()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Paths of `snapshot` equal to `path` or inside the directory `path`.
+fn tracked_under(snapshot: &HashMap<String, String>, path: &str) -> Vec<String> {
+    let prefix = format!("{path}/");
+    snapshot
+        .keys()
+        .filter(|p| path.is_empty() || *p == path || p.starts_with(&prefix))
+        .cloned()
+        .collect()
+}
+
+fn parse_date(date: &str) -> Result<DateTime<FixedOffset>> {
+    signature::parse_date(date).ok_or_else(|| error::RepositoryError::InvalidDate(date.to_string()))
 }
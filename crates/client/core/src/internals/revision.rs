@@ -0,0 +1,89 @@
+/// A single ancestry step applied after the base of a revision is resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// `^N`: the N-th parent of the commit (`^` alone is the first parent).
+    Parent(usize),
+    /// `~N`: the N-th generation ancestor following first parents.
+    Ancestor(usize),
+}
+
+/// A parsed revision such as `main`, `HEAD@{2}`, `stash@{0}^2` or `a1b2c3d~3`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Revision {
+    pub name: String,
+    pub reflog_index: Option<usize>,
+    pub steps: Vec<Step>,
+}
+
+impl Revision {
+    pub fn parse(revision: &str) -> Option<Self> {
+        let revision = revision.trim();
+        let end = revision.find(['~', '^']).unwrap_or(revision.len());
+        let (base, mut suffix) = revision.split_at(end);
+
+        let (name, reflog_index) = match base.split_once("@{") {
+            Some((name, rest)) => (name, Some(rest.strip_suffix('}')?.parse().ok()?)),
+            None => (base, None),
+        };
+        let name = match name {
+            "" if reflog_index.is_some() => "HEAD",
+            "" => return None,
+            "@" => "HEAD",
+            name => name,
+        };
+
+        let mut steps = Vec::new();
+        while let Some(kind) = suffix.chars().next() {
+            suffix = &suffix[1..];
+            let digits = suffix
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(suffix.len());
+            let count = match &suffix[..digits] {
+                "" => 1,
+                n => n.parse().ok()?,
+            };
+            suffix = &suffix[digits..];
+
+            match kind {
+                '^' if count == 0 => {}
+                '^' => steps.push(Step::Parent(count)),
+                '~' => steps.push(Step::Ancestor(count)),
+                _ => return None,
+            }
+        }
+
+        Some(Self {
+            name: name.to_string(),
+            reflog_index,
+            steps,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_revisions() {
+        let rev = Revision::parse("main").unwrap();
+        assert_eq!(rev.name, "main");
+        assert_eq!(rev.reflog_index, None);
+        assert!(rev.steps.is_empty());
+
+        let rev = Revision::parse("HEAD@{2}~3^2^").unwrap();
+        assert_eq!(rev.name, "HEAD");
+        assert_eq!(rev.reflog_index, Some(2));
+        assert_eq!(
+            rev.steps,
+            vec![Step::Ancestor(3), Step::Parent(2), Step::Parent(1)]
+        );
+
+        let rev = Revision::parse("@{1}").unwrap();
+        assert_eq!(rev.name, "HEAD");
+        assert_eq!(rev.reflog_index, Some(1));
+
+        assert!(Revision::parse("main@{x}").is_none());
+        assert!(Revision::parse("~1").is_none());
+    }
+}
@@ -0,0 +1,285 @@
+use crate::error;
+use crate::internals::merge::Conflict;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+pub type Result<T> = std::result::Result<T, error::SequencerError>;
+
+/// Directory holding the state of an interrupted rebase.
+pub const REBASE_DIR: &str = "rebase-merge";
+
+/// Directory holding the state of an interrupted cherry-pick or revert.
+pub const SEQUENCER_DIR: &str = "sequencer";
+
+/// Multi-step operation that replays commits through the sequencer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Rebase,
+    CherryPick,
+    Revert,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rebase => "rebase",
+            Self::CherryPick => "cherry-pick",
+            Self::Revert => "revert",
+        }
+    }
+
+    /// Directory under `.flux/` where the state of the operation is kept.
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            Self::Rebase => REBASE_DIR,
+            Self::CherryPick | Self::Revert => SEQUENCER_DIR,
+        }
+    }
+
+    fn parse(operation: &str) -> Option<Self> {
+        [Self::Rebase, Self::CherryPick, Self::Revert]
+            .into_iter()
+            .find(|op| op.as_str() == operation)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Replay the commit as is.
+    Pick,
+    /// Leave the commit out.
+    Drop,
+    /// Meld the commit into the previous one, keeping both messages.
+    Squash,
+    /// Replay the commit with the message given on the todo line.
+    Reword,
+    /// Apply the inverse of the commit.
+    Revert,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Pick => "pick",
+            Self::Drop => "drop",
+            Self::Squash => "squash",
+            Self::Reword => "reword",
+            Self::Revert => "revert",
+        }
+    }
+
+    fn parse(action: &str) -> Option<Self> {
+        match action {
+            "pick" | "p" => Some(Self::Pick),
+            "drop" | "d" => Some(Self::Drop),
+            "squash" | "s" => Some(Self::Squash),
+            "reword" | "r" => Some(Self::Reword),
+            "revert" => Some(Self::Revert),
+            _ => None,
+        }
+    }
+}
+
+/// A single line of a todo list: `<action> <commit> [message]`.
+///
+/// For `reword` the message is the new commit message, for every other action it is only
+/// a reminder of the commit summary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub action: Action,
+    pub commit: String,
+    pub message: String,
+}
+
+impl Step {
+    pub fn new(action: Action, commit: &str, message: &str) -> Self {
+        Self {
+            action,
+            commit: commit.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    fn parse(line: &str) -> Result<Self> {
+        let invalid = || error::SequencerError::InvalidTodo(line.to_string());
+        let mut parts = line.trim().splitn(3, char::is_whitespace);
+        let action = parts.next().and_then(Action::parse).ok_or_else(invalid)?;
+        let commit = parts.next().filter(|c| !c.is_empty()).ok_or_else(invalid)?;
+        let message = parts.next().unwrap_or("").trim();
+        Ok(Self::new(action, commit, message))
+    }
+
+    /// Parses a todo list, skipping blank lines and `#` comments.
+    pub fn parse_todo(todo: &str) -> Result<Vec<Self>> {
+        todo.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::parse)
+            .collect()
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.action.as_str(), self.commit)?;
+        if !self.message.is_empty() {
+            write!(f, " {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of running the steps of a sequence.
+#[derive(Debug)]
+pub enum Progress {
+    /// There was nothing to replay.
+    UpToDate,
+    /// Every step was applied, the branch now points at the given commit.
+    Done(String),
+    /// A step stopped with conflicts that have to be resolved before continuing.
+    Stopped {
+        step: Step,
+        conflicts: Vec<Conflict>,
+    },
+}
+
+/// State of a multi-step operation, kept in a directory under `.flux/` so it can be
+/// continued, skipped or aborted from a later invocation.
+#[derive(Debug)]
+pub struct Sequencer {
+    path: PathBuf,
+}
+
+impl Sequencer {
+    pub fn new(flux_dir: &Path, dir_name: &str) -> Self {
+        Self {
+            path: flux_dir.join(dir_name),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.path.is_dir()
+    }
+
+    /// The operation whose state is stored, `None` when nothing is in progress.
+    pub fn operation(&self) -> Result<Option<Operation>> {
+        Ok(self
+            .read("operation")?
+            .as_deref()
+            .and_then(Operation::parse))
+    }
+
+    pub fn start(&self) -> Result<()> {
+        fs::create_dir_all(&self.path).map_err(|e| error::IoError::create_error(&self.path, e))?;
+        Ok(())
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        if self.path.is_dir() {
+            fs::remove_dir_all(&self.path)
+                .map_err(|e| error::IoError::delete_error(&self.path, e))?;
+        }
+        Ok(())
+    }
+
+    /// Reads a state file, `None` when it does not exist.
+    pub fn read(&self, name: &str) -> Result<Option<String>> {
+        let path = self.path.join(name);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let content =
+            fs::read_to_string(&path).map_err(|e| error::IoError::read_error(&path, e))?;
+        Ok(Some(content.trim_end().to_string()))
+    }
+
+    /// Writes a state file, removing it when `value` is `None`.
+    pub fn write(&self, name: &str, value: Option<&str>) -> Result<()> {
+        let path = self.path.join(name);
+        match value {
+            Some(value) => fs::write(&path, format!("{value}\n"))
+                .map_err(|e| error::IoError::write_error(&path, e))?,
+            None if path.is_file() => {
+                fs::remove_file(&path).map_err(|e| error::IoError::delete_error(&path, e))?
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Steps that have not been applied yet.
+    pub fn todo(&self) -> Result<Vec<Step>> {
+        Step::parse_todo(&self.read("todo")?.unwrap_or_default())
+    }
+
+    pub fn write_todo(&self, steps: &[Step]) -> Result<()> {
+        let todo: Vec<String> = steps.iter().map(Step::to_string).collect();
+        self.write("todo", Some(&todo.join("\n")))
+    }
+
+    /// Records a step as finished.
+    pub fn mark_done(&self, step: &Step) -> Result<()> {
+        let mut done = self.read("done")?.unwrap_or_default();
+        if !done.is_empty() {
+            done.push('\n');
+        }
+        done.push_str(&step.to_string());
+        self.write("done", Some(&done))
+    }
+
+    /// The step that stopped with conflicts and the paths that were conflicted.
+    pub fn stopped(&self) -> Result<Option<(Step, Vec<String>)>> {
+        let Some(line) = self.read("current")? else {
+            return Ok(None);
+        };
+        let step = Step::parse(&line)?;
+        let paths = self
+            .read("conflicts")?
+            .unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect();
+        Ok(Some((step, paths)))
+    }
+
+    pub fn set_stopped(&self, stopped: Option<(&Step, &[Conflict])>) -> Result<()> {
+        match stopped {
+            Some((step, conflicts)) => {
+                let paths: Vec<&str> = conflicts.iter().map(|c| c.path.as_str()).collect();
+                self.write("current", Some(&step.to_string()))?;
+                self.write("conflicts", Some(&paths.join("\n")))
+            }
+            None => {
+                self.write("current", None)?;
+                self.write("conflicts", None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_todo() {
+        let todo = "# rebase onto main\npick a1b2c3d First\n\ns 1234567 Second\nreword abcdef0 New message\ndrop fedcba9";
+        let steps = Step::parse_todo(todo).unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step::new(Action::Pick, "a1b2c3d", "First"),
+                Step::new(Action::Squash, "1234567", "Second"),
+                Step::new(Action::Reword, "abcdef0", "New message"),
+                Step::new(Action::Drop, "fedcba9", ""),
+            ]
+        );
+        assert_eq!(steps[2].to_string(), "reword abcdef0 New message");
+
+        let err = Step::parse_todo("edit a1b2c3d").unwrap_err();
+        assert!(matches!(err, error::SequencerError::InvalidTodo(..)));
+        assert!(Step::parse_todo("pick").is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use crate::error;
+use ssh_key::{Algorithm, HashAlg, LineEnding, PrivateKey, PublicKey, SshSig, rand_core::OsRng};
+use std::{fmt, fs, path::Path};
+
+pub type Result<T> = std::result::Result<T, error::SigningError>;
+
+/// Namespace signatures are made in, so they cannot be replayed as signatures of other tools.
+pub const NAMESPACE: &str = "flux";
+
+/// Outcome of checking the signature of an object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verification {
+    Unsigned,
+    /// The signature is malformed or does not match the content.
+    Bad(String),
+    /// The signature is valid but the key is not allowed for the committer.
+    Untrusted { fingerprint: String },
+    /// The signature is valid and made by a key allowed for `principal`.
+    Good {
+        principal: String,
+        fingerprint: String,
+    },
+}
+
+impl Verification {
+    pub fn is_good(&self) -> bool {
+        matches!(self, Self::Good { .. })
+    }
+
+    /// Signed with a key that produced a valid signature, trusted or not.
+    pub fn is_valid(&self) -> bool {
+        matches!(self, Self::Good { .. } | Self::Untrusted { .. })
+    }
+}
+
+impl fmt::Display for Verification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unsigned => write!(f, "No signature"),
+            Self::Bad(reason) => write!(f, "Bad signature: {reason}"),
+            Self::Untrusted { fingerprint } => write!(
+                f,
+                "Good signature with ED25519 key {fingerprint}, but the key is not an allowed signer"
+            ),
+            Self::Good {
+                principal,
+                fingerprint,
+            } => write!(
+                f,
+                "Good signature from \"{principal}\" with ED25519 key {fingerprint}"
+            ),
+        }
+    }
+}
+
+/// Keys allowed to sign for each principal, in the format of OpenSSH's allowed signers file:
+/// `<principal>[,<principal>...] <key type> <base64 key> [comment]`.
+#[derive(Debug, Default)]
+pub struct AllowedSigners {
+    entries: Vec<(Vec<String>, PublicKey)>,
+}
+
+impl AllowedSigners {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut entries = Vec::new();
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || error::SigningError::InvalidAllowedSigner(line.to_string());
+            let (principals, key) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let key = PublicKey::from_openssh(key.trim()).map_err(|_| invalid())?;
+            let principals = principals.split(',').map(String::from).collect();
+            entries.push((principals, key));
+        }
+        Ok(Self { entries })
+    }
+
+    /// Reads an allowed signers file, a missing file allows nobody.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content =
+            fs::read_to_string(path).map_err(|e| error::IoError::read_error(path, e))?;
+        Self::parse(&content)
+    }
+
+    /// Whether `key` may sign for `principal`, `*` allowing any principal.
+    pub fn allows(&self, principal: &str, key: &PublicKey) -> bool {
+        self.entries.iter().any(|(principals, allowed)| {
+            allowed.key_data() == key.key_data()
+                && principals.iter().any(|p| p == "*" || p == principal)
+        })
+    }
+}
+
+/// Generates an ed25519 key pair, writing the private key to `path` and the public key to
+/// `path.pub`. Returns the public key.
+pub fn generate_key(path: &Path, comment: &str) -> Result<PublicKey> {
+    if path.exists() {
+        return Err(error::SigningError::KeyExists(path.to_path_buf()));
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| error::IoError::create_error(parent, e))?;
+    }
+
+    let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519)?;
+    key.set_comment(comment);
+    key.write_openssh_file(path, LineEnding::LF)?;
+
+    let public = key.public_key().clone();
+    let public_path = path.with_extension("pub");
+    fs::write(&public_path, format!("{}\n", public.to_openssh()?))
+        .map_err(|e| error::IoError::write_error(&public_path, e))?;
+    Ok(public)
+}
+
+/// Loads an unencrypted ed25519 private key in the OpenSSH format.
+pub fn load_key(path: &Path) -> Result<PrivateKey> {
+    let pem = fs::read_to_string(path).map_err(|e| error::IoError::read_error(path, e))?;
+    let key = PrivateKey::from_openssh(pem)?;
+    if key.is_encrypted() {
+        return Err(error::SigningError::EncryptedKey(path.to_path_buf()));
+    }
+    if key.algorithm() != Algorithm::Ed25519 {
+        return Err(error::SigningError::UnsupportedKey(key.algorithm().to_string()));
+    }
+    Ok(key)
+}
+
+/// Signs `payload`, returning the armored `SSH SIGNATURE` block.
+pub fn sign(key: &PrivateKey, payload: &[u8]) -> Result<String> {
+    let signature = key.sign(NAMESPACE, HashAlg::Sha512, payload)?;
+    Ok(signature.to_pem(LineEnding::LF)?.trim_end().to_string())
+}
+
+/// Checks an armored signature of `payload` made on behalf of `principal`.
+pub fn verify(
+    payload: &[u8],
+    signature: Option<&str>,
+    principal: &str,
+    allowed: &AllowedSigners,
+) -> Verification {
+    let Some(signature) = signature else {
+        return Verification::Unsigned;
+    };
+    let signature = match SshSig::from_pem(signature) {
+        Ok(signature) => signature,
+        Err(e) => return Verification::Bad(e.to_string()),
+    };
+    if signature.algorithm() != Algorithm::Ed25519 {
+        return Verification::Bad(format!("unsupported {} key", signature.algorithm()));
+    }
+
+    let key = PublicKey::from(signature.public_key().clone());
+    if let Err(e) = key.verify(NAMESPACE, payload, &signature) {
+        return Verification::Bad(e.to_string());
+    }
+
+    let fingerprint = key.fingerprint(HashAlg::Sha256).to_string();
+    if allowed.allows(principal, &key) {
+        Verification::Good {
+            principal: principal.to_string(),
+            fingerprint,
+        }
+    } else {
+        Verification::Untrusted { fingerprint }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let other = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        let allowed = AllowedSigners::parse(&format!(
+            "# signers\nalice@example.com,bob@example.com {}\n",
+            key.public_key().to_openssh().unwrap()
+        ))
+        .unwrap();
+
+        let signature = sign(&key, b"payload").unwrap();
+        assert!(signature.starts_with("-----BEGIN SSH SIGNATURE-----"));
+
+        let good = verify(b"payload", Some(&signature), "bob@example.com", &allowed);
+        assert!(good.is_good());
+        let untrusted = verify(b"payload", Some(&signature), "eve@example.com", &allowed);
+        assert!(matches!(untrusted, Verification::Untrusted { .. }));
+        let tampered = verify(b"payload!", Some(&signature), "bob@example.com", &allowed);
+        assert!(matches!(tampered, Verification::Bad(..)));
+        assert_eq!(
+            verify(b"payload", None, "bob@example.com", &allowed),
+            Verification::Unsigned
+        );
+
+        let signature = sign(&other, b"payload").unwrap();
+        let unknown = verify(b"payload", Some(&signature), "alice@example.com", &allowed);
+        assert!(unknown.is_valid() && !unknown.is_good());
+
+        assert!(AllowedSigners::parse("alice@example.com not-a-key").is_err());
+    }
+}
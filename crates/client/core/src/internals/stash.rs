@@ -0,0 +1,121 @@
+use crate::error;
+use crate::internals::reflog::{Reflog, ReflogEntry};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub type Result<T> = std::result::Result<T, error::StashError>;
+
+pub const STASH_REF: &str = "refs/stash";
+
+/// Stack of stash entries. `refs/stash` points at the newest entry and its
+/// reflog keeps every entry, so `stash@{0}` is the last line of the log.
+#[derive(Debug)]
+pub struct Stash {
+    ref_path: PathBuf,
+    reflog: Reflog,
+}
+
+impl Stash {
+    pub fn new(flux_dir: &Path) -> Self {
+        Self {
+            ref_path: flux_dir.join(STASH_REF),
+            reflog: Reflog::new(flux_dir),
+        }
+    }
+
+    /// Parses `stash@{N}` or a plain `N`. No reference means the newest entry.
+    pub fn parse_reference(reference: Option<&str>) -> Result<usize> {
+        let Some(reference) = reference else {
+            return Ok(0);
+        };
+
+        let index = reference
+            .strip_prefix("stash@{")
+            .and_then(|r| r.strip_suffix('}'))
+            .unwrap_or(reference);
+
+        index
+            .parse()
+            .map_err(|_| error::StashError::InvalidReference(reference.to_string()))
+    }
+
+    /// Returns the stash entries, newest first.
+    pub fn list(&self) -> Result<Vec<ReflogEntry>> {
+        let mut entries = self.reflog.read(STASH_REF)?;
+        entries.reverse();
+        Ok(entries)
+    }
+
+    pub fn get(&self, index: usize) -> Result<ReflogEntry> {
+        let entries = self.list()?;
+        if entries.is_empty() {
+            return Err(error::StashError::Empty);
+        }
+
+        entries
+            .into_iter()
+            .nth(index)
+            .ok_or(error::StashError::MissingEntry(index))
+    }
+
+    pub fn push(&self, commit_hash: &str, identity: &str, message: &str) -> Result<()> {
+        let old = self.list()?.first().map(|e| e.new_hash.clone());
+        let entry = ReflogEntry::new(old.as_deref().unwrap_or(""), commit_hash, identity, message);
+        self.reflog.append(STASH_REF, &entry)?;
+        self.write_ref(commit_hash)
+    }
+
+    /// Removes an entry from the stack and returns it.
+    pub fn drop(&self, index: usize) -> Result<ReflogEntry> {
+        let mut entries = self.list()?;
+        if entries.is_empty() {
+            return Err(error::StashError::Empty);
+        }
+        if index >= entries.len() {
+            return Err(error::StashError::MissingEntry(index));
+        }
+
+        let dropped = entries.remove(index);
+        match entries.first() {
+            Some(top) => self.write_ref(&top.new_hash)?,
+            None => self.remove_ref()?,
+        }
+
+        entries.reverse();
+        self.reflog.write(STASH_REF, &entries)?;
+
+        Ok(dropped)
+    }
+
+    fn write_ref(&self, commit_hash: &str) -> Result<()> {
+        fs::write(&self.ref_path, commit_hash)
+            .map_err(|e| error::RefsError::from(error::IoError::write_error(&self.ref_path, e)))?;
+        Ok(())
+    }
+
+    fn remove_ref(&self) -> Result<()> {
+        if self.ref_path.is_file() {
+            fs::remove_file(&self.ref_path).map_err(|e| {
+                error::RefsError::from(error::IoError::delete_error(&self.ref_path, e))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reference() {
+        assert_eq!(Stash::parse_reference(None).unwrap(), 0);
+        assert_eq!(Stash::parse_reference(Some("2")).unwrap(), 2);
+        assert_eq!(Stash::parse_reference(Some("stash@{3}")).unwrap(), 3);
+
+        let err = Stash::parse_reference(Some("stash@{x}")).unwrap_err();
+        assert!(matches!(err, error::StashError::InvalidReference(..)));
+    }
+}
@@ -220,6 +220,103 @@ impl WorkTree {
         }
     }
 
+    /// Reads a file relative to the work tree root. Returns `None` if the file does not exist.
+    pub fn read_file(&self, rel_path: &str) -> Result<Option<Vec<u8>>, error::WorkTreeError> {
+        let path = self.path.join(rel_path);
+        if !path.is_file() {
+            return Ok(None);
+        }
+
+        let content = fs::read(&path).map_err(|e| error::IoError::Read {
+            path: path.clone(),
+            source: e,
+        })?;
+
+        Ok(Some(content))
+    }
+
+    pub fn write_file(&self, rel_path: &str, content: &[u8]) -> Result<(), error::WorkTreeError> {
+        let path = self.path.join(rel_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| error::IoError::Create {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+
+        fs::write(&path, content).map_err(|e| error::IoError::Write {
+            path: path.clone(),
+            source: e,
+        })?;
+
+        Ok(())
+    }
+
+    /// Removes a file relative to the work tree root along with any parent directories left empty.
+    pub fn remove_file(&self, rel_path: &str) -> Result<(), error::WorkTreeError> {
+        let path = self.path.join(rel_path);
+        if path.is_file() {
+            fs::remove_file(&path).map_err(|e| error::IoError::Delete {
+                path: path.clone(),
+                source: e,
+            })?;
+        }
+
+        let mut parent = path.parent();
+        while let Some(dir) = parent {
+            if dir == self.path || fs::remove_dir(dir).is_err() {
+                break;
+            }
+            parent = dir.parent();
+        }
+
+        Ok(())
+    }
+
+    /// Renames a file relative to the work tree root, creating the destination directories
+    /// and removing the source directories left empty.
+    pub fn rename_file(&self, from: &str, to: &str) -> Result<(), error::WorkTreeError> {
+        let source = self.path.join(from);
+        let destination = self.path.join(to);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|e| error::IoError::Create {
+                path: parent.to_path_buf(),
+                source: e,
+            })?;
+        }
+
+        fs::rename(&source, &destination).map_err(|e| error::IoError::Write {
+            path: destination.clone(),
+            source: e,
+        })?;
+
+        self.remove_file(from)
+    }
+
+    /// Moves the tracked files of the work tree from the `current` snapshot to the `target` one.
+    /// Only paths whose blobs differ are written or removed, untracked files are left alone.
+    pub fn checkout_map(
+        &self,
+        current: &HashMap<String, String>,
+        target: &HashMap<String, String>,
+        object_store: &ObjectStore,
+    ) -> Result<(), error::WorkTreeError> {
+        for path in current.keys() {
+            if !target.contains_key(path) {
+                self.remove_file(path)?;
+            }
+        }
+
+        for (path, hash) in target {
+            if current.get(path) != Some(hash) || !self.path.join(path).is_file() {
+                let content = object_store.blob_content(hash)?;
+                self.write_file(path, &content)?;
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
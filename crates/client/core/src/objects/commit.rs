@@ -4,10 +4,13 @@ use crate::objects::object_type::FluxObject;
 use crate::utils;
 
 use super::object_type::ObjectType;
-use chrono::Local;
+use super::signature::Signature;
+
+/// Header holding the armored SSH signature of a signed commit.
+const SIGNATURE_HEADER: &str = "sshsig";
 
 pub struct Commit {
-    parent_hash: Option<String>,
+    parents: Vec<String>,
     pub tree_hash: String,
     pub content: Vec<u8>,
 }
@@ -17,34 +20,33 @@ impl Commit {
         tree_hash: String,
         user_name: String,
         user_email: String,
-        parent_hash: Option<String>,
+        parents: Vec<String>,
         message: String,
     ) -> Self {
-        let now = Local::now();
-        let parent_line = match parent_hash {
-            Some(ref h) => format!("parent {}\n", h),
-            None => String::new(),
-        };
+        let signature = Signature::now(&user_name, &user_email).to_string();
+        Self::with_signatures(tree_hash, signature.clone(), signature, parents, message)
+    }
+
+    /// Builds a commit from preformatted `Name <email> <timestamp> <offset>` author and
+    /// committer signatures, e.g. to keep the original author of a replayed commit.
+    pub fn with_signatures(
+        tree_hash: String,
+        author: String,
+        committer: String,
+        parents: Vec<String>,
+        message: String,
+    ) -> Self {
+        let parent_line: String = parents.iter().map(|h| format!("parent {}\n", h)).collect();
         let content = format!(
-            "tree {}\n{}author {} <{}> {} {}\ncommitter {} <{}> {} {}\n\n{}",
-            tree_hash,
-            parent_line,
-            user_name,
-            user_email,
-            now.timestamp(),
-            now.format("%z"),
-            user_name,
-            user_email,
-            now.timestamp(),
-            now.format("%z"),
-            message
+            "tree {}\n{}author {}\ncommitter {}\n\n{}",
+            tree_hash, parent_line, author, committer, message
         )
         .as_bytes()
         .to_owned();
 
         Self {
             content,
-            parent_hash,
+            parents,
             tree_hash,
         }
     }
@@ -53,19 +55,22 @@ impl Commit {
         let content_str = String::from_utf8_lossy(&content);
 
         let mut tree_hash = String::new();
-        let mut parent_hash = None;
+        let mut parents = Vec::new();
 
         for line in content_str.lines() {
+            if line.is_empty() {
+                break;
+            }
             if line.starts_with("tree ") {
                 tree_hash = line.strip_prefix("tree ").unwrap_or("").to_string();
             } else if line.starts_with("parent ") {
-                parent_hash = Some(line.strip_prefix("parent ").unwrap_or("").to_string());
+                parents.push(line.strip_prefix("parent ").unwrap_or("").to_string());
             }
         }
 
         Self {
             content,
-            parent_hash,
+            parents,
             tree_hash,
         }
     }
@@ -75,10 +80,127 @@ impl Commit {
     }
 
     pub fn parent_hash(&self) -> Option<&str> {
-        self.parent_hash.as_deref()
+        self.parents.first().map(String::as_str)
+    }
+
+    pub fn parents(&self) -> &[String] {
+        &self.parents
+    }
+
+    fn header(&self, key: &str) -> Option<String> {
+        let content_str = String::from_utf8_lossy(&self.content);
+        content_str
+            .lines()
+            .take_while(|line| !line.is_empty())
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix(' '))
+            .map(String::from)
+    }
+
+    /// Returns the author signature, `Name <email> <timestamp> <offset>`.
+    pub fn author(&self) -> Option<String> {
+        self.header("author")
+    }
+
+    /// Returns the committer signature, `Name <email> <timestamp> <offset>`.
+    pub fn committer(&self) -> Option<String> {
+        self.header("committer")
+    }
+
+    /// Returns the parsed author signature.
+    pub fn author_signature(&self) -> Option<Signature> {
+        Signature::parse(&self.author()?)
+    }
+
+    /// Returns the parsed committer signature.
+    pub fn committer_signature(&self) -> Option<Signature> {
+        Signature::parse(&self.committer()?)
+    }
+
+    /// Returns the armored signature stored in the `sshsig` header, if the commit is signed.
+    pub fn signature(&self) -> Option<String> {
+        let content_str = String::from_utf8_lossy(&self.content);
+        let mut lines = content_str.lines().take_while(|line| !line.is_empty());
+        let first =
+            lines.find_map(|line| line.strip_prefix(SIGNATURE_HEADER)?.strip_prefix(' '))?;
+
+        let mut signature = first.to_string();
+        for line in lines.map_while(|line| line.strip_prefix(' ')) {
+            signature.push('\n');
+            signature.push_str(line);
+        }
+        Some(signature)
+    }
+
+    /// Returns the content the signature was made over: the commit without its signature.
+    pub fn signed_payload(&self) -> Vec<u8> {
+        let content_str = String::from_utf8_lossy(&self.content);
+        let (header, message) = content_str
+            .split_once("\n\n")
+            .unwrap_or((content_str.as_ref(), ""));
+
+        let mut payload = String::with_capacity(content_str.len());
+        let mut in_signature = false;
+        for line in header.lines() {
+            in_signature = line.starts_with(&format!("{SIGNATURE_HEADER} "))
+                || (in_signature && line.starts_with(' '));
+            if !in_signature {
+                payload.push_str(line);
+                payload.push('\n');
+            }
+        }
+        payload.push('\n');
+        payload.push_str(message);
+        payload.into_bytes()
+    }
+
+    /// Returns a copy of the commit with `signature` added as the last header.
+    pub fn with_signature(&self, signature: &str) -> Self {
+        let content_str = String::from_utf8_lossy(&self.content);
+        let (header, message) = content_str
+            .split_once("\n\n")
+            .unwrap_or((content_str.as_ref(), ""));
+        let signature = signature.trim_end().replace('\n', "\n ");
+        let content = format!("{header}\n{SIGNATURE_HEADER} {signature}\n\n{message}");
+        Self::from_content(content.into_bytes())
+    }
+
+    /// Returns the commit message, everything after the first blank line.
+    pub fn message(&self) -> String {
+        let content_str = String::from_utf8_lossy(&self.content);
+        match content_str.split_once("\n\n") {
+            Some((_, message)) => message.to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Returns the first line of the commit message.
+    pub fn summary(&self) -> String {
+        self.message().lines().next().unwrap_or("").to_string()
     }
 }
 
+/// Cleans up a message written in an editor: drops `#` comment lines and trailing
+/// whitespace, collapses runs of blank lines and trims blank lines at both ends.
+pub fn clean_message(message: &str) -> String {
+    let mut cleaned = String::new();
+    let mut blank = false;
+    for line in message.lines().filter(|line| !line.starts_with('#')) {
+        let line = line.trim_end();
+        if line.is_empty() {
+            blank = !cleaned.is_empty();
+            continue;
+        }
+        if blank {
+            cleaned.push_str("\n\n");
+        } else if !cleaned.is_empty() {
+            cleaned.push('\n');
+        }
+        cleaned.push_str(line);
+        blank = false;
+    }
+    cleaned
+}
+
 impl FluxObject for Commit {
     fn object_type(&self) -> ObjectType {
         ObjectType::Commit
@@ -112,3 +234,41 @@ impl FluxObject for Commit {
         self.content.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_header() {
+        let commit = Commit::with_signatures(
+            "t".repeat(40),
+            "A <a@b.c> 0 +0000".to_string(),
+            "A <a@b.c> 0 +0000".to_string(),
+            vec!["p".repeat(40)],
+            "Message\n\nBody\n".to_string(),
+        );
+        assert_eq!(commit.signature(), None);
+        assert_eq!(commit.signed_payload(), commit.content);
+
+        let signed = commit.with_signature("-----BEGIN-----\nabc\n-----END-----\n");
+        assert!(
+            String::from_utf8_lossy(&signed.content).contains("sshsig -----BEGIN-----\n abc\n")
+        );
+        assert_eq!(
+            signed.signature().as_deref(),
+            Some("-----BEGIN-----\nabc\n-----END-----")
+        );
+        assert_eq!(signed.signed_payload(), commit.content);
+        assert_eq!(signed.message(), "Message\n\nBody\n");
+        assert_eq!(signed.parents(), commit.parents());
+        assert_eq!(signed.committer(), commit.committer());
+    }
+
+    #[test]
+    fn clean_editor_message() {
+        let message = "\n\nSummary  \n# comment\n\n\n\nBody line\n#\tmodified:   a.txt\n\n";
+        assert_eq!(clean_message(message), "Summary\n\nBody line");
+        assert_eq!(clean_message("# only comments\n\n"), "");
+    }
+}
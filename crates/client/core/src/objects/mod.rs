@@ -1,4 +1,5 @@
 pub mod blob;
 pub mod commit;
 pub mod object_type;
+pub mod signature;
 pub mod tree;
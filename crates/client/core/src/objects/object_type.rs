@@ -9,7 +9,7 @@ pub trait FluxObject {
     fn content(&self) -> Vec<u8>;
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum ObjectType {
     Blob,
     Tree,
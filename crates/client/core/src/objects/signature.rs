@@ -0,0 +1,217 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use std::{fmt, str::FromStr};
+
+/// Identity and time stamped on a commit: `Name <email> <unix timestamp> <+hhmm>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub time: DateTime<FixedOffset>,
+}
+
+impl Signature {
+    pub fn new(name: &str, email: &str, time: DateTime<FixedOffset>) -> Self {
+        Self {
+            name: name.to_string(),
+            email: email.to_string(),
+            time,
+        }
+    }
+
+    /// Signature at the current local time.
+    pub fn now(name: &str, email: &str) -> Self {
+        Self::new(name, email, Local::now().fixed_offset())
+    }
+
+    /// Parses a `Name <email> <timestamp> <+hhmm>` header value.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (name, rest) = value.split_once('<')?;
+        let (email, time) = rest.split_once('>')?;
+        let (timestamp, offset) = time.trim().split_once(' ')?;
+        Some(Self::new(
+            name.trim(),
+            email.trim(),
+            at_offset(timestamp.parse().ok()?, parse_offset(offset)?)?,
+        ))
+    }
+
+    /// Parses a `Name <email>` identity, as given to `--author`.
+    pub fn parse_identity(identity: &str) -> Option<(String, String)> {
+        let (name, rest) = identity.split_once('<')?;
+        let email = rest.strip_suffix('>')?.trim();
+        let name = name.trim();
+        if name.is_empty() || email.is_empty() || email.contains(['<', '>']) {
+            return None;
+        }
+        Some((name.to_string(), email.to_string()))
+    }
+
+    /// The time of the signature shown in `zone`.
+    pub fn time_in(&self, zone: DateZone) -> DateTime<FixedOffset> {
+        match zone {
+            DateZone::Original => self.time,
+            DateZone::Local => self.time.with_timezone(&Local).fixed_offset(),
+            DateZone::Utc => self.time.with_timezone(&Utc).fixed_offset(),
+            DateZone::Fixed(offset) => self.time.with_timezone(&offset),
+        }
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} <{}> {} {}",
+            self.name,
+            self.email,
+            self.time.timestamp(),
+            self.time.format("%z")
+        )
+    }
+}
+
+/// Timezone dates are printed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateZone {
+    /// The offset recorded in the commit.
+    #[default]
+    Original,
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+}
+
+impl FromStr for DateZone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "original" => Ok(Self::Original),
+            "local" => Ok(Self::Local),
+            "utc" | "UTC" => Ok(Self::Utc),
+            offset => parse_offset(offset)
+                .map(Self::Fixed)
+                .ok_or_else(|| format!("expected original, local, utc or +hhmm, got '{offset}'")),
+        }
+    }
+}
+
+/// Parses a `+hhmm` or `-hh:mm` offset.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    let (sign, digits) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    if digits.len() != 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+fn at_offset(timestamp: i64, offset: FixedOffset) -> Option<DateTime<FixedOffset>> {
+    DateTime::from_timestamp(timestamp, 0).map(|time| time.with_timezone(&offset))
+}
+
+/// Parses a date given to `--date` or a `FLUX_*_DATE` variable. Accepted formats are the
+/// internal `<timestamp> <+hhmm>`, `@<timestamp>`, RFC 3339, RFC 2822 and
+/// `YYYY-MM-DD[ HH:MM[:SS]] [+hhmm]`, the latter in the local timezone when no offset is given.
+pub fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    let date = date.trim();
+
+    if let Some(timestamp) = date.strip_prefix('@') {
+        return at_offset(timestamp.parse().ok()?, FixedOffset::east_opt(0)?);
+    }
+    if let Some((timestamp, offset)) = date.split_once(' ')
+        && let (Ok(timestamp), Some(offset)) = (timestamp.parse(), parse_offset(offset))
+    {
+        return at_offset(timestamp, offset);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(date) {
+        return Some(time);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc2822(date) {
+        return Some(time);
+    }
+
+    let (naive, offset) = match date.rsplit_once(' ') {
+        Some((naive, offset)) if parse_offset(offset).is_some() => (naive, parse_offset(offset)),
+        _ => (date, None),
+    };
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(naive, format).ok())
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(naive, "%Y-%m-%d")
+                .ok()?
+                .and_hms_opt(0, 0, 0)
+        })?;
+
+    match offset {
+        Some(offset) => offset.from_local_datetime(&naive).single(),
+        None => Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|time| time.fixed_offset()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let signature = Signature::parse("Jane Doe <jane@example.com> 1700000000 +0530").unwrap();
+        assert_eq!(signature.name, "Jane Doe");
+        assert_eq!(signature.email, "jane@example.com");
+        assert_eq!(signature.time.timestamp(), 1_700_000_000);
+        assert_eq!(
+            signature.to_string(),
+            "Jane Doe <jane@example.com> 1700000000 +0530"
+        );
+
+        let utc = signature.time_in(DateZone::Utc);
+        assert_eq!(utc.timestamp(), 1_700_000_000);
+        assert_eq!(utc.format("%H:%M %z").to_string(), "22:13 +0000");
+        let zone: DateZone = "-0200".parse().unwrap();
+        assert_eq!(
+            signature.time_in(zone).format("%H:%M %z").to_string(),
+            "20:13 -0200"
+        );
+        assert!("somewhere".parse::<DateZone>().is_err());
+
+        assert_eq!(
+            Signature::parse_identity("Jane Doe <jane@example.com>"),
+            Some(("Jane Doe".to_string(), "jane@example.com".to_string()))
+        );
+        assert_eq!(Signature::parse_identity("jane@example.com"), None);
+    }
+
+    #[test]
+    fn parse_dates() {
+        let expected = 1_700_000_000;
+        for date in [
+            "1700000000 +0530",
+            "@1700000000",
+            "2023-11-15T03:43:20+05:30",
+            "Tue, 14 Nov 2023 22:13:20 +0000",
+            "2023-11-14 20:13:20 -0200",
+        ] {
+            let time = parse_date(date).unwrap_or_else(|| panic!("failed to parse {date}"));
+            assert_eq!(time.timestamp(), expected, "{date}");
+        }
+        assert_eq!(
+            parse_date("2023-11-14 20:13:20 -0200")
+                .unwrap()
+                .format("%z")
+                .to_string(),
+            "-0200"
+        );
+        assert!(parse_date("2023-11-14").is_some());
+        assert!(parse_date("yesterday").is_none());
+    }
+}
@@ -6,6 +6,7 @@ use crate::{
 use super::object_type::ObjectType;
 use std::{any::Any, collections::HashMap, fs, path::Path};
 
+#[derive(Debug, Clone)]
 pub struct TreeEntry {
     pub mode: String,
     pub hash: String,
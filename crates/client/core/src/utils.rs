@@ -15,12 +15,15 @@ pub struct GenericObject {
 /// Decompresses zlib-compressed data using the DEFLATE algorithm.
 /// Takes compressed bytes and returns the original uncompressed data
 pub fn decompress(compressed: Vec<u8>) -> Vec<u8> {
-    let mut decoder = ZlibDecoder::new(&compressed[..]);
+    try_decompress(&compressed).expect("Failed to decompress data")
+}
+
+/// Same as `decompress`, but returns an error instead of panicking on corrupt data.
+pub fn try_decompress(compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut decoder = ZlibDecoder::new(compressed);
     let mut result = Vec::new();
-    decoder
-        .read_to_end(&mut result)
-        .expect("Failed to decompress data");
-    result
+    decoder.read_to_end(&mut result)?;
+    Ok(result)
 }
 
 /// Computes the SHA-1 hash of the given data and returns it.
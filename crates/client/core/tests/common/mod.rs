@@ -1,4 +1,6 @@
 use anyhow::Context;
+use flux_core::internals::config::{GLOBAL_CONFIG_ENV, SYSTEM_CONFIG_ENV};
+use flux_core::internals::credentials::CREDENTIALS_ENV;
 use std::{
     env, fs,
     path::{Path, PathBuf},
@@ -26,7 +28,17 @@ impl Drop for WorkingDirGuard {
 
 pub fn setup_test_project() -> (TempDir, PathBuf) {
     let temp = TempDir::new().unwrap();
-    let project_path = temp.path().to_path_buf();
+    let project_path = temp.path().join("project");
+    fs::create_dir(&project_path).unwrap();
+
+    // Keep the configuration of the machine and of the user out of the tests. It lives next
+    // to the project, inside the temporary directory, so it is removed with it.
+    let config_dir = temp.path().join("config");
+    unsafe {
+        env::set_var(GLOBAL_CONFIG_ENV, config_dir.join("global"));
+        env::set_var(SYSTEM_CONFIG_ENV, config_dir.join("system"));
+        env::set_var(CREDENTIALS_ENV, config_dir.join("credentials"));
+    }
 
     fs::write(
         project_path.join("README.md"),
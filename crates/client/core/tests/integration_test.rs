@@ -1,7 +1,17 @@
 use flux_core::error;
-use flux_core::internals::repository::Repository;
+use flux_core::internals::config::{ConfigLevel, GLOBAL_CONFIG_ENV};
+use flux_core::internals::credentials::{CREDENTIALS_ENV, CredentialStore};
+use flux_core::internals::diff::ChangeStatus;
+use flux_core::internals::repository::{CommitOptions, LogOptions, Repository};
+use flux_core::internals::sequencer::{Operation, Progress};
+use flux_core::internals::signing::{AllowedSigners, Verification};
+use flux_core::objects::signature::DateZone;
 use serial_test::serial;
+use std::collections::HashSet;
+use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 mod common;
 
@@ -96,8 +106,247 @@ fn set() {
     assert!(project_path.join(".flux/config").exists());
 
     let config = fs::read_to_string(".flux/config").unwrap();
-    assert!(config.contains("user_name = \"user\""));
-    assert!(config.contains("user_email = \"user@gmail.com\""));
+    assert!(config.starts_with("# Configuration file for flux\n"));
+    assert!(config.ends_with("\n\n[user]\nname = \"user\"\nemail = \"user@gmail.com\"\n"));
+}
+
+#[test]
+#[serial]
+fn layered_config() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let global_path = PathBuf::from(env::var(GLOBAL_CONFIG_ENV).unwrap());
+
+    let mut config = Repository::open_config(None).unwrap();
+    assert!(config.list(None).is_empty());
+    assert!(matches!(
+        config.set_at(
+            ConfigLevel::Local,
+            "remote.origin.url".to_string(),
+            "x".to_string()
+        ),
+        Err(error::ConfigError::MissingLevel(ConfigLevel::Local))
+    ));
+    config
+        .set_at(
+            ConfigLevel::Global,
+            "user.name".to_string(),
+            "global".to_string(),
+        )
+        .unwrap();
+    config
+        .set_at(
+            ConfigLevel::Global,
+            "user.email".to_string(),
+            "global@example.com".to_string(),
+        )
+        .unwrap();
+    assert!(global_path.is_file());
+
+    let mut repo = Repository::init(None, false).unwrap();
+    let credentials = repo.config.get_credentials().unwrap();
+    assert_eq!(credentials.user_name, "global");
+    repo.add(".").unwrap();
+    let initial = repo.commit("Uses the global identity".to_string()).unwrap();
+    let log_line = fs::read_to_string(".flux/logs/HEAD").unwrap();
+    assert!(log_line.starts_with(&format!(
+        "{} {initial} global <global@example.com> ",
+        "0".repeat(40)
+    )));
+
+    repo.set("user.name".to_string(), "local".to_string())
+        .unwrap();
+    let entry = repo.config.get_entry("user.name").unwrap().unwrap();
+    assert_eq!(
+        (entry.level, entry.value.as_str()),
+        (ConfigLevel::Local, "local")
+    );
+    assert!(entry.path.ends_with(".flux/config"));
+
+    let levels: Vec<(ConfigLevel, String)> = repo
+        .config
+        .list(None)
+        .into_iter()
+        .map(|entry| (entry.level, entry.key))
+        .collect();
+    assert_eq!(
+        levels,
+        [
+            (ConfigLevel::Global, "user.name".to_string()),
+            (ConfigLevel::Global, "user.email".to_string()),
+            (ConfigLevel::Local, "user.name".to_string()),
+        ]
+    );
+
+    assert!(
+        repo.config
+            .unset_at(ConfigLevel::Local, "user.name")
+            .unwrap()
+    );
+    assert!(
+        !repo
+            .config
+            .unset_at(ConfigLevel::Local, "user.name")
+            .unwrap()
+    );
+    assert_eq!(
+        repo.config.get("user.name").unwrap().as_deref(),
+        Some("global")
+    );
+
+    fs::remove_dir_all(global_path.parent().unwrap()).unwrap();
+}
+
+#[test]
+#[serial]
+fn typed_config() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+
+    let mut repo = Repository::init(None, false).unwrap();
+    // Files written before the sectioned format are still understood.
+    fs::write(
+        ".flux/config",
+        "# kept\nuser_name = \"old\"\nrename_threshold = \"70\"\n",
+    )
+    .unwrap();
+    let mut repo_config = Repository::open_config(None).unwrap();
+    assert_eq!(
+        repo_config.get("user.name").unwrap().as_deref(),
+        Some("old")
+    );
+    assert_eq!(
+        repo_config.get_int("diff.rename_threshold").unwrap(),
+        Some(70)
+    );
+
+    repo_config
+        .set("user.name".to_string(), "new".to_string())
+        .unwrap();
+    let content = fs::read_to_string(".flux/config").unwrap();
+    assert!(content.starts_with("# kept\n"));
+    assert!(!content.contains("user_name"));
+    assert!(content.contains("[user]\nname = \"new\"\n"));
+
+    assert!(matches!(
+        repo.config
+            .set("diff.rename_threshold".to_string(), "101".to_string()),
+        Err(error::ConfigError::InvalidValue { .. })
+    ));
+    assert!(matches!(
+        repo.config
+            .set("commit.sign".to_string(), "maybe".to_string()),
+        Err(error::ConfigError::InvalidValue { .. })
+    ));
+    assert!(matches!(
+        repo.config.set("name".to_string(), "x".to_string()),
+        Err(error::ConfigError::InvalidKey(_))
+    ));
+
+    let mut repo = Repository::open(None).unwrap();
+    assert_eq!(repo.config.get_bool("commit.sign").unwrap(), Some(false));
+    repo.config
+        .set("commit.sign".to_string(), "yes".to_string())
+        .unwrap();
+    assert_eq!(repo.config.get_bool("commit.sign").unwrap(), Some(true));
+    repo.config
+        .set("diff.rename_threshold".to_string(), "60".to_string())
+        .unwrap();
+    assert_eq!(repo.rename_options().unwrap().threshold, 60);
+
+    repo.config
+        .set("pager.exclude".to_string(), "a, b".to_string())
+        .unwrap();
+    assert_eq!(repo.config.get_list("pager.exclude").unwrap(), ["a", "b"]);
+    repo.config
+        .set("alias.co".to_string(), "branch switch".to_string())
+        .unwrap();
+    assert_eq!(
+        repo.config.get("alias.co").unwrap().as_deref(),
+        Some("branch switch")
+    );
+    assert_eq!(repo.config.get("remote.upstream.url").unwrap(), None);
+}
+
+#[test]
+#[serial]
+fn credential_store() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let path = PathBuf::from(env::var(CREDENTIALS_ENV).unwrap());
+
+    let mut repo = Repository::init(None, false).unwrap();
+    repo.set(
+        "remote.origin.url".to_string(),
+        "http://localhost:50051/test/project".to_string(),
+    )
+    .unwrap();
+    let store = CredentialStore::from_config(&repo.config).unwrap();
+    assert!(matches!(&store, CredentialStore::File(file) if *file == path));
+
+    store
+        .store("http://localhost:50051/test/project", "first")
+        .unwrap();
+    store
+        .store("http://localhost:50051/other", "second")
+        .unwrap();
+    store.store("http://example.com:50051", "third").unwrap();
+    assert_eq!(
+        fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+    // Tokens belong to the server, every repository on it shares one.
+    assert_eq!(
+        store.get("http://localhost:50051/project").unwrap(),
+        Some("second".to_string())
+    );
+    assert!(
+        !fs::read_to_string(".flux/config")
+            .unwrap()
+            .contains("second")
+    );
+
+    assert!(Repository::logout(None, None).unwrap());
+    assert!(!Repository::logout(None, None).unwrap());
+    assert_eq!(store.get("http://localhost:50051").unwrap(), None);
+    assert_eq!(
+        store.get("http://example.com:50051/").unwrap(),
+        Some("third".to_string())
+    );
+
+    // External helpers keep the tokens themselves, here in a plain file.
+    let helper = project_path.join("helper.sh");
+    fs::write(
+        &helper,
+        "#!/bin/sh\nread url\ncase $1 in\n  get) cat tokens 2>/dev/null || true ;;\n  \
+         store) read token; echo \"$token\" > tokens ;;\n  erase) rm -f tokens ;;\nesac\n",
+    )
+    .unwrap();
+    fs::set_permissions(&helper, fs::Permissions::from_mode(0o755)).unwrap();
+    repo.set(
+        "credential.helper".to_string(),
+        helper.to_string_lossy().to_string(),
+    )
+    .unwrap();
+    let store = CredentialStore::from_config(&repo.config).unwrap();
+    assert_eq!(store.get("http://localhost:50051").unwrap(), None);
+    store.store("http://localhost:50051", "helped").unwrap();
+    assert_eq!(
+        store.get("http://localhost:50051").unwrap(),
+        Some("helped".to_string())
+    );
+    assert!(store.erase("http://localhost:50051").unwrap());
+    assert_eq!(store.get("http://localhost:50051").unwrap(), None);
+
+    repo.set("credential.helper".to_string(), "missing".to_string())
+        .unwrap();
+    let store = CredentialStore::from_config(&repo.config).unwrap();
+    assert!(matches!(
+        store.get("http://localhost:50051"),
+        Err(error::CredentialError::Helper { .. })
+    ));
 }
 
 #[test]
@@ -413,3 +662,1024 @@ fn branching_errors() {
     ));
     println!("{err}");
 }
+
+#[test]
+#[serial]
+fn stash() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+
+    let mut repo = Repository::init(None, false).unwrap();
+    repo.set("user_name".to_string(), "test".to_string())
+        .unwrap();
+    repo.set("user_email".to_string(), "test@gmail.com".to_string())
+        .unwrap();
+    repo.add(".").unwrap();
+    repo.commit("Initial commit".to_string()).unwrap();
+
+    fs::write("README.md", "Work in progress").unwrap();
+    let message = repo.stash_push(None).unwrap();
+    assert!(message.starts_with("WIP on main: "));
+    assert_eq!(
+        fs::read_to_string("README.md").unwrap(),
+        "Read this file before running the project"
+    );
+    assert_eq!(repo.stash_list().unwrap().len(), 1);
+    assert_eq!(repo.stash_show(None, false).unwrap(), "M  README.md\n");
+    assert!(project_path.join(".flux/refs/stash").exists());
+    assert!(project_path.join(".flux/logs/refs/stash").exists());
+
+    let err = repo.stash_push(None).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::Stash(error::StashError::NoLocalChanges)
+    ));
+    println!("{err}");
+
+    fs::write("src/main.rs", "pub fn main() {}").unwrap();
+    repo.add(".").unwrap();
+    repo.commit("Change main".to_string()).unwrap();
+
+    let conflicts = repo.stash_pop(None, false).unwrap();
+    assert!(conflicts.is_empty());
+    assert_eq!(fs::read_to_string("README.md").unwrap(), "Work in progress");
+    assert_eq!(
+        fs::read_to_string("src/main.rs").unwrap(),
+        "pub fn main() {}"
+    );
+    assert!(repo.stash_list().unwrap().is_empty());
+    assert!(!project_path.join(".flux/refs/stash").exists());
+
+    let err = repo.stash_drop(None).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::Stash(error::StashError::Empty)
+    ));
+    println!("{err}");
+}
+
+#[test]
+#[serial]
+fn stash_conflict() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+
+    let mut repo = Repository::init(None, false).unwrap();
+    repo.set("user_name".to_string(), "test".to_string())
+        .unwrap();
+    repo.set("user_email".to_string(), "test@gmail.com".to_string())
+        .unwrap();
+    repo.add(".").unwrap();
+    repo.commit("Initial commit".to_string()).unwrap();
+
+    fs::write("README.md", "Stashed line\n").unwrap();
+    repo.stash_push(Some("readme".to_string())).unwrap();
+    assert_eq!(
+        repo.stash_list().unwrap(),
+        vec!["stash@{0}: On main: readme"]
+    );
+
+    fs::write("README.md", "Committed line\n").unwrap();
+    repo.add(".").unwrap();
+    repo.commit("Change README".to_string()).unwrap();
+
+    let conflicts = repo.stash_pop(Some("stash@{0}"), false).unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].path, "README.md");
+    assert_eq!(
+        fs::read_to_string("README.md").unwrap(),
+        "<<<<<<< Updated upstream\nCommitted line\n=======\nStashed line\n>>>>>>> Stashed changes\n"
+    );
+    assert_eq!(repo.stash_list().unwrap().len(), 1);
+}
+
+#[test]
+#[serial]
+fn reflog() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+
+    let mut repo = Repository::init(None, false).unwrap();
+    repo.set("user_name".to_string(), "test".to_string())
+        .unwrap();
+    repo.set("user_email".to_string(), "test@gmail.com".to_string())
+        .unwrap();
+    repo.add(".").unwrap();
+    let first = repo.commit("First commit".to_string()).unwrap();
+
+    repo.new_branch("feature").unwrap();
+    fs::write("README.md", "Feature work").unwrap();
+    repo.add(".").unwrap();
+    let second = repo.commit("Feature commit".to_string()).unwrap();
+
+    repo.switch_branch("main", false).unwrap();
+    repo.delete_branch("feature").unwrap();
+
+    let head_log = repo.reflog(None).unwrap();
+    assert_eq!(head_log.len(), 4);
+    assert!(head_log[0].ends_with("HEAD@{0}: checkout: moving from feature to main"));
+    assert!(head_log[1].ends_with("HEAD@{1}: commit: Feature commit"));
+    assert!(head_log[3].ends_with("HEAD@{3}: commit (initial): First commit"));
+
+    let feature_log = repo.reflog(Some("feature")).unwrap();
+    assert_eq!(feature_log.len(), 3);
+    assert!(feature_log[0].ends_with("feature@{0}: branch: deleted feature"));
+
+    let log_line = fs::read_to_string(".flux/logs/refs/heads/main").unwrap();
+    assert!(log_line.starts_with(&format!(
+        "{} {} test <test@gmail.com> ",
+        "0".repeat(40),
+        first
+    )));
+
+    assert_eq!(repo.resolve_revision("HEAD@{1}").unwrap(), second);
+    assert_eq!(repo.resolve_revision("feature@{1}").unwrap(), second);
+    assert_eq!(repo.resolve_revision("HEAD@{1}~1").unwrap(), first);
+    assert_eq!(repo.resolve_revision(&second[..8]).unwrap(), second);
+    assert_eq!(repo.resolve_revision("main").unwrap(), first);
+
+    let err = repo.resolve_revision("HEAD@{10}").unwrap_err();
+    assert!(matches!(err, error::RepositoryError::UnknownRevision(..)));
+    println!("{err}");
+
+    assert_eq!(repo.expire_reflogs(i64::MAX).unwrap(), 8);
+    assert!(repo.reflog(None).unwrap().is_empty());
+}
+
+#[test]
+#[serial]
+fn prune_unreachable_objects() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+
+    let mut repo = Repository::init(None, false).unwrap();
+    repo.set("user_name".to_string(), "test".to_string())
+        .unwrap();
+    repo.set("user_email".to_string(), "test@gmail.com".to_string())
+        .unwrap();
+    repo.add(".").unwrap();
+    let commit = repo.commit("First commit".to_string()).unwrap();
+
+    fs::write("README.md", "Staged and replaced").unwrap();
+    repo.add("README.md").unwrap();
+    let replaced = repo.hash_object("README.md".to_string(), false).unwrap();
+    fs::write("README.md", "Staged").unwrap();
+    repo.add("README.md").unwrap();
+    let staged = repo.hash_object("README.md".to_string(), false).unwrap();
+
+    let temp_file = project_path.join(".flux/objects/ab/cdef.tmp");
+    fs::create_dir_all(temp_file.parent().unwrap()).unwrap();
+    fs::write(&temp_file, "interrupted write").unwrap();
+
+    let report = repo.prune(Duration::ZERO, true).unwrap();
+    assert_eq!(report.objects, vec![replaced.clone()]);
+    assert_eq!(report.temp_files, 1);
+    assert!(repo.object_store.exists(&replaced));
+    assert!(temp_file.exists());
+
+    let report = repo.gc(Duration::from_secs(3600), Duration::ZERO).unwrap();
+    assert_eq!(report.prune.objects, vec![replaced.clone()]);
+    assert_eq!(report.prune.temp_files, 1);
+    assert!(report.prune.bytes > 0);
+    assert!(!repo.object_store.exists(&replaced));
+    assert!(!temp_file.exists());
+
+    assert!(repo.object_store.exists(&commit));
+    assert!(repo.object_store.exists(&staged));
+    assert_eq!(repo.reflog(None).unwrap().len(), 1);
+}
+
+#[test]
+#[serial]
+fn fsck() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+
+    let mut repo = Repository::init(None, false).unwrap();
+    repo.set("user_name".to_string(), "test".to_string())
+        .unwrap();
+    repo.set("user_email".to_string(), "test@gmail.com".to_string())
+        .unwrap();
+    repo.add(".").unwrap();
+    repo.commit("First commit".to_string()).unwrap();
+
+    let report = repo.fsck(true).unwrap();
+    assert!(report.is_ok(), "{:?}", report.problems);
+    assert!(report.dangling.is_empty());
+    assert!(report.checked > 0);
+
+    fs::write("notes.txt", "Never committed").unwrap();
+    let dangling = repo.hash_object("notes.txt".to_string(), true).unwrap();
+    let report = repo.fsck(false).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.dangling.len(), 1);
+    assert_eq!(report.dangling[0].0, dangling);
+
+    let readme = repo.hash_object("README.md".to_string(), false).unwrap();
+    let readme_path = repo.object_store.object_path(&readme);
+    let lib = repo.hash_object("src/lib.rs".to_string(), false).unwrap();
+    fs::copy(repo.object_store.object_path(&lib), &readme_path).unwrap();
+    let main = repo.hash_object("src/main.rs".to_string(), false).unwrap();
+    fs::remove_file(repo.object_store.object_path(&main)).unwrap();
+
+    let report = repo.fsck(false).unwrap();
+    assert!(!report.is_ok());
+    let problems: Vec<String> = report.problems.iter().map(|p| p.to_string()).collect();
+    assert!(problems.contains(&format!("error: object {readme} hashes to {lib}")));
+    assert!(
+        problems
+            .iter()
+            .any(|p| p.starts_with(&format!("missing object {main} referenced by tree ")))
+    );
+}
+
+fn commit_file(repo: &mut Repository, path: &str, content: &str, message: &str) -> String {
+    fs::write(path, content).unwrap();
+    repo.add(".").unwrap();
+    repo.commit(message.to_string()).unwrap()
+}
+
+fn setup_rebase_repo() -> Repository {
+    let mut repo = Repository::init(None, false).unwrap();
+    repo.set("user_name".to_string(), "test".to_string())
+        .unwrap();
+    repo.set("user_email".to_string(), "test@gmail.com".to_string())
+        .unwrap();
+    repo.add(".").unwrap();
+    repo.commit("Initial commit".to_string()).unwrap();
+    repo
+}
+
+#[test]
+#[serial]
+fn rebase() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    repo.new_branch("feature").unwrap();
+    let first = commit_file(&mut repo, "src/lib.rs", "feature lib", "Change lib");
+    commit_file(&mut repo, "notes.txt", "notes", "Add notes");
+
+    repo.switch_branch("main", false).unwrap();
+    let main = commit_file(&mut repo, "README.md", "main readme", "Change readme");
+
+    repo.switch_branch("feature", false).unwrap();
+    let progress = repo.rebase("main", None, None).unwrap();
+    let Progress::Done(head) = progress else {
+        panic!("rebase should finish without conflicts");
+    };
+
+    assert_eq!(repo.resolve_revision("feature").unwrap(), head);
+    assert_eq!(repo.resolve_revision("HEAD~2").unwrap(), main);
+    assert_ne!(repo.resolve_revision("HEAD~1").unwrap(), first);
+    assert_eq!(fs::read_to_string("README.md").unwrap(), "main readme");
+    assert_eq!(fs::read_to_string("src/lib.rs").unwrap(), "feature lib");
+    assert_eq!(fs::read_to_string("notes.txt").unwrap(), "notes");
+    assert!(!project_path.join(".flux/rebase-merge").exists());
+
+    let original = repo.object_store.retrieve_commit(&first).unwrap();
+    let replayed = repo
+        .object_store
+        .retrieve_commit(&repo.resolve_revision("HEAD~1").unwrap())
+        .unwrap();
+    assert_eq!(replayed.author(), original.author());
+    assert_eq!(replayed.message(), "Change lib");
+
+    assert!(matches!(
+        repo.rebase("main", None, None).unwrap(),
+        Progress::UpToDate
+    ));
+}
+
+#[test]
+#[serial]
+fn rebase_conflict() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    repo.new_branch("feature").unwrap();
+    let feature = commit_file(&mut repo, "README.md", "feature\n", "Feature readme");
+    repo.switch_branch("main", false).unwrap();
+    let main = commit_file(&mut repo, "README.md", "main\n", "Main readme");
+    repo.switch_branch("feature", false).unwrap();
+
+    let progress = repo.rebase("main", None, None).unwrap();
+    let Progress::Stopped { step, conflicts } = progress else {
+        panic!("rebase should stop on the conflict");
+    };
+    assert_eq!(step.commit, feature);
+    assert_eq!(conflicts.len(), 1);
+    assert!(
+        fs::read_to_string("README.md")
+            .unwrap()
+            .contains("<<<<<<< HEAD")
+    );
+
+    let err = repo.rebase("main", None, None).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::Sequencer(error::SequencerError::InProgress(..))
+    ));
+
+    repo.sequence_abort(Operation::Rebase).unwrap();
+    assert_eq!(repo.resolve_revision("HEAD").unwrap(), feature);
+    assert_eq!(fs::read_to_string("README.md").unwrap(), "feature\n");
+    assert!(!project_path.join(".flux/rebase-merge").exists());
+
+    repo.rebase("main", None, None).unwrap();
+    let err = repo.sequence_continue(Operation::Rebase).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::Sequencer(error::SequencerError::UnresolvedConflicts { .. })
+    ));
+    println!("{err}");
+
+    fs::write("README.md", "main and feature\n").unwrap();
+    repo.add("README.md").unwrap();
+    let Progress::Done(head) = repo.sequence_continue(Operation::Rebase).unwrap() else {
+        panic!("rebase should finish after resolving the conflict");
+    };
+    assert_eq!(repo.resolve_revision("HEAD^").unwrap(), main);
+    assert_eq!(repo.resolve_revision("HEAD").unwrap(), head);
+    assert_eq!(
+        fs::read_to_string("README.md").unwrap(),
+        "main and feature\n"
+    );
+}
+
+#[test]
+#[serial]
+fn rebase_todo() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+    let base = repo.resolve_revision("HEAD").unwrap();
+
+    let a = commit_file(&mut repo, "a.txt", "a", "Add a");
+    let b = commit_file(&mut repo, "b.txt", "b", "Add b");
+    let c = commit_file(&mut repo, "c.txt", "c", "Add c");
+    let d = commit_file(&mut repo, "d.txt", "d", "Add d");
+
+    let todo = format!(
+        "# todo\npick {}\nsquash {} Add b\ndrop {}\nreword {} Add d with a new message\n",
+        &a[..7],
+        &b[..7],
+        c,
+        d
+    );
+    let Progress::Done(_) = repo.rebase(&base, None, Some(&todo)).unwrap() else {
+        panic!("rebase should finish");
+    };
+
+    let head = repo
+        .object_store
+        .retrieve_commit(&repo.resolve_revision("HEAD").unwrap())
+        .unwrap();
+    assert_eq!(head.message(), "Add d with a new message");
+    let squashed = repo
+        .object_store
+        .retrieve_commit(&repo.resolve_revision("HEAD~1").unwrap())
+        .unwrap();
+    assert_eq!(squashed.message(), "Add a\n\nAdd b");
+    assert_eq!(squashed.parent_hash(), Some(base.as_str()));
+
+    assert!(project_path.join("a.txt").exists());
+    assert!(project_path.join("b.txt").exists());
+    assert!(!project_path.join("c.txt").exists());
+    assert!(project_path.join("d.txt").exists());
+}
+
+#[test]
+#[serial]
+fn cherry_pick_and_revert() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    repo.new_branch("release").unwrap();
+    repo.switch_branch("main", false).unwrap();
+    let fix = commit_file(&mut repo, "src/lib.rs", "fixed lib", "Fix lib");
+    let readme = commit_file(&mut repo, "README.md", "main\n", "Main readme");
+
+    repo.switch_branch("release", false).unwrap();
+    let Progress::Done(picked) = repo.cherry_pick(&[fix[..7].to_string()]).unwrap() else {
+        panic!("cherry-pick should apply cleanly");
+    };
+    let picked = repo.object_store.retrieve_commit(&picked).unwrap();
+    assert_eq!(
+        picked.message(),
+        format!("Fix lib\n\n(cherry picked from commit {fix})\n")
+    );
+    assert_eq!(fs::read_to_string("src/lib.rs").unwrap(), "fixed lib");
+    assert_eq!(
+        fs::read_to_string("README.md").unwrap(),
+        "Read this file before running the project"
+    );
+
+    let Progress::Done(reverted) = repo.revert(&["HEAD".to_string()]).unwrap() else {
+        panic!("revert should apply cleanly");
+    };
+    let reverted = repo.object_store.retrieve_commit(&reverted).unwrap();
+    assert!(reverted.message().starts_with("Revert \"Fix lib"));
+    assert_eq!(
+        fs::read_to_string("src/lib.rs").unwrap(),
+        "pub fn add(a: i32, b: i32) -> i64 { a + b }"
+    );
+
+    commit_file(&mut repo, "README.md", "release\n", "Release readme");
+    let head = repo.resolve_revision("HEAD").unwrap();
+    let Progress::Stopped { conflicts, .. } =
+        repo.cherry_pick(std::slice::from_ref(&readme)).unwrap()
+    else {
+        panic!("cherry-pick should stop on the conflict");
+    };
+    assert_eq!(conflicts[0].path, "README.md");
+
+    let err = repo.revert(std::slice::from_ref(&readme)).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::Sequencer(error::SequencerError::InProgress("cherry-pick"))
+    ));
+    let err = repo.sequence_continue(Operation::Revert).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::Sequencer(error::SequencerError::NotInProgress("revert"))
+    ));
+
+    repo.sequence_abort(Operation::CherryPick).unwrap();
+    assert_eq!(repo.resolve_revision("HEAD").unwrap(), head);
+    assert_eq!(fs::read_to_string("README.md").unwrap(), "release\n");
+
+    repo.cherry_pick(&[readme]).unwrap();
+    fs::write("README.md", "release and main\n").unwrap();
+    repo.add("README.md").unwrap();
+    let Progress::Done(resolved) = repo.sequence_continue(Operation::CherryPick).unwrap() else {
+        panic!("cherry-pick should finish after resolving the conflict");
+    };
+    assert_eq!(repo.resolve_revision("HEAD^").unwrap(), head);
+    assert_eq!(repo.resolve_revision("HEAD").unwrap(), resolved);
+    assert!(!project_path.join(".flux/sequencer").exists());
+}
+
+#[test]
+#[serial]
+fn stage_hunks() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    let original: String = (1..=12).map(|i| format!("line {i}\n")).collect();
+    commit_file(&mut repo, "notes.txt", &original, "Add notes");
+
+    let changed = original
+        .replace("line 2\n", "line two\n")
+        .replace("line 11\n", "line eleven\n");
+    fs::write("notes.txt", &changed).unwrap();
+    fs::write("README.md", "unstaged readme").unwrap();
+
+    let mut paths = repo.unstaged_paths(".").unwrap();
+    paths.sort();
+    assert_eq!(paths, vec!["README.md", "notes.txt"]);
+
+    let hunks = repo.file_hunks("notes.txt").unwrap();
+    assert_eq!(hunks.len(), 2);
+
+    let err = repo.stage_hunks("notes.txt", &[2]).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::InvalidHunk { count: 2, .. }
+    ));
+
+    repo.stage_hunks("notes.txt", &[1]).unwrap();
+    let remaining = repo.file_hunks("notes.txt").unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].old_range, hunks[0].old_range);
+
+    let head = repo.commit("Rename line 11".to_string()).unwrap();
+    let tree = repo.object_store.commit_to_map(head).unwrap();
+    let notes = repo.object_store.blob_content(&tree["notes.txt"]).unwrap();
+    assert_eq!(
+        String::from_utf8(notes).unwrap(),
+        original.replace("line 11\n", "line eleven\n")
+    );
+    assert!(tree.contains_key("src/main.rs"));
+    assert_eq!(fs::read_to_string("notes.txt").unwrap(), changed);
+
+    let err = repo.file_hunks("missing.txt").unwrap_err();
+    assert!(matches!(err, error::RepositoryError::PathNotFound(..)));
+}
+
+#[test]
+#[serial]
+fn remove_files() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+    commit_file(&mut repo, "notes.txt", "notes", "Add notes");
+
+    let err = repo.remove("src", false, false).unwrap_err();
+    assert!(matches!(err, error::RepositoryError::RecursiveRemove(..)));
+    let err = repo.remove("missing.txt", false, false).unwrap_err();
+    assert!(matches!(err, error::RepositoryError::PathNotFound(..)));
+
+    fs::write("README.md", "local edit").unwrap();
+    let err = repo.remove("README.md", false, false).unwrap_err();
+    assert!(matches!(
+        err,
+        error::RepositoryError::LocalChangesOverwritten { .. }
+    ));
+
+    assert_eq!(
+        repo.remove("./README.md", true, false).unwrap(),
+        vec!["README.md"]
+    );
+    assert!(project_path.join("README.md").exists());
+    assert!(!repo.index.map.contains_key("README.md"));
+    assert!(repo.index.map.contains_key("src/main.rs"));
+
+    assert_eq!(
+        repo.remove("src/", false, true).unwrap(),
+        vec!["src/lib.rs", "src/main.rs"]
+    );
+    assert!(!project_path.join("src").exists());
+
+    let head = repo.commit("Remove files".to_string()).unwrap();
+    let tree = repo.object_store.commit_to_map(head).unwrap();
+    assert_eq!(tree.keys().collect::<Vec<_>>(), vec!["notes.txt"]);
+}
+
+#[test]
+#[serial]
+fn remove_after_partial_add() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    // Adding one path keeps the other tracked files staged.
+    fs::write("notes.txt", "notes").unwrap();
+    repo.add("notes.txt").unwrap();
+    assert_eq!(
+        repo.remove("src/lib.rs", false, false).unwrap(),
+        vec!["src/lib.rs"]
+    );
+
+    let head = repo.commit("Add notes, remove lib".to_string()).unwrap();
+    let tree = repo.object_store.commit_to_map(head).unwrap();
+    let mut paths: Vec<_> = tree.keys().cloned().collect();
+    paths.sort();
+    assert_eq!(paths, vec!["README.md", "notes.txt", "src/main.rs"]);
+}
+
+#[test]
+#[serial]
+fn move_files() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    let moved = repo.move_path("README.md", "docs/README.md").unwrap();
+    assert_eq!(
+        moved,
+        vec![("README.md".to_string(), "docs/README.md".to_string())]
+    );
+    assert!(!project_path.join("README.md").exists());
+    assert!(project_path.join("docs/README.md").exists());
+
+    let moved = repo.move_path("src", "docs").unwrap();
+    assert_eq!(
+        moved,
+        vec![
+            ("src/lib.rs".to_string(), "docs/src/lib.rs".to_string()),
+            ("src/main.rs".to_string(), "docs/src/main.rs".to_string()),
+        ]
+    );
+    assert!(!project_path.join("src").exists());
+
+    fs::write("notes.txt", "untracked").unwrap();
+    let err = repo.move_path("docs/README.md", "notes.txt").unwrap_err();
+    assert!(matches!(err, error::RepositoryError::PathExists(..)));
+    let err = repo.move_path("docs", "docs/inner").unwrap_err();
+    assert!(matches!(err, error::RepositoryError::MoveIntoItself { .. }));
+
+    let head = repo.commit("Move files".to_string()).unwrap();
+    let tree = repo.object_store.commit_to_map(head).unwrap();
+    let mut paths: Vec<&String> = tree.keys().collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec!["docs/README.md", "docs/src/lib.rs", "docs/src/main.rs"]
+    );
+}
+
+#[test]
+#[serial]
+fn rename_detection() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    let notes: String = (1..=10).map(|i| format!("note {i}\n")).collect();
+    let added = commit_file(&mut repo, "notes.txt", &notes, "Add notes");
+
+    repo.new_branch("feature").unwrap();
+    repo.move_path("notes.txt", "docs/notes.txt").unwrap();
+    let staged = repo.diff(&[], true, Some(Default::default())).unwrap();
+    assert_eq!(staged.len(), 1);
+    assert_eq!(staged[0].status, ChangeStatus::Renamed(100));
+    repo.status().unwrap();
+    let moved = repo.commit("Move notes".to_string()).unwrap();
+
+    let revisions = [added.clone(), moved];
+    let changes = repo.diff(&revisions, false, None).unwrap();
+    let statuses: Vec<ChangeStatus> = changes.iter().map(|c| c.status).collect();
+    assert_eq!(statuses, vec![ChangeStatus::Added, ChangeStatus::Deleted]);
+
+    fs::write("docs/notes.txt", notes.replace("note 10\n", "last note\n")).unwrap();
+    let changes = repo
+        .diff(
+            std::slice::from_ref(&added),
+            false,
+            Some(Default::default()),
+        )
+        .unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].status, ChangeStatus::Renamed(90));
+    assert_eq!(changes[0].old_path.as_deref(), Some("notes.txt"));
+    let patch = repo.diff_patch(&changes).unwrap();
+    assert!(patch.contains("rename from notes.txt\nrename to docs/notes.txt\n"));
+    assert!(patch.contains("+last note\n"));
+    repo.add(".").unwrap();
+    repo.commit("Edit notes".to_string()).unwrap();
+    let options = LogOptions {
+        follow: Some("docs/notes.txt".to_string()),
+        ..Default::default()
+    };
+    repo.log(None, &options).unwrap();
+
+    repo.switch_branch("main", false).unwrap();
+    let fix = commit_file(
+        &mut repo,
+        "notes.txt",
+        &notes.replace("note 1\n", "first note\n"),
+        "Fix first note",
+    );
+
+    repo.switch_branch("feature", false).unwrap();
+    let Progress::Done(_) = repo.cherry_pick(&[fix]).unwrap() else {
+        panic!("the change should follow the rename");
+    };
+    assert!(!project_path.join("notes.txt").exists());
+    let merged = fs::read_to_string("docs/notes.txt").unwrap();
+    assert!(merged.starts_with("first note\n"));
+    assert!(merged.ends_with("last note\n"));
+}
+
+#[test]
+#[serial]
+fn signed_commits() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    let (key_path, public) = repo.generate_signing_key(None).unwrap();
+    assert!(key_path.ends_with(".flux/signing_key"));
+    assert!(public.starts_with("ssh-ed25519 "));
+    assert!(repo.generate_signing_key(None).is_err());
+
+    let (_, unsigned) = repo.verify_commit("HEAD").unwrap();
+    assert_eq!(unsigned, Verification::Unsigned);
+
+    fs::write("README.md", "signed").unwrap();
+    repo.add(".").unwrap();
+    let options = CommitOptions {
+        sign: Some(true),
+        ..Default::default()
+    };
+    let signed = repo
+        .commit_with(Some("Signed".to_string()), &options)
+        .unwrap();
+    let (hash, verification) = repo.verify_commit("HEAD").unwrap();
+    assert_eq!(hash, signed);
+    assert!(verification.is_good(), "{verification}");
+
+    repo.set("sign_commits".to_string(), "true".to_string())
+        .unwrap();
+    commit_file(
+        &mut repo,
+        "README.md",
+        "signed by config",
+        "Signed by config",
+    );
+    assert!(repo.verify_commit("HEAD").unwrap().1.is_good());
+
+    let options = LogOptions {
+        show_signature: true,
+        ..Default::default()
+    };
+    repo.log(None, &options).unwrap();
+    assert!(repo.fsck(true).unwrap().is_ok());
+
+    let nobody = AllowedSigners::default();
+    let known = HashSet::from([repo.resolve_revision("HEAD~2").unwrap()]);
+    let new_commits = repo.verify_new_commits(&known, &nobody).unwrap();
+    assert_eq!(new_commits.len(), 2);
+    assert!(
+        new_commits
+            .iter()
+            .all(|(_, v)| v.is_valid() && !v.is_good())
+    );
+}
+
+#[test]
+#[serial]
+fn author_and_committer() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    fs::write("README.md", "authored").unwrap();
+    repo.add(".").unwrap();
+    let options = CommitOptions {
+        author: Some("Jane Doe <jane@example.com>".to_string()),
+        date: Some("2023-11-14 20:13:20 -0200".to_string()),
+        ..Default::default()
+    };
+    let hash = repo
+        .commit_with(Some("Authored".to_string()), &options)
+        .unwrap();
+
+    let commit = repo.object_store.retrieve_commit(&hash).unwrap();
+    let author = commit.author_signature().unwrap();
+    assert_eq!(author.name, "Jane Doe");
+    assert_eq!(author.email, "jane@example.com");
+    assert_eq!(author.time.timestamp(), 1_700_000_000);
+    assert_eq!(author.time.format("%z").to_string(), "-0200");
+    let committer = commit.committer_signature().unwrap();
+    assert_eq!(committer.email, "test@gmail.com");
+
+    let invalid = CommitOptions {
+        author: Some("jane@example.com".to_string()),
+        ..Default::default()
+    };
+    fs::write("README.md", "invalid").unwrap();
+    repo.add(".").unwrap();
+    assert!(matches!(
+        repo.commit_with(Some("Invalid".to_string()), &invalid),
+        Err(error::RepositoryError::InvalidIdentity(_))
+    ));
+    let invalid = CommitOptions {
+        date: Some("yesterday".to_string()),
+        ..Default::default()
+    };
+    assert!(matches!(
+        repo.commit_with(Some("Invalid".to_string()), &invalid),
+        Err(error::RepositoryError::InvalidDate(_))
+    ));
+
+    // SAFETY: the tests touching the environment run serially.
+    unsafe {
+        env::set_var("FLUX_AUTHOR_NAME", "Env Author");
+        env::set_var("FLUX_AUTHOR_EMAIL", "author@example.com");
+        env::set_var("FLUX_COMMITTER_NAME", "Env Committer");
+        env::set_var("FLUX_COMMITTER_DATE", "@1600000000");
+    }
+    let hash = repo.commit("From env".to_string());
+    unsafe {
+        for var in [
+            "FLUX_AUTHOR_NAME",
+            "FLUX_AUTHOR_EMAIL",
+            "FLUX_COMMITTER_NAME",
+            "FLUX_COMMITTER_DATE",
+        ] {
+            env::remove_var(var);
+        }
+    }
+
+    let commit = repo.object_store.retrieve_commit(&hash.unwrap()).unwrap();
+    let author = commit.author_signature().unwrap();
+    assert_eq!(
+        (author.name.as_str(), author.email.as_str()),
+        ("Env Author", "author@example.com")
+    );
+    let committer = commit.committer_signature().unwrap();
+    assert_eq!(committer.name, "Env Committer");
+    assert_eq!(committer.email, "test@gmail.com");
+    assert_eq!(committer.time.timestamp(), 1_600_000_000);
+
+    let options = LogOptions {
+        date: Some(DateZone::Utc),
+        ..Default::default()
+    };
+    repo.log(None, &options).unwrap();
+}
+
+#[test]
+#[serial]
+fn amend_and_empty_commits() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+    let initial = repo.resolve_revision("HEAD").unwrap();
+
+    assert!(matches!(
+        repo.commit("Nothing".to_string()),
+        Err(error::RepositoryError::IndexEmpty)
+    ));
+    let allow_empty = CommitOptions {
+        allow_empty: true,
+        ..Default::default()
+    };
+    let empty = repo
+        .commit_with(Some("Empty".to_string()), &allow_empty)
+        .unwrap();
+    let empty_commit = repo.object_store.retrieve_commit(&empty).unwrap();
+    let initial_commit = repo.object_store.retrieve_commit(&initial).unwrap();
+    assert_eq!(empty_commit.tree_hash, initial_commit.tree_hash);
+    assert!(matches!(
+        repo.commit_with(Some("  \n".to_string()), &allow_empty),
+        Err(error::RepositoryError::EmptyMessage)
+    ));
+
+    let author = CommitOptions {
+        author: Some("Jane Doe <jane@example.com>".to_string()),
+        ..Default::default()
+    };
+    fs::write("README.md", "first draft").unwrap();
+    repo.add(".").unwrap();
+    let draft = repo
+        .commit_with(Some("Draft".to_string()), &author)
+        .unwrap();
+
+    fs::write("README.md", "final").unwrap();
+    repo.add(".").unwrap();
+    let amend = CommitOptions {
+        amend: true,
+        ..Default::default()
+    };
+    let amended = repo.commit_with(None, &amend).unwrap();
+    assert_ne!(amended, draft);
+    let commit = repo.object_store.retrieve_commit(&amended).unwrap();
+    assert_eq!(commit.message(), "Draft");
+    assert_eq!(commit.parents(), [empty.as_str()]);
+    assert_eq!(commit.author_signature().unwrap().name, "Jane Doe");
+    assert_eq!(commit.committer_signature().unwrap().name, "test");
+    let draft = repo.object_store.retrieve_commit(&draft).unwrap();
+    assert_ne!(commit.tree_hash, draft.tree_hash);
+
+    let template = repo.commit_template(true).unwrap();
+    assert!(template.starts_with("Draft\n\n# Please enter the commit message"));
+    assert!(template.contains("#\tmodified:   README.md\n"));
+
+    // SAFETY: the tests touching the environment run serially.
+    unsafe {
+        env::set_var("FLUX_EDITOR", "printf 'Final\\n# comment\\n' >");
+    }
+    let message = repo.edit_commit_message(true);
+    unsafe {
+        env::remove_var("FLUX_EDITOR");
+    }
+    let reworded = repo.commit_with(Some(message.unwrap()), &amend).unwrap();
+    let commit = repo.object_store.retrieve_commit(&reworded).unwrap();
+    assert_eq!(commit.message(), "Final");
+    assert_eq!(commit.parents(), [empty.as_str()]);
+    assert!(repo.reflog(None).unwrap()[0].ends_with("HEAD@{0}: commit (amend): Final"));
+}
+
+fn install_hook(name: &str, script: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = Path::new(".flux/hooks").join(name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, format!("#!/bin/sh\n{script}\n")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+#[serial]
+fn hooks() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+    let mut repo = setup_rebase_repo();
+
+    install_hook("pre-commit", "test ! -f .flux/block");
+    install_hook(
+        "commit-msg",
+        "grep -q '^[A-Z]' \"$1\" && printf '\\n\\nChecked' >> \"$1\"",
+    );
+    install_hook(
+        "post-checkout",
+        "echo \"$1 $2 $3\" > \"$FLUX_DIR/checkout\"",
+    );
+
+    fs::write(".flux/block", "").unwrap();
+    fs::write("README.md", "hooked").unwrap();
+    repo.add(".").unwrap();
+    assert!(matches!(
+        repo.commit("Blocked".to_string()),
+        Err(error::RepositoryError::Hook(_))
+    ));
+    fs::remove_file(".flux/block").unwrap();
+
+    assert!(matches!(
+        repo.commit("lowercase".to_string()),
+        Err(error::RepositoryError::Hook(_))
+    ));
+    let skipped = CommitOptions {
+        no_verify: true,
+        ..Default::default()
+    };
+    let first = repo
+        .commit_with(Some("lowercase".to_string()), &skipped)
+        .unwrap();
+    let commit = repo.object_store.retrieve_commit(&first).unwrap();
+    assert_eq!(commit.message(), "lowercase");
+
+    let second = commit_file(&mut repo, "README.md", "checked", "Capitalized");
+    let commit = repo.object_store.retrieve_commit(&second).unwrap();
+    assert_eq!(commit.message(), "Capitalized\n\nChecked");
+
+    repo.new_branch("feature").unwrap();
+    repo.switch_branch("main", false).unwrap();
+    assert_eq!(
+        fs::read_to_string(".flux/checkout").unwrap(),
+        format!("{second} {second} 1\n")
+    );
+
+    install_hook("pre-rebase", "exit 1");
+    assert!(matches!(
+        repo.rebase("feature", None, None),
+        Err(error::RepositoryError::Hook(_))
+    ));
+
+    // The switch is done by the time post-checkout runs, its failure is only a warning.
+    install_hook("post-checkout", "exit 1");
+    repo.switch_branch("feature", false).unwrap();
+    let notes = commit_file(&mut repo, "notes.txt", "notes", "Add notes");
+    repo.switch_branch("main", false).unwrap();
+    assert_eq!(repo.refs.head_commit().unwrap(), second);
+
+    install_hook("pre-merge-commit", "test ! -f .flux/block");
+    fs::write(".flux/block", "").unwrap();
+    assert!(matches!(
+        repo.cherry_pick(std::slice::from_ref(&notes)),
+        Err(error::RepositoryError::Hook(_))
+    ));
+    assert_eq!(repo.refs.head_commit().unwrap(), second);
+    fs::remove_file(".flux/block").unwrap();
+    let Progress::Done(picked) = repo.sequence_continue(Operation::CherryPick).unwrap() else {
+        panic!("the stopped cherry-pick should continue once the hook passes");
+    };
+    let picked = repo.object_store.retrieve_commit(&picked).unwrap();
+    assert_eq!(picked.parents(), [second.as_str()]);
+    assert_eq!(fs::read_to_string("notes.txt").unwrap(), "notes");
+}
+
+#[test]
+#[serial]
+fn browse_revisions() {
+    let (_temp, project_path) = common::setup_test_project();
+    let _guard = common::WorkingDirGuard::new(&project_path).unwrap();
+
+    let mut repo = setup_rebase_repo();
+    let first = repo.refs.head_commit().unwrap();
+    let second = commit_file(&mut repo, "src/lib.rs", "pub fn add() {}", "Change lib");
+
+    let history = repo.history("HEAD", 10).unwrap();
+    let hashes: Vec<&str> = history.iter().map(|(hash, _)| hash.as_str()).collect();
+    assert_eq!(hashes, [second.as_str(), first.as_str()]);
+    assert_eq!(history[0].1.message(), "Change lib");
+    assert_eq!(repo.history(&second, 1).unwrap().len(), 1);
+
+    let (_, root) = repo.tree_at("HEAD", "").unwrap();
+    let mut names: Vec<String> = root.iter().map(|entry| entry.name.clone()).collect();
+    names.sort();
+    assert_eq!(names, ["README.md", "src"]);
+    assert!(root.iter().any(|entry| entry.name == "src" && entry.is_dir()));
+
+    let (_, src) = repo.tree_at(&first, "./src/").unwrap();
+    assert_eq!(src.len(), 2);
+
+    let (_, lib) = repo.blob_at("HEAD", "src/lib.rs").unwrap();
+    assert_eq!(lib, b"pub fn add() {}");
+    let (_, lib) = repo.blob_at("HEAD~1", "src/lib.rs").unwrap();
+    assert_eq!(lib, b"pub fn add(a: i32, b: i32) -> i64 { a + b }");
+
+    assert!(matches!(
+        repo.tree_at("HEAD", "README.md"),
+        Err(error::RepositoryError::NotADirectory(_))
+    ));
+    assert!(matches!(
+        repo.blob_at("HEAD", "src"),
+        Err(error::RepositoryError::NotAFile(_))
+    ));
+    assert!(matches!(
+        repo.blob_at("HEAD", "src/missing.rs"),
+        Err(error::RepositoryError::PathNotInRevision { .. })
+    ));
+    assert!(matches!(
+        repo.blob_at("HEAD", "README.md/inner"),
+        Err(error::RepositoryError::PathNotInRevision { .. })
+    ));
+}
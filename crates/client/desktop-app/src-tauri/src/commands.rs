@@ -1,4 +1,4 @@
-use crate::{AppState, models::{BranchInfo, RepositoryInfo}};
+use crate::{AppState, models::{BranchInfo, HunkInfo, RepositoryInfo}};
 use flux_core::{error::{ConfigError, RefsError}, internals::repository::Repository};
 use tauri::State;
 
@@ -38,8 +38,8 @@ pub fn update_user_config(
         .as_mut()
         .ok_or_else(|| "No repository open".to_string())?;
     
-    repo.config.set("user_name".to_string(), user_name).map_err(|e: ConfigError| e.to_string())?;
-    repo.config.set("user_email".to_string(), user_email).map_err(|e: ConfigError| e.to_string())?;
+    repo.config.set("user.name".to_string(), user_name).map_err(|e: ConfigError| e.to_string())?;
+    repo.config.set("user.email".to_string(), user_email).map_err(|e: ConfigError| e.to_string())?;
     
     Ok(())
 }
@@ -51,7 +51,7 @@ pub fn update_origin(origin: String, state: State<AppState>) -> Result<(), Strin
         .as_mut()
         .ok_or_else(|| "No repository open".to_string())?;
     
-    repo.config.set("origin".to_string(), origin).map_err(|e: ConfigError| e.to_string())?;
+    repo.config.set("remote.origin.url".to_string(), origin).map_err(|e: ConfigError| e.to_string())?;
     
     Ok(())
 }
@@ -76,4 +76,39 @@ pub fn get_branches(state: State<AppState>) -> Result<Vec<BranchInfo>, String> {
     
     branches.sort_by(|a, b| a.name.cmp(&b.name));
     Ok(branches)
-}
\ No newline at end of file
+}
+
+#[tauri::command]
+pub fn get_unstaged_files(path: String, state: State<AppState>) -> Result<Vec<String>, String> {
+    let repo_lock = state.repository.lock().unwrap();
+    let repo = repo_lock
+        .as_ref()
+        .ok_or_else(|| "No repository open".to_string())?;
+
+    repo.unstaged_paths(&path).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_file_hunks(path: String, state: State<AppState>) -> Result<Vec<HunkInfo>, String> {
+    let repo_lock = state.repository.lock().unwrap();
+    let repo = repo_lock
+        .as_ref()
+        .ok_or_else(|| "No repository open".to_string())?;
+
+    let hunks = repo.file_hunks(&path).map_err(|e| e.to_string())?;
+    Ok(hunks
+        .iter()
+        .enumerate()
+        .map(|(index, hunk)| HunkInfo::from_hunk(index, hunk))
+        .collect())
+}
+
+#[tauri::command]
+pub fn stage_hunks(path: String, hunks: Vec<usize>, state: State<AppState>) -> Result<(), String> {
+    let mut repo_lock = state.repository.lock().unwrap();
+    let repo = repo_lock
+        .as_mut()
+        .ok_or_else(|| "No repository open".to_string())?;
+
+    repo.stage_hunks(&path, &hunks).map_err(|e| e.to_string())
+}
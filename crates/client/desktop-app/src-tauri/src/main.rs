@@ -25,6 +25,9 @@ fn main() {
             commands::update_user_config,
             commands::update_origin,
             commands::get_branches,
+            commands::get_unstaged_files,
+            commands::get_file_hunks,
+            commands::stage_hunks,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
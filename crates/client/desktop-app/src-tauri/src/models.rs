@@ -1,5 +1,6 @@
 use flux_core::internals::repository::Repository;
 use flux_core::error::ConfigError;
+use flux_core::internals::diff::{Hunk, LineKind};
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -31,11 +32,11 @@ impl RepositoryInfo {
         
         branches.sort_by(|a, b| a.name.cmp(&b.name));
         
-        let user_name = repo.config.get("user_name")
+        let user_name = repo.config.get("user.name")
             .map_err(|e: ConfigError| e.to_string())?;
-        let user_email = repo.config.get("user_email")
+        let user_email = repo.config.get("user.email")
             .map_err(|e: ConfigError| e.to_string())?;
-        let origin = repo.config.get("origin")
+        let origin = repo.config.get("remote.origin.url")
             .map_err(|e: ConfigError| e.to_string())?;
         
         Ok(Self {
@@ -55,4 +56,41 @@ impl RepositoryInfo {
 pub struct BranchInfo {
     pub name: String,
     pub is_current: bool,
-}
\ No newline at end of file
+}
+#[derive(Serialize)]
+pub struct HunkInfo {
+    pub index: usize,
+    pub header: String,
+    pub lines: Vec<HunkLineInfo>,
+}
+
+#[derive(Serialize)]
+pub struct HunkLineInfo {
+    /// One of "context", "removed" or "added".
+    pub kind: String,
+    pub text: String,
+}
+
+impl HunkInfo {
+    pub fn from_hunk(index: usize, hunk: &Hunk) -> Self {
+        let lines = hunk
+            .lines
+            .iter()
+            .map(|line| HunkLineInfo {
+                kind: match line.kind {
+                    LineKind::Context => "context",
+                    LineKind::Removed => "removed",
+                    LineKind::Added => "added",
+                }
+                .to_string(),
+                text: line.text.trim_end_matches('\n').to_string(),
+            })
+            .collect();
+
+        Self {
+            index,
+            header: hunk.header(),
+            lines,
+        }
+    }
+}
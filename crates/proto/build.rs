@@ -1,10 +1,18 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=schemas/models.proto");
 
-    tonic_build::configure()
+    let mut builder = tonic_build::configure()
         .build_client(true)
-        .build_server(true)
-        .compile_protos(&["schemas/models.proto"], &["schemas"])?;
+        .build_server(true);
+
+    // The HTTP gateway of the server reads and writes the messages as JSON.
+    if std::env::var_os("CARGO_FEATURE_SERDE").is_some() {
+        builder = builder
+            .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+            .message_attribute(".", "#[serde(default)]");
+    }
+
+    builder.compile_protos(&["schemas/models.proto"], &["schemas"])?;
 
     Ok(())
 }
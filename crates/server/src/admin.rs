@@ -0,0 +1,78 @@
+use crate::cli::{SecretArgs, UserCommand};
+use crate::error::UserStoreError;
+use crate::repo_store::RepoStore;
+use crate::user_store::{Secret, UserStore};
+use std::error::Error;
+use std::{fs, io};
+
+/// Runs a `flux_server user` command against the user store.
+pub fn run(
+    command: UserCommand,
+    user_store: &mut UserStore,
+    repositories: &RepoStore,
+) -> Result<(), Box<dyn Error>> {
+    match command {
+        UserCommand::Add {
+            name,
+            email,
+            secret,
+            admin,
+        } => {
+            if repositories.organizations.contains_key(&name) {
+                return Err(UserStoreError::UserNameTaken(name).into());
+            }
+            let credential = read_secret(secret)?.into_credential(&email)?;
+            user_store.add_user(name, email.clone(), credential, admin)?;
+            println!("Added {email}.");
+        }
+        UserCommand::Disable { email } => {
+            user_store.set_disabled(&email, true)?;
+            println!("Disabled {email}.");
+        }
+        UserCommand::Enable { email } => {
+            user_store.set_disabled(&email, false)?;
+            println!("Enabled {email}.");
+        }
+        UserCommand::SetSecret { email, secret } => {
+            let credential = read_secret(secret)?.into_credential(&email)?;
+            user_store.set_credential(&email, credential)?;
+            println!("Changed the secret of {email}.");
+        }
+        UserCommand::Revoke { email, id } => {
+            let revoked = user_store.revoke_tokens(&email, id.as_deref())?;
+            println!("Revoked {revoked} token(s) of {email}.");
+        }
+        UserCommand::List => {
+            let mut users: Vec<_> = user_store.map.iter().collect();
+            users.sort_by_key(|(email, _)| email.as_str());
+            for (email, entry) in users {
+                let login = match (&entry.password_hash, &entry.public_key) {
+                    (Some(_), _) => "password",
+                    (_, Some(_)) => "key",
+                    _ => "none",
+                };
+                let state = match (entry.disabled, entry.admin) {
+                    (true, _) => "disabled",
+                    (false, true) => "admin",
+                    (false, false) => "enabled",
+                };
+                println!("{email}\t{}\t{login}\t{state}", entry.user_name);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn read_secret(args: SecretArgs) -> Result<Secret, Box<dyn Error>> {
+    if let Some(path) = args.key {
+        return Ok(Secret::PublicKey(fs::read_to_string(path)?));
+    }
+
+    let mut password = String::new();
+    io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.is_empty() {
+        return Err("The password cannot be empty.".into());
+    }
+    Ok(Secret::Password(password))
+}
@@ -0,0 +1,262 @@
+use std::sync::Arc;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{self, HeaderMap};
+use tonic::codegen::{BoxFuture, Context, Poll, Service};
+use tonic::{Request, Status};
+use tower_layer::Layer;
+
+use crate::tokens::{Claims, Scope, TokenIssuer};
+use crate::user_store::SharedUserStore;
+
+/// Authenticates every request to the server once, before it reaches a service.
+///
+/// A request with an `authorization: Bearer <token>` header gets the claims of the token in
+/// its extensions, read them with `identity` or `require`. Requests without the header pass
+/// through anonymously, an invalid, expired or revoked token is refused right away.
+#[derive(Debug, Clone)]
+pub struct AuthLayer {
+    tokens: Arc<TokenIssuer>,
+    user_store: SharedUserStore,
+}
+
+impl AuthLayer {
+    pub fn new(tokens: Arc<TokenIssuer>, user_store: SharedUserStore) -> Self {
+        Self { tokens, user_store }
+    }
+
+    /// Claims of the bearer token in `headers`, `None` when there is no `authorization`
+    /// header.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Option<Claims>, Status> {
+        match bearer_token(headers)? {
+            Some(token) => Ok(Some(
+                self.tokens.authenticate(&self.user_store, &token).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Authenticate<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Authenticate {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Authenticate<S> {
+    inner: S,
+    layer: AuthLayer,
+}
+
+impl<S> Service<http::Request<BoxBody>> for Authenticate<S>
+where
+    S: Service<http::Request<BoxBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<BoxBody>) -> Self::Future {
+        // The clone may not be ready, so the ready service is used and the clone kept.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            match layer.authenticate(request.headers()).await {
+                Ok(Some(claims)) => {
+                    request.extensions_mut().insert(claims);
+                }
+                Ok(None) => {}
+                Err(status) => return Ok(status.into_http()),
+            }
+            inner.call(request).await
+        })
+    }
+}
+
+/// Token of the `authorization` header, `None` when the header is missing.
+fn bearer_token(headers: &HeaderMap) -> Result<Option<String>, Status> {
+    let Some(value) = headers.get(http::header::AUTHORIZATION) else {
+        return Ok(None);
+    };
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|token| Some(token.trim().to_string()))
+        .ok_or_else(|| {
+            Status::unauthenticated("Invalid authorization header, expected a bearer token")
+        })
+}
+
+/// Claims of the token the request was authenticated with, `None` for anonymous requests.
+pub fn identity<T>(request: &Request<T>) -> Option<&Claims> {
+    request.extensions().get::<Claims>()
+}
+
+/// Claims of an authenticated request, anonymous requests are refused.
+pub fn authenticated<T>(request: &Request<T>) -> Result<Claims, Status> {
+    identity(request)
+        .cloned()
+        .ok_or_else(|| Status::unauthenticated("Missing access token, run flux auth first."))
+}
+
+/// Claims of an authenticated request whose token allows `scope`.
+pub fn require<T>(request: &Request<T>, scope: Scope) -> Result<Claims, Status> {
+    let claims = authenticated(request)?;
+    if !claims.allows(scope) {
+        return Err(Status::permission_denied(format!(
+            "The access token does not have the {scope} scope."
+        )));
+    }
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_store::{Credential, UserStore};
+    use std::convert::Infallible;
+    use std::time::Duration;
+
+    /// Answers with the name in the claims the layer put into the request, if any.
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<http::Request<BoxBody>> for Echo {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = BoxFuture<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<BoxBody>) -> Self::Future {
+            let name = request
+                .extensions()
+                .get::<Claims>()
+                .map(|claims| claims.name.clone());
+            Box::pin(async move {
+                let mut response = http::Response::new(tonic::body::empty_body());
+                if let Some(name) = name {
+                    response
+                        .headers_mut()
+                        .insert("x-name", name.parse().unwrap());
+                }
+                Ok(response)
+            })
+        }
+    }
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        layer: AuthLayer,
+        user_store: SharedUserStore,
+        /// A token of alice.
+        token: String,
+    }
+
+    fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.json").to_string_lossy().to_string();
+        let mut store = UserStore::new(Some(path)).unwrap();
+        let password = Credential::PasswordHash("hash".to_string());
+        store
+            .add_user("alice".into(), "alice@x".into(), password, false)
+            .unwrap();
+        let tokens = Arc::new(TokenIssuer::new(
+            "secret".to_string(),
+            Duration::from_secs(60),
+        ));
+        let (token, claims) = tokens
+            .issue("alice", "alice@x", Scope::DEFAULT.to_vec())
+            .unwrap();
+        store.add_token(&claims).unwrap();
+        let user_store = SharedUserStore::new(store);
+
+        Fixture {
+            _dir: dir,
+            layer: AuthLayer::new(tokens, user_store.clone()),
+            user_store,
+            token,
+        }
+    }
+
+    async fn call(layer: &AuthLayer, authorization: Option<&str>) -> http::Response<BoxBody> {
+        let mut request = http::Request::new(tonic::body::empty_body());
+        if let Some(value) = authorization {
+            request
+                .headers_mut()
+                .insert(http::header::AUTHORIZATION, value.parse().unwrap());
+        }
+        layer.layer(Echo).call(request).await.unwrap()
+    }
+
+    /// Name the inner service saw and the status the layer refused the request with.
+    fn outcome(response: &http::Response<BoxBody>) -> (Option<&str>, Option<tonic::Code>) {
+        let name = response
+            .headers()
+            .get("x-name")
+            .map(|v| v.to_str().unwrap());
+        let code = Status::from_header_map(response.headers()).map(|status| status.code());
+        (name, code)
+    }
+
+    #[tokio::test]
+    async fn requests_without_a_token_pass_anonymously() {
+        let fixture = fixture();
+        let response = call(&fixture.layer, None).await;
+        assert_eq!(outcome(&response), (None, None));
+    }
+
+    #[tokio::test]
+    async fn the_claims_of_the_token_reach_the_service() {
+        let fixture = fixture();
+        let bearer = format!("Bearer {}", fixture.token);
+        let response = call(&fixture.layer, Some(&bearer)).await;
+        assert_eq!(outcome(&response), (Some("alice"), None));
+    }
+
+    #[tokio::test]
+    async fn invalid_tokens_are_refused_before_the_service() {
+        let fixture = fixture();
+        let (head, _) = fixture.token.rsplit_once('.').unwrap();
+        let forged = format!("Bearer {head}.c2lnbmF0dXJl");
+        for authorization in [forged.as_str(), "Basic YWxpY2U6cHc="] {
+            let response = call(&fixture.layer, Some(authorization)).await;
+            assert_eq!(
+                outcome(&response),
+                (None, Some(tonic::Code::Unauthenticated))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn tokens_of_disabled_accounts_are_refused() {
+        let fixture = fixture();
+        let bearer = format!("Bearer {}", fixture.token);
+        fixture
+            .user_store
+            .lock()
+            .await
+            .set_disabled("alice@x", true)
+            .unwrap();
+        let response = call(&fixture.layer, Some(&bearer)).await;
+        assert_eq!(
+            outcome(&response),
+            (None, Some(tonic::Code::Unauthenticated))
+        );
+    }
+}
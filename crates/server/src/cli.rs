@@ -1,17 +1,129 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use std::net::IpAddr;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 pub struct Cli {
-    #[arg(short = 'p', long = "port", default_value_t = 50051)]
-    pub port: usize,
+    #[command(subcommand)]
+    pub command: Option<Command>,
 
-    #[arg(long = "upload-root", default_value_t = String::from("uploads"))]
-    pub upload_root: String,
+    /// TOML file with the server settings, the options below override it
+    #[arg(long = "config", value_name = "path")]
+    pub config: Option<String>,
 
-    #[arg(long = "secret", short = 's')]
-    pub secret: String,
+    /// Address to listen on [default: ::1]
+    #[arg(long = "bind", value_name = "address")]
+    pub bind: Option<IpAddr>,
+
+    /// Port of the gRPC server [default: 50051]
+    #[arg(short = 'p', long = "port")]
+    pub port: Option<u16>,
+
+    /// Directory the pushed repositories are stored in [default: uploads]
+    #[arg(long = "upload-root")]
+    pub upload_root: Option<String>,
+
+    /// Key signing the access tokens, visible to other users in `ps`, prefer --secret-file
+    #[arg(long = "secret", short = 's', conflicts_with = "secret_file")]
+    pub secret: Option<String>,
+
+    /// File with the key signing the access tokens
+    #[arg(long = "secret-file", value_name = "path")]
+    pub secret_file: Option<String>,
+
+    /// PEM certificate chain to serve TLS with
+    #[arg(long = "tls-cert", value_name = "path", requires = "tls_key")]
+    pub tls_cert: Option<String>,
+
+    /// PEM private key of the TLS certificate
+    #[arg(long = "tls-key", value_name = "path", requires = "tls_cert")]
+    pub tls_key: Option<String>,
 
     #[arg(long = "user-store-path")]
-    pub user_store_path: Option<String>
+    pub user_store_path: Option<String>,
+
+    /// Lifetime of issued access tokens in hours [default: 720]
+    #[arg(long = "token-ttl", value_name = "hours")]
+    pub token_ttl: Option<u64>,
+
+    /// Run fsck on pushed repositories and reject the ones that fail
+    #[arg(long = "fsck-objects")]
+    pub fsck_objects: bool,
+
+    /// Use strict fsck checks on pushed repositories (implies --fsck-objects)
+    #[arg(long = "fsck-strict")]
+    pub fsck_strict: bool,
+
+    /// JSON file with the per repository policies, e.g. required commit signatures
+    #[arg(long = "policy-path")]
+    pub policy_path: Option<String>,
+
+    /// JSON file with the organizations and the repository metadata
+    #[arg(long = "repo-store-path")]
+    pub repo_store_path: Option<String>,
+
+    /// Also serve the HTTP/JSON gateway and the HTML pages on this port
+    #[arg(long = "http-port", value_name = "port")]
+    pub http_port: Option<u16>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage the accounts in the user store instead of serving
+    ///
+    /// A running server picks up the changes the next time it uses the store. A change the
+    /// server saves at the same moment, like a token issued by a login, can be lost.
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommand {
+    /// Create an account that logs in with a password or an SSH key
+    Add {
+        name: String,
+        email: String,
+
+        #[command(flatten)]
+        secret: SecretArgs,
+
+        /// Allow the user to request tokens with the admin scope
+        #[arg(long)]
+        admin: bool,
+    },
+
+    /// Disable an account, its tokens stop working and it can no longer log in
+    Disable { email: String },
+
+    /// Enable a disabled account again
+    Enable { email: String },
+
+    /// Replace the password or key of an account, the way to give one to an account
+    /// created before logging in required it
+    SetSecret {
+        email: String,
+
+        #[command(flatten)]
+        secret: SecretArgs,
+    },
+
+    /// Revoke a token of a user, or all of them without an id
+    Revoke { email: String, id: Option<String> },
+
+    /// List every account
+    List,
+}
+
+#[derive(Args, Debug)]
+#[group(required = true, multiple = false)]
+pub struct SecretArgs {
+    /// Read the password from the first line of stdin
+    #[arg(long = "password-stdin")]
+    pub password_stdin: bool,
+
+    /// OpenSSH public key file the user signs login challenges with
+    #[arg(long = "key", value_name = "path")]
+    pub key: Option<String>,
 }
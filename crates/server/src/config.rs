@@ -0,0 +1,357 @@
+use crate::cli::Cli;
+use crate::error::ServerConfigError;
+use serde::Deserialize;
+use std::fs::{self, OpenOptions};
+use std::net::{IpAddr, Ipv6Addr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+pub type Result<T> = std::result::Result<T, ServerConfigError>;
+
+/// Settings of the server, read from the TOML file of `--config`. Options given on the
+/// command line take precedence over the file, e.g.
+///
+/// ```toml
+/// bind = "0.0.0.0"
+/// port = 50051
+/// http_port = 8080
+/// upload_root = "/var/lib/flux/uploads"
+/// user_store_path = "/var/lib/flux/users.json"
+/// secret_file = "/etc/flux/secret"
+///
+/// [tls]
+/// cert = "/etc/flux/cert.pem"
+/// key = "/etc/flux/key.pem"
+///
+/// [limits]
+/// max_push_size = 104857600
+/// request_timeout = 300
+///
+/// [log]
+/// level = "info"
+/// file = "/var/log/flux/server.log"
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Address both the gRPC server and the gateway listen on
+    pub bind: IpAddr,
+    pub port: u16,
+    pub http_port: Option<u16>,
+    pub upload_root: String,
+    pub user_store_path: Option<String>,
+    pub policy_path: Option<String>,
+    pub repo_store_path: Option<String>,
+    /// Key signing the access tokens, `secret_file` keeps it out of the config itself
+    pub secret: Option<String>,
+    pub secret_file: Option<String>,
+    /// Lifetime of issued access tokens in hours
+    pub token_ttl: u64,
+    pub fsck_objects: bool,
+    pub fsck_strict: bool,
+    /// Serve over TLS with this certificate, plain text without it
+    pub tls: Option<TlsConfig>,
+    pub limits: Limits,
+    pub log: LogConfig,
+}
+
+/// PEM files of the certificate chain and the private key of the server.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Largest repository archive a push may upload, in bytes
+    pub max_push_size: Option<u64>,
+    /// Requests served at once on a single connection
+    pub concurrency_per_connection: Option<usize>,
+    /// Seconds a gRPC request may take before it is cancelled
+    pub request_timeout: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// `error`, `warn`, `info`, `debug`, `trace` or `off`
+    pub level: String,
+    /// Appends to this file instead of writing to stderr
+    pub file: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V6(Ipv6Addr::LOCALHOST),
+            port: 50051,
+            http_port: None,
+            upload_root: String::from("uploads"),
+            user_store_path: None,
+            policy_path: None,
+            repo_store_path: None,
+            secret: None,
+            secret_file: None,
+            token_ttl: 720,
+            fsck_objects: false,
+            fsck_strict: false,
+            tls: None,
+            limits: Limits::default(),
+            log: LogConfig::default(),
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: String::from("info"),
+            file: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn open(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).map_err(|e| ServerConfigError::Open {
+            path: path.to_string(),
+            source: e,
+        })?;
+        toml::from_str(&content).map_err(|e| ServerConfigError::Parse {
+            path: path.to_string(),
+            source: e,
+        })
+    }
+
+    /// The file of `--config` (or the defaults), overridden by the other options of `cli`.
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let mut config = match &cli.config {
+            Some(path) => Self::open(path)?,
+            None => Self::default(),
+        };
+        if let Some(bind) = cli.bind {
+            config.bind = bind;
+        }
+        if let Some(port) = cli.port {
+            config.port = port;
+        }
+        if let Some(http_port) = cli.http_port {
+            config.http_port = Some(http_port);
+        }
+        if let Some(upload_root) = &cli.upload_root {
+            config.upload_root = upload_root.clone();
+        }
+        if let Some(path) = &cli.user_store_path {
+            config.user_store_path = Some(path.clone());
+        }
+        if let Some(path) = &cli.policy_path {
+            config.policy_path = Some(path.clone());
+        }
+        if let Some(path) = &cli.repo_store_path {
+            config.repo_store_path = Some(path.clone());
+        }
+        // Either kind of secret on the command line replaces both in the file.
+        if cli.secret.is_some() || cli.secret_file.is_some() {
+            config.secret = cli.secret.clone();
+            config.secret_file = cli.secret_file.clone();
+        }
+        if let Some(token_ttl) = cli.token_ttl {
+            config.token_ttl = token_ttl;
+        }
+        config.fsck_objects |= cli.fsck_objects;
+        config.fsck_strict |= cli.fsck_strict;
+        if let (Some(cert), Some(key)) = (&cli.tls_cert, &cli.tls_key) {
+            config.tls = Some(TlsConfig {
+                cert: cert.clone(),
+                key: key.clone(),
+            });
+        }
+        Ok(config)
+    }
+
+    /// The token signing key, read from `secret_file` when that is set.
+    pub fn secret(&self) -> Result<String> {
+        let secret = match (&self.secret, &self.secret_file) {
+            (Some(_), Some(_)) => return Err(ServerConfigError::ConflictingSecrets),
+            (Some(secret), None) => secret.clone(),
+            (None, Some(path)) => fs::read_to_string(path)
+                .map_err(|e| ServerConfigError::Open {
+                    path: path.clone(),
+                    source: e,
+                })?
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+            (None, None) => return Err(ServerConfigError::MissingSecret),
+        };
+        if secret.is_empty() {
+            return Err(ServerConfigError::MissingSecret);
+        }
+        Ok(secret)
+    }
+
+    pub fn token_ttl(&self) -> Duration {
+        Duration::from_secs(self.token_ttl * 60 * 60)
+    }
+}
+
+impl LogConfig {
+    /// Installs the global subscriber writing the events of the server.
+    pub fn init(&self) -> Result<()> {
+        let level: LevelFilter = self
+            .level
+            .parse()
+            .map_err(|_| ServerConfigError::LogLevel(self.level.clone()))?;
+        let builder = tracing_subscriber::fmt().with_max_level(level);
+        match &self.file {
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| ServerConfigError::Open {
+                        path: path.clone(),
+                        source: e,
+                    })?;
+                builder
+                    .with_ansi(false)
+                    .with_writer(BoxMakeWriter::new(std::sync::Mutex::new(file)))
+                    .init();
+            }
+            None => builder.with_writer(std::io::stderr).init(),
+        }
+        Ok(())
+    }
+}
+
+impl TlsConfig {
+    /// Contents of the certificate and the key files.
+    pub fn read(&self) -> Result<(Vec<u8>, Vec<u8>)> {
+        let read = |path: &String| {
+            fs::read(path).map_err(|e| ServerConfigError::Open {
+                path: path.clone(),
+                source: e,
+            })
+        };
+        Ok((read(&self.cert)?, read(&self.key)?))
+    }
+
+    /// Accepts the TLS connections of the gateway, offering HTTP/2 and HTTP/1.1.
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        let (cert, key) = self.read()?;
+        let chain = rustls_pemfile::certs(&mut cert.as_slice())
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| ServerConfigError::Tls(e.to_string()))?;
+        let key = rustls_pemfile::private_key(&mut key.as_slice())
+            .map_err(|e| ServerConfigError::Tls(e.to_string()))?
+            .ok_or_else(|| ServerConfigError::Tls(format!("No private key in '{}'.", self.key)))?;
+        let mut config = rustls::ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(chain, key)
+            .map_err(|e| ServerConfigError::Tls(e.to_string()))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::path::Path;
+
+    fn load(args: &[&str]) -> Result<ServerConfig> {
+        ServerConfig::load(&Cli::parse_from([&["flux_server"], args].concat()))
+    }
+
+    fn write(dir: &Path, name: &str, content: &str) -> String {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn command_line_overrides_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "server.toml",
+            "port = 1\nhttp_port = 2\nsecret = \"from the file\"\nfsck_objects = true\n\
+             [tls]\ncert = \"cert.pem\"\nkey = \"key.pem\"\n[log]\nlevel = \"debug\"\n",
+        );
+
+        let defaults = load(&[]).unwrap();
+        assert_eq!(defaults.port, 50051);
+        assert_eq!(defaults.bind, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert!(defaults.tls.is_none());
+
+        let config = load(&["--config", &path, "--port", "3", "--secret-file", "key"]).unwrap();
+        assert_eq!(config.port, 3);
+        assert_eq!(config.http_port, Some(2));
+        assert_eq!(config.secret, None);
+        assert_eq!(config.secret_file.as_deref(), Some("key"));
+        assert!(config.fsck_objects);
+        assert_eq!(config.tls.as_ref().unwrap().cert, "cert.pem");
+        assert_eq!(config.log.level, "debug");
+        assert_eq!(config.token_ttl(), Duration::from_secs(720 * 60 * 60));
+    }
+
+    #[test]
+    fn unknown_settings_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "server.toml", "prot = 1\n");
+        assert!(matches!(
+            load(&["--config", &path]),
+            Err(ServerConfigError::Parse { .. })
+        ));
+        let missing = dir
+            .path()
+            .join("missing.toml")
+            .to_string_lossy()
+            .to_string();
+        assert!(matches!(
+            load(&["--config", &missing]),
+            Err(ServerConfigError::Open { .. })
+        ));
+    }
+
+    #[test]
+    fn secret_from_the_config_or_its_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = write(dir.path(), "secret", "s3cret\r\n");
+        let empty = write(dir.path(), "empty", "\n");
+        let secret = |secret: Option<&str>, secret_file: Option<&str>| {
+            ServerConfig {
+                secret: secret.map(String::from),
+                secret_file: secret_file.map(String::from),
+                ..ServerConfig::default()
+            }
+            .secret()
+        };
+
+        assert_eq!(secret(Some("inline"), None).unwrap(), "inline");
+        assert_eq!(secret(None, Some(&file)).unwrap(), "s3cret");
+        assert!(matches!(
+            secret(Some("inline"), Some(&file)),
+            Err(ServerConfigError::ConflictingSecrets)
+        ));
+        assert!(matches!(
+            secret(None, None),
+            Err(ServerConfigError::MissingSecret)
+        ));
+        assert!(matches!(
+            secret(None, Some(&empty)),
+            Err(ServerConfigError::MissingSecret)
+        ));
+        assert!(matches!(
+            secret(None, Some(&format!("{file}.missing"))),
+            Err(ServerConfigError::Open { .. })
+        ));
+    }
+}
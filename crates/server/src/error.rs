@@ -11,4 +11,77 @@ pub enum UserStoreError {
 
     #[error("Email '{0}' is already registered on the server.")]
     EmailAlredyRegistered(String),
-}
\ No newline at end of file
+
+    #[error("The user name '{0}' is already taken.")]
+    UserNameTaken(String),
+
+    #[error("Invalid user name '{0}', it names the namespace of the user's repositories.")]
+    InvalidUserName(String),
+
+    #[error("No user with the email '{0}' is registered on the server.")]
+    UnknownUser(String),
+
+    #[error("The account of '{0}' is disabled.")]
+    Disabled(String),
+
+    #[error("Invalid credentials.")]
+    InvalidCredentials,
+
+    #[error("Invalid public key. {0}")]
+    PublicKey(String),
+
+    #[error("Failed to hash the password. {0}")]
+    PasswordHash(#[from] argon2::password_hash::Error),
+}
+#[derive(Debug, Error)]
+pub enum PolicyStoreError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum RepoStoreError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error(transparent)]
+    SerdeJson(#[from] serde_json::Error),
+
+    #[error("The namespace '{0}' is already taken.")]
+    NamespaceTaken(String),
+
+    #[error("No organization named '{0}' exists on the server.")]
+    UnknownOrganization(String),
+}
+
+#[derive(Debug, Error)]
+pub enum ServerConfigError {
+    #[error("Failed to open '{path}'. {source}")]
+    Open {
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Invalid server config '{path}'. {source}")]
+    Parse {
+        path: String,
+        #[source]
+        source: toml::de::Error,
+    },
+
+    #[error("A secret is required to serve, set it with --secret-file, --secret or in the config.")]
+    MissingSecret,
+
+    #[error("Only one of secret and secret_file can be set.")]
+    ConflictingSecrets,
+
+    #[error("Invalid TLS certificate or key. {0}")]
+    Tls(String),
+
+    #[error("Invalid log level '{0}'.")]
+    LogLevel(String),
+}
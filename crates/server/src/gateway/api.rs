@@ -0,0 +1,289 @@
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use futures::StreamExt;
+use proto::models::auth_serviec_server::AuthServiec;
+use proto::models::browse_service_server::BrowseService;
+use proto::models::clone_service_server::CloneService;
+use proto::models::repository_service_server::RepositoryService;
+use proto::models::{
+    ChallengeRequest, ChallengeResponse, CloneRequest, GetBlobRequest, IssueTokenResponse,
+    ListCommitsRequest, ListCommitsResponse, ListRefsRequest, ListRefsResponse,
+    ListRepositoriesRequest, ListRepositoriesResponse, ListTreeRequest, ListTreeResponse,
+    LoginRequest, RegisterRequest, RepositoryInfo, RepositoryRequest, RevokeRequest,
+    RevokeResponse, TokenRequest, TokenStatusResponse,
+};
+
+use super::{ApiError, Gateway, Identity, service_request};
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+/// `<namespace>/<name>`, the repository path the services take.
+fn repository_path(Path((namespace, name)): Path<(String, String)>) -> String {
+    format!("{namespace}/{name}")
+}
+
+pub(super) async fn register(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Json(request): Json<RegisterRequest>,
+) -> ApiResult<IssueTokenResponse> {
+    let request = service_request(&identity, request);
+    Ok(Json(
+        gateway.auth_service.register(request).await?.into_inner(),
+    ))
+}
+
+pub(super) async fn challenge(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Json(request): Json<ChallengeRequest>,
+) -> ApiResult<ChallengeResponse> {
+    let request = service_request(&identity, request);
+    Ok(Json(
+        gateway.auth_service.challenge(request).await?.into_inner(),
+    ))
+}
+
+pub(super) async fn login(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Json(request): Json<LoginRequest>,
+) -> ApiResult<IssueTokenResponse> {
+    let request = service_request(&identity, request);
+    Ok(Json(
+        gateway.auth_service.login(request).await?.into_inner(),
+    ))
+}
+
+pub(super) async fn refresh(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+) -> ApiResult<IssueTokenResponse> {
+    let request = service_request(&identity, TokenRequest {});
+    Ok(Json(
+        gateway.auth_service.refresh(request).await?.into_inner(),
+    ))
+}
+
+pub(super) async fn revoke(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Json(request): Json<RevokeRequest>,
+) -> ApiResult<RevokeResponse> {
+    let request = service_request(&identity, request);
+    Ok(Json(
+        gateway.auth_service.revoke(request).await?.into_inner(),
+    ))
+}
+
+pub(super) async fn token_status(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+) -> ApiResult<TokenStatusResponse> {
+    let request = service_request(&identity, TokenRequest {});
+    Ok(Json(
+        gateway
+            .auth_service
+            .token_status(request)
+            .await?
+            .into_inner(),
+    ))
+}
+
+/// `?namespace=`, the namespace of the caller when missing.
+pub(super) async fn list_repositories(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Query(request): Query<ListRepositoriesRequest>,
+) -> ApiResult<ListRepositoriesResponse> {
+    let request = service_request(&identity, request);
+    let response = gateway
+        .repository_service
+        .list_repositories(request)
+        .await?;
+    Ok(Json(response.into_inner()))
+}
+
+pub(super) async fn describe_repository(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    path: Path<(String, String)>,
+) -> ApiResult<RepositoryInfo> {
+    let request = RepositoryRequest {
+        repository: repository_path(path),
+    };
+    let request = service_request(&identity, request);
+    let response = gateway
+        .repository_service
+        .describe_repository(request)
+        .await?;
+    Ok(Json(response.into_inner()))
+}
+
+/// `?page_size=&page_token=`
+pub(super) async fn list_refs(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    path: Path<(String, String)>,
+    Query(mut request): Query<ListRefsRequest>,
+) -> ApiResult<ListRefsResponse> {
+    request.repository = repository_path(path);
+    let request = service_request(&identity, request);
+    Ok(Json(
+        gateway
+            .browse_service
+            .list_refs(request)
+            .await?
+            .into_inner(),
+    ))
+}
+
+/// `?revision=&page_size=&page_token=`
+pub(super) async fn list_commits(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    path: Path<(String, String)>,
+    Query(mut request): Query<ListCommitsRequest>,
+) -> ApiResult<ListCommitsResponse> {
+    request.repository = repository_path(path);
+    let request = service_request(&identity, request);
+    Ok(Json(
+        gateway
+            .browse_service
+            .list_commits(request)
+            .await?
+            .into_inner(),
+    ))
+}
+
+/// `?revision=&path=&page_size=&page_token=`
+pub(super) async fn list_tree(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    path: Path<(String, String)>,
+    Query(mut request): Query<ListTreeRequest>,
+) -> ApiResult<ListTreeResponse> {
+    request.repository = repository_path(path);
+    let request = service_request(&identity, request);
+    Ok(Json(
+        gateway
+            .browse_service
+            .list_tree(request)
+            .await?
+            .into_inner(),
+    ))
+}
+
+/// Content of a file, `?path=&revision=`, from `offset` to its end.
+pub(super) async fn raw(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    path: Path<(String, String)>,
+    Query(mut request): Query<GetBlobRequest>,
+) -> Result<Response, ApiError> {
+    request.repository = repository_path(path);
+
+    let blob = gateway
+        .browse_service
+        .read_blob(service_request(&identity, request))
+        .await?;
+    let headers = [
+        (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+        (header::ETAG, format!("\"{}\"", blob.hash)),
+    ];
+    Ok((headers, [("x-flux-commit", blob.commit)], blob.content).into_response())
+}
+
+/// The stored `.flux.tar.gz` of a repository, as `flux clone` receives it.
+pub(super) async fn archive(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    path: Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    let file_name = format!("{}.flux.tar.gz", path.1);
+    let request = CloneRequest {
+        name: repository_path(path),
+    };
+    let mut chunks = gateway
+        .clone_service
+        .clone_repository(service_request(&identity, request))
+        .await?
+        .into_inner();
+
+    // A missing archive is reported in the stream, before any content is sent.
+    let first = match chunks.next().await {
+        Some(chunk) => vec![Ok(chunk?)],
+        None => Vec::new(),
+    };
+    let body = futures::stream::iter(first)
+        .chain(chunks)
+        .map(|chunk| chunk.map(|chunk| chunk.content));
+
+    let headers = [
+        (header::CONTENT_TYPE, "application/gzip".to_string()),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{file_name}\""),
+        ),
+    ];
+    Ok((headers, Body::from_stream(body)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{fixture, get};
+    use axum::http::StatusCode;
+    use serde_json::Value;
+
+    #[tokio::test]
+    async fn private_repositories_are_described_to_their_members_only() {
+        let fixture = fixture().await;
+        let uri = "/api/repos/alice/project";
+
+        let (status, body) = get(&fixture.router, uri, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            body["error"],
+            "The repository is private, run flux auth first."
+        );
+
+        let (status, _) = get(&fixture.router, uri, Some(&fixture.bob)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, body) = get(&fixture.router, uri, Some(&fixture.alice)).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["name"], "project");
+        assert_eq!(body["visibility"], "private");
+    }
+
+    #[tokio::test]
+    async fn private_repositories_are_listed_to_their_members_only() {
+        let fixture = fixture().await;
+        let names = |body: &str| {
+            let body: Value = serde_json::from_str(body).unwrap();
+            let repositories = body["repositories"].as_array().cloned().unwrap_or_default();
+            repositories
+                .iter()
+                .map(|repository| repository["name"].as_str().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let uri = "/api/repos?namespace=alice";
+        let (status, body) = get(&fixture.router, uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(names(&body).is_empty());
+        let (_, body) = get(&fixture.router, uri, Some(&fixture.bob)).await;
+        assert!(names(&body).is_empty());
+
+        // Without a namespace, the caller's own repositories.
+        let (_, body) = get(&fixture.router, "/api/repos", Some(&fixture.alice)).await;
+        assert_eq!(names(&body), ["project"]);
+        let (status, _) = get(&fixture.router, "/api/repos", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
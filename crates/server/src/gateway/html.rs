@@ -0,0 +1,313 @@
+use axum::extract::{Path, Query, State};
+use axum::response::{Html, IntoResponse, Response};
+use chrono::{DateTime, FixedOffset};
+use proto::models::browse_service_server::BrowseService;
+use proto::models::repository_service_server::RepositoryService;
+use proto::models::{
+    GetBlobRequest, ListCommitsRequest, ListRepositoriesRequest, ListTreeRequest, Person,
+};
+use serde::Deserialize;
+use std::fmt::Write;
+use tonic::Status;
+
+use super::{Gateway, Identity, http_status, service_request};
+
+const COMMITS_PER_PAGE: u32 = 30;
+
+/// Where a page is in a repository, from its query string.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(super) struct Location {
+    revision: String,
+    path: String,
+    page_token: String,
+}
+
+/// A failed service call, shown as a page with the message of the service.
+pub(super) struct PageError(Status);
+
+impl From<Status> for PageError {
+    fn from(status: Status) -> Self {
+        Self(status)
+    }
+}
+
+impl IntoResponse for PageError {
+    fn into_response(self) -> Response {
+        let body = format!("<p>{}</p>", escape(self.0.message()));
+        (http_status(self.0.code()), page("Error", body)).into_response()
+    }
+}
+
+type PageResult = Result<Html<String>, PageError>;
+
+fn page(title: &str, body: String) -> Html<String> {
+    Html(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n\
+         <style>body {{ font-family: sans-serif; margin: 2em; }} \
+         td {{ padding: 0.2em 1em 0.2em 0; }} pre {{ background: #f6f6f6; padding: 1em; }}\
+         </style>\n</head>\n<body>\n<h1>{title}</h1>\n{body}\n</body>\n</html>\n",
+        title = escape(title)
+    ))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent-encodes a query string value.
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn link(href: &str, text: &str) -> String {
+    format!("<a href=\"{}\">{}</a>", escape(href), escape(text))
+}
+
+fn short(hash: &str) -> &str {
+    hash.get(..10).unwrap_or(hash)
+}
+
+fn date(person: &Person) -> String {
+    FixedOffset::east_opt(person.utc_offset)
+        .zip(DateTime::from_timestamp(person.time, 0))
+        .map(|(offset, time)| {
+            time.with_timezone(&offset)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_default()
+}
+
+/// Links to the files and the history of a repository, at `revision`.
+fn navigation(namespace: &str, name: &str, revision: &str) -> String {
+    let base = format!("/ui/{namespace}/{name}");
+    format!(
+        "<p>{} | {} | {}</p>\n",
+        link(&format!("/ui/{namespace}"), namespace),
+        link(&format!("{base}?revision={}", encode(revision)), "Files"),
+        link(
+            &format!("{base}/commits?revision={}", encode(revision)),
+            "Commits"
+        ),
+    )
+}
+
+pub(super) async fn repositories(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Path(namespace): Path<String>,
+) -> PageResult {
+    let request = ListRepositoriesRequest {
+        namespace: namespace.clone(),
+    };
+    let repositories = gateway
+        .repository_service
+        .list_repositories(service_request(&identity, request))
+        .await?
+        .into_inner()
+        .repositories;
+
+    let mut body = String::from("<table>\n");
+    for repository in &repositories {
+        let href = format!("/ui/{}/{}", repository.namespace, repository.name);
+        let _ = writeln!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            link(&href, &repository.name),
+            escape(&repository.visibility),
+            escape(&repository.description)
+        );
+    }
+    body.push_str("</table>");
+    if repositories.is_empty() {
+        body = "<p>No repositories.</p>".to_string();
+    }
+    Ok(page(&namespace, body))
+}
+
+/// The files of a directory, `?path=&revision=`.
+pub(super) async fn tree(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(location): Query<Location>,
+) -> PageResult {
+    let request = ListTreeRequest {
+        repository: format!("{namespace}/{name}"),
+        revision: location.revision.clone(),
+        path: location.path.clone(),
+        page_size: 0,
+        page_token: location.page_token,
+    };
+    let listing = gateway
+        .browse_service
+        .list_tree(service_request(&identity, request))
+        .await?
+        .into_inner();
+
+    let base = format!("/ui/{namespace}/{name}");
+    let revision = encode(&location.revision);
+    let mut body = navigation(&namespace, &name, &location.revision);
+    let _ = writeln!(
+        body,
+        "<p>{} at <code>{}</code></p>\n<table>",
+        escape(&format!("/{}", location.path.trim_matches('/'))),
+        short(&listing.commit)
+    );
+
+    let mut entries = listing.entries;
+    entries.sort_by_key(|entry| entry.kind != "tree");
+    for entry in entries {
+        let path = encode(&entry.path);
+        let href = match entry.kind.as_str() {
+            "tree" => format!("{base}?revision={revision}&path={path}"),
+            _ => format!("{base}/blob?revision={revision}&path={path}"),
+        };
+        let name = match entry.kind.as_str() {
+            "tree" => format!("{}/", entry.name),
+            _ => entry.name,
+        };
+        let _ = writeln!(body, "<tr><td>{}</td></tr>", link(&href, &name));
+    }
+    body.push_str("</table>");
+
+    if !listing.next_page_token.is_empty() {
+        let href = format!(
+            "{base}?revision={revision}&path={}&page_token={}",
+            encode(&location.path),
+            encode(&listing.next_page_token)
+        );
+        let _ = write!(body, "\n<p>{}</p>", link(&href, "More"));
+    }
+    Ok(page(&format!("{namespace}/{name}"), body))
+}
+
+/// The history of a revision, `?revision=`.
+pub(super) async fn commits(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(location): Query<Location>,
+) -> PageResult {
+    let request = ListCommitsRequest {
+        repository: format!("{namespace}/{name}"),
+        revision: location.revision.clone(),
+        page_size: COMMITS_PER_PAGE,
+        page_token: location.page_token,
+    };
+    let history = gateway
+        .browse_service
+        .list_commits(service_request(&identity, request))
+        .await?
+        .into_inner();
+
+    let base = format!("/ui/{namespace}/{name}");
+    let mut body = navigation(&namespace, &name, &location.revision);
+    body.push_str("<table>\n");
+    for commit in &history.commits {
+        let summary = commit.message.lines().next().unwrap_or_default();
+        let author = commit.author.clone().unwrap_or_default();
+        let _ = writeln!(
+            body,
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            link(
+                &format!("{base}?revision={}", commit.hash),
+                short(&commit.hash)
+            ),
+            escape(summary),
+            escape(&author.name),
+            date(&author)
+        );
+    }
+    body.push_str("</table>");
+
+    if !history.next_page_token.is_empty() {
+        let href = format!(
+            "{base}/commits?revision={}&page_token={}",
+            encode(&location.revision),
+            encode(&history.next_page_token)
+        );
+        let _ = write!(body, "\n<p>{}</p>", link(&href, "Older"));
+    }
+    Ok(page(&format!("{namespace}/{name}"), body))
+}
+
+/// The content of a file, `?path=&revision=`.
+pub(super) async fn blob(
+    State(gateway): State<Gateway>,
+    identity: Identity,
+    Path((namespace, name)): Path<(String, String)>,
+    Query(location): Query<Location>,
+) -> PageResult {
+    let request = GetBlobRequest {
+        repository: format!("{namespace}/{name}"),
+        revision: location.revision.clone(),
+        path: location.path.clone(),
+        offset: 0,
+        limit: 0,
+    };
+    let blob = gateway
+        .browse_service
+        .get_blob(service_request(&identity, request))
+        .await?
+        .into_inner();
+
+    let raw = format!(
+        "/api/repos/{namespace}/{name}/raw?revision={}&path={}",
+        encode(&blob.commit),
+        encode(&location.path)
+    );
+    let mut body = navigation(&namespace, &name, &location.revision);
+    let _ = writeln!(
+        body,
+        "<p>{} ({} bytes, {})</p>",
+        escape(&location.path),
+        blob.size,
+        link(&raw, "raw")
+    );
+    match std::str::from_utf8(&blob.content) {
+        Ok(text) if blob.next_offset == 0 => {
+            let _ = write!(body, "<pre>{}</pre>", escape(text));
+        }
+        Ok(_) => body.push_str("<p>The file is too large to be shown.</p>"),
+        Err(_) => body.push_str("<p>Binary file.</p>"),
+    }
+    Ok(page(&format!("{namespace}/{name}"), body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{fixture, get};
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn private_repositories_are_shown_to_their_members_only() {
+        let fixture = fixture().await;
+
+        let (status, body) = get(&fixture.router, "/ui/alice", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<p>No repositories.</p>"));
+        let (_, body) = get(&fixture.router, "/ui/alice", Some(&fixture.alice)).await;
+        assert!(body.contains("<a href=\"/ui/alice/project\">project</a>"));
+
+        let (status, body) = get(&fixture.router, "/ui/alice/project", None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert!(body.contains("<title>Error</title>"));
+        assert!(body.contains("The repository is private, run flux auth first."));
+        let uri = "/ui/alice/project/commits";
+        let (status, _) = get(&fixture.router, uri, Some(&fixture.bob)).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+    }
+}
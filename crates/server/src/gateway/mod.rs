@@ -0,0 +1,326 @@
+//! Optional HTTP/JSON front of the server, for tools that do not speak gRPC.
+//!
+//! Requests are authenticated like gRPC ones, with an `authorization: Bearer <token>` header,
+//! and handled by the same service implementations. Errors are a JSON object with the
+//! message of the service, under the HTTP status closest to its gRPC code.
+
+use axum::Router;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Extension, Json};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::service::TowerToHyperService;
+use std::io;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tonic::{Code, Status};
+
+use crate::auth_layer::AuthLayer;
+use crate::services::auth_service::FluxAuthService;
+use crate::services::browse_service::FluxBrowseService;
+use crate::services::clone_service::FluxCloneService;
+use crate::services::repository_service::FluxRepositoryService;
+use crate::tokens::Claims;
+
+mod api;
+mod html;
+
+#[derive(Debug, Clone)]
+pub struct Gateway {
+    auth: AuthLayer,
+    auth_service: Arc<FluxAuthService>,
+    clone_service: Arc<FluxCloneService>,
+    repository_service: Arc<FluxRepositoryService>,
+    browse_service: Arc<FluxBrowseService>,
+}
+
+impl Gateway {
+    pub fn new(
+        auth: AuthLayer,
+        auth_service: Arc<FluxAuthService>,
+        clone_service: Arc<FluxCloneService>,
+        repository_service: Arc<FluxRepositoryService>,
+        browse_service: Arc<FluxBrowseService>,
+    ) -> Self {
+        Self {
+            auth,
+            auth_service,
+            clone_service,
+            repository_service,
+            browse_service,
+        }
+    }
+
+    /// JSON endpoints under `/api` and read-only HTML pages under `/ui`.
+    pub fn router(self) -> Router {
+        Router::new()
+            .route("/api/auth/register", post(api::register))
+            .route("/api/auth/challenge", post(api::challenge))
+            .route("/api/auth/login", post(api::login))
+            .route("/api/auth/refresh", post(api::refresh))
+            .route("/api/auth/revoke", post(api::revoke))
+            .route("/api/auth/status", get(api::token_status))
+            .route("/api/repos", get(api::list_repositories))
+            .route("/api/repos/:namespace/:name", get(api::describe_repository))
+            .route("/api/repos/:namespace/:name/refs", get(api::list_refs))
+            .route(
+                "/api/repos/:namespace/:name/commits",
+                get(api::list_commits),
+            )
+            .route("/api/repos/:namespace/:name/tree", get(api::list_tree))
+            .route("/api/repos/:namespace/:name/raw", get(api::raw))
+            .route("/api/repos/:namespace/:name/archive", get(api::archive))
+            .route("/ui/:namespace", get(html::repositories))
+            .route("/ui/:namespace/:name", get(html::tree))
+            .route("/ui/:namespace/:name/commits", get(html::commits))
+            .route("/ui/:namespace/:name/blob", get(html::blob))
+            .layer(middleware::from_fn_with_state(self.clone(), authenticate))
+            .with_state(self)
+    }
+
+    /// Serves the routes on `listener`, over TLS when `tls` is set.
+    pub async fn serve(self, listener: TcpListener, tls: Option<TlsAcceptor>) -> io::Result<()> {
+        let router = self.router();
+        let Some(tls) = tls else {
+            return axum::serve(listener, router).await;
+        };
+        loop {
+            let (stream, peer) = listener.accept().await?;
+            let (tls, router) = (tls.clone(), router.clone());
+            tokio::spawn(async move {
+                // A failed handshake only drops its own connection.
+                let stream = match tls.accept(stream).await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        tracing::debug!(%peer, "TLS handshake failed: {}", e);
+                        return;
+                    }
+                };
+                let service = TowerToHyperService::new(router);
+                if let Err(e) = auto::Builder::new(TokioExecutor::new())
+                    .serve_connection(TokioIo::new(stream), service)
+                    .await
+                {
+                    tracing::debug!(%peer, "Gateway connection failed: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Puts the claims of the bearer token of a request in its extensions.
+async fn authenticate(
+    State(gateway): State<Gateway>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match gateway.auth.authenticate(request.headers()).await {
+        Ok(Some(claims)) => {
+            request.extensions_mut().insert(claims);
+        }
+        Ok(None) => {}
+        Err(status) => return ApiError(status).into_response(),
+    }
+    next.run(request).await
+}
+
+/// The caller of a gateway request, `None` when anonymous.
+type Identity = Option<Extension<Claims>>;
+
+/// A service request made on behalf of the caller of a gateway request.
+fn service_request<T>(identity: &Identity, message: T) -> tonic::Request<T> {
+    let mut request = tonic::Request::new(message);
+    if let Some(Extension(claims)) = identity {
+        request.extensions_mut().insert(claims.clone());
+    }
+    request
+}
+
+/// The HTTP status closest to a gRPC code.
+fn http_status(code: Code) -> StatusCode {
+    match code {
+        Code::Ok => StatusCode::OK,
+        Code::InvalidArgument | Code::OutOfRange => StatusCode::BAD_REQUEST,
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::FailedPrecondition => StatusCode::PRECONDITION_FAILED,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// A failed service call, answered with `{"error": <message>}`.
+#[derive(Debug)]
+pub struct ApiError(Status);
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> Self {
+        Self(status)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.0.message() });
+        (http_status(self.0.code()), Json(body)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy_store::PolicyStore;
+    use crate::repo_store::RepoStore;
+    use crate::tokens::{Scope, TokenIssuer};
+    use crate::user_store::{Credential, SharedUserStore, UserStore};
+    use axum::body::Body;
+    use axum::http::header;
+    use proto::models::CreateRepositoryRequest;
+    use proto::models::repository_service_server::RepositoryService;
+    use std::time::Duration;
+    use tokio::sync::Mutex;
+    use tower::ServiceExt;
+
+    pub(super) struct Fixture {
+        _dir: tempfile::TempDir,
+        pub(super) router: Router,
+        pub(super) alice: String,
+        pub(super) bob: String,
+    }
+
+    /// A gateway where alice owns the private repository `alice/project`, with a token of
+    /// alice and one of bob.
+    pub(super) async fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| Some(dir.path().join(name).to_string_lossy().to_string());
+        let tokens = Arc::new(TokenIssuer::new(
+            "secret".to_string(),
+            Duration::from_secs(60),
+        ));
+        let mut users = UserStore::new(path("users.json")).unwrap();
+        let mut issued = Vec::new();
+        for name in ["alice", "bob"] {
+            let email = format!("{name}@x");
+            let credential = Credential::PasswordHash("hash".into());
+            users
+                .add_user(name.into(), email.clone(), credential, false)
+                .unwrap();
+            let (token, claims) = tokens.issue(name, &email, Scope::DEFAULT.to_vec()).unwrap();
+            users.add_token(&claims).unwrap();
+            issued.push((token, claims));
+        }
+        let user_store = SharedUserStore::new(users);
+        let mut repositories = RepoStore::open(path("repo_store.json")).unwrap();
+        repositories.claim_namespace("alice", "alice@x").unwrap();
+        let repositories = Arc::new(Mutex::new(repositories));
+        let policies = Arc::new(Mutex::new(
+            PolicyStore::open(path("policies.json")).unwrap(),
+        ));
+
+        let upload_root = dir.path().join("repos").to_string_lossy().to_string();
+        let repository_service = Arc::new(FluxRepositoryService::new(
+            upload_root.clone(),
+            policies.clone(),
+            repositories.clone(),
+            user_store.clone(),
+        ));
+        let mut request = tonic::Request::new(CreateRepositoryRequest {
+            repository: "project".to_string(),
+            visibility: "private".to_string(),
+            ..Default::default()
+        });
+        request.extensions_mut().insert(issued[0].1.clone());
+        repository_service.create_repository(request).await.unwrap();
+
+        let gateway = Gateway::new(
+            AuthLayer::new(tokens.clone(), user_store.clone()),
+            Arc::new(
+                FluxAuthService::new(tokens, user_store).with_repositories(repositories.clone()),
+            ),
+            Arc::new(
+                FluxCloneService::new(upload_root.clone())
+                    .with_policies(policies.clone())
+                    .with_repositories(repositories.clone()),
+            ),
+            repository_service,
+            Arc::new(FluxBrowseService::new(upload_root, policies, repositories)),
+        );
+        let [(alice, _), (bob, _)] = <[_; 2]>::try_from(issued).unwrap();
+        Fixture {
+            _dir: dir,
+            router: gateway.router(),
+            alice,
+            bob,
+        }
+    }
+
+    /// Status and body of a GET of `uri`, with `token` as its bearer token.
+    pub(super) async fn get(
+        router: &Router,
+        uri: &str,
+        token: Option<&str>,
+    ) -> (StatusCode, String) {
+        let mut request = Request::get(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let response = router
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn grpc_codes_map_to_the_closest_http_status() {
+        let expected = [
+            (Code::Ok, StatusCode::OK),
+            (Code::InvalidArgument, StatusCode::BAD_REQUEST),
+            (Code::OutOfRange, StatusCode::BAD_REQUEST),
+            (Code::Unauthenticated, StatusCode::UNAUTHORIZED),
+            (Code::PermissionDenied, StatusCode::FORBIDDEN),
+            (Code::NotFound, StatusCode::NOT_FOUND),
+            (Code::AlreadyExists, StatusCode::CONFLICT),
+            (Code::Aborted, StatusCode::CONFLICT),
+            (Code::FailedPrecondition, StatusCode::PRECONDITION_FAILED),
+            (Code::ResourceExhausted, StatusCode::TOO_MANY_REQUESTS),
+            (Code::Unimplemented, StatusCode::NOT_IMPLEMENTED),
+            (Code::Unavailable, StatusCode::SERVICE_UNAVAILABLE),
+            (Code::DeadlineExceeded, StatusCode::GATEWAY_TIMEOUT),
+            (Code::Internal, StatusCode::INTERNAL_SERVER_ERROR),
+            (Code::Unknown, StatusCode::INTERNAL_SERVER_ERROR),
+            (Code::DataLoss, StatusCode::INTERNAL_SERVER_ERROR),
+        ];
+        for (code, status) in expected {
+            assert_eq!(http_status(code), status, "{code:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_tokens_are_refused_before_the_handlers() {
+        let fixture = fixture().await;
+        let forged = format!("{}x", fixture.alice);
+
+        for uri in ["/api/repos/alice/project", "/ui/alice"] {
+            let (status, body) = get(&fixture.router, uri, Some(&forged)).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{uri}");
+            let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+            assert!(body["error"].is_string(), "{uri}");
+        }
+    }
+}
@@ -1,51 +1,159 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
-use crate::cli::Cli;
+use std::time::Duration;
+use crate::auth_layer::AuthLayer;
+use crate::cli::{Cli, Command};
+use crate::config::{ServerConfig, TlsConfig};
+use crate::gateway::Gateway;
+use crate::services::access_service::FluxAccessService;
+use crate::services::browse_service::FluxBrowseService;
 use crate::services::auth_service::FluxAuthService;
 use crate::services::clone_service::FluxCloneService;
+use crate::policy_store::PolicyStore;
 use crate::services::push_service::FluxPushService;
-use crate::user_store::UserStore;
+use crate::services::repository_service::FluxRepositoryService;
+use crate::repo_store::RepoStore;
+use crate::tokens::TokenIssuer;
+use crate::user_store::{SharedUserStore, UserStore};
 use clap::Parser;
+use proto::models::access_service_server::AccessServiceServer;
 use proto::models::auth_serviec_server::AuthServiecServer;
+use proto::models::browse_service_server::BrowseServiceServer;
 use proto::models::clone_service_server::CloneServiceServer;
 use proto::models::push_service_server::PushServiceServer;
+use proto::models::repository_service_server::RepositoryServiceServer;
 use tokio::sync::Mutex;
-use tonic::transport::Server;
+use tonic::transport::{Identity, Server, ServerTlsConfig};
 
+mod admin;
+mod auth_layer;
 mod cli;
+mod config;
+mod gateway;
+mod policy_store;
+mod receive_hooks;
+mod repo_store;
 mod services;
+mod tokens;
 mod user_store;
 mod error;
 
-// TODO: Save token for each user on server and check request so the tokens match
-// THIS NEEDS SOME BIG CLEANING AFTER IT IS DONE
-// thinking about it I don't think any token should be checked for clone, i mean if it is bound to a repository than you can't have it before cloning.
-// either let the user clone anything on the server that is in the uploads folder or save the token inside home, something like ~/.flux/credentials
-// and those credentials would be considered if no local ones (per repo) are set.
-// this gets way more complicated than i thought :))
-// cleanup, see tower layer for tonic, see if we can have an auth interceptor
-// figure out how to test?!
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
-    let port = cli.port;
-    let upload_root = cli.upload_root;
-    let addr = format!("[::1]:{}", port).parse()?;
-    let secret = cli.secret;
-    let user_store = match UserStore::open(cli.user_store_path.clone()) {
+    let config = ServerConfig::load(&cli).map_err(|e| e.to_string())?;
+    let mut user_store = match UserStore::open(config.user_store_path.clone()) {
         Ok(store) => store,
-        Err(_) => UserStore::new(cli.user_store_path)?,
+        Err(_) => UserStore::new(config.user_store_path.clone())?,
     };
 
-    let shared_store = Arc::new(Mutex::new(user_store));
-    let auth_service = FluxAuthService::new(secret, shared_store.clone());
-    let push_service = FluxPushService::new(upload_root.clone(), shared_store.clone());
-    let clone_service = FluxCloneService::new(upload_root.clone());
+    let mut repositories = RepoStore::open(config.repo_store_path.clone())?;
+
+    if let Some(Command::User { command }) = cli.command {
+        return admin::run(command, &mut user_store, &repositories);
+    }
+    config.log.init().map_err(|e| e.to_string())?;
+    for email in user_store.without_credential() {
+        tracing::warn!(
+            "{} has no password or key and cannot log in, give it one with `flux_server user set-secret`",
+            email
+        );
+    }
+    for name in repositories.claim_namespaces(&user_store)? {
+        tracing::warn!(
+            "Several accounts are named {}, none of them owns its namespace",
+            name
+        );
+    }
+    let secret = config.secret().map_err(|e| e.to_string())?;
+    let upload_root = config.upload_root.clone();
+
+    let shared_store = SharedUserStore::new(user_store);
+    let policies = Arc::new(Mutex::new(PolicyStore::open(config.policy_path.clone())?));
+    let repositories = Arc::new(Mutex::new(repositories));
+    let tokens = Arc::new(TokenIssuer::new(secret, config.token_ttl()));
+    let auth_service = Arc::new(
+        FluxAuthService::new(tokens.clone(), shared_store.clone())
+            .with_repositories(repositories.clone()),
+    );
+    let mut push_service = FluxPushService::new(upload_root.clone())
+        .with_policies(policies.clone())
+        .with_repositories(repositories.clone());
+    if config.fsck_objects || config.fsck_strict {
+        push_service = push_service.with_fsck(config.fsck_strict);
+    }
+    if let Some(max_push_size) = config.limits.max_push_size {
+        push_service = push_service.with_max_push_size(max_push_size);
+    }
+    let clone_service = Arc::new(
+        FluxCloneService::new(upload_root.clone())
+            .with_policies(policies.clone())
+            .with_repositories(repositories.clone()),
+    );
+    let access_service =
+        FluxAccessService::new(policies.clone(), repositories.clone(), shared_store.clone());
+    let browse_service = Arc::new(FluxBrowseService::new(
+        upload_root.clone(),
+        policies.clone(),
+        repositories.clone(),
+    ));
+    let repository_service = Arc::new(FluxRepositoryService::new(
+        upload_root,
+        policies,
+        repositories,
+        shared_store.clone(),
+    ));
+    let auth_layer = AuthLayer::new(tokens, shared_store);
+
+    // The gateway calls the same service instances, after the same authentication.
+    if let Some(http_port) = config.http_port {
+        let addr = SocketAddr::new(config.bind, http_port);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let tls = config
+            .tls
+            .as_ref()
+            .map(TlsConfig::acceptor)
+            .transpose()
+            .map_err(|e| e.to_string())?;
+        let gateway = Gateway::new(
+            auth_layer.clone(),
+            auth_service.clone(),
+            clone_service.clone(),
+            repository_service.clone(),
+            browse_service.clone(),
+        );
+        tracing::info!(%addr, tls = tls.is_some(), "Serving the HTTP gateway");
+        tokio::spawn(async move {
+            if let Err(e) = gateway.serve(listener, tls).await {
+                tracing::error!("The HTTP gateway stopped: {}", e);
+            }
+        });
+    }
+
+    let mut server = Server::builder()
+        .trace_fn(|request| tracing::info_span!("grpc", path = %request.uri().path()));
+    if let Some(tls) = &config.tls {
+        let (cert, key) = tls.read().map_err(|e| e.to_string())?;
+        server = server.tls_config(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))?;
+    }
+    if let Some(limit) = config.limits.concurrency_per_connection {
+        server = server.concurrency_limit_per_connection(limit);
+    }
+    if let Some(seconds) = config.limits.request_timeout {
+        server = server.timeout(Duration::from_secs(seconds));
+    }
 
-    Server::builder()
-        .add_service(AuthServiecServer::new(auth_service))
+    let addr = SocketAddr::new(config.bind, config.port);
+    tracing::info!(%addr, tls = config.tls.is_some(), "Serving gRPC");
+    // Every service goes through the layer, it authenticates the bearer token of a request.
+    server
+        .layer(auth_layer)
+        .add_service(AuthServiecServer::from_arc(auth_service))
         .add_service(PushServiceServer::new(push_service))
-        .add_service(CloneServiceServer::new(clone_service))
+        .add_service(CloneServiceServer::from_arc(clone_service))
+        .add_service(AccessServiceServer::new(access_service))
+        .add_service(RepositoryServiceServer::from_arc(repository_service))
+        .add_service(BrowseServiceServer::from_arc(browse_service))
         .serve(addr)
         .await?;
 
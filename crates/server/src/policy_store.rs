@@ -0,0 +1,416 @@
+use crate::error;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::{collections::HashMap, fs::File, path::PathBuf};
+use tonic::Status;
+
+use crate::tokens::{Claims, Scope};
+
+pub type Result<T> = std::result::Result<T, error::PolicyStoreError>;
+
+/// What pushed commits must carry to be accepted.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignaturePolicy {
+    /// Signatures are not checked.
+    #[default]
+    Off,
+    /// Every new commit must have a valid signature, from any key.
+    Signed,
+    /// Every new commit must be signed by a key allowed for its committer.
+    Trusted,
+}
+
+/// Who may clone a repository besides its owner and collaborators.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    /// Anyone may clone, without an access token.
+    #[default]
+    Public,
+    /// Only the owner and the collaborators may clone.
+    Private,
+}
+
+impl fmt::Display for Visibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Visibility::Public => write!(f, "public"),
+            Visibility::Private => write!(f, "private"),
+        }
+    }
+}
+
+impl FromStr for Visibility {
+    type Err = Status;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "public" => Ok(Visibility::Public),
+            "private" => Ok(Visibility::Private),
+            _ => Err(Status::invalid_argument(format!(
+                "Unknown visibility '{s}', expected public or private."
+            ))),
+        }
+    }
+}
+
+/// Access of a user to a repository, each role includes the ones before it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Clone the repository.
+    Read,
+    /// Push to the repository.
+    Write,
+    /// Manage the visibility and the collaborators of the repository.
+    Admin,
+}
+
+impl Role {
+    /// Token scope needed to act with the role.
+    fn scope(self) -> Scope {
+        match self {
+            Role::Read => Scope::Read,
+            Role::Write | Role::Admin => Scope::Write,
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Role::Read => "read",
+            Role::Write => "write",
+            Role::Admin => "admin",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Role {
+    type Err = Status;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Role::Read),
+            "write" => Ok(Role::Write),
+            "admin" => Ok(Role::Admin),
+            _ => Err(Status::invalid_argument(format!(
+                "Unknown role '{s}', expected read, write or admin."
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct RepoPolicy {
+    #[serde(default)]
+    pub visibility: Visibility,
+    /// Roles of the users other than the owner, by email.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub collaborators: HashMap<String, Role>,
+    #[serde(default)]
+    pub signatures: SignaturePolicy,
+    /// Lines in the allowed signers format: `<email> ssh-ed25519 <key>`.
+    #[serde(default)]
+    pub allowed_signers: Vec<String>,
+}
+
+impl RepoPolicy {
+    /// Role of the caller on the repository, the higher of the one its namespace gives them
+    /// (see `RepoStore::namespace_role`) and the one as a collaborator. Server admins have
+    /// every role, anyone may read a public repository.
+    pub fn role(&self, namespace_role: Option<Role>, identity: Option<&Claims>) -> Option<Role> {
+        let role = match identity {
+            Some(claims) if claims.allows(Scope::Admin) => Some(Role::Admin),
+            Some(claims) => namespace_role.max(self.collaborators.get(&claims.sub).copied()),
+            None => None,
+        };
+        match self.visibility {
+            Visibility::Public => role.max(Some(Role::Read)),
+            Visibility::Private => role,
+        }
+    }
+
+    /// Checks that the caller may act with `role` on the repository.
+    pub fn authorize(
+        &self,
+        namespace_role: Option<Role>,
+        identity: Option<&Claims>,
+        role: Role,
+    ) -> std::result::Result<(), Status> {
+        // Public repositories are read without a token, whatever one the request carries.
+        if role == Role::Read && self.visibility == Visibility::Public {
+            return Ok(());
+        }
+        let Some(claims) = identity else {
+            return Err(Status::unauthenticated(match role {
+                Role::Read => "The repository is private, run flux auth first.",
+                _ => "Missing access token, run flux auth first.",
+            }));
+        };
+        if self
+            .role(namespace_role, identity)
+            .is_none_or(|granted| granted < role)
+        {
+            return Err(Status::permission_denied(format!(
+                "Access to the repository needs the {role} role."
+            )));
+        }
+        if !claims.allows(role.scope()) {
+            return Err(Status::permission_denied(format!(
+                "The access token does not have the {} scope.",
+                role.scope()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Per repository policies, keyed by `<user>/<repo>`.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct PolicyStore {
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub map: HashMap<String, RepoPolicy>,
+}
+
+impl PolicyStore {
+    /// Loads the policies, a missing file means no repository has a policy.
+    pub fn open(path_str: Option<String>) -> Result<Self> {
+        let path = PathBuf::from(path_str.unwrap_or_else(|| "repo_policies.json".to_string()));
+        if !path.exists() {
+            return Ok(Self {
+                path,
+                map: HashMap::new(),
+            });
+        }
+
+        let file = File::open(&path)?;
+        let mut store: Self = serde_json::from_reader(file)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = File::create(&self.path)?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+
+    pub fn get(&self, user_name: &str, repo_name: &str) -> RepoPolicy {
+        self.map
+            .get(&format!("{user_name}/{repo_name}"))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Drops the policy of a deleted repository.
+    pub fn remove(&mut self, user_name: &str, repo_name: &str) -> Result<Option<RepoPolicy>> {
+        let policy = self.map.remove(&format!("{user_name}/{repo_name}"));
+        self.save()?;
+        Ok(policy)
+    }
+
+    /// Changes the policy of a repository and saves the store.
+    pub fn update(
+        &mut self,
+        user_name: &str,
+        repo_name: &str,
+        f: impl FnOnce(&mut RepoPolicy),
+    ) -> Result<RepoPolicy> {
+        let policy = self
+            .map
+            .entry(format!("{user_name}/{repo_name}"))
+            .or_default();
+        f(policy);
+        let policy = policy.clone();
+        self.save()?;
+        Ok(policy)
+    }
+}
+
+/// Owner and name of a repository path, `<owner>/<repo>` or `<repo>` for a repository of the
+/// caller.
+pub fn repository_path(
+    path: &str,
+    identity: Option<&Claims>,
+) -> std::result::Result<(String, String), Status> {
+    let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let (owner, repo) = match parts.as_slice() {
+        [repo] => {
+            let claims = identity
+                .ok_or_else(|| Status::invalid_argument("Path must be in format 'user/repo'"))?;
+            (claims.name.as_str(), *repo)
+        }
+        [owner, repo] => (*owner, *repo),
+        _ => {
+            return Err(Status::invalid_argument(
+                "Path must be in format 'user/repo'",
+            ));
+        }
+    };
+
+    let owner = namespace(owner)?;
+    let repo =
+        file_name(repo).ok_or_else(|| Status::invalid_argument("Invalid repository name"))?;
+    Ok((owner, repo))
+}
+
+/// Checks the name of a user or organization. Names starting with a dot are reserved, e.g.
+/// for the global hooks directory.
+pub fn namespace(name: &str) -> std::result::Result<String, Status> {
+    file_name(name)
+        .filter(|name| !name.starts_with('.'))
+        .ok_or_else(|| Status::invalid_argument("Invalid user or organization name"))
+}
+
+/// `name` when it is a single path component.
+fn file_name(name: &str) -> Option<String> {
+    std::path::Path::new(name)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .filter(|f| *f == name)
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo_store::RepoStore;
+
+    fn claims(name: &str, email: &str, scopes: &[Scope]) -> Claims {
+        Claims {
+            sub: email.to_string(),
+            name: name.to_string(),
+            jti: String::new(),
+            iat: 0,
+            exp: 0,
+            scopes: scopes.to_vec(),
+        }
+    }
+
+    fn private() -> RepoPolicy {
+        RepoPolicy {
+            visibility: Visibility::Private,
+            ..RepoPolicy::default()
+        }
+    }
+
+    #[test]
+    fn anyone_reads_a_public_repository() {
+        let public = RepoPolicy::default();
+        let bob = claims("bob", "bob@x", Scope::DEFAULT);
+
+        assert!(public.authorize(None, None, Role::Read).is_ok());
+        assert!(public.authorize(None, Some(&bob), Role::Read).is_ok());
+        assert_eq!(public.role(None, None), Some(Role::Read));
+        assert_eq!(
+            public
+                .authorize(None, None, Role::Write)
+                .unwrap_err()
+                .code(),
+            tonic::Code::Unauthenticated
+        );
+        assert_eq!(
+            public
+                .authorize(None, Some(&bob), Role::Write)
+                .unwrap_err()
+                .code(),
+            tonic::Code::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn only_members_read_a_private_repository() {
+        let private = private();
+        let bob = claims("bob", "bob@x", Scope::DEFAULT);
+
+        assert_eq!(private.role(None, None), None);
+        assert_eq!(
+            private
+                .authorize(None, None, Role::Read)
+                .unwrap_err()
+                .code(),
+            tonic::Code::Unauthenticated
+        );
+        assert_eq!(
+            private
+                .authorize(None, Some(&bob), Role::Read)
+                .unwrap_err()
+                .code(),
+            tonic::Code::PermissionDenied
+        );
+        assert!(
+            private
+                .authorize(Some(Role::Read), Some(&bob), Role::Read)
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn collaborators_get_their_role_and_no_more() {
+        for role in [Role::Read, Role::Write, Role::Admin] {
+            let mut policy = private();
+            policy.collaborators.insert("bob@x".to_string(), role);
+            let bob = claims("bob", "bob@x", Scope::DEFAULT);
+
+            assert_eq!(policy.role(None, Some(&bob)), Some(role));
+            for needed in [Role::Read, Role::Write, Role::Admin] {
+                assert_eq!(
+                    policy.authorize(None, Some(&bob), needed).is_ok(),
+                    needed <= role,
+                    "{role} collaborator acting as {needed}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn the_namespace_owner_and_not_a_namesake_administers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("repo_store.json");
+        let mut repositories = RepoStore::open(Some(path.to_string_lossy().to_string())).unwrap();
+        repositories.claim_namespace("alice", "alice@x").unwrap();
+        let policy = private();
+
+        let owner = claims("alice", "alice@x", Scope::DEFAULT);
+        let namesake = claims("alice", "mallory@x", Scope::DEFAULT);
+        let role = |claims: &Claims| repositories.namespace_role("alice", Some(claims));
+        assert!(
+            policy
+                .authorize(role(&owner), Some(&owner), Role::Admin)
+                .is_ok()
+        );
+        assert!(
+            policy
+                .authorize(role(&namesake), Some(&namesake), Role::Read)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn admin_tokens_have_every_role() {
+        let admin = claims("root", "root@x", &[Scope::Admin]);
+        assert_eq!(private().role(None, Some(&admin)), Some(Role::Admin));
+        assert!(private().authorize(None, Some(&admin), Role::Admin).is_ok());
+    }
+
+    #[test]
+    fn the_role_needs_a_token_with_its_scope() {
+        let mut policy = private();
+        policy
+            .collaborators
+            .insert("bob@x".to_string(), Role::Write);
+        let read_only = claims("bob", "bob@x", &[Scope::Read]);
+
+        assert!(policy.authorize(None, Some(&read_only), Role::Read).is_ok());
+        let denied = policy
+            .authorize(None, Some(&read_only), Role::Write)
+            .unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::PermissionDenied);
+        assert!(denied.message().contains("scope"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Runs before a push is stored, a non-zero exit rejects it.
+pub const PRE_RECEIVE: &str = "pre-receive";
+/// Runs after a push is stored, e.g. to trigger CI or send notifications.
+pub const POST_RECEIVE: &str = "post-receive";
+
+/// Directory holding the hooks, globally under the upload root and per repository next to
+/// its archive: `<upload-root>/.hooks/` and `<upload-root>/<user>/<repo>/.hooks/`.
+pub const HOOKS_DIR: &str = ".hooks";
+
+/// A ref changed by a push, given to hooks on stdin as `<old hash> <new hash> <ref>`.
+/// Created refs have an all zero old hash, deleted ones an all zero new hash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefUpdate {
+    pub old: String,
+    pub new: String,
+    pub name: String,
+}
+
+/// Combined output of the hooks that ran.
+#[derive(Debug, Default)]
+pub struct HookOutput {
+    pub success: bool,
+    pub output: String,
+}
+
+/// Receive hooks of a single repository, as run for one push.
+#[derive(Debug, Clone)]
+pub struct ReceiveHooks {
+    dirs: Vec<PathBuf>,
+    repository: String,
+    /// Name of the user pushing, not necessarily the owner of the repository.
+    push_user: String,
+}
+
+impl ReceiveHooks {
+    pub fn new(upload_root: &Path, owner: &str, repo_name: &str, push_user: &str) -> Self {
+        Self {
+            dirs: vec![
+                upload_root.join(HOOKS_DIR),
+                upload_root.join(owner).join(repo_name).join(HOOKS_DIR),
+            ],
+            repository: format!("{owner}/{repo_name}"),
+            push_user: push_user.to_string(),
+        }
+    }
+
+    /// Executable `name` hooks, the global one first.
+    fn hooks(&self, name: &str) -> Vec<PathBuf> {
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(name))
+            .filter(|path| is_executable(path))
+            .collect()
+    }
+
+    pub fn exists(&self, name: &str) -> bool {
+        !self.hooks(name).is_empty()
+    }
+
+    /// Runs the global and then the repository `name` hook from `dir`, stopping at the first
+    /// failure. Hooks get the ref updates on stdin and `FLUX_REPOSITORY`/`FLUX_PUSH_USER` in
+    /// their environment.
+    pub fn run(&self, name: &str, updates: &[RefUpdate], dir: &Path) -> io::Result<HookOutput> {
+        let stdin: String = updates
+            .iter()
+            .map(|update| format!("{} {} {}\n", update.old, update.new, update.name))
+            .collect();
+
+        let mut result = HookOutput {
+            success: true,
+            output: String::new(),
+        };
+        for hook in self.hooks(name) {
+            let mut child = Command::new(&hook)
+                .current_dir(dir)
+                .env("FLUX_REPOSITORY", &self.repository)
+                .env("FLUX_PUSH_USER", &self.push_user)
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()?;
+            if let Some(mut pipe) = child.stdin.take() {
+                // A hook may exit without reading its input, a broken pipe is not a failure.
+                let _ = pipe.write_all(stdin.as_bytes());
+            }
+
+            let output = child.wait_with_output()?;
+            result
+                .output
+                .push_str(&String::from_utf8_lossy(&output.stdout));
+            result
+                .output
+                .push_str(&String::from_utf8_lossy(&output.stderr));
+            if !output.status.success() {
+                result.success = false;
+                break;
+            }
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
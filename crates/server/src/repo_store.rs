@@ -0,0 +1,253 @@
+use crate::error;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs::File, path::PathBuf};
+
+use crate::policy_store::Role;
+use crate::tokens::{self, Claims};
+use crate::user_store::UserStore;
+
+pub type Result<T> = std::result::Result<T, error::RepoStoreError>;
+
+/// Branch of a new repository unless another one is asked for.
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// A namespace shared by several users, its repositories live in `<upload-root>/<name>/`
+/// like the ones of a user.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Organization {
+    pub created_at: u64,
+    /// Roles of the members on every repository of the organization, by email.
+    #[serde(default)]
+    pub members: HashMap<String, Role>,
+}
+
+/// Metadata of a repository. Repositories pushed before they had metadata have none.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RepoEntry {
+    #[serde(default)]
+    pub description: String,
+    pub default_branch: String,
+    pub created_at: u64,
+}
+
+impl RepoEntry {
+    pub fn new(description: String, default_branch: String) -> Self {
+        Self {
+            description,
+            default_branch,
+            created_at: tokens::now(),
+        }
+    }
+}
+
+impl Default for RepoEntry {
+    fn default() -> Self {
+        Self::new(String::new(), DEFAULT_BRANCH.to_string())
+    }
+}
+
+/// Organizations and repository metadata.
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RepoStore {
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(default)]
+    pub organizations: HashMap<String, Organization>,
+    /// Emails of the users who own the namespaces named after them. Names were not always
+    /// unique, so ownership is decided by the email and not by the name in a token.
+    #[serde(default)]
+    pub owners: HashMap<String, String>,
+    /// Keyed by `<namespace>/<repo>`.
+    #[serde(default)]
+    pub repositories: HashMap<String, RepoEntry>,
+}
+
+impl RepoStore {
+    /// Loads the store, a missing file means no organization and no metadata.
+    pub fn open(path_str: Option<String>) -> Result<Self> {
+        let path = PathBuf::from(path_str.unwrap_or_else(|| "repo_store.json".to_string()));
+        if !path.exists() {
+            return Ok(Self {
+                path,
+                ..Self::default()
+            });
+        }
+
+        let file = File::open(&path)?;
+        let mut store: Self = serde_json::from_reader(file)?;
+        store.path = path;
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let file = File::create(&self.path)?;
+        Ok(serde_json::to_writer_pretty(file, self)?)
+    }
+
+    /// Role the namespace gives the caller on each of its repositories: members get their
+    /// role in an organization, a user administers the namespace they own.
+    pub fn namespace_role(&self, namespace: &str, identity: Option<&Claims>) -> Option<Role> {
+        let claims = identity?;
+        match self.organizations.get(namespace) {
+            Some(organization) => organization.members.get(&claims.sub).copied(),
+            None => self
+                .owners
+                .get(namespace)
+                .is_some_and(|owner| *owner == claims.sub)
+                .then_some(Role::Admin),
+        }
+    }
+
+    /// Makes `user_email` the owner of the namespace `user_name`, unless it already has an
+    /// owner or is an organization.
+    pub fn claim_namespace(&mut self, user_name: &str, user_email: &str) -> Result<()> {
+        if self.claim(user_name, user_email) {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Claims the namespaces of the accounts in `users` that were created before owners
+    /// were recorded. Returns the names shared by several accounts, those get no owner.
+    pub fn claim_namespaces(&mut self, users: &UserStore) -> Result<Vec<String>> {
+        let mut names: Vec<_> = users.map.values().map(|entry| &entry.user_name).collect();
+        names.sort();
+        names.dedup();
+
+        let mut shared = Vec::new();
+        let mut claimed = false;
+        for name in names {
+            match users.owner_of(name) {
+                Some(email) => claimed |= self.claim(name, email),
+                None => shared.push(name.clone()),
+            }
+        }
+        if claimed {
+            self.save()?;
+        }
+        Ok(shared)
+    }
+
+    fn claim(&mut self, user_name: &str, user_email: &str) -> bool {
+        if self.owners.contains_key(user_name) || self.organizations.contains_key(user_name) {
+            return false;
+        }
+        self.owners
+            .insert(user_name.to_string(), user_email.to_string());
+        true
+    }
+
+    pub fn get(&self, namespace: &str, repo_name: &str) -> Option<&RepoEntry> {
+        self.repositories.get(&format!("{namespace}/{repo_name}"))
+    }
+
+    pub fn insert(&mut self, namespace: &str, repo_name: &str, entry: RepoEntry) -> Result<()> {
+        self.repositories
+            .insert(format!("{namespace}/{repo_name}"), entry);
+        self.save()
+    }
+
+    pub fn remove(&mut self, namespace: &str, repo_name: &str) -> Result<Option<RepoEntry>> {
+        let entry = self
+            .repositories
+            .remove(&format!("{namespace}/{repo_name}"));
+        self.save()?;
+        Ok(entry)
+    }
+
+    /// Creates an organization administered by `admin_email`.
+    pub fn create_organization(&mut self, name: &str, admin_email: &str) -> Result<&Organization> {
+        if self.organizations.contains_key(name) || self.owners.contains_key(name) {
+            return Err(error::RepoStoreError::NamespaceTaken(name.to_string()));
+        }
+        let organization = Organization {
+            created_at: tokens::now(),
+            members: HashMap::from([(admin_email.to_string(), Role::Admin)]),
+        };
+        self.organizations.insert(name.to_string(), organization);
+        self.save()?;
+        Ok(&self.organizations[name])
+    }
+
+    /// Gives `user_email` the `role` on the repositories of an organization, or removes them
+    /// from it when `role` is `None`.
+    pub fn set_member(
+        &mut self,
+        name: &str,
+        user_email: &str,
+        role: Option<Role>,
+    ) -> Result<&Organization> {
+        let organization = self
+            .organizations
+            .get_mut(name)
+            .ok_or_else(|| error::RepoStoreError::UnknownOrganization(name.to_string()))?;
+        match role {
+            Some(role) => organization.members.insert(user_email.to_string(), role),
+            None => organization.members.remove(user_email),
+        };
+        self.save()?;
+        Ok(&self.organizations[name])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_store::Credential;
+
+    fn claims(name: &str, email: &str) -> Claims {
+        Claims {
+            sub: email.to_string(),
+            name: name.to_string(),
+            jti: String::new(),
+            iat: 0,
+            exp: 0,
+            scopes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn the_owner_and_not_the_name_administers_a_namespace() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir
+            .path()
+            .join("repo_store.json")
+            .to_string_lossy()
+            .to_string();
+        let mut store = RepoStore::open(Some(path)).unwrap();
+        store.claim_namespace("alice", "alice@x").unwrap();
+        store.claim_namespace("alice", "mallory@x").unwrap();
+
+        let owner = claims("alice", "alice@x");
+        let namesake = claims("alice", "mallory@x");
+        assert_eq!(
+            store.namespace_role("alice", Some(&owner)),
+            Some(Role::Admin)
+        );
+        assert_eq!(store.namespace_role("alice", Some(&namesake)), None);
+    }
+
+    #[test]
+    fn names_of_several_accounts_are_not_claimed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().to_string();
+        let mut users = UserStore::new(Some(path("users.json"))).unwrap();
+        let password = || Credential::PasswordHash("hash".to_string());
+        users
+            .add_user("alice".into(), "alice@x".into(), password(), false)
+            .unwrap();
+        users
+            .add_user("bob".into(), "bob@x".into(), password(), false)
+            .unwrap();
+        // A store from before names were unique.
+        users.map.get_mut("bob@x").unwrap().user_name = "alice".into();
+
+        let mut store = RepoStore::open(Some(path("repo_store.json"))).unwrap();
+        assert_eq!(store.claim_namespaces(&users).unwrap(), vec!["alice"]);
+        assert!(store.owners.is_empty());
+        for email in ["alice@x", "bob@x"] {
+            let claims = claims("alice", email);
+            assert_eq!(store.namespace_role("alice", Some(&claims)), None);
+        }
+    }
+}
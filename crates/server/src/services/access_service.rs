@@ -0,0 +1,194 @@
+use proto::models::access_service_server::AccessService;
+use proto::models::{
+    AccessResponse, Collaborator, CollaboratorRequest, RepositoryRequest, VisibilityRequest,
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tonic::{Request, Response, Status};
+
+use crate::auth_layer;
+use crate::policy_store::{self, PolicyStore, RepoPolicy, Role, Visibility};
+use crate::repo_store::RepoStore;
+use crate::user_store::SharedUserStore;
+
+#[derive(Debug)]
+pub struct FluxAccessService {
+    policies: Arc<Mutex<PolicyStore>>,
+    repositories: Arc<Mutex<RepoStore>>,
+    user_store: SharedUserStore,
+}
+
+impl FluxAccessService {
+    pub fn new(
+        policies: Arc<Mutex<PolicyStore>>,
+        repositories: Arc<Mutex<RepoStore>>,
+        user_store: SharedUserStore,
+    ) -> Self {
+        Self {
+            policies,
+            repositories,
+            user_store,
+        }
+    }
+
+    /// Applies `f` to the policy of a repository the caller administers.
+    async fn update<T>(
+        &self,
+        request: &Request<T>,
+        repository: &str,
+        f: impl FnOnce(&mut RepoPolicy),
+    ) -> Result<Response<AccessResponse>, Status> {
+        let claims = auth_layer::authenticated(request)?;
+        let (owner, repo) = policy_store::repository_path(repository, Some(&claims))?;
+        let namespace_role = self
+            .repositories
+            .lock()
+            .await
+            .namespace_role(&owner, Some(&claims));
+
+        let mut policies = self.policies.lock().await;
+        policies
+            .get(&owner, &repo)
+            .authorize(namespace_role, Some(&claims), Role::Admin)?;
+        let policy = policies
+            .update(&owner, &repo, f)
+            .map_err(|e| Status::internal(format!("Failed to save the repository policy: {e}")))?;
+        Ok(Response::new(access_response(owner, &policy)))
+    }
+}
+
+fn access_response(owner: String, policy: &RepoPolicy) -> AccessResponse {
+    let mut collaborators: Vec<Collaborator> = policy
+        .collaborators
+        .iter()
+        .map(|(email, role)| Collaborator {
+            user_email: email.clone(),
+            role: role.to_string(),
+        })
+        .collect();
+    collaborators.sort_by(|a, b| a.user_email.cmp(&b.user_email));
+
+    AccessResponse {
+        owner,
+        visibility: policy.visibility.to_string(),
+        collaborators,
+    }
+}
+
+#[tonic::async_trait]
+impl AccessService for FluxAccessService {
+    async fn get_access(
+        &self,
+        request: Request<RepositoryRequest>,
+    ) -> Result<Response<AccessResponse>, Status> {
+        let claims = auth_layer::authenticated(&request)?;
+        let (owner, repo) =
+            policy_store::repository_path(&request.get_ref().repository, Some(&claims))?;
+
+        let namespace_role = self
+            .repositories
+            .lock()
+            .await
+            .namespace_role(&owner, Some(&claims));
+        let policy = self.policies.lock().await.get(&owner, &repo);
+        // Readers of a public repository are anyone, they do not get to see its collaborators.
+        policy.authorize(namespace_role, Some(&claims), Role::Write)?;
+        Ok(Response::new(access_response(owner, &policy)))
+    }
+
+    async fn set_visibility(
+        &self,
+        request: Request<VisibilityRequest>,
+    ) -> Result<Response<AccessResponse>, Status> {
+        let visibility: Visibility = request.get_ref().visibility.parse()?;
+        let repository = request.get_ref().repository.clone();
+        self.update(&request, &repository, |policy| {
+            policy.visibility = visibility
+        })
+        .await
+    }
+
+    async fn add_collaborator(
+        &self,
+        request: Request<CollaboratorRequest>,
+    ) -> Result<Response<AccessResponse>, Status> {
+        let req = request.get_ref();
+        let role: Role = if req.role.is_empty() {
+            Role::Write
+        } else {
+            req.role.parse()?
+        };
+        // Only registered users can be added, so a typo does not grant access to a stranger
+        // who registers the address later.
+        if !self
+            .user_store
+            .lock()
+            .await
+            .map
+            .contains_key(&req.user_email)
+        {
+            return Err(Status::not_found(format!(
+                "No user is registered with the email {}.",
+                req.user_email
+            )));
+        }
+
+        let (repository, email) = (req.repository.clone(), req.user_email.clone());
+        self.update(&request, &repository, |policy| {
+            policy.collaborators.insert(email, role);
+        })
+        .await
+    }
+
+    async fn remove_collaborator(
+        &self,
+        request: Request<CollaboratorRequest>,
+    ) -> Result<Response<AccessResponse>, Status> {
+        let req = request.get_ref();
+        let (repository, email) = (req.repository.clone(), req.user_email.clone());
+        self.update(&request, &repository, |policy| {
+            policy.collaborators.remove(&email);
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::{Claims, Scope};
+    use crate::user_store::UserStore;
+
+    #[tokio::test]
+    async fn readers_of_a_public_repository_do_not_see_its_collaborators() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| Some(dir.path().join(name).to_string_lossy().to_string());
+        let mut repositories = RepoStore::open(path("repo_store.json")).unwrap();
+        repositories.claim_namespace("alice", "alice@x").unwrap();
+        let service = FluxAccessService::new(
+            Arc::new(Mutex::new(
+                PolicyStore::open(path("policies.json")).unwrap(),
+            )),
+            Arc::new(Mutex::new(repositories)),
+            SharedUserStore::new(UserStore::new(path("users.json")).unwrap()),
+        );
+
+        let get_access = |name: &str, email: &str| {
+            let mut request = Request::new(RepositoryRequest {
+                repository: "alice/project".to_string(),
+            });
+            request.extensions_mut().insert(Claims {
+                sub: email.to_string(),
+                name: name.to_string(),
+                jti: String::new(),
+                iat: 0,
+                exp: 0,
+                scopes: Scope::DEFAULT.to_vec(),
+            });
+            service.get_access(request)
+        };
+        assert!(get_access("alice", "alice@x").await.is_ok());
+        let denied = get_access("bob", "bob@x").await.unwrap_err();
+        assert_eq!(denied.code(), tonic::Code::PermissionDenied);
+    }
+}
@@ -1,59 +1,363 @@
-use jsonwebtoken::{EncodingKey, Header, encode};
-use proto::models::{IssueTokenRequest, IssueTokenResponse, auth_serviec_server::AuthServiec};
-use serde::{Deserialize, Serialize};
+use proto::models::{
+    ChallengeRequest, ChallengeResponse, IssueTokenResponse, LoginRequest, RegisterRequest,
+    RevokeRequest, RevokeResponse, TokenInfo, TokenRequest, TokenStatusResponse,
+    auth_serviec_server::AuthServiec,
+};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tonic::{Request, Response, Status};
 
-use crate::user_store::UserStore;
+use crate::auth_layer;
+use crate::error::{RepoStoreError, UserStoreError};
+use crate::repo_store::RepoStore;
+use crate::tokens::{self, Scope, TokenIssuer};
+use crate::user_store::{Credential, Secret, SharedUserStore, UserStore};
+
+/// How long a login challenge can be answered.
+const CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
 
 #[derive(Debug)]
 pub struct FluxAuthService {
-    secret: String,
-    user_store: Arc<Mutex<UserStore>>,
+    tokens: Arc<TokenIssuer>,
+    user_store: SharedUserStore,
+    /// Organizations, whose names users cannot register with.
+    repositories: Arc<Mutex<RepoStore>>,
+    /// Open challenges with the email they were requested for, each can be answered once.
+    /// Keyed by the challenge, so requesting one does not drop those of other clients.
+    challenges: Mutex<HashMap<String, (String, Instant)>>,
 }
 
 impl FluxAuthService {
-    pub fn new(secret: String, user_store: Arc<Mutex<UserStore>>) -> Self {
-        Self { secret, user_store }
+    pub fn new(tokens: Arc<TokenIssuer>, user_store: SharedUserStore) -> Self {
+        Self {
+            tokens,
+            user_store,
+            repositories: Arc::default(),
+            challenges: Mutex::default(),
+        }
+    }
+
+    pub fn with_repositories(mut self, repositories: Arc<Mutex<RepoStore>>) -> Self {
+        self.repositories = repositories;
+        self
+    }
+
+    /// Issues a token and records it in the user store.
+    fn issue_token(
+        &self,
+        user_store: &mut UserStore,
+        user_name: &str,
+        user_email: &str,
+        scopes: Vec<Scope>,
+    ) -> Result<IssueTokenResponse, Status> {
+        let (token, claims) = self.tokens.issue(user_name, user_email, scopes)?;
+        user_store.add_token(&claims).map_err(auth_status)?;
+
+        Ok(IssueTokenResponse {
+            access_token: token,
+            token_id: claims.jti,
+            expires_at: claims.exp,
+        })
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct Claims {
-    user_name: String,
-    user_email: String,
+/// Scopes of a new token, only admins may request the admin scope.
+fn requested_scopes(requested: &[String], admin: bool) -> Result<Vec<Scope>, Status> {
+    if requested.is_empty() {
+        return Ok(Scope::DEFAULT.to_vec());
+    }
+
+    let mut scopes = requested
+        .iter()
+        .map(|scope| scope.parse())
+        .collect::<Result<Vec<Scope>, Status>>()?;
+    if scopes.contains(&Scope::Admin) && !admin {
+        return Err(Status::permission_denied(
+            "Only administrators may request the admin scope.",
+        ));
+    }
+    scopes.sort();
+    scopes.dedup();
+    Ok(scopes)
+}
+
+/// Hashes or verifies a secret on the blocking pool, without stalling the async workers.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, UserStoreError> + Send + 'static,
+) -> Result<T, Status> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(auth_status)
+}
+
+fn owner_status(error: RepoStoreError) -> Status {
+    Status::internal(format!(
+        "Failed to record the owner of the namespace. {error}"
+    ))
+}
+
+/// Maps user store errors to a status, without telling whether an email is registered.
+fn auth_status(error: UserStoreError) -> Status {
+    match error {
+        UserStoreError::UnknownUser(_) | UserStoreError::InvalidCredentials => {
+            Status::unauthenticated("Invalid email or credentials.")
+        }
+        UserStoreError::Disabled(_) => Status::permission_denied(error.to_string()),
+        UserStoreError::EmailAlredyRegistered(_)
+        | UserStoreError::UserNameTaken(_)
+        | UserStoreError::InvalidUserName(_)
+        | UserStoreError::PublicKey(_) => Status::invalid_argument(error.to_string()),
+        _ => Status::internal(format!("Remote user store failed. {error}")),
+    }
 }
 
 #[tonic::async_trait]
 impl AuthServiec for FluxAuthService {
-    async fn issue_token(
+    async fn register(
         &self,
-        request: Request<IssueTokenRequest>,
+        request: Request<RegisterRequest>,
     ) -> Result<Response<IssueTokenResponse>, Status> {
         let req = request.into_inner();
-
-        let claims = Claims {
-            user_name: req.user_name.clone(),
-            user_email: req.user_email.clone(),
+        if req.user_name.is_empty() || req.user_email.is_empty() {
+            return Err(Status::invalid_argument(
+                "A user name and email are required.",
+            ));
+        }
+        let secret = match (req.password.is_empty(), req.public_key.is_empty()) {
+            (false, true) => Secret::Password(req.password),
+            (true, false) => Secret::PublicKey(req.public_key),
+            _ => {
+                return Err(Status::invalid_argument(
+                    "Register with either a password or a public key.",
+                ));
+            }
         };
+        let scopes = requested_scopes(&req.scopes, false)?;
+        let user_email = req.user_email.clone();
+        let credential = blocking(move || secret.into_credential(&user_email)).await?;
+
+        // Locked before the user store like in `create_organization`, so a name cannot be
+        // taken by an organization and a user at once.
+        let mut repositories = self.repositories.lock().await;
+        if repositories.organizations.contains_key(&req.user_name)
+            || repositories.owners.contains_key(&req.user_name)
+        {
+            return Err(auth_status(UserStoreError::UserNameTaken(req.user_name)));
+        }
+        let mut user_store = self.user_store.lock().await;
+        user_store
+            .add_user(
+                req.user_name.clone(),
+                req.user_email.clone(),
+                credential,
+                false,
+            )
+            .map_err(auth_status)?;
+        repositories
+            .claim_namespace(&req.user_name, &req.user_email)
+            .map_err(owner_status)?;
+        let response =
+            self.issue_token(&mut user_store, &req.user_name, &req.user_email, scopes)?;
 
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(self.secret.as_ref()),
-        )
-        .map_err(|_| tonic::Status::internal("Failed to generate token"))?;
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn challenge(
+        &self,
+        request: Request<ChallengeRequest>,
+    ) -> Result<Response<ChallengeResponse>, Status> {
+        let req = request.into_inner();
+        // The email is part of the signed text, so an answer cannot be used for another account.
+        let challenge = format!("flux-login:{}:{}", req.user_email, tokens::random_hex(32));
+
+        let mut challenges = self.challenges.lock().await;
+        challenges.retain(|_, (_, created)| created.elapsed() < CHALLENGE_TTL);
+        challenges.insert(challenge.clone(), (req.user_email, Instant::now()));
+
+        Ok(tonic::Response::new(ChallengeResponse { challenge }))
+    }
+
+    async fn login(
+        &self,
+        request: Request<LoginRequest>,
+    ) -> Result<Response<IssueTokenResponse>, Status> {
+        let req = request.into_inner();
+        let credential = self.user_store.lock().await.credential(&req.user_email);
 
-        self.user_store
+        if !req.password.is_empty() {
+            let password = req.password;
+            blocking(move || match credential {
+                Ok(credential) => credential.verify_password(&password),
+                // Hashed anyway, an unknown email must not be refused faster than a wrong password.
+                Err(error) => {
+                    let _ = Credential::unknown_user().verify_password(&password);
+                    Err(error)
+                }
+            })
+            .await?;
+        } else {
+            let credential = credential.map_err(auth_status)?;
+            let open = self.challenges.lock().await.remove(&req.challenge);
+            match open {
+                Some((user_email, created))
+                    if user_email == req.user_email && created.elapsed() < CHALLENGE_TTL => {}
+                _ => return Err(Status::unauthenticated("Unknown or expired challenge.")),
+            }
+            let (user_email, challenge) = (req.user_email.clone(), req.challenge);
+            blocking(move || credential.verify_signature(&user_email, &challenge, &req.signature))
+                .await?;
+        }
+
+        // Only a user who proved their identity learns whether the account is disabled.
+        let mut repositories = self.repositories.lock().await;
+        let mut user_store = self.user_store.lock().await;
+        let entry = user_store
+            .active_user(&req.user_email)
+            .map_err(auth_status)?;
+        let user_name = entry.user_name.clone();
+        let scopes = requested_scopes(&req.scopes, entry.admin)?;
+        // Accounts added by `flux_server user add` get their namespace at the first login.
+        if user_store.owner_of(&user_name) == Some(req.user_email.as_str()) {
+            repositories
+                .claim_namespace(&user_name, &req.user_email)
+                .map_err(owner_status)?;
+        }
+
+        let response = self.issue_token(&mut user_store, &user_name, &req.user_email, scopes)?;
+        Ok(tonic::Response::new(response))
+    }
+
+    /// Replaces the token the request is authorized with by a new one with the same scopes.
+    async fn refresh(
+        &self,
+        request: Request<TokenRequest>,
+    ) -> Result<Response<IssueTokenResponse>, Status> {
+        let claims = auth_layer::authenticated(&request)?;
+
+        let mut user_store = self.user_store.lock().await;
+        let response =
+            self.issue_token(&mut user_store, &claims.name, &claims.sub, claims.scopes)?;
+        user_store
+            .revoke_tokens(&claims.sub, Some(&claims.jti))
+            .map_err(auth_status)?;
+        Ok(tonic::Response::new(response))
+    }
+
+    async fn revoke(
+        &self,
+        request: Request<RevokeRequest>,
+    ) -> Result<Response<RevokeResponse>, Status> {
+        let claims = auth_layer::authenticated(&request)?;
+        let req = request.into_inner();
+
+        let id = match (req.all, req.token_id.is_empty()) {
+            (true, _) => None,
+            (false, true) => Some(claims.jti.as_str()),
+            (false, false) => Some(req.token_id.as_str()),
+        };
+        let revoked = self
+            .user_store
             .lock()
             .await
-            .add_user(req.user_name, req.user_email, token.clone())
-            .await
-            .map_err(|e| Status::internal(format!("Failed to add user to remote user store. {e}")))?;
+            .revoke_tokens(&claims.sub, id)
+            .map_err(auth_status)?;
+        Ok(tonic::Response::new(RevokeResponse {
+            revoked: revoked as u32,
+        }))
+    }
 
-        Ok(tonic::Response::new(proto::models::IssueTokenResponse {
-            access_token: token,
+    async fn token_status(
+        &self,
+        request: Request<TokenRequest>,
+    ) -> Result<Response<TokenStatusResponse>, Status> {
+        let claims = auth_layer::authenticated(&request)?;
+
+        let tokens = self
+            .user_store
+            .lock()
+            .await
+            .active_tokens(&claims.sub)
+            .map_err(auth_status)?
+            .into_iter()
+            .map(|token| TokenInfo {
+                id: token.id,
+                scopes: token.scopes.iter().map(Scope::to_string).collect(),
+                issued_at: token.issued_at,
+                expires_at: token.expires_at,
+            })
+            .collect();
+        Ok(tonic::Response::new(TokenStatusResponse {
+            user_name: claims.name,
+            user_email: claims.sub,
+            token_id: claims.jti,
+            tokens,
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn challenges_of_other_clients_stay_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.json").to_string_lossy().to_string();
+        let user_store = SharedUserStore::new(UserStore::new(Some(path)).unwrap());
+        let tokens = Arc::new(TokenIssuer::new("secret".to_string(), CHALLENGE_TTL));
+        let service = FluxAuthService::new(tokens, user_store);
+
+        let mut issued = Vec::new();
+        for _ in 0..2 {
+            let request = Request::new(ChallengeRequest {
+                user_email: "alice@x".to_string(),
+            });
+            issued.push(
+                service
+                    .challenge(request)
+                    .await
+                    .unwrap()
+                    .into_inner()
+                    .challenge,
+            );
+        }
+        assert_ne!(issued[0], issued[1]);
+        let challenges = service.challenges.lock().await;
+        for challenge in &issued {
+            assert_eq!(challenges[challenge].0, "alice@x");
+        }
+    }
+
+    #[tokio::test]
+    async fn unknown_emails_are_refused_like_wrong_passwords() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| Some(dir.path().join(name).to_string_lossy().to_string());
+        let mut store = UserStore::new(path("users.json")).unwrap();
+        let credential = Secret::Password("right".to_string())
+            .into_credential("alice@x")
+            .unwrap();
+        store
+            .add_user("alice".into(), "alice@x".into(), credential, false)
+            .unwrap();
+        let tokens = Arc::new(TokenIssuer::new("secret".to_string(), CHALLENGE_TTL));
+        let repositories = RepoStore::open(path("repo_store.json")).unwrap();
+        let service = FluxAuthService::new(tokens, SharedUserStore::new(store))
+            .with_repositories(Arc::new(Mutex::new(repositories)));
+
+        let login = |user_email: &str, password: &str| {
+            service.login(Request::new(LoginRequest {
+                user_email: user_email.to_string(),
+                password: password.to_string(),
+                ..Default::default()
+            }))
+        };
+        let wrong_password = login("alice@x", "wrong").await.unwrap_err();
+        let unknown_email = login("mallory@x", "wrong").await.unwrap_err();
+        assert_eq!(unknown_email.code(), tonic::Code::Unauthenticated);
+        assert_eq!(unknown_email.message(), wrong_password.message());
+        assert!(login("alice@x", "right").await.is_ok());
+    }
+}
@@ -0,0 +1,493 @@
+use flux_core::error::RepositoryError;
+use flux_core::internals::repository::Repository;
+use flux_core::objects::signature::Signature;
+use proto::models::browse_service_server::BrowseService;
+use proto::models::{
+    CommitInfo, GetBlobRequest, GetBlobResponse, ListCommitsRequest, ListCommitsResponse,
+    ListRefsRequest, ListRefsResponse, ListTreeRequest, ListTreeResponse, Person, RefInfo,
+    TreeEntryInfo,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+use tempfile::TempDir;
+use tokio::sync::Mutex;
+use tonic::{Request, Response, Status};
+
+use super::push_service::{temp_dir, unpack_archive};
+use crate::auth_layer;
+use crate::policy_store::{self, PolicyStore, Role};
+use crate::repo_store::{DEFAULT_BRANCH, RepoStore};
+
+const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 1000;
+const DEFAULT_BLOB_LIMIT: usize = 1024 * 1024;
+const MAX_BLOB_LIMIT: usize = 4 * 1024 * 1024;
+/// Repositories kept unpacked, the least recently browsed one is dropped for a new one.
+const UNPACKED_LIMIT: usize = 16;
+
+#[derive(Debug)]
+pub struct FluxBrowseService {
+    upload_root: PathBuf,
+    policies: Arc<Mutex<PolicyStore>>,
+    repositories: Arc<Mutex<RepoStore>>,
+    /// Stored archives already unpacked, by repository directory.
+    unpacked: Mutex<HashMap<PathBuf, Unpacked>>,
+}
+
+/// A stored archive unpacked into a temporary directory. Requests browsing it hold the
+/// directory, so replacing it after a push does not pull it from under them.
+#[derive(Debug)]
+struct Unpacked {
+    /// Modification time and length of the archive, a push changes them.
+    archive: (Option<SystemTime>, u64),
+    dir: Arc<TempDir>,
+    used: Instant,
+}
+
+impl FluxBrowseService {
+    pub fn new(
+        upload_root: String,
+        policies: Arc<Mutex<PolicyStore>>,
+        repositories: Arc<Mutex<RepoStore>>,
+    ) -> Self {
+        Self {
+            upload_root: PathBuf::from(upload_root),
+            policies,
+            repositories,
+            unpacked: Mutex::default(),
+        }
+    }
+
+    /// Runs `f` on the stored repository of a repository the caller can read, with the
+    /// revision browsed when a request has none. The archive is only unpacked again once a
+    /// push replaced it.
+    async fn browse<T, R, F>(
+        &self,
+        request: &Request<R>,
+        repository: &str,
+        f: F,
+    ) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Repository, &str) -> Result<T, Status> + Send + 'static,
+    {
+        let identity = auth_layer::identity(request);
+        let (namespace, name) = policy_store::repository_path(repository, identity)?;
+        let repositories = self.repositories.lock().await;
+        let namespace_role = repositories.namespace_role(&namespace, identity);
+        let default_branch = repositories
+            .get(&namespace, &name)
+            .map(|entry| entry.default_branch.clone())
+            .unwrap_or_else(|| DEFAULT_BRANCH.to_string());
+        drop(repositories);
+        self.policies
+            .lock()
+            .await
+            .get(&namespace, &name)
+            .authorize(namespace_role, identity, Role::Read)?;
+
+        let repo_dir = self.upload_root.join(&namespace).join(&name);
+        let archive_path = repo_dir.join(".flux.tar.gz");
+        let metadata = match tokio::fs::metadata(&archive_path).await {
+            Ok(metadata) => metadata,
+            Err(_) if repo_dir.is_dir() => {
+                return Err(Status::not_found(format!(
+                    "Nothing was pushed to {namespace}/{name} yet."
+                )));
+            }
+            Err(_) => {
+                return Err(Status::not_found(format!(
+                    "Repository {namespace}/{name} does not exist."
+                )));
+            }
+        };
+        let archive = (metadata.modified().ok(), metadata.len());
+        let cached = self
+            .unpacked
+            .lock()
+            .await
+            .get_mut(&repo_dir)
+            .filter(|unpacked| unpacked.archive == archive)
+            .map(|unpacked| {
+                unpacked.used = Instant::now();
+                unpacked.dir.clone()
+            });
+        let fresh = cached.is_none();
+
+        let (dir, result) = tokio::task::spawn_blocking(move || {
+            let (dir, repository) = match cached {
+                Some(dir) => {
+                    let repository = open_unpacked(dir.path())?;
+                    (dir, repository)
+                }
+                None => {
+                    let bytes = std::fs::read(&archive_path).map_err(|e| {
+                        Status::internal(format!("Failed to read the stored repository: {e}"))
+                    })?;
+                    let dir = temp_dir()?;
+                    let repository = unpack_archive(&bytes, dir.path())?;
+                    (Arc::new(dir), repository)
+                }
+            };
+            // Repositories pushed from another branch than the default one are browsed at HEAD.
+            let default_revision = match repository.refs.branches.get(&default_branch) {
+                Some(hash) if !hash.is_empty() => default_branch,
+                _ => "HEAD".to_string(),
+            };
+            Ok::<_, Status>((dir, f(&repository, &default_revision)))
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))??;
+
+        if fresh {
+            let mut unpacked = self.unpacked.lock().await;
+            if unpacked.len() >= UNPACKED_LIMIT && !unpacked.contains_key(&repo_dir) {
+                let oldest = unpacked
+                    .iter()
+                    .min_by_key(|(_, unpacked)| unpacked.used)
+                    .map(|(repo_dir, _)| repo_dir.clone());
+                if let Some(oldest) = oldest {
+                    unpacked.remove(&oldest);
+                }
+            }
+            let used = Instant::now();
+            unpacked.insert(repo_dir, Unpacked { archive, dir, used });
+        }
+        result
+    }
+
+    /// Content of a file from the offset of the request to its end, in one response instead
+    /// of the pages of `get_blob`.
+    pub async fn read_blob(
+        &self,
+        request: Request<GetBlobRequest>,
+    ) -> Result<GetBlobResponse, Status> {
+        self.blob(&request, None).await
+    }
+
+    /// At most `limit` bytes of a file, from the offset of the request.
+    async fn blob(
+        &self,
+        request: &Request<GetBlobRequest>,
+        limit: Option<usize>,
+    ) -> Result<GetBlobResponse, Status> {
+        let GetBlobRequest {
+            repository,
+            revision: requested,
+            path,
+            offset,
+            ..
+        } = request.get_ref().clone();
+
+        self.browse(request, &repository, move |repository, default_revision| {
+            let revision = revision(requested, default_revision);
+            let commit = repository
+                .resolve_revision(&revision)
+                .map_err(browse_error)?;
+            let (hash, content) = repository.blob_at(&revision, &path).map_err(browse_error)?;
+
+            let size = content.len();
+            let start = usize::try_from(offset).unwrap_or(usize::MAX);
+            if start > size {
+                return Err(Status::out_of_range(format!(
+                    "Offset {offset} is past the end of the {size} bytes of '{path}'."
+                )));
+            }
+            let end = limit.map_or(size, |limit| size.min(start.saturating_add(limit)));
+            let next_offset = match end < size {
+                true => end as u64,
+                false => 0,
+            };
+
+            Ok(GetBlobResponse {
+                commit,
+                hash,
+                size: size as u64,
+                content: content[start..end].to_vec(),
+                next_offset,
+            })
+        })
+        .await
+    }
+}
+
+fn open_unpacked(dir: &Path) -> Result<Repository, Status> {
+    Repository::open(Some(dir.to_string_lossy().to_string()))
+        .map_err(|e| Status::internal(format!("Failed to read the stored repository: {e}")))
+}
+
+fn browse_error(e: RepositoryError) -> Status {
+    match e {
+        RepositoryError::UnknownRevision(_) | RepositoryError::PathNotInRevision { .. } => {
+            Status::not_found(e.to_string())
+        }
+        RepositoryError::AmbiguousRevision(_)
+        | RepositoryError::NotADirectory(_)
+        | RepositoryError::NotAFile(_) => Status::invalid_argument(e.to_string()),
+        e => Status::internal(format!("Failed to read the stored repository: {e}")),
+    }
+}
+
+fn page_size(requested: u32) -> usize {
+    match requested {
+        0 => DEFAULT_PAGE_SIZE,
+        size => (size as usize).min(MAX_PAGE_SIZE),
+    }
+}
+
+/// The page of `items`, sorted by `key`, after the item whose key is `page_token`. Returns the
+/// page and the token of the next one.
+fn page<T>(
+    mut items: Vec<T>,
+    key: impl Fn(&T) -> &str,
+    page_token: &str,
+    page_size: usize,
+) -> (Vec<T>, String) {
+    items.sort_by(|a, b| key(a).cmp(key(b)));
+    let start = match page_token {
+        "" => 0,
+        token => items.partition_point(|item| key(item) <= token),
+    };
+    let mut items = items.split_off(start);
+    if items.len() <= page_size {
+        return (items, String::new());
+    }
+    items.truncate(page_size);
+    let next_page_token = items.last().map(|item| key(item).to_string());
+    (items, next_page_token.unwrap_or_default())
+}
+
+fn person(signature: Option<Signature>) -> Option<Person> {
+    signature.map(|signature| Person {
+        name: signature.name,
+        email: signature.email,
+        time: signature.time.timestamp(),
+        utc_offset: signature.time.offset().local_minus_utc(),
+    })
+}
+
+fn revision(requested: String, default_revision: &str) -> String {
+    match requested.is_empty() {
+        true => default_revision.to_string(),
+        false => requested,
+    }
+}
+
+#[tonic::async_trait]
+impl BrowseService for FluxBrowseService {
+    async fn list_refs(
+        &self,
+        request: Request<ListRefsRequest>,
+    ) -> Result<Response<ListRefsResponse>, Status> {
+        let ListRefsRequest {
+            repository,
+            page_size: size,
+            page_token,
+        } = request.get_ref().clone();
+
+        let response = self
+            .browse(&request, &repository, move |repository, _| {
+                let refs: Vec<RefInfo> = repository
+                    .refs
+                    .branches
+                    .iter()
+                    .filter(|(_, hash)| !hash.is_empty())
+                    .map(|(branch, hash)| RefInfo {
+                        name: format!("refs/heads/{}", branch),
+                        commit: hash.clone(),
+                    })
+                    .collect();
+                let (refs, next_page_token) =
+                    page(refs, |r| r.name.as_str(), &page_token, page_size(size));
+
+                Ok(ListRefsResponse {
+                    head: repository.refs.head_ref().unwrap_or_default(),
+                    refs,
+                    next_page_token,
+                })
+            })
+            .await?;
+        Ok(Response::new(response))
+    }
+
+    /// The page token is the hash of the first commit of the next page.
+    async fn list_commits(
+        &self,
+        request: Request<ListCommitsRequest>,
+    ) -> Result<Response<ListCommitsResponse>, Status> {
+        let ListCommitsRequest {
+            repository,
+            revision: requested,
+            page_size: size,
+            page_token,
+        } = request.get_ref().clone();
+
+        let response = self
+            .browse(
+                &request,
+                &repository,
+                move |repository, default_revision| {
+                    let start = match page_token.is_empty() {
+                        true => revision(requested, default_revision),
+                        false => page_token,
+                    };
+                    let size = page_size(size);
+                    let mut history = repository.history(&start, size + 1).map_err(browse_error)?;
+                    let next_page_token = match history.len() > size {
+                        true => history.pop().map(|(hash, _)| hash).unwrap_or_default(),
+                        false => String::new(),
+                    };
+
+                    let commits = history
+                        .into_iter()
+                        .map(|(hash, commit)| CommitInfo {
+                            hash,
+                            tree: commit.tree_hash.clone(),
+                            parents: commit.parents().to_vec(),
+                            author: person(commit.author_signature()),
+                            committer: person(commit.committer_signature()),
+                            message: commit.message(),
+                        })
+                        .collect();
+                    Ok(ListCommitsResponse {
+                        commits,
+                        next_page_token,
+                    })
+                },
+            )
+            .await?;
+        Ok(Response::new(response))
+    }
+
+    async fn list_tree(
+        &self,
+        request: Request<ListTreeRequest>,
+    ) -> Result<Response<ListTreeResponse>, Status> {
+        let ListTreeRequest {
+            repository,
+            revision: requested,
+            path,
+            page_size: size,
+            page_token,
+        } = request.get_ref().clone();
+
+        let response = self
+            .browse(
+                &request,
+                &repository,
+                move |repository, default_revision| {
+                    let revision = revision(requested, default_revision);
+                    let commit = repository
+                        .resolve_revision(&revision)
+                        .map_err(browse_error)?;
+                    let (_, entries) =
+                        repository.tree_at(&revision, &path).map_err(browse_error)?;
+
+                    let dir = path.trim_start_matches("./").trim_matches('/');
+                    let entries: Vec<TreeEntryInfo> = entries
+                        .into_iter()
+                        .map(|entry| TreeEntryInfo {
+                            path: match dir {
+                                "" | "." => entry.name.clone(),
+                                dir => format!("{}/{}", dir, entry.name),
+                            },
+                            kind: match entry.is_dir() {
+                                true => "tree",
+                                false => "blob",
+                            }
+                            .to_string(),
+                            name: entry.name,
+                            mode: entry.mode,
+                            hash: entry.hash,
+                        })
+                        .collect();
+                    let (entries, next_page_token) =
+                        page(entries, |e| e.name.as_str(), &page_token, page_size(size));
+
+                    Ok(ListTreeResponse {
+                        commit,
+                        entries,
+                        next_page_token,
+                    })
+                },
+            )
+            .await?;
+        Ok(Response::new(response))
+    }
+
+    async fn get_blob(
+        &self,
+        request: Request<GetBlobRequest>,
+    ) -> Result<Response<GetBlobResponse>, Status> {
+        let limit = match request.get_ref().limit {
+            0 => DEFAULT_BLOB_LIMIT,
+            limit => (limit as usize).min(MAX_BLOB_LIMIT),
+        };
+        Ok(Response::new(self.blob(&request, Some(limit)).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::fs::{self, File};
+    use std::time::Duration;
+
+    /// Stores the archive of a new repository as `alice/project`, as a push does.
+    fn store_archive(upload_root: &Path, modified: SystemTime) {
+        let work = tempfile::tempdir().unwrap();
+        Repository::init(Some(work.path().to_string_lossy().to_string()), false).unwrap();
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        tar.append_dir_all(".", work.path().join(".flux")).unwrap();
+
+        let repo_dir = upload_root.join("alice").join("project");
+        fs::create_dir_all(&repo_dir).unwrap();
+        let archive = repo_dir.join(".flux.tar.gz");
+        fs::write(&archive, tar.into_inner().unwrap().finish().unwrap()).unwrap();
+        File::options()
+            .write(true)
+            .open(&archive)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn archives_are_unpacked_again_only_after_a_push() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| Some(dir.path().join(name).to_string_lossy().to_string());
+        let upload_root = dir.path().join("uploads");
+        let service = FluxBrowseService::new(
+            upload_root.to_string_lossy().to_string(),
+            Arc::new(Mutex::new(
+                PolicyStore::open(path("policies.json")).unwrap(),
+            )),
+            Arc::new(Mutex::new(
+                RepoStore::open(path("repo_store.json")).unwrap(),
+            )),
+        );
+        let unpacked_dir = || async {
+            let request = Request::new(ListRefsRequest {
+                repository: "alice/project".to_string(),
+                ..Default::default()
+            });
+            service.list_refs(request).await.unwrap();
+            let unpacked = service.unpacked.lock().await;
+            unpacked[&upload_root.join("alice").join("project")]
+                .dir
+                .clone()
+        };
+
+        let pushed_at = SystemTime::now() - Duration::from_secs(60);
+        store_archive(&upload_root, pushed_at);
+        let first = unpacked_dir().await;
+        assert!(Arc::ptr_eq(&first, &unpacked_dir().await));
+
+        store_archive(&upload_root, pushed_at + Duration::from_secs(30));
+        assert!(!Arc::ptr_eq(&first, &unpacked_dir().await));
+    }
+}
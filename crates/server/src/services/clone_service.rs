@@ -3,13 +3,21 @@ use proto::models::clone_service_server::CloneService;
 use proto::models::{Chunk, CloneRequest};
 use std::path::PathBuf;
 use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status};
 
+use crate::auth_layer;
+use crate::policy_store::{self, PolicyStore, Role};
+use crate::repo_store::RepoStore;
+
 #[derive(Debug, Default)]
 pub struct FluxCloneService {
     upload_root: String,
     chunk_size: usize,
+    policies: Arc<Mutex<PolicyStore>>,
+    repositories: Arc<Mutex<RepoStore>>,
 }
 
 impl FluxCloneService {
@@ -17,8 +25,20 @@ impl FluxCloneService {
         Self {
             upload_root,
             chunk_size: 256 * 1024,
+            policies: Arc::default(),
+            repositories: Arc::default(),
         }
     }
+
+    pub fn with_policies(mut self, policies: Arc<Mutex<PolicyStore>>) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    pub fn with_repositories(mut self, repositories: Arc<Mutex<RepoStore>>) -> Self {
+        self.repositories = repositories;
+        self
+    }
 }
 
 #[tonic::async_trait]
@@ -30,34 +50,20 @@ impl CloneService for FluxCloneService {
         &self,
         request: Request<CloneRequest>,
     ) -> Result<Response<Self::CloneRepositoryStream>, Status> {
+        let identity = auth_layer::identity(&request).cloned();
         let full_path = request.into_inner().name;
 
-        let parts: Vec<String> = full_path
-            .split('/')
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .collect();
-
-        if parts.len() < 2 {
-            return Err(Status::invalid_argument(
-                "Path must be in format 'user/repo'",
-            ));
-        }
-
-        let user_name = parts[0].clone();
-        let repo_name = parts[1].clone();
-
-        let safe_user = std::path::Path::new(&user_name)
-            .file_name()
-            .and_then(|f| f.to_str())
-            .ok_or_else(|| Status::invalid_argument("Invalid user name"))?
-            .to_string();
-
-        let safe_repo = std::path::Path::new(&repo_name)
-            .file_name()
-            .and_then(|f| f.to_str())
-            .ok_or_else(|| Status::invalid_argument("Invalid repo name"))?
-            .to_string();
+        let (safe_user, safe_repo) = policy_store::repository_path(&full_path, identity.as_ref())?;
+        let namespace_role = self
+            .repositories
+            .lock()
+            .await
+            .namespace_role(&safe_user, identity.as_ref());
+        self.policies
+            .lock()
+            .await
+            .get(&safe_user, &safe_repo)
+            .authorize(namespace_role, identity.as_ref(), Role::Read)?;
 
         let (tx, rx) = tokio::sync::mpsc::channel(32);
         let upload_root = self.upload_root.clone();
@@ -1,3 +1,6 @@
+pub mod access_service;
+pub mod browse_service;
 pub mod clone_service;
 pub mod push_service;
+pub mod repository_service;
 pub mod auth_service;
\ No newline at end of file
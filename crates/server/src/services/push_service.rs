@@ -1,55 +1,267 @@
+use flux_core::internals::reflog::ZERO_HASH;
+use flux_core::internals::repository::Repository;
+use flux_core::internals::signing::AllowedSigners;
 use proto::models::push_service_server::PushService;
 use proto::models::{Chunk, UploadStatus, UploadStatusCode};
-use tokio::sync::Mutex;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use tempfile::TempDir;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
 use tonic::{Request, Response, Status, Streaming};
 
-use crate::user_store::UserStore;
+use crate::auth_layer;
+use crate::policy_store::{self, PolicyStore, RepoPolicy, Role, SignaturePolicy};
+use crate::receive_hooks::{self, HookOutput, ReceiveHooks, RefUpdate};
+use crate::repo_store::{RepoEntry, RepoStore};
+use crate::tokens::Scope;
 
 #[derive(Debug)]
 pub struct FluxPushService {
     upload_root: String,
-    user_store: Arc<Mutex<UserStore>>
+    /// `Some(strict)` when pushed repositories are checked before being stored
+    fsck: Option<bool>,
+    /// Largest archive in bytes a push may upload
+    max_push_size: Option<u64>,
+    policies: Arc<Mutex<PolicyStore>>,
+    repositories: Arc<Mutex<RepoStore>>,
 }
 
 impl FluxPushService {
-    pub fn new(upload_root: String, user_store: Arc<Mutex<UserStore>>) -> Self {
-        Self { upload_root, user_store }
+    pub fn new(upload_root: String) -> Self {
+        Self {
+            upload_root,
+            fsck: None,
+            max_push_size: None,
+            policies: Arc::default(),
+            repositories: Arc::default(),
+        }
+    }
+
+    pub fn with_fsck(mut self, strict: bool) -> Self {
+        self.fsck = Some(strict);
+        self
+    }
+
+    pub fn with_max_push_size(mut self, max_push_size: u64) -> Self {
+        self.max_push_size = Some(max_push_size);
+        self
+    }
+
+    pub fn with_policies(mut self, policies: Arc<Mutex<PolicyStore>>) -> Self {
+        self.policies = policies;
+        self
+    }
+
+    pub fn with_repositories(mut self, repositories: Arc<Mutex<RepoStore>>) -> Self {
+        self.repositories = repositories;
+        self
+    }
+}
+
+/// Unpacks a repository archive into `dir` and opens it.
+pub(crate) fn unpack_archive(archive: &[u8], dir: &Path) -> Result<Repository, Status> {
+    Repository::dearchive(archive.to_vec(), &dir.join(".flux"))
+        .map_err(|e| Status::invalid_argument(format!("Invalid repository archive: {}", e)))?;
+    Repository::open(Some(dir.to_string_lossy().to_string()))
+        .map_err(|e| Status::invalid_argument(format!("Invalid repository archive: {}", e)))
+}
+
+pub(crate) fn temp_dir() -> Result<TempDir, Status> {
+    tempfile::tempdir()
+        .map_err(|e| Status::internal(format!("Failed to create temporary directory: {}", e)))
+}
+
+/// Objects of the archive currently stored for the repository, so only the commits a push
+/// adds are held to the signature policy.
+fn known_objects(previous: Option<&[u8]>) -> Result<HashSet<String>, Status> {
+    let Some(previous) = previous else {
+        return Ok(HashSet::new());
+    };
+    let dir = temp_dir()?;
+    let repository = unpack_archive(previous, dir.path())?;
+    let objects = repository
+        .object_store
+        .list_objects()
+        .map_err(|e| Status::internal(format!("Failed to read stored repository: {}", e)))?;
+    Ok(objects.into_iter().collect())
+}
+
+/// Branches of a repository and the commits they point at.
+fn branch_heads(repository: &Repository) -> HashMap<String, String> {
+    repository
+        .refs
+        .branch_names()
+        .into_iter()
+        .filter_map(|branch| {
+            let hash = repository.resolve_revision(&branch).ok()?;
+            Some((format!("refs/heads/{}", branch), hash))
+        })
+        .collect()
+}
+
+/// Refs a push changes, comparing its branches with the ones of the stored archive.
+fn ref_updates(repository: &Repository, previous: Option<&[u8]>) -> Result<Vec<RefUpdate>, Status> {
+    let new = branch_heads(repository);
+    let old = match previous {
+        Some(previous) => {
+            let dir = temp_dir()?;
+            branch_heads(&unpack_archive(previous, dir.path())?)
+        }
+        None => HashMap::new(),
+    };
+
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+
+    let hash = |map: &HashMap<String, String>, name: &String| {
+        map.get(name)
+            .cloned()
+            .unwrap_or_else(|| ZERO_HASH.to_string())
+    };
+    Ok(names
+        .into_iter()
+        .map(|name| RefUpdate {
+            old: hash(&old, name),
+            new: hash(&new, name),
+            name: name.clone(),
+        })
+        .filter(|update| update.old != update.new)
+        .collect())
+}
+
+fn check_signatures(
+    repository: &Repository,
+    policy: &RepoPolicy,
+    known: &HashSet<String>,
+) -> Result<(), Status> {
+    let allowed = AllowedSigners::parse(&policy.allowed_signers.join("\n"))
+        .map_err(|e| Status::internal(format!("Invalid repository policy: {}", e)))?;
+    let verifications = repository
+        .verify_new_commits(known, &allowed)
+        .map_err(|e| Status::internal(format!("Failed to verify pushed commits: {}", e)))?;
+
+    let rejected: Vec<String> = verifications
+        .iter()
+        .filter(|(_, verification)| match policy.signatures {
+            SignaturePolicy::Off => false,
+            SignaturePolicy::Signed => !verification.is_valid(),
+            SignaturePolicy::Trusted => !verification.is_good(),
+        })
+        .map(|(hash, verification)| format!("{}: {}", hash, verification))
+        .collect();
+
+    if !rejected.is_empty() {
+        return Err(Status::permission_denied(format!(
+            "Pushed commits are rejected by the signature policy of the repository:\n{}",
+            rejected.join("\n")
+        )));
+    }
+
+    Ok(())
+}
+
+/// A pushed repository unpacked for the receive hooks, removed once dropped.
+struct Received {
+    dir: TempDir,
+    updates: Vec<RefUpdate>,
+}
+
+impl Received {
+    /// Runs the post-receive hooks from the unpacked repository, where the pre-receive ones
+    /// ran as well.
+    fn post_receive(&self, hooks: &ReceiveHooks) -> io::Result<HookOutput> {
+        hooks.run(receive_hooks::POST_RECEIVE, &self.updates, self.dir.path())
+    }
+}
+
+/// Checks a pushed repository before it is stored.
+struct Receive {
+    fsck: Option<bool>,
+    policy: RepoPolicy,
+    hooks: ReceiveHooks,
+}
+
+impl Receive {
+    fn is_needed(&self) -> bool {
+        self.fsck.is_some()
+            || self.policy.signatures != SignaturePolicy::Off
+            || self.hooks.exists(receive_hooks::PRE_RECEIVE)
+            || self.hooks.exists(receive_hooks::POST_RECEIVE)
+    }
+
+    /// Unpacks a pushed archive into a temporary directory, verifies it and runs the
+    /// pre-receive hooks from there. Returns the unpacked repository with its ref updates, or
+    /// the rejection message of a failed hook.
+    fn check(
+        &self,
+        archive: &[u8],
+        previous: Option<&[u8]>,
+    ) -> Result<Result<Received, String>, Status> {
+        let dir = temp_dir()?;
+        let repository = unpack_archive(archive, dir.path())?;
+        verify_repository(&repository, self.fsck, &self.policy, previous)?;
+
+        if !self.hooks.exists(receive_hooks::PRE_RECEIVE)
+            && !self.hooks.exists(receive_hooks::POST_RECEIVE)
+        {
+            return Ok(Ok(Received {
+                dir,
+                updates: Vec::new(),
+            }));
+        }
+        let updates = ref_updates(&repository, previous)?;
+        let output = self
+            .hooks
+            .run(receive_hooks::PRE_RECEIVE, &updates, dir.path())
+            .map_err(|e| Status::internal(format!("Failed to run the pre-receive hook: {}", e)))?;
+        if !output.success {
+            return Ok(Err(output.output));
+        }
+        Ok(Ok(Received { dir, updates }))
     }
 }
 
+/// Runs fsck on a pushed repository when `fsck` is set and checks the signatures of the new
+/// commits against the repository policy.
+fn verify_repository(
+    repository: &Repository,
+    fsck: Option<bool>,
+    policy: &RepoPolicy,
+    previous: Option<&[u8]>,
+) -> Result<(), Status> {
+    if let Some(strict) = fsck {
+        let report = repository
+            .fsck(strict)
+            .map_err(|e| Status::internal(format!("Failed to check pushed objects: {}", e)))?;
+
+        if !report.is_ok() {
+            let problems: Vec<String> = report.problems.iter().map(|p| p.to_string()).collect();
+            return Err(Status::invalid_argument(format!(
+                "Pushed objects failed fsck:\n{}",
+                problems.join("\n")
+            )));
+        }
+    }
+
+    if policy.signatures != SignaturePolicy::Off {
+        check_signatures(repository, policy, &known_objects(previous)?)?;
+    }
+
+    Ok(())
+}
+
 #[tonic::async_trait]
 impl PushService for FluxPushService {
     async fn push(
         &self,
         request: Request<Streaming<Chunk>>,
     ) -> Result<Response<UploadStatus>, Status> {
-        let metadata = request.metadata();
-
-        let user_email = metadata
-            .get("user-email")
-            .and_then(|v| v.to_str().ok())
-            .ok_or_else(|| Status::unauthenticated("Missing user-email header"))?
-            .to_string();
-
-        let user_name = metadata
-            .get("user-name")
-            .and_then(|v| v.to_str().ok())
-            .ok_or_else(|| Status::unauthenticated("Missing user-name header"))?
-            .to_string();
-
-        let access_token = metadata
-            .get("authorization")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.strip_prefix("Bearer "))
-            .ok_or_else(|| Status::unauthenticated("Missing or invalid authorization token"))?
-            .to_string();
-
-        if !self.user_store.lock().await.is_token_valid(user_name.clone(), user_email, access_token).await {
-            return Err(Status::permission_denied("Failed to validate user credentials"));
-        }
+        // Repositories are stored under the name of the authenticated user.
+        let claims = auth_layer::require(&request, Scope::Write)?;
 
         let mut stream = request.into_inner();
         let mut repo_name: Option<String> = None;
@@ -60,31 +272,77 @@ impl PushService for FluxPushService {
                 repo_name = Some(chunk.repo_name.clone());
             }
             buf.extend_from_slice(&chunk.content);
+            // Checked while receiving, an oversized push is never held in memory whole.
+            if let Some(max) = self.max_push_size.filter(|max| buf.len() as u64 > *max) {
+                return Err(Status::resource_exhausted(format!(
+                    "The pushed repository is larger than the limit of {} bytes",
+                    max
+                )));
+            }
         }
 
         let raw_repo_name = repo_name
-            .filter(|n| !n.is_empty() && !n.chars().all(|c| c == '.'))
             .ok_or_else(|| Status::invalid_argument("Missing or invalid repository name"))?;
 
-        let safe_user_dir = std::path::Path::new(&user_name)
-            .file_name()
-            .and_then(|f| f.to_str())
-            .ok_or_else(|| Status::invalid_argument("Invalid user name"))?;
-
-        let safe_repo_name = std::path::Path::new(&raw_repo_name)
-            .file_name()
-            .and_then(|f| f.to_str())
-            .ok_or_else(|| Status::invalid_argument("Invalid repository name"))?;
+        // `<repo>` is a repository of the caller, `<owner>/<repo>` one shared with them.
+        let (owner, repo) = policy_store::repository_path(&raw_repo_name, Some(&claims))?;
+        let namespace_role = self
+            .repositories
+            .lock()
+            .await
+            .namespace_role(&owner, Some(&claims));
+        let policy = self.policies.lock().await.get(&owner, &repo);
+        policy.authorize(namespace_role, Some(&claims), Role::Write)?;
+        let (safe_user_dir, safe_repo_name) = (owner.as_str(), repo.as_str());
 
         let repo_dir = PathBuf::from(&self.upload_root)
             .join(safe_user_dir)
             .join(safe_repo_name);
 
+        let archive_path = repo_dir.join(".flux.tar.gz");
+        let hooks = ReceiveHooks::new(
+            Path::new(&self.upload_root),
+            safe_user_dir,
+            safe_repo_name,
+            &claims.name,
+        );
+        let receive = Receive {
+            fsck: self.fsck,
+            policy,
+            hooks: hooks.clone(),
+        };
+        let mut received = None;
+        if receive.is_needed() {
+            let previous = tokio::fs::read(&archive_path).await.ok();
+            let checked = tokio::task::spawn_blocking(move || {
+                receive
+                    .check(&buf, previous.as_deref())
+                    .map(|checked| checked.map(|unpacked| (buf, unpacked)))
+            })
+            .await
+            .map_err(|e| Status::internal(e.to_string()))??;
+
+            match checked {
+                Ok((checked, unpacked)) => {
+                    buf = checked;
+                    received = Some(unpacked);
+                }
+                Err(message) => {
+                    return Ok(Response::new(UploadStatus {
+                        response_message: format!(
+                            "Push rejected by the pre-receive hook:\n{}",
+                            message.trim_end()
+                        ),
+                        code: UploadStatusCode::Failed as i32,
+                    }));
+                }
+            }
+        }
+
         tokio::fs::create_dir_all(&repo_dir).await.map_err(|e| {
             Status::internal(format!("Failed to create directory: {}", e))
         })?;
 
-        let archive_path = repo_dir.join(".flux.tar.gz");
         let mut file = tokio::fs::File::create(&archive_path)
             .await
             .map_err(|e| Status::internal(format!("Failed to create file: {}", e)))?;
@@ -92,9 +350,171 @@ impl PushService for FluxPushService {
         file.write_all(&buf).await.map_err(|e| Status::internal(e.to_string()))?;
         file.flush().await.map_err(|e| Status::internal(e.to_string()))?;
 
+        // The first push creates a repository that was not created with the repository service.
+        let mut repositories = self.repositories.lock().await;
+        if repositories.get(safe_user_dir, safe_repo_name).is_none() {
+            repositories
+                .insert(safe_user_dir, safe_repo_name, RepoEntry::default())
+                .map_err(|e| Status::internal(format!("Failed to save the repository: {}", e)))?;
+        }
+        drop(repositories);
+        tracing::info!(
+            user = %claims.sub,
+            repository = %format!("{}/{}", safe_user_dir, safe_repo_name),
+            size = buf.len(),
+            "Stored a push"
+        );
+
+        let mut response_message = format!("Stored in {}/{}", safe_user_dir, safe_repo_name);
+        // A post-receive hook makes the push unpacked, see `Receive::is_needed`.
+        if let Some(received) = received.filter(|_| hooks.exists(receive_hooks::POST_RECEIVE)) {
+            // The push is stored already, a failing hook is only reported.
+            let output = tokio::task::spawn_blocking(move || received.post_receive(&hooks))
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+                .map_err(|e| {
+                    Status::internal(format!("Failed to run the post-receive hook: {}", e))
+                })?;
+
+            if !output.output.trim().is_empty() {
+                response_message.push('\n');
+                response_message.push_str(output.output.trim_end());
+            }
+            if !output.success {
+                response_message.push_str("\nThe post-receive hook failed.");
+            }
+        }
+
         Ok(Response::new(UploadStatus {
-            response_message: format!("Stored in {}/{}", safe_user_dir, safe_repo_name),
+            response_message,
             code: UploadStatusCode::Ok as i32,
         }))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokens::Claims;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use proto::models::push_service_client::PushServiceClient;
+    use proto::models::push_service_server::PushServiceServer;
+    use std::fs;
+    use tonic::transport::Server;
+    use tonic::transport::server::TcpIncoming;
+
+    /// Archive of the `.flux` directory of a new repository, as a client pushes it.
+    fn pushed_archive(dir: &Path) -> Vec<u8> {
+        let work = dir.join("work");
+        fs::create_dir_all(&work).unwrap();
+        Repository::init(Some(work.to_string_lossy().to_string()), false).unwrap();
+
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        tar.append_dir_all(".", work.join(".flux")).unwrap();
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    /// Writes an executable hook into `dir`.
+    #[cfg(unix)]
+    fn write_hook(dir: &Path, name: &str, script: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        fs::create_dir_all(dir).unwrap();
+        let hook = dir.join(name);
+        fs::write(&hook, script).unwrap();
+        fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn receive_hooks_run_in_the_pushed_repository() {
+        let upload_root = tempfile::tempdir().unwrap();
+        let hooks_dir = upload_root.path().join(receive_hooks::HOOKS_DIR);
+        let out = upload_root.path().join("hooks.out");
+        for name in [receive_hooks::PRE_RECEIVE, receive_hooks::POST_RECEIVE] {
+            let script = format!(
+                "#!/bin/sh\ntest -d .flux/objects && echo \"{} $(pwd -P)\" >> '{}'\n",
+                name,
+                out.display()
+            );
+            write_hook(&hooks_dir, name, &script);
+        }
+
+        let receive = Receive {
+            fsck: None,
+            policy: RepoPolicy::default(),
+            hooks: ReceiveHooks::new(upload_root.path(), "alice", "project", "alice"),
+        };
+        let received = receive
+            .check(&pushed_archive(upload_root.path()), None)
+            .unwrap()
+            .unwrap();
+        assert!(received.post_receive(&receive.hooks).unwrap().success);
+
+        let dir = received.dir.path().canonicalize().unwrap();
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            format!("pre-receive {0}\npost-receive {0}\n", dir.display())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hooks_are_told_who_pushed_and_not_the_owner() {
+        let upload_root = tempfile::tempdir().unwrap();
+        let out = upload_root.path().join("hooks.out");
+        write_hook(
+            &upload_root.path().join(receive_hooks::HOOKS_DIR),
+            receive_hooks::POST_RECEIVE,
+            &format!(
+                "#!/bin/sh\necho \"$FLUX_PUSH_USER $FLUX_REPOSITORY\" >> '{}'\n",
+                out.display()
+            ),
+        );
+
+        let path = |name: &str| Some(upload_root.path().join(name).to_string_lossy().to_string());
+        let mut repositories = RepoStore::open(path("repo_store.json")).unwrap();
+        repositories.claim_namespace("alice", "alice@x").unwrap();
+        let mut policies = PolicyStore::open(path("policies.json")).unwrap();
+        policies
+            .update("alice", "project", |policy| {
+                policy.collaborators.insert("bob@x".to_string(), Role::Write);
+            })
+            .unwrap();
+        let service = FluxPushService::new(upload_root.path().to_string_lossy().to_string())
+            .with_policies(Arc::new(Mutex::new(policies)))
+            .with_repositories(Arc::new(Mutex::new(repositories)));
+
+        // Stands in for the auth layer, bob is authenticated.
+        let bob = Claims {
+            sub: "bob@x".to_string(),
+            name: "bob".to_string(),
+            jti: String::new(),
+            iat: 0,
+            exp: 0,
+            scopes: Scope::DEFAULT.to_vec(),
+        };
+        let server = PushServiceServer::with_interceptor(service, move |mut request: Request<()>| {
+            request.extensions_mut().insert(bob.clone());
+            Ok(request)
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        tokio::spawn(
+            Server::builder()
+                .add_service(server)
+                .serve_with_incoming(incoming),
+        );
+
+        let chunk = Chunk {
+            repo_name: "alice/project".to_string(),
+            content: pushed_archive(upload_root.path()),
+        };
+        let mut client = PushServiceClient::connect(format!("http://{addr}")).await.unwrap();
+        client.push(tokio_stream::iter([chunk])).await.unwrap();
+
+        assert_eq!(fs::read_to_string(&out).unwrap(), "bob alice/project\n");
+    }
+}
@@ -0,0 +1,678 @@
+use proto::models::repository_service_server::RepositoryService;
+use proto::models::{
+    Collaborator, CreateRepositoryRequest, ListRepositoriesRequest, ListRepositoriesResponse,
+    MemberRequest, OrganizationInfo, OrganizationRequest, RenameRepositoryRequest, RepositoryInfo,
+    RepositoryRequest,
+};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tonic::{Request, Response, Status};
+
+use crate::auth_layer;
+use crate::error::RepoStoreError;
+use crate::policy_store::{self, PolicyStore, RepoPolicy, Role, Visibility};
+use crate::repo_store::{DEFAULT_BRANCH, Organization, RepoEntry, RepoStore};
+use crate::tokens::{Claims, Scope};
+use crate::user_store::SharedUserStore;
+
+#[derive(Debug)]
+pub struct FluxRepositoryService {
+    upload_root: PathBuf,
+    policies: Arc<Mutex<PolicyStore>>,
+    repositories: Arc<Mutex<RepoStore>>,
+    user_store: SharedUserStore,
+}
+
+impl FluxRepositoryService {
+    pub fn new(
+        upload_root: String,
+        policies: Arc<Mutex<PolicyStore>>,
+        repositories: Arc<Mutex<RepoStore>>,
+        user_store: SharedUserStore,
+    ) -> Self {
+        Self {
+            upload_root: PathBuf::from(upload_root),
+            policies,
+            repositories,
+            user_store,
+        }
+    }
+
+    fn repo_dir(&self, namespace: &str, name: &str) -> PathBuf {
+        self.upload_root.join(namespace).join(name)
+    }
+
+    /// Checks that the caller may act with `role` on an existing repository, returns its
+    /// policy.
+    async fn authorize(
+        &self,
+        namespace: &str,
+        name: &str,
+        identity: Option<&Claims>,
+        role: Role,
+    ) -> Result<RepoPolicy, Status> {
+        let namespace_role = self
+            .repositories
+            .lock()
+            .await
+            .namespace_role(namespace, identity);
+        let policy = self.policies.lock().await.get(namespace, name);
+        policy.authorize(namespace_role, identity, role)?;
+
+        if !self.repo_dir(namespace, name).is_dir() {
+            return Err(Status::not_found(format!(
+                "Repository {namespace}/{name} does not exist."
+            )));
+        }
+        Ok(policy)
+    }
+
+    async fn info(&self, namespace: &str, name: &str, policy: &RepoPolicy) -> RepositoryInfo {
+        let entry = self.repositories.lock().await.get(namespace, name).cloned();
+        let (description, default_branch, created_at) = match entry {
+            Some(entry) => (entry.description, entry.default_branch, entry.created_at),
+            None => (String::new(), DEFAULT_BRANCH.to_string(), 0),
+        };
+        RepositoryInfo {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            description,
+            default_branch,
+            created_at,
+            visibility: policy.visibility.to_string(),
+        }
+    }
+}
+
+fn store_status(error: RepoStoreError) -> Status {
+    match error {
+        RepoStoreError::NamespaceTaken(_) => Status::already_exists(error.to_string()),
+        RepoStoreError::UnknownOrganization(_) => Status::not_found(error.to_string()),
+        _ => Status::internal(format!("Failed to save the repository store. {error}")),
+    }
+}
+
+fn policy_status(error: crate::error::PolicyStoreError) -> Status {
+    Status::internal(format!("Failed to save the repository policy. {error}"))
+}
+
+fn io_status(error: std::io::Error) -> Status {
+    Status::internal(format!(
+        "Failed to update the repository directory. {error}"
+    ))
+}
+
+fn organization_info(name: &str, organization: &Organization) -> OrganizationInfo {
+    let mut members: Vec<Collaborator> = organization
+        .members
+        .iter()
+        .map(|(email, role)| Collaborator {
+            user_email: email.clone(),
+            role: role.to_string(),
+        })
+        .collect();
+    members.sort_by(|a, b| a.user_email.cmp(&b.user_email));
+
+    OrganizationInfo {
+        name: name.to_string(),
+        created_at: organization.created_at,
+        members,
+    }
+}
+
+#[tonic::async_trait]
+impl RepositoryService for FluxRepositoryService {
+    async fn create_repository(
+        &self,
+        request: Request<CreateRepositoryRequest>,
+    ) -> Result<Response<RepositoryInfo>, Status> {
+        let claims = auth_layer::authenticated(&request)?;
+        let req = request.into_inner();
+        let (namespace, name) = policy_store::repository_path(&req.repository, Some(&claims))?;
+        let visibility: Visibility = match req.visibility.is_empty() {
+            true => Visibility::default(),
+            false => req.visibility.parse()?,
+        };
+
+        // A new repository has no collaborators, so only the namespace gives access to it.
+        let namespace_role = self
+            .repositories
+            .lock()
+            .await
+            .namespace_role(&namespace, Some(&claims));
+        RepoPolicy::default().authorize(namespace_role, Some(&claims), Role::Write)?;
+
+        let dir = self.repo_dir(&namespace, &name);
+        if dir.exists() {
+            return Err(Status::already_exists(format!(
+                "Repository {namespace}/{name} already exists."
+            )));
+        }
+        tokio::fs::create_dir_all(&dir).await.map_err(io_status)?;
+
+        let default_branch = match req.default_branch.is_empty() {
+            true => DEFAULT_BRANCH.to_string(),
+            false => req.default_branch,
+        };
+        self.repositories
+            .lock()
+            .await
+            .insert(
+                &namespace,
+                &name,
+                RepoEntry::new(req.description, default_branch),
+            )
+            .map_err(store_status)?;
+        let policy = self
+            .policies
+            .lock()
+            .await
+            .update(&namespace, &name, |policy| policy.visibility = visibility)
+            .map_err(policy_status)?;
+
+        Ok(Response::new(self.info(&namespace, &name, &policy).await))
+    }
+
+    async fn delete_repository(
+        &self,
+        request: Request<RepositoryRequest>,
+    ) -> Result<Response<RepositoryInfo>, Status> {
+        let claims = auth_layer::authenticated(&request)?;
+        let (namespace, name) =
+            policy_store::repository_path(&request.get_ref().repository, Some(&claims))?;
+        let policy = self
+            .authorize(&namespace, &name, Some(&claims), Role::Admin)
+            .await?;
+        let info = self.info(&namespace, &name, &policy).await;
+
+        tokio::fs::remove_dir_all(self.repo_dir(&namespace, &name))
+            .await
+            .map_err(io_status)?;
+        self.repositories
+            .lock()
+            .await
+            .remove(&namespace, &name)
+            .map_err(store_status)?;
+        self.policies
+            .lock()
+            .await
+            .remove(&namespace, &name)
+            .map_err(policy_status)?;
+
+        Ok(Response::new(info))
+    }
+
+    async fn rename_repository(
+        &self,
+        request: Request<RenameRepositoryRequest>,
+    ) -> Result<Response<RepositoryInfo>, Status> {
+        let claims = auth_layer::authenticated(&request)?;
+        let req = request.into_inner();
+        let (namespace, name) = policy_store::repository_path(&req.repository, Some(&claims))?;
+        let (_, new_name) =
+            policy_store::repository_path(&format!("{namespace}/{}", req.new_name), None)?;
+        self.authorize(&namespace, &name, Some(&claims), Role::Admin)
+            .await?;
+
+        let new_dir = self.repo_dir(&namespace, &new_name);
+        if new_dir.exists() {
+            return Err(Status::already_exists(format!(
+                "Repository {namespace}/{new_name} already exists."
+            )));
+        }
+        tokio::fs::rename(self.repo_dir(&namespace, &name), &new_dir)
+            .await
+            .map_err(io_status)?;
+
+        // Metadata and policy follow the repository, its hooks live in its directory.
+        let mut repositories = self.repositories.lock().await;
+        if let Some(entry) = repositories
+            .remove(&namespace, &name)
+            .map_err(store_status)?
+        {
+            repositories
+                .insert(&namespace, &new_name, entry)
+                .map_err(store_status)?;
+        }
+        drop(repositories);
+        let mut policies = self.policies.lock().await;
+        let policy = policies
+            .remove(&namespace, &name)
+            .map_err(policy_status)?
+            .unwrap_or_default();
+        let policy = policies
+            .update(&namespace, &new_name, |p| *p = policy)
+            .map_err(policy_status)?;
+        drop(policies);
+
+        Ok(Response::new(
+            self.info(&namespace, &new_name, &policy).await,
+        ))
+    }
+
+    async fn describe_repository(
+        &self,
+        request: Request<RepositoryRequest>,
+    ) -> Result<Response<RepositoryInfo>, Status> {
+        let identity = auth_layer::identity(&request).cloned();
+        let (namespace, name) =
+            policy_store::repository_path(&request.get_ref().repository, identity.as_ref())?;
+        let policy = self
+            .authorize(&namespace, &name, identity.as_ref(), Role::Read)
+            .await?;
+
+        Ok(Response::new(self.info(&namespace, &name, &policy).await))
+    }
+
+    /// Repositories of a namespace the caller can read.
+    async fn list_repositories(
+        &self,
+        request: Request<ListRepositoriesRequest>,
+    ) -> Result<Response<ListRepositoriesResponse>, Status> {
+        let identity = auth_layer::identity(&request).cloned();
+        let namespace = match (request.into_inner().namespace, &identity) {
+            (namespace, _) if !namespace.is_empty() => policy_store::namespace(&namespace)?,
+            (_, Some(claims)) => claims.name.clone(),
+            (_, None) => {
+                return Err(Status::invalid_argument(
+                    "A namespace is required without an access token.",
+                ));
+            }
+        };
+
+        let mut names = Vec::new();
+        if let Ok(mut entries) = tokio::fs::read_dir(self.upload_root.join(&namespace)).await {
+            while let Some(entry) = entries.next_entry().await.map_err(io_status)? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with('.') && entry.path().is_dir() {
+                    names.push(name);
+                }
+            }
+        }
+        names.sort();
+
+        let mut repositories = Vec::new();
+        for name in names {
+            if let Ok(policy) = self
+                .authorize(&namespace, &name, identity.as_ref(), Role::Read)
+                .await
+            {
+                repositories.push(self.info(&namespace, &name, &policy).await);
+            }
+        }
+        Ok(Response::new(ListRepositoriesResponse { repositories }))
+    }
+
+    /// Creates an organization administered by the caller.
+    async fn create_organization(
+        &self,
+        request: Request<OrganizationRequest>,
+    ) -> Result<Response<OrganizationInfo>, Status> {
+        let claims = auth_layer::require(&request, Scope::Write)?;
+        let name = policy_store::namespace(&request.get_ref().name)?;
+
+        // Locked before the user store like in `register`, so a name cannot be taken by an
+        // organization and a user at once.
+        let mut repositories = self.repositories.lock().await;
+        let user_taken = self
+            .user_store
+            .lock()
+            .await
+            .map
+            .values()
+            .any(|entry| entry.user_name == name);
+        if user_taken || self.upload_root.join(&name).exists() {
+            return Err(store_status(RepoStoreError::NamespaceTaken(name)));
+        }
+
+        let organization = repositories
+            .create_organization(&name, &claims.sub)
+            .map_err(store_status)?;
+        Ok(Response::new(organization_info(&name, organization)))
+    }
+
+    async fn set_member(
+        &self,
+        request: Request<MemberRequest>,
+    ) -> Result<Response<OrganizationInfo>, Status> {
+        let claims = auth_layer::require(&request, Scope::Write)?;
+        let req = request.into_inner();
+        let role: Option<Role> = match req.role.is_empty() {
+            true => None,
+            false => Some(req.role.parse()?),
+        };
+        if role.is_some()
+            && !self
+                .user_store
+                .lock()
+                .await
+                .map
+                .contains_key(&req.user_email)
+        {
+            return Err(Status::not_found(format!(
+                "No user is registered with the email {}.",
+                req.user_email
+            )));
+        }
+
+        let mut repositories = self.repositories.lock().await;
+        let organization = repositories
+            .organizations
+            .get(&req.organization)
+            .ok_or_else(|| {
+                store_status(RepoStoreError::UnknownOrganization(
+                    req.organization.clone(),
+                ))
+            })?;
+        let is_admin = organization.members.get(&claims.sub) == Some(&Role::Admin);
+        if !is_admin && !claims.allows(Scope::Admin) {
+            return Err(Status::permission_denied(
+                "Managing the members of an organization needs the admin role.",
+            ));
+        }
+        let admins = organization
+            .members
+            .iter()
+            .filter(|(email, role)| **role == Role::Admin && **email != req.user_email)
+            .count();
+        if admins == 0 && role != Some(Role::Admin) {
+            return Err(Status::failed_precondition(
+                "An organization needs at least one admin.",
+            ));
+        }
+
+        let organization = repositories
+            .set_member(&req.organization, &req.user_email, role)
+            .map_err(store_status)?;
+        Ok(Response::new(organization_info(
+            &req.organization,
+            organization,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_store::{Credential, UserStore};
+    use tonic::Code;
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        upload_root: PathBuf,
+        service: FluxRepositoryService,
+    }
+
+    /// A service where alice and bob are registered and alice owns her namespace.
+    fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| Some(dir.path().join(name).to_string_lossy().to_string());
+        let mut users = UserStore::new(path("users.json")).unwrap();
+        for name in ["alice", "bob"] {
+            let credential = Credential::PasswordHash("hash".into());
+            users
+                .add_user(name.into(), format!("{name}@x"), credential, false)
+                .unwrap();
+        }
+        let mut repositories = RepoStore::open(path("repo_store.json")).unwrap();
+        repositories.claim_namespace("alice", "alice@x").unwrap();
+
+        let upload_root = dir.path().join("repos");
+        let service = FluxRepositoryService::new(
+            upload_root.to_string_lossy().to_string(),
+            Arc::new(Mutex::new(
+                PolicyStore::open(path("policies.json")).unwrap(),
+            )),
+            Arc::new(Mutex::new(repositories)),
+            SharedUserStore::new(users),
+        );
+        Fixture {
+            _dir: dir,
+            upload_root,
+            service,
+        }
+    }
+
+    fn request<T>(message: T, name: Option<&str>, scopes: &[Scope]) -> Request<T> {
+        let mut request = Request::new(message);
+        if let Some(name) = name {
+            request.extensions_mut().insert(Claims {
+                sub: format!("{name}@x"),
+                name: name.to_string(),
+                jti: String::new(),
+                iat: 0,
+                exp: 0,
+                scopes: scopes.to_vec(),
+            });
+        }
+        request
+    }
+
+    fn as_user<T>(message: T, name: &str) -> Request<T> {
+        request(message, Some(name), Scope::DEFAULT)
+    }
+
+    fn create(repository: &str, visibility: &str) -> CreateRepositoryRequest {
+        CreateRepositoryRequest {
+            repository: repository.to_string(),
+            description: format!("About {repository}"),
+            default_branch: String::new(),
+            visibility: visibility.to_string(),
+        }
+    }
+
+    fn repository(repository: &str) -> RepositoryRequest {
+        RepositoryRequest {
+            repository: repository.to_string(),
+        }
+    }
+
+    fn member(user: &str, role: &str) -> MemberRequest {
+        MemberRequest {
+            organization: "acme".to_string(),
+            user_email: format!("{user}@x"),
+            role: role.to_string(),
+        }
+    }
+
+    async fn list(service: &FluxRepositoryService, name: Option<&str>) -> Vec<String> {
+        let message = ListRepositoriesRequest {
+            namespace: "alice".to_string(),
+        };
+        let response = service
+            .list_repositories(request(message, name, Scope::DEFAULT))
+            .await
+            .unwrap();
+        let repositories = response.into_inner().repositories;
+        repositories.into_iter().map(|info| info.name).collect()
+    }
+
+    #[tokio::test]
+    async fn created_repositories_are_described_and_listed_to_their_readers() {
+        let fixture = fixture();
+        let service = &fixture.service;
+
+        let info = service
+            .create_repository(as_user(create("project", "private"), "alice"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.namespace, "alice");
+        assert_eq!(info.description, "About project");
+        assert_eq!(info.default_branch, DEFAULT_BRANCH);
+        assert_eq!(info.visibility, "private");
+        service
+            .create_repository(as_user(create("site", ""), "alice"))
+            .await
+            .unwrap();
+
+        let again = service
+            .create_repository(as_user(create("project", ""), "alice"))
+            .await
+            .unwrap_err();
+        assert_eq!(again.code(), Code::AlreadyExists);
+        let elsewhere = service
+            .create_repository(as_user(create("alice/intruder", ""), "bob"))
+            .await
+            .unwrap_err();
+        assert_eq!(elsewhere.code(), Code::PermissionDenied);
+        let anonymous = service
+            .create_repository(request(create("alice/anonymous", ""), None, &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(anonymous.code(), Code::Unauthenticated);
+
+        let described = service
+            .describe_repository(as_user(repository("alice/project"), "alice"))
+            .await
+            .unwrap();
+        assert_eq!(described.into_inner(), info);
+        let hidden = service
+            .describe_repository(as_user(repository("alice/project"), "bob"))
+            .await
+            .unwrap_err();
+        assert_eq!(hidden.code(), Code::PermissionDenied);
+
+        assert_eq!(list(service, Some("alice")).await, ["project", "site"]);
+        assert_eq!(list(service, Some("bob")).await, ["site"]);
+        assert_eq!(list(service, None).await, ["site"]);
+    }
+
+    #[tokio::test]
+    async fn only_admins_rename_and_delete_repositories() {
+        let fixture = fixture();
+        let (service, upload_root) = (&fixture.service, &fixture.upload_root);
+        service
+            .create_repository(as_user(create("project", "private"), "alice"))
+            .await
+            .unwrap();
+
+        let rename = |name: &str, new_name: &str| {
+            let message = RenameRepositoryRequest {
+                repository: "alice/project".to_string(),
+                new_name: new_name.to_string(),
+            };
+            as_user(message, name)
+        };
+        let denied = service.rename_repository(rename("bob", "taken")).await;
+        assert_eq!(denied.unwrap_err().code(), Code::PermissionDenied);
+        let renamed = service
+            .rename_repository(rename("alice", "renamed"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(renamed.name, "renamed");
+        // The description and visibility follow the repository.
+        assert_eq!(renamed.description, "About project");
+        assert_eq!(renamed.visibility, "private");
+        let gone = service
+            .describe_repository(as_user(repository("alice/project"), "alice"))
+            .await
+            .unwrap_err();
+        assert_eq!(gone.code(), Code::NotFound);
+
+        let denied = service
+            .delete_repository(as_user(repository("alice/renamed"), "bob"))
+            .await
+            .unwrap_err();
+        assert_eq!(denied.code(), Code::PermissionDenied);
+        service
+            .delete_repository(as_user(repository("alice/renamed"), "alice"))
+            .await
+            .unwrap();
+        assert!(!upload_root.join("alice/renamed").exists());
+        let gone = service
+            .delete_repository(as_user(repository("alice/renamed"), "alice"))
+            .await
+            .unwrap_err();
+        assert_eq!(gone.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn organizations_need_a_free_name_and_a_write_token() {
+        let fixture = fixture();
+        let (service, upload_root) = (&fixture.service, &fixture.upload_root);
+        let organization = |name: &str| OrganizationRequest {
+            name: name.to_string(),
+        };
+
+        let read_only = request(organization("acme"), Some("alice"), &[Scope::Read]);
+        let denied = service.create_organization(read_only).await.unwrap_err();
+        assert_eq!(denied.code(), Code::PermissionDenied);
+
+        let info = service
+            .create_organization(as_user(organization("acme"), "alice"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.name, "acme");
+        assert_eq!(info.members.len(), 1);
+        assert_eq!(info.members[0].user_email, "alice@x");
+        assert_eq!(info.members[0].role, "admin");
+
+        // bob has never pushed, his name is taken by his account alone.
+        std::fs::create_dir_all(upload_root.join("legacy")).unwrap();
+        for name in ["acme", "alice", "bob", "legacy"] {
+            let taken = service
+                .create_organization(as_user(organization(name), "alice"))
+                .await
+                .unwrap_err();
+            assert_eq!(taken.code(), Code::AlreadyExists, "{name}");
+        }
+    }
+
+    #[tokio::test]
+    async fn organizations_keep_at_least_one_admin() {
+        let fixture = fixture();
+        let service = &fixture.service;
+        let organization = OrganizationRequest {
+            name: "acme".to_string(),
+        };
+        service
+            .create_organization(as_user(organization, "alice"))
+            .await
+            .unwrap();
+
+        let demoted = service
+            .set_member(as_user(member("alice", "write"), "alice"))
+            .await
+            .unwrap_err();
+        assert_eq!(demoted.code(), Code::FailedPrecondition);
+        let removed = service
+            .set_member(as_user(member("alice", ""), "alice"))
+            .await
+            .unwrap_err();
+        assert_eq!(removed.code(), Code::FailedPrecondition);
+
+        let info = service
+            .set_member(as_user(member("bob", "write"), "alice"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.members.len(), 2);
+        let not_admin = service
+            .set_member(as_user(member("bob", "admin"), "bob"))
+            .await
+            .unwrap_err();
+        assert_eq!(not_admin.code(), Code::PermissionDenied);
+        let unknown = service
+            .set_member(as_user(member("carol", "read"), "alice"))
+            .await
+            .unwrap_err();
+        assert_eq!(unknown.code(), Code::NotFound);
+
+        // Once bob is an admin too, alice may leave.
+        service
+            .set_member(as_user(member("bob", "admin"), "alice"))
+            .await
+            .unwrap();
+        let info = service
+            .set_member(as_user(member("alice", ""), "alice"))
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.members.len(), 1);
+        assert_eq!(info.members[0].user_email, "bob@x");
+    }
+}
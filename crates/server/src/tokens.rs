@@ -0,0 +1,245 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tonic::Status;
+
+use crate::user_store::SharedUserStore;
+
+/// What a token may be used for. `Admin` implies the other scopes.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Read,
+    Write,
+    Admin,
+}
+
+impl Scope {
+    /// Scopes of a token when none are requested.
+    pub const DEFAULT: &[Scope] = &[Scope::Read, Scope::Write];
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for Scope {
+    type Err = Status;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read" => Ok(Scope::Read),
+            "write" => Ok(Scope::Write),
+            "admin" => Ok(Scope::Admin),
+            _ => Err(Status::invalid_argument(format!(
+                "Unknown scope '{s}', expected read, write or admin."
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Claims {
+    /// Email of the user.
+    pub sub: String,
+    pub name: String,
+    /// Id of the token, revoking it in the user store invalidates the token.
+    pub jti: String,
+    pub iat: u64,
+    pub exp: u64,
+    pub scopes: Vec<Scope>,
+}
+
+impl Claims {
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+}
+
+/// Signs and verifies the JWT access tokens with the server secret.
+#[derive(Debug)]
+pub struct TokenIssuer {
+    secret: String,
+    ttl: Duration,
+}
+
+impl TokenIssuer {
+    pub fn new(secret: String, ttl: Duration) -> Self {
+        Self { secret, ttl }
+    }
+
+    pub fn issue(
+        &self,
+        user_name: &str,
+        user_email: &str,
+        scopes: Vec<Scope>,
+    ) -> Result<(String, Claims), Status> {
+        let iat = now();
+        let claims = Claims {
+            sub: user_email.to_string(),
+            name: user_name.to_string(),
+            jti: random_hex(16),
+            iat,
+            exp: iat + self.ttl.as_secs(),
+            scopes,
+        };
+
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.secret.as_ref()),
+        )
+        .map_err(|_| Status::internal("Failed to generate token"))?;
+        Ok((token, claims))
+    }
+
+    /// Checks the signature and expiry of a token.
+    pub fn verify(&self, token: &str) -> Result<Claims, Status> {
+        let mut validation = Validation::default();
+        validation.leeway = 0;
+        validation.set_required_spec_claims(&["exp", "sub", "jti"]);
+
+        decode::<Claims>(
+            token,
+            &DecodingKey::from_secret(self.secret.as_ref()),
+            &validation,
+        )
+        .map(|data| data.claims)
+        .map_err(|e| Status::unauthenticated(format!("Invalid access token. {e}")))
+    }
+
+    /// Verifies a token and checks that it is neither revoked nor of a disabled account.
+    pub async fn authenticate(
+        &self,
+        user_store: &SharedUserStore,
+        token: &str,
+    ) -> Result<Claims, Status> {
+        let claims = self.verify(token)?;
+        if !user_store.lock().await.is_token_active(&claims) {
+            return Err(Status::unauthenticated(
+                "The access token was revoked, run flux auth again.",
+            ));
+        }
+        Ok(claims)
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// `len` random bytes as a hex string.
+pub fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_store::{Credential, UserStore};
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    /// A store with the account of alice and a token issued to her.
+    fn alice(dir: &tempfile::TempDir, tokens: &TokenIssuer) -> (SharedUserStore, String) {
+        let path = dir.path().join("users.json").to_string_lossy().to_string();
+        let mut store = UserStore::new(Some(path)).unwrap();
+        let password = Credential::PasswordHash("hash".to_string());
+        store
+            .add_user("alice".into(), "alice@x".into(), password, false)
+            .unwrap();
+        let (token, claims) = tokens
+            .issue("alice", "alice@x", Scope::DEFAULT.to_vec())
+            .unwrap();
+        store.add_token(&claims).unwrap();
+        (SharedUserStore::new(store), token)
+    }
+
+    #[tokio::test]
+    async fn issued_tokens_authenticate() {
+        let dir = tempfile::tempdir().unwrap();
+        let tokens = TokenIssuer::new("secret".to_string(), TTL);
+        let (store, token) = alice(&dir, &tokens);
+
+        let claims = tokens.authenticate(&store, &token).await.unwrap();
+        assert_eq!(
+            (claims.sub.as_str(), claims.name.as_str()),
+            ("alice@x", "alice")
+        );
+        assert_eq!(claims.exp, claims.iat + TTL.as_secs());
+    }
+
+    #[test]
+    fn tokens_signed_with_another_secret_are_refused() {
+        let (token, _) = TokenIssuer::new("other".to_string(), TTL)
+            .issue("alice", "alice@x", Scope::DEFAULT.to_vec())
+            .unwrap();
+        let refused = TokenIssuer::new("secret".to_string(), TTL)
+            .verify(&token)
+            .unwrap_err();
+        assert_eq!(refused.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[test]
+    fn expired_tokens_are_refused_without_leeway() {
+        let tokens = TokenIssuer::new("secret".to_string(), TTL);
+        let (_, mut claims) = tokens
+            .issue("alice", "alice@x", Scope::DEFAULT.to_vec())
+            .unwrap();
+        // A second ago, well within the minute of leeway jsonwebtoken allows by default.
+        claims.exp = now() - 1;
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        let refused = tokens.verify(&token).unwrap_err();
+        assert_eq!(refused.code(), tonic::Code::Unauthenticated);
+        assert!(refused.message().contains("ExpiredSignature"));
+    }
+
+    #[tokio::test]
+    async fn revoked_tokens_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let tokens = TokenIssuer::new("secret".to_string(), TTL);
+        let (store, token) = alice(&dir, &tokens);
+        let jti = tokens.verify(&token).unwrap().jti;
+
+        store
+            .lock()
+            .await
+            .revoke_tokens("alice@x", Some(&jti))
+            .unwrap();
+        let refused = tokens.authenticate(&store, &token).await.unwrap_err();
+        assert_eq!(refused.code(), tonic::Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn tokens_of_disabled_accounts_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let tokens = TokenIssuer::new("secret".to_string(), TTL);
+        let (store, token) = alice(&dir, &tokens);
+
+        store.lock().await.set_disabled("alice@x", true).unwrap();
+        assert!(tokens.authenticate(&store, &token).await.is_err());
+        store.lock().await.set_disabled("alice@x", false).unwrap();
+        assert!(tokens.authenticate(&store, &token).await.is_ok());
+    }
+}
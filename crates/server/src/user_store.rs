@@ -1,6 +1,17 @@
 use crate::error;
+use crate::policy_store;
+use argon2::Argon2;
+use argon2::password_hash::{
+    PasswordHash, PasswordHasher, PasswordVerifier, SaltString, rand_core::OsRng,
+};
+use flux_core::internals::signing::{self, AllowedSigners};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs::File, path::PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::SystemTime;
+use std::{collections::HashMap, fs, fs::File, io, path::PathBuf};
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::tokens::{self, Claims, Scope};
 
 pub type Result<T> = std::result::Result<T, error::UserStoreError>;
 
@@ -8,6 +19,9 @@ pub type Result<T> = std::result::Result<T, error::UserStoreError>;
 pub struct UserStore {
     #[serde(skip)]
     pub path: PathBuf,
+    /// The file as this store last read or wrote it.
+    #[serde(skip)]
+    stamp: Option<Stamp>,
     #[serde(flatten)]
     pub map: HashMap<String, UserStoreEntry>,
 }
@@ -15,14 +29,173 @@ pub struct UserStore {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct UserStoreEntry {
     pub user_name: String,
-    pub access_token: String,
+    /// Tokens issued to the user that have not expired yet.
+    #[serde(default)]
+    pub tokens: Vec<TokenEntry>,
+    /// Argon2 hash of the password in the PHC string format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_hash: Option<String>,
+    /// OpenSSH public key challenges are signed with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    #[serde(default)]
+    pub disabled: bool,
+    /// Whether the user may request tokens with the admin scope.
+    #[serde(default)]
+    pub admin: bool,
+}
+
+/// An issued token, identified by the `jti` claim.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenEntry {
+    pub id: String,
+    pub scopes: Vec<Scope>,
+    pub issued_at: u64,
+    pub expires_at: u64,
+    #[serde(default)]
+    pub revoked: bool,
+}
+
+/// What a user proves their identity with.
+#[derive(Debug, Clone)]
+pub enum Secret {
+    Password(String),
+    PublicKey(String),
+}
+
+/// A secret as the store keeps it. Checking a password is slow, so the credential is copied
+/// out of the store and verified without holding its lock.
+#[derive(Debug, Clone)]
+pub enum Credential {
+    /// Argon2 hash of the password in the PHC string format.
+    PasswordHash(String),
+    /// OpenSSH public key challenges are signed with.
+    PublicKey(String),
+}
+
+impl Secret {
+    /// Hashes a password or checks a public key, for `user_email` to register with.
+    pub fn into_credential(self, user_email: &str) -> Result<Credential> {
+        match self {
+            Secret::Password(password) => Ok(Credential::PasswordHash(hash_password(&password)?)),
+            Secret::PublicKey(key) => {
+                // Parse it now so a malformed key is refused at registration, not at login.
+                signers(user_email, &key)?;
+                Ok(Credential::PublicKey(key.trim().to_string()))
+            }
+        }
+    }
+}
+
+/// Password hash of no account, checked when an email is unknown so that refusing it takes
+/// as long as refusing a wrong password.
+static UNKNOWN_USER: LazyLock<Credential> = LazyLock::new(|| {
+    Credential::PasswordHash(hash_password("").expect("hashing a constant password"))
+});
+
+fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default().hash_password(password.as_bytes(), &salt)?;
+    Ok(hash.to_string())
+}
+
+impl Credential {
+    /// Stands in for the credential of an unknown account, it matches no password.
+    pub fn unknown_user() -> &'static Credential {
+        &UNKNOWN_USER
+    }
+
+    pub fn verify_password(&self, password: &str) -> Result<()> {
+        let Credential::PasswordHash(hash) = self else {
+            return Err(error::UserStoreError::InvalidCredentials);
+        };
+        let hash = PasswordHash::new(hash)?;
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .map_err(|_| error::UserStoreError::InvalidCredentials)
+    }
+
+    /// Checks that `signature` signs `challenge` with the key of `user_email`.
+    pub fn verify_signature(
+        &self,
+        user_email: &str,
+        challenge: &str,
+        signature: &str,
+    ) -> Result<()> {
+        let Credential::PublicKey(key) = self else {
+            return Err(error::UserStoreError::InvalidCredentials);
+        };
+        let allowed = signers(user_email, key)?;
+        if !signing::verify(challenge.as_bytes(), Some(signature), user_email, &allowed).is_good() {
+            return Err(error::UserStoreError::InvalidCredentials);
+        }
+        Ok(())
+    }
+}
+
+fn signers(user_email: &str, key: &str) -> Result<AllowedSigners> {
+    AllowedSigners::parse(&format!("{user_email} {key}"))
+        .map_err(|e| error::UserStoreError::PublicKey(e.to_string()))
+}
+
+impl UserStoreEntry {
+    /// Replaces whatever the user logged in with by `credential`.
+    fn set_credential(&mut self, credential: Credential) {
+        (self.password_hash, self.public_key) = match credential {
+            Credential::PasswordHash(hash) => (Some(hash), None),
+            Credential::PublicKey(key) => (None, Some(key)),
+        };
+    }
+}
+
+/// Tells whether the file changed. Saving replaces the file, so on unix its inode changes
+/// too, even within the resolution of the modification time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    #[cfg(unix)]
+    inode: u64,
+}
+
+impl Stamp {
+    fn of(path: &PathBuf) -> io::Result<Self> {
+        let metadata = fs::metadata(path)?;
+        Ok(Self {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+            #[cfg(unix)]
+            inode: std::os::unix::fs::MetadataExt::ino(&metadata),
+        })
+    }
+}
+
+/// The user store of a running server, shared by its services. `flux_server user` edits
+/// the file while the server runs, so `lock` reads it again when it changed. A command
+/// saving at the same moment as the server can still overwrite the server's change.
+#[derive(Debug, Clone)]
+pub struct SharedUserStore(Arc<Mutex<UserStore>>);
+
+impl SharedUserStore {
+    pub fn new(store: UserStore) -> Self {
+        Self(Arc::new(Mutex::new(store)))
+    }
+
+    pub async fn lock(&self) -> MutexGuard<'_, UserStore> {
+        let mut store = self.0.lock().await;
+        if let Err(e) = store.reload() {
+            tracing::warn!("Keeping the user store in memory, reading it failed: {}", e);
+        }
+        store
+    }
 }
 
 impl UserStore {
     pub fn new(path_str: Option<String>) -> Result<Self> {
         let path = PathBuf::from(path_str.unwrap_or_else(|| "user_store.json".to_string()));
-        let store = Self {
+        let mut store = Self {
             path,
+            stamp: None,
             map: HashMap::new(),
         };
         store.save()?;
@@ -31,46 +204,241 @@ impl UserStore {
 
     pub fn open(path_str: Option<String>) -> Result<Self> {
         let path = PathBuf::from(path_str.unwrap_or_else(|| "user_store.json".to_string()));
+        Self::read(path)
+    }
+
+    fn read(path: PathBuf) -> Result<Self> {
+        let stamp = Stamp::of(&path)?;
         let file = File::open(&path)?;
         let mut store: Self = serde_json::from_reader(file)?;
         store.path = path;
+        store.stamp = Some(stamp);
         Ok(store)
     }
 
-    pub fn save(&self) -> Result<()> {
-        let file = File::create(&self.path)?;
-        Ok(serde_json::to_writer_pretty(file, self)?)
+    /// Reads the file again if something else saved it since this store did.
+    pub fn reload(&mut self) -> Result<()> {
+        if self.stamp != Some(Stamp::of(&self.path)?) {
+            *self = Self::read(self.path.clone())?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        // Written next to the store and renamed over it, so no reader sees half of it.
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        serde_json::to_writer_pretty(File::create(&temp)?, self)?;
+        fs::rename(&temp, &self.path)?;
+        self.stamp = Some(Stamp::of(&self.path)?);
+        Ok(())
     }
 
-    pub async fn add_user(
+    pub fn add_user(
         &mut self,
         user_name: String,
         user_email: String,
-        access_token: String,
+        credential: Credential,
+        admin: bool,
     ) -> Result<()> {
         if self.map.contains_key(&user_email) {
             return Err(error::UserStoreError::EmailAlredyRegistered(user_email));
         }
-        let entry = UserStoreEntry {
+        // The name is the namespace of the user's repositories, it decides who owns them.
+        if policy_store::namespace(&user_name).is_err() {
+            return Err(error::UserStoreError::InvalidUserName(user_name));
+        }
+        if self.map.values().any(|entry| entry.user_name == user_name) {
+            return Err(error::UserStoreError::UserNameTaken(user_name));
+        }
+        let mut entry = UserStoreEntry {
             user_name,
-            access_token,
+            tokens: Vec::new(),
+            password_hash: None,
+            public_key: None,
+            disabled: false,
+            admin,
         };
+        entry.set_credential(credential);
 
         self.map.insert(user_email, entry);
         self.save()?;
         Ok(())
     }
 
-    pub async fn is_token_valid(
-        &self,
-        user_name: String,
-        user_email: String,
-        access_token: String,
-    ) -> bool {
-        if let Some(entry) = self.map.get(&user_email) {
-            return entry.access_token == access_token && entry.user_name == user_name;
-        } else {
-            false
+    /// Enabled account of `user_email`.
+    pub fn active_user(&self, user_email: &str) -> Result<&UserStoreEntry> {
+        let entry = self
+            .map
+            .get(user_email)
+            .ok_or_else(|| error::UserStoreError::UnknownUser(user_email.to_string()))?;
+        if entry.disabled {
+            return Err(error::UserStoreError::Disabled(user_email.to_string()));
+        }
+        Ok(entry)
+    }
+
+    /// What the account of `user_email` logs in with, whether it is enabled or not.
+    pub fn credential(&self, user_email: &str) -> Result<Credential> {
+        let entry = self
+            .map
+            .get(user_email)
+            .ok_or_else(|| error::UserStoreError::UnknownUser(user_email.to_string()))?;
+        match (&entry.password_hash, &entry.public_key) {
+            (Some(hash), _) => Ok(Credential::PasswordHash(hash.clone())),
+            (None, Some(key)) => Ok(Credential::PublicKey(key.clone())),
+            (None, None) => Err(error::UserStoreError::InvalidCredentials),
+        }
+    }
+
+    /// Email of the only account named `user_name`. Stores from before names were unique
+    /// can have several, then none of them owns the namespace of the name.
+    pub fn owner_of(&self, user_name: &str) -> Option<&str> {
+        let mut named = self
+            .map
+            .iter()
+            .filter(|(_, entry)| entry.user_name == user_name);
+        match (named.next(), named.next()) {
+            (Some((email, _)), None) => Some(email),
+            _ => None,
+        }
+    }
+
+    /// Emails of the accounts that have neither a password nor a key, they cannot log in.
+    pub fn without_credential(&self) -> Vec<&str> {
+        let mut emails: Vec<_> = self
+            .map
+            .iter()
+            .filter(|(_, entry)| entry.password_hash.is_none() && entry.public_key.is_none())
+            .map(|(email, _)| email.as_str())
+            .collect();
+        emails.sort();
+        emails
+    }
+
+    /// Replaces the password or key of an account.
+    pub fn set_credential(&mut self, user_email: &str, credential: Credential) -> Result<()> {
+        self.entry_mut(user_email)?.set_credential(credential);
+        self.save()
+    }
+
+    fn entry_mut(&mut self, user_email: &str) -> Result<&mut UserStoreEntry> {
+        self.map
+            .get_mut(user_email)
+            .ok_or_else(|| error::UserStoreError::UnknownUser(user_email.to_string()))
+    }
+
+    /// Records a token issued to `claims.sub`, dropping the expired ones.
+    pub fn add_token(&mut self, claims: &Claims) -> Result<()> {
+        let now = tokens::now();
+        let entry = self.entry_mut(&claims.sub)?;
+        entry.tokens.retain(|token| token.expires_at > now);
+        entry.tokens.push(TokenEntry {
+            id: claims.jti.clone(),
+            scopes: claims.scopes.clone(),
+            issued_at: claims.iat,
+            expires_at: claims.exp,
+            revoked: false,
+        });
+        self.save()
+    }
+
+    /// Whether the token of `claims` was issued by this store, is not revoked and belongs to
+    /// an enabled account.
+    pub fn is_token_active(&self, claims: &Claims) -> bool {
+        self.active_user(&claims.sub).is_ok_and(|entry| {
+            entry.user_name == claims.name
+                && entry
+                    .tokens
+                    .iter()
+                    .any(|token| token.id == claims.jti && !token.revoked)
+        })
+    }
+
+    /// Tokens of a user that are neither revoked nor expired.
+    pub fn active_tokens(&self, user_email: &str) -> Result<Vec<TokenEntry>> {
+        let now = tokens::now();
+        let entry = self
+            .map
+            .get(user_email)
+            .ok_or_else(|| error::UserStoreError::UnknownUser(user_email.to_string()))?;
+        Ok(entry
+            .tokens
+            .iter()
+            .filter(|token| !token.revoked && token.expires_at > now)
+            .cloned()
+            .collect())
+    }
+
+    /// Revokes the token `id` of a user, or all of them when `id` is `None`. Returns the
+    /// number of tokens revoked.
+    pub fn revoke_tokens(&mut self, user_email: &str, id: Option<&str>) -> Result<usize> {
+        let entry = self.entry_mut(user_email)?;
+        let mut revoked = 0;
+        for token in entry.tokens.iter_mut().filter(|token| !token.revoked) {
+            if id.is_none_or(|id| id == token.id) {
+                token.revoked = true;
+                revoked += 1;
+            }
         }
+        self.save()?;
+        Ok(revoked)
+    }
+
+    /// Disables or re-enables an account. Disabled users cannot log in or push.
+    pub fn set_disabled(&mut self, user_email: &str, disabled: bool) -> Result<()> {
+        self.entry_mut(user_email)?.disabled = disabled;
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_names_are_unique_namespaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.json").to_string_lossy().to_string();
+        let mut store = UserStore::new(Some(path)).unwrap();
+        let password = || Credential::PasswordHash("hash".to_string());
+
+        store
+            .add_user("alice".into(), "alice@x".into(), password(), false)
+            .unwrap();
+        let taken = store.add_user("alice".into(), "mallory@x".into(), password(), false);
+        assert!(matches!(
+            taken,
+            Err(error::UserStoreError::UserNameTaken(_))
+        ));
+        for name in ["", ".hooks", "a/b", ".."] {
+            let invalid = store.add_user(name.into(), "mallory@x".into(), password(), false);
+            assert!(matches!(
+                invalid,
+                Err(error::UserStoreError::InvalidUserName(_))
+            ));
+        }
+        assert_eq!(store.map.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn shared_store_sees_changes_saved_by_another_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.json").to_string_lossy().to_string();
+        let shared = SharedUserStore::new(UserStore::new(Some(path.clone())).unwrap());
+
+        let mut admin = UserStore::open(Some(path.clone())).unwrap();
+        let password = Credential::PasswordHash("hash".to_string());
+        admin
+            .add_user("alice".into(), "alice@x".into(), password, false)
+            .unwrap();
+        assert!(shared.lock().await.active_user("alice@x").is_ok());
+
+        let mut admin = UserStore::open(Some(path)).unwrap();
+        admin.set_disabled("alice@x", true).unwrap();
+        assert!(matches!(
+            shared.lock().await.active_user("alice@x"),
+            Err(error::UserStoreError::Disabled(_))
+        ));
     }
 }